@@ -21,6 +21,8 @@ use near_primitives::challenge::{
     MaybeEncodedShardChunk, PartialState, SlashedValidator,
 };
 use near_primitives::checked_feature;
+use near_primitives::epoch_manager::block_info::BlockInfo;
+use near_primitives::epoch_manager::epoch_info::EpochInfo;
 use near_primitives::hash::{hash, CryptoHash};
 use near_primitives::merkle::{
     combine_hash, merklize, verify_path, Direction, MerklePath, MerklePathItem, PartialMerkleTree,
@@ -32,8 +34,9 @@ use near_primitives::sharding::{
 };
 use near_primitives::state_part::PartId;
 use near_primitives::syncing::{
-    get_num_state_parts, ReceiptProofResponse, RootProof, ShardStateSyncResponseHeader,
-    ShardStateSyncResponseHeaderV1, ShardStateSyncResponseHeaderV2, StateHeaderKey, StatePartKey,
+    get_num_state_parts, EpochSyncFinalizationResponse, EpochSyncResponse, ReceiptProofResponse,
+    RootProof, ShardStateSyncResponseHeader, ShardStateSyncResponseHeaderV1,
+    ShardStateSyncResponseHeaderV2, StateHeaderKey, StatePartKey,
 };
 use near_primitives::transaction::{
     ExecutionOutcomeWithId, ExecutionOutcomeWithIdAndProof, SignedTransaction,
@@ -60,7 +63,7 @@ use crate::block_processing_utils::{
 use crate::blocks_delay_tracker::BlocksDelayTracker;
 use crate::crypto_hash_timer::CryptoHashTimer;
 use crate::flat_storage_creator::FlatStorageCreator;
-use crate::lightclient::get_epoch_block_producers_view;
+use crate::lightclient::{get_epoch_block_producers_view, get_light_client_block_hash};
 use crate::migrations::check_if_block_is_first_with_chunk_of_version;
 use crate::missing_chunks::{BlockLike, MissingChunksPool};
 use crate::state_request_tracker::StateRequestTracker;
@@ -891,10 +894,15 @@ impl Chain {
 
         let head = self.store.head()?;
         let tail = self.store.tail()?;
-        let gc_stop_height = self.runtime_adapter.get_gc_stop_height(&head.last_block_hash);
+        let mut gc_stop_height = self.runtime_adapter.get_gc_stop_height(&head.last_block_hash);
         if gc_stop_height > head.height {
             return Err(Error::GCError("gc_stop_height cannot be larger than head.height".into()));
         }
+        // With split storage, only data which has already been copied to cold
+        // storage can be garbage collected.
+        if let Some(cold_head) = self.store.cold_head()? {
+            gc_stop_height = gc_stop_height.min(cold_head.height);
+        }
         let prev_epoch_id = self.get_block_header(&head.prev_block_hash)?.epoch_id().clone();
         let epoch_change = prev_epoch_id != head.epoch_id;
         let mut fork_tail = self.store.fork_tail()?;
@@ -1782,6 +1790,54 @@ impl Chain {
         chain_update.commit()
     }
 
+    /// Saves data received at the end of epoch sync and makes the last block
+    /// before the synced epoch the header head so that header sync continues
+    /// from there.
+    ///
+    /// The response must have been validated by the caller.
+    pub fn finish_epoch_sync(
+        &mut self,
+        response: EpochSyncFinalizationResponse,
+    ) -> Result<(), Error> {
+        let EpochSyncFinalizationResponse {
+            cur_epoch_header,
+            prev_epoch_headers,
+            header_sync_init_header,
+            header_sync_init_header_tree,
+            prev_epoch_first_block_info,
+            prev_epoch_prev_last_block_info,
+            prev_epoch_last_block_info,
+            prev_epoch_info,
+            cur_epoch_info,
+            next_epoch_info,
+        } = response;
+        self.runtime_adapter.epoch_sync_init_epoch_manager(
+            prev_epoch_first_block_info,
+            prev_epoch_prev_last_block_info,
+            prev_epoch_last_block_info,
+            header_sync_init_header.epoch_id(),
+            prev_epoch_info,
+            cur_epoch_header.epoch_id(),
+            cur_epoch_info,
+            cur_epoch_header.next_epoch_id(),
+            next_epoch_info,
+        )?;
+
+        let mut chain_store_update = self.store.store_update();
+        for header in prev_epoch_headers {
+            chain_store_update.save_block_header_no_update_tree(header)?;
+        }
+        chain_store_update
+            .save_block_merkle_tree(*header_sync_init_header.hash(), header_sync_init_header_tree);
+        let tip = Tip::from_header(&header_sync_init_header);
+        chain_store_update.save_block_header_no_update_tree(header_sync_init_header)?;
+        chain_store_update.force_save_header_head(&tip)?;
+        chain_store_update.commit()?;
+        info!(target: "sync", "Epoch sync finished; header head is now {} at {}",
+              tip.last_block_hash, tip.height);
+        Ok(())
+    }
+
     /// Returns if given block header is on the current chain.
     ///
     /// This is done by fetching header by height and checking that it’s the
@@ -4475,6 +4531,94 @@ impl Chain {
         Ok(headers)
     }
 
+    /// Returns light client block of given epoch which lets a node doing epoch
+    /// sync advance to the next epoch or `UpToDate` if given epoch is the epoch
+    /// of the head.
+    pub fn get_epoch_sync_response(&self, epoch_id: &EpochId) -> Result<EpochSyncResponse, Error> {
+        if &self.head()?.epoch_id == epoch_id {
+            return Ok(EpochSyncResponse::UpToDate);
+        }
+        let light_client_block_view = self.store.get_epoch_light_client_block(&epoch_id.0)?;
+        Ok(EpochSyncResponse::Advance {
+            light_client_block_view: LightClientBlockView::clone(&light_client_block_view),
+        })
+    }
+
+    /// Returns data a node doing epoch sync needs to start header sync from the
+    /// last block before given epoch.
+    ///
+    /// Headers of the previous epoch are returned starting with the block the
+    /// light client block of that epoch was created for, which lets the
+    /// receiver link them to the light client blocks it has validated.
+    pub fn get_epoch_sync_finalization_response(
+        &self,
+        epoch_id: &EpochId,
+    ) -> Result<EpochSyncFinalizationResponse, Error> {
+        let head = self.head()?;
+        let block_in_epoch = if &head.epoch_id == epoch_id {
+            head.last_block_hash
+        } else {
+            get_light_client_block_hash(&*self.store.get_epoch_light_client_block(&epoch_id.0)?)
+        };
+        let epoch_start_height = self.runtime_adapter.get_epoch_start_height(&block_in_epoch)?;
+        let cur_epoch_header = self.get_block_header_by_height(epoch_start_height)?;
+        if cur_epoch_header.epoch_id() != epoch_id {
+            return Err(Error::InvalidEpochHash);
+        }
+        let header_sync_init_header = self.get_block_header(cur_epoch_header.prev_hash())?;
+        let header_sync_init_header_tree = PartialMerkleTree::clone(
+            &*self.store.get_block_merkle_tree(header_sync_init_header.hash())?,
+        );
+
+        let prev_epoch_id = header_sync_init_header.epoch_id();
+        let prev_epoch_block_hash = get_light_client_block_hash(
+            &*self.store.get_epoch_light_client_block(&prev_epoch_id.0)?,
+        );
+        let mut prev_epoch_headers = vec![];
+        let mut hash = *header_sync_init_header.prev_hash();
+        loop {
+            let header = self.get_block_header(&hash)?;
+            if header.epoch_id() != prev_epoch_id {
+                return Err(Error::Other(format!(
+                    "light client block {} not found in epoch {:?}",
+                    prev_epoch_block_hash, prev_epoch_id
+                )));
+            }
+            hash = *header.prev_hash();
+            let done = header.hash() == &prev_epoch_block_hash;
+            prev_epoch_headers.push(header);
+            if done {
+                break;
+            }
+        }
+        prev_epoch_headers.reverse();
+
+        let (
+            prev_epoch_first_block_info,
+            prev_epoch_prev_last_block_info,
+            prev_epoch_last_block_info,
+            prev_epoch_info,
+            cur_epoch_info,
+            next_epoch_info,
+        ) = self.runtime_adapter.get_epoch_sync_data(
+            header_sync_init_header.hash(),
+            epoch_id,
+            cur_epoch_header.next_epoch_id(),
+        )?;
+        Ok(EpochSyncFinalizationResponse {
+            cur_epoch_header,
+            prev_epoch_headers,
+            header_sync_init_header,
+            header_sync_init_header_tree,
+            prev_epoch_first_block_info: BlockInfo::clone(&prev_epoch_first_block_info),
+            prev_epoch_prev_last_block_info: BlockInfo::clone(&prev_epoch_prev_last_block_info),
+            prev_epoch_last_block_info: BlockInfo::clone(&prev_epoch_last_block_info),
+            prev_epoch_info: EpochInfo::clone(&prev_epoch_info),
+            cur_epoch_info: EpochInfo::clone(&cur_epoch_info),
+            next_epoch_info: EpochInfo::clone(&next_epoch_info),
+        })
+    }
+
     /// Returns a vector of chunk headers, each of which corresponds to the previous chunk of
     /// a chunk in the block after `prev_block`
     /// This function is important when the block after `prev_block` has different number of chunks
@@ -4566,6 +4710,80 @@ impl Chain {
     pub fn patch_state_in_progress(&self) -> bool {
         !self.pending_state_patch.is_empty()
     }
+
+    /// Records the current head of the chain so that it can be rewound to it
+    /// with [`Self::sandbox_restore`].
+    #[cfg(feature = "sandbox")]
+    pub fn sandbox_snapshot(&self) -> Result<SandboxSnapshot, Error> {
+        let head = self.head()?;
+        let final_head = self.final_head()?;
+        let num_shards = self.runtime_adapter.num_shards(&head.epoch_id)?;
+        let mut state_roots = Vec::with_capacity(num_shards as usize);
+        let mut flat_heads = Vec::with_capacity(num_shards as usize);
+        for shard_id in 0..num_shards {
+            let shard_uid = self.runtime_adapter.shard_id_to_uid(shard_id, &head.epoch_id)?;
+            let chunk_extra = self.get_chunk_extra(&head.last_block_hash, &shard_uid)?;
+            state_roots.push(*chunk_extra.state_root());
+            flat_heads.push(near_store::flat_state::store_helper::get_flat_head(
+                self.store.store(),
+                shard_id,
+            ));
+        }
+        Ok(SandboxSnapshot { head, final_head, state_roots, flat_heads })
+    }
+
+    /// Rewinds the chain head to the one recorded in the snapshot.
+    ///
+    /// Blocks produced since the snapshot stay in the store but are no longer
+    /// on the canonical chain.  Fails if the snapshot's block or state has
+    /// been garbage collected or if flat storage has moved past it.
+    #[cfg(feature = "sandbox")]
+    pub fn sandbox_restore(&mut self, snapshot: &SandboxSnapshot) -> Result<(), Error> {
+        let head = &snapshot.head;
+        self.get_block_header(&head.last_block_hash)?;
+        for (shard_id, state_root) in snapshot.state_roots.iter().enumerate() {
+            let shard_id = shard_id as ShardId;
+            self.runtime_adapter
+                .get_view_trie_for_shard(shard_id, &head.last_block_hash, *state_root)?
+                .retrieve_root_node()?;
+            let flat_head =
+                near_store::flat_state::store_helper::get_flat_head(self.store.store(), shard_id);
+            if flat_head != snapshot.flat_heads[shard_id as usize] {
+                let flat_head_height = match flat_head {
+                    Some(flat_head) => self.get_block_header(&flat_head)?.height(),
+                    None => continue,
+                };
+                if flat_head_height > head.height {
+                    return Err(Error::Other(format!(
+                        "flat storage head of shard {} at height {} is ahead of the snapshot at height {}",
+                        shard_id, flat_head_height, head.height
+                    )));
+                }
+            }
+        }
+
+        let mut chain_store_update = self.store.store_update();
+        chain_store_update.save_head(head)?;
+        chain_store_update.save_final_head(&snapshot.final_head)?;
+        chain_store_update.commit()?;
+        self.pending_state_patch.clear();
+        Ok(())
+    }
+}
+
+/// Chain pointers recorded by [`Chain::sandbox_snapshot`].
+#[cfg(feature = "sandbox")]
+#[derive(Clone, Debug)]
+pub struct SandboxSnapshot {
+    /// Head of the chain when the snapshot was taken.
+    pub head: Tip,
+    /// Final head of the chain when the snapshot was taken.
+    pub final_head: Tip,
+    /// State roots of the shards after the head block, indexed by shard id.
+    pub state_roots: Vec<StateRoot>,
+    /// Flat storage heads of the shards, indexed by shard id.  `None` for
+    /// shards without flat storage.
+    pub flat_heads: Vec<Option<CryptoHash>>,
 }
 
 /// Chain update helper, contains information that is needed to process block
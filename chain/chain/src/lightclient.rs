@@ -1,9 +1,13 @@
 use near_chain_primitives::Error;
-use near_primitives::block::BlockHeader;
+use near_primitives::block::{Approval, ApprovalInner, BlockHeader};
 use near_primitives::hash::{hash, CryptoHash};
-use near_primitives::types::EpochId;
+use near_primitives::merkle::combine_hash;
+use near_primitives::types::validator_stake::ValidatorStake;
+use near_primitives::types::{Balance, EpochId};
 use near_primitives::views::validator_stake_view::ValidatorStakeView;
-use near_primitives::views::{BlockHeaderInnerLiteView, LightClientBlockView};
+use near_primitives::views::{
+    BlockHeaderInnerLiteView, LightClientBlockLiteView, LightClientBlockView,
+};
 
 use crate::{ChainStoreAccess, RuntimeAdapter};
 
@@ -70,3 +74,192 @@ pub fn create_light_client_block_view(
         approvals_after_next,
     })
 }
+
+/// Returns hash of the block given light client block was created for.
+pub fn get_light_client_block_hash(block_view: &LightClientBlockView) -> CryptoHash {
+    LightClientBlockLiteView {
+        prev_block_hash: block_view.prev_block_hash,
+        inner_rest_hash: block_view.inner_rest_hash,
+        inner_lite: block_view.inner_lite.clone(),
+    }
+    .hash()
+}
+
+/// Validates light client block of epoch `epoch_id`.
+///
+/// Checks that the block belongs to the epoch, that it has been endorsed by
+/// block producers of the epoch holding more than two thirds of the stake and
+/// that block producers of the next epoch it carries are the ones the block
+/// commits to.  This is what light clients verify (see the light client spec)
+/// and what lets epoch sync advance from one epoch to the next knowing only
+/// block producers of the former.
+///
+/// # Arguments
+///  * `block_view` - the light client block to validate
+///  * `epoch_id` - epoch the light client block is expected to belong to
+///  * `block_producers` - ordered list of block producers of `epoch_id`
+///
+/// Returns hash of the block on success.
+pub fn validate_light_client_block(
+    block_view: &LightClientBlockView,
+    epoch_id: &EpochId,
+    block_producers: &[ValidatorStake],
+) -> Result<CryptoHash, Error> {
+    if block_view.inner_lite.epoch_id != epoch_id.0 {
+        return Err(Error::InvalidEpochHash);
+    }
+
+    let block_hash = get_light_client_block_hash(block_view);
+    let next_block_hash = combine_hash(&block_view.next_block_inner_hash, &block_hash);
+    let approval_data = Approval::get_data_for_sig(
+        &ApprovalInner::Endorsement(next_block_hash),
+        block_view.inner_lite.height + 2,
+    );
+    let mut total_stake: Balance = 0;
+    let mut approved_stake: Balance = 0;
+    for (i, block_producer) in block_producers.iter().enumerate() {
+        total_stake += block_producer.stake();
+        if let Some(Some(signature)) = block_view.approvals_after_next.get(i) {
+            if !signature.verify(&approval_data, block_producer.public_key()) {
+                return Err(Error::InvalidSignature);
+            }
+            approved_stake += block_producer.stake();
+        }
+    }
+    if approved_stake * 3 <= total_stake * 2 {
+        return Err(Error::NotEnoughApprovals);
+    }
+
+    let next_block_producers: Vec<ValidatorStake> = block_view
+        .next_bps
+        .as_ref()
+        .ok_or(Error::InvalidNextBPHash)?
+        .iter()
+        .cloned()
+        .map(ValidatorStakeView::into_validator_stake)
+        .collect();
+    // Depending on protocol version, the hash is computed over either the
+    // current or the legacy serialisation of validator stakes.
+    let next_bp_hash = block_view.inner_lite.next_bp_hash;
+    if CryptoHash::hash_borsh_iter(&next_block_producers) != next_bp_hash
+        && CryptoHash::hash_borsh_iter(next_block_producers.into_iter().map(|bp| bp.into_v1()))
+            != next_bp_hash
+    {
+        return Err(Error::InvalidNextBPHash);
+    }
+    Ok(block_hash)
+}
+
+#[cfg(test)]
+mod tests {
+    use assert_matches::assert_matches;
+    use near_crypto::KeyType;
+    use near_primitives::validator_signer::{InMemoryValidatorSigner, ValidatorSigner};
+
+    use super::*;
+
+    fn signers(names: &[&str]) -> Vec<InMemoryValidatorSigner> {
+        names
+            .iter()
+            .map(|name| {
+                InMemoryValidatorSigner::from_seed(name.parse().unwrap(), KeyType::ED25519, name)
+            })
+            .collect()
+    }
+
+    fn stakes(signers: &[InMemoryValidatorSigner]) -> Vec<ValidatorStake> {
+        signers
+            .iter()
+            .map(|signer| {
+                ValidatorStake::new(signer.validator_id().clone(), signer.public_key(), 100)
+            })
+            .collect()
+    }
+
+    /// Builds a light client block for `epoch_id` approved by the given subset of `signers`.
+    fn make_block(
+        epoch_id: &EpochId,
+        signers: &[InMemoryValidatorSigner],
+        approvers: &[usize],
+        next_bps: Vec<ValidatorStake>,
+    ) -> LightClientBlockView {
+        let height = 10;
+        let mut block_view = LightClientBlockView {
+            prev_block_hash: hash(b"prev"),
+            next_block_inner_hash: hash(b"next"),
+            inner_lite: BlockHeaderInnerLiteView {
+                height,
+                epoch_id: epoch_id.0,
+                next_epoch_id: hash(b"next_epoch"),
+                prev_state_root: CryptoHash::default(),
+                outcome_root: CryptoHash::default(),
+                timestamp: 0,
+                timestamp_nanosec: 0,
+                next_bp_hash: CryptoHash::hash_borsh_iter(&next_bps),
+                block_merkle_root: CryptoHash::default(),
+            },
+            inner_rest_hash: hash(b"rest"),
+            next_bps: Some(next_bps.into_iter().map(Into::into).collect()),
+            approvals_after_next: vec![],
+        };
+        let block_hash = get_light_client_block_hash(&block_view);
+        let next_block_hash = combine_hash(&block_view.next_block_inner_hash, &block_hash);
+        block_view.approvals_after_next = (0..signers.len())
+            .map(|i| {
+                approvers.contains(&i).then(|| {
+                    signers[i]
+                        .sign_approval(&ApprovalInner::Endorsement(next_block_hash), height + 2)
+                })
+            })
+            .collect();
+        block_view
+    }
+
+    #[test]
+    fn test_validate_light_client_block() {
+        let epoch_id = EpochId(hash(b"epoch"));
+        let signers = signers(&["test0", "test1", "test2"]);
+        let bps = stakes(&signers);
+        let next_bps = stakes(&self::signers(&["test3", "test4"]));
+
+        let block_view = make_block(&epoch_id, &signers, &[0, 1, 2], next_bps.clone());
+        assert_eq!(
+            validate_light_client_block(&block_view, &epoch_id, &bps).unwrap(),
+            get_light_client_block_hash(&block_view)
+        );
+
+        // Exactly 2/3 of the stake is not enough.
+        let block_view = make_block(&epoch_id, &signers, &[0, 2], next_bps.clone());
+        assert_matches!(
+            validate_light_client_block(&block_view, &epoch_id, &bps),
+            Err(Error::NotEnoughApprovals)
+        );
+
+        let block_view = make_block(&epoch_id, &signers, &[0, 1, 2], next_bps);
+        assert_matches!(
+            validate_light_client_block(&block_view, &EpochId(hash(b"other")), &bps),
+            Err(Error::InvalidEpochHash)
+        );
+
+        // Approvals from block producers other than the expected ones.
+        let mut wrong_bps = bps.clone();
+        wrong_bps.swap(0, 1);
+        assert_matches!(
+            validate_light_client_block(&block_view, &epoch_id, &wrong_bps),
+            Err(Error::InvalidSignature)
+        );
+
+        // Next block producers not matching the committed hash.
+        let mut tampered = block_view;
+        tampered.next_bps.as_mut().unwrap().pop();
+        assert_matches!(
+            validate_light_client_block(&tampered, &epoch_id, &bps),
+            Err(Error::InvalidNextBPHash)
+        );
+        tampered.next_bps = None;
+        assert_matches!(
+            validate_light_client_block(&tampered, &epoch_id, &bps),
+            Err(Error::InvalidNextBPHash)
+        );
+    }
+}
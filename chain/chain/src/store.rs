@@ -39,8 +39,8 @@ use near_primitives::utils::{
 use near_primitives::views::LightClientBlockView;
 use near_store::{
     DBCol, KeyForStateChanges, ShardTries, Store, StoreUpdate, WrappedTrieChanges, CHUNK_TAIL_KEY,
-    FINAL_HEAD_KEY, FORK_TAIL_KEY, HEADER_HEAD_KEY, HEAD_KEY, LARGEST_TARGET_HEIGHT_KEY,
-    LATEST_KNOWN_KEY, TAIL_KEY,
+    COLD_HEAD_KEY, FINAL_HEAD_KEY, FORK_TAIL_KEY, HEADER_HEAD_KEY, HEAD_KEY,
+    LARGEST_TARGET_HEIGHT_KEY, LATEST_KNOWN_KEY, TAIL_KEY,
 };
 
 use crate::chunks_store::ReadOnlyChunksStore;
@@ -416,6 +416,14 @@ impl ChainStore {
         ChainStoreUpdate::new(self)
     }
 
+    /// Tip up to which the data has been copied to cold storage.
+    ///
+    /// Returns `None` if the node doesn’t have split storage or if nothing has
+    /// been copied to cold storage yet.
+    pub fn cold_head(&self) -> Result<Option<Tip>, Error> {
+        self.store.get_ser(DBCol::BlockMisc, COLD_HEAD_KEY).map_err(|e| e.into())
+    }
+
     pub fn iterate_state_sync_infos(&self) -> Result<Vec<(CryptoHash, StateSyncInfo)>, Error> {
         self.store
             .iter(DBCol::StateDlInfos)
@@ -1619,13 +1627,18 @@ impl<'a> ChainStoreUpdate<'a> {
     pub fn force_save_header_head(&mut self, t: &Tip) -> Result<(), Error> {
         self.try_save_latest_known(t.height)?;
 
-        // TODO #3488
-        // Bowen: It seems that height_to_hashes is used to update DBCol::BlockHeight, which stores blocks,
-        // not block headers, by height. Therefore I wonder whether this line here breaks some invariant
-        // since now we potentially don't have the corresponding block in storage.
-
-        //self.chain_tore_cache_update.height_to_hashes.insert(t.height, Some(t.last_block_hash));
-        //self.chain_store_cache_update.next_block_hashes.insert(t.prev_block_hash, t.last_block_hash);
+        // Header sync walks the height index back from new headers until it
+        // finds a known one so the header head needs to be indexed just like
+        // headers saved in `save_header_head_if_not_challenged` are.  Block
+        // merkle tree of the header head must have been saved already.
+        let block_ordinal = self.get_block_merkle_tree(&t.last_block_hash)?.size();
+        self.chain_store_cache_update
+            .block_ordinal_to_hash
+            .insert(block_ordinal, t.last_block_hash);
+        self.chain_store_cache_update.height_to_hashes.insert(t.height, Some(t.last_block_hash));
+        self.chain_store_cache_update
+            .next_block_hashes
+            .insert(t.prev_block_hash, t.last_block_hash);
         self.header_head = Some(t.clone());
         Ok(())
     }
@@ -2409,7 +2422,8 @@ impl<'a> ChainStoreUpdate<'a> {
             | DBCol::_TransactionRefCount
             | DBCol::_TransactionResult
             | DBCol::StateChangesForSplitStates
-            | DBCol::CachedContractCode => {
+            | DBCol::CachedContractCode
+            | DBCol::CachedContractCodeInfo => {
                 unreachable!();
             }
             #[cfg(feature = "protocol_feature_flat_state")]
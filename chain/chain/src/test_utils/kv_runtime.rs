@@ -45,7 +45,9 @@ use near_store::{
     DBCol, PartialStorage, ShardTries, Store, StoreUpdate, Trie, TrieChanges, WrappedTrieChanges,
 };
 
-use crate::types::{ApplySplitStateResult, ApplyTransactionResult, BlockHeaderInfo};
+use crate::types::{
+    ApplySplitStateResult, ApplyTransactionResult, BlockHeaderInfo, SimulateTransactionResult,
+};
 use crate::{BlockHeader, RuntimeAdapter};
 
 use near_primitives::epoch_manager::ShardConfig;
@@ -896,6 +898,14 @@ impl RuntimeAdapter for KeyValueRuntime {
         false
     }
 
+    fn update_tracked_accounts(
+        &self,
+        _tracked_accounts: Vec<AccountId>,
+        _epoch_id: &EpochId,
+    ) -> Result<Option<EpochHeight>, Error> {
+        unimplemented!();
+    }
+
     fn validate_tx(
         &self,
         _gas_price: Balance,
@@ -1111,6 +1121,19 @@ impl RuntimeAdapter for KeyValueRuntime {
         })
     }
 
+    fn simulate_transaction(
+        &self,
+        _state_roots: &HashMap<ShardId, StateRoot>,
+        _height: BlockHeight,
+        _block_timestamp: u64,
+        _prev_block_hash: &CryptoHash,
+        _gas_price: Balance,
+        _gas_limit: Option<Gas>,
+        _transaction: &SignedTransaction,
+    ) -> Result<SimulateTransactionResult, Error> {
+        unimplemented!();
+    }
+
     fn check_state_transition(
         &self,
         _partial_storage: PartialStorage,
@@ -23,8 +23,8 @@ use near_primitives::state_part::PartId;
 use near_primitives::transaction::{ExecutionOutcomeWithId, SignedTransaction};
 use near_primitives::types::validator_stake::{ValidatorStake, ValidatorStakeIter};
 use near_primitives::types::{
-    AccountId, Balance, BlockHeight, BlockHeightDelta, EpochId, Gas, MerkleHash, NumBlocks,
-    ShardId, StateChangesForSplitStates, StateRoot, StateRootNode,
+    AccountId, Balance, BlockHeight, BlockHeightDelta, EpochHeight, EpochId, Gas, MerkleHash,
+    NumBlocks, ShardId, StateChanges, StateChangesForSplitStates, StateRoot, StateRootNode,
 };
 use near_primitives::version::{
     ProtocolVersion, MIN_GAS_PRICE_NEP_92, MIN_GAS_PRICE_NEP_92_FIX, MIN_PROTOCOL_VERSION_NEP_92,
@@ -106,6 +106,16 @@ pub struct ApplyTransactionResult {
     pub processed_delayed_receipts: Vec<Receipt>,
 }
 
+/// Result of `RuntimeAdapter::simulate_transaction`.
+pub struct SimulateTransactionResult {
+    /// Outcomes of the transaction and of all the receipts it caused, in execution order.
+    pub outcomes: Vec<ExecutionOutcomeWithId>,
+    /// Changes made by the transaction and its receipts.
+    pub state_changes: StateChanges,
+    /// Receipts which were not executed because their receiver's shard is not tracked.
+    pub unprocessed_receipts: Vec<Receipt>,
+}
+
 impl ApplyTransactionResult {
     /// Returns root and paths for all the outcomes in the result.
     pub fn compute_outcomes_proof(
@@ -385,6 +395,16 @@ pub trait RuntimeAdapter: EpochManagerAdapter + Send + Sync {
         is_me: bool,
     ) -> bool;
 
+    /// Changes the accounts whose shards the client tracks, see `tracked_accounts` of the
+    /// client config. The change applies from the epoch after the next one of `epoch_id`,
+    /// so that the client can catch up the newly tracked shards during the next epoch.
+    /// Returns the height of that epoch, or None if the client tracks all shards.
+    fn update_tracked_accounts(
+        &self,
+        tracked_accounts: Vec<AccountId>,
+        epoch_id: &EpochId,
+    ) -> Result<Option<EpochHeight>, Error>;
+
     /// Get the block height for which garbage collection should not go over
     fn get_gc_stop_height(&self, block_hash: &CryptoHash) -> BlockHeight;
 
@@ -467,6 +487,22 @@ pub trait RuntimeAdapter: EpochManagerAdapter + Send + Sync {
         use_flat_storage: bool,
     ) -> Result<ApplyTransactionResult, Error>;
 
+    /// Executes the transaction on top of the post-state of `prev_block_hash` as if it was
+    /// included in the next block, without committing anything. Receipts produced by it are
+    /// followed as long as the state root of their receiver's shard is in `state_roots`.
+    /// The simulation fails once it burns more than `gas_limit`, which defaults to the gas
+    /// limit of a chunk when not set.
+    fn simulate_transaction(
+        &self,
+        state_roots: &HashMap<ShardId, StateRoot>,
+        height: BlockHeight,
+        block_timestamp: u64,
+        prev_block_hash: &CryptoHash,
+        gas_price: Balance,
+        gas_limit: Option<Gas>,
+        transaction: &SignedTransaction,
+    ) -> Result<SimulateTransactionResult, Error>;
+
     fn check_state_transition(
         &self,
         partial_storage: PartialStorage,
@@ -565,6 +601,15 @@ pub trait RuntimeAdapter: EpochManagerAdapter + Send + Sync {
     ) -> Result<bool, Error>;
 
     fn get_protocol_config(&self, epoch_id: &EpochId) -> Result<ProtocolConfig, Error>;
+
+    /// Returns gas traces of the function calls of a recently executed receipt.
+    #[cfg(feature = "sandbox")]
+    fn get_gas_traces(
+        &self,
+        _receipt_id: &CryptoHash,
+    ) -> Option<Vec<near_primitives::gas_trace::FunctionCallTrace>> {
+        None
+    }
 }
 
 /// The last known / checked height and time when we have processed it.
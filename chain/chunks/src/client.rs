@@ -1,9 +1,10 @@
 use std::collections::HashMap;
 
 use actix::Message;
+use near_chain_configs::TransactionPoolConfig;
 use near_network::types::MsgRecipient;
 use near_o11y::{WithSpanContext, WithSpanContextExt};
-use near_pool::{PoolIteratorWrapper, TransactionPool};
+use near_pool::{InsertTransactionResult, PoolIteratorWrapper, TransactionPool};
 use near_primitives::{
     epoch_manager::RngSeed,
     sharding::{EncodedShardChunk, PartialEncodedChunk, ShardChunk, ShardChunkHeader},
@@ -65,20 +66,26 @@ pub struct ShardedTransactionPool {
     /// Useful to make tests deterministic and reproducible,
     /// while keeping the security of randomization of transactions in pool
     rng_seed: RngSeed,
+
+    /// Limits applied to the pool of every shard.
+    pool_config: TransactionPoolConfig,
 }
 
 impl ShardedTransactionPool {
-    pub fn new(rng_seed: RngSeed) -> Self {
+    pub fn new(rng_seed: RngSeed, pool_config: TransactionPoolConfig) -> Self {
         TransactionPool::init_metrics();
-        Self { tx_pools: HashMap::new(), rng_seed }
+        Self { tx_pools: HashMap::new(), rng_seed, pool_config }
     }
 
     pub fn get_pool_iterator(&mut self, shard_id: ShardId) -> Option<PoolIteratorWrapper<'_>> {
         self.tx_pools.get_mut(&shard_id).map(|pool| pool.pool_iterator())
     }
 
-    /// Returns true if transaction is not in the pool before call
-    pub fn insert_transaction(&mut self, shard_id: ShardId, tx: SignedTransaction) -> bool {
+    pub fn insert_transaction(
+        &mut self,
+        shard_id: ShardId,
+        tx: SignedTransaction,
+    ) -> InsertTransactionResult {
         self.pool_for_shard(shard_id).insert_transaction(tx)
     }
 
@@ -88,6 +95,13 @@ impl ShardedTransactionPool {
         }
     }
 
+    /// Drops all transactions from the pools of all shards.
+    pub fn clear(&mut self) {
+        for pool in self.tx_pools.values_mut() {
+            pool.clear();
+        }
+    }
+
     /// Computes a deterministic random seed for given `shard_id`.
     /// This seed is used to randomize the transaction pool.
     /// For better security we want the seed to different in each shard.
@@ -100,9 +114,12 @@ impl ShardedTransactionPool {
     }
 
     fn pool_for_shard(&mut self, shard_id: ShardId) -> &mut TransactionPool {
-        self.tx_pools
-            .entry(shard_id)
-            .or_insert_with(|| TransactionPool::new(Self::random_seed(&self.rng_seed, shard_id)))
+        self.tx_pools.entry(shard_id).or_insert_with(|| {
+            TransactionPool::new(
+                Self::random_seed(&self.rng_seed, shard_id),
+                self.pool_config.clone(),
+            )
+        })
     }
 
     pub fn reintroduce_transactions(
@@ -1,5 +1,5 @@
 use once_cell::sync::OnceCell;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::Arc;
 
@@ -8,10 +8,12 @@ use chrono::DateTime;
 use near_primitives::time::Utc;
 
 use near_chain_configs::ProtocolConfigView;
+use near_primitives::errors::InvalidTxError;
 use near_primitives::hash::CryptoHash;
 use near_primitives::merkle::{MerklePath, PartialMerkleTree};
 use near_primitives::network::PeerId;
 use near_primitives::sharding::ChunkHash;
+use near_primitives::transaction::SignedTransaction;
 use near_primitives::types::{
     AccountId, BlockHeight, BlockReference, EpochId, EpochReference, MaybeBlockId, ShardId,
     TransactionOrReceiptId,
@@ -709,6 +711,8 @@ impl Message for GetStateChangesWithCauseInBlock {
 pub struct GetStateChangesWithCauseInBlockForTrackedShards {
     pub block_hash: CryptoHash,
     pub epoch_id: EpochId,
+    /// If set, only changes of accounts on these shards are returned.
+    pub shard_ids: Option<HashSet<ShardId>>,
 }
 
 impl Message for GetStateChangesWithCauseInBlockForTrackedShards {
@@ -917,13 +921,62 @@ impl From<near_chain_primitives::Error> for GetMaintenanceWindowsError {
     }
 }
 
+pub struct SimulateTransaction {
+    pub signed_transaction: SignedTransaction,
+}
+
+/// Outcomes and state changes of a transaction executed on top of the chain head.
+#[derive(Debug)]
+pub struct SimulateTransactionResponse {
+    /// Head block the transaction was executed on top of.
+    pub block_hash: CryptoHash,
+    pub transaction_outcome: ExecutionOutcomeWithIdView,
+    pub receipts_outcome: Vec<ExecutionOutcomeWithIdView>,
+    pub state_changes: StateChangesView,
+    /// Receipts for shards not tracked by the node, which were not executed.
+    pub unprocessed_receipts: Vec<ReceiptView>,
+}
+
+impl Message for SimulateTransaction {
+    type Result = Result<SimulateTransactionResponse, SimulateTransactionError>;
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum SimulateTransactionError {
+    #[error("Transaction is invalid: {0:?}")]
+    InvalidTransaction(InvalidTxError),
+    #[error("Node doesn't track the shard of the transaction signer")]
+    DoesNotTrackShard,
+    #[error("IO Error: {0}")]
+    IOError(String),
+    #[error("It is a bug if you receive this error type, please, report this incident: https://github.com/near/nearcore/issues/new/choose. Details: {0}")]
+    Unreachable(String),
+}
+
+impl From<near_chain_primitives::Error> for SimulateTransactionError {
+    fn from(error: near_chain_primitives::Error) -> Self {
+        match error {
+            near_chain_primitives::Error::IOErr(error) => Self::IOError(error.to_string()),
+            _ => Self::Unreachable(error.to_string()),
+        }
+    }
+}
+
 #[cfg(feature = "sandbox")]
 #[derive(Debug)]
 pub enum SandboxMessage {
     SandboxPatchState(Vec<near_primitives::state_record::StateRecord>),
+    /// Replaces the whole state of the accounts with the records.
+    SandboxReplaceAccounts(
+        Vec<near_primitives::types::AccountId>,
+        Vec<near_primitives::state_record::StateRecord>,
+    ),
     SandboxPatchStateStatus,
     SandboxFastForward(near_primitives::types::BlockHeightDelta),
     SandboxFastForwardStatus,
+    SandboxSnapshot(String),
+    SandboxRestore(String),
+    SandboxGasTrace(near_primitives::hash::CryptoHash),
 }
 
 #[cfg(feature = "sandbox")]
@@ -932,6 +985,9 @@ pub enum SandboxResponse {
     SandboxPatchStateFinished(bool),
     SandboxFastForwardFinished(bool),
     SandboxFastForwardFailed(String),
+    SandboxSnapshotFailed(String),
+    SandboxRestoreFailed(String),
+    SandboxGasTrace(Option<Vec<near_primitives::gas_trace::FunctionCallTrace>>),
     SandboxNoResponse,
 }
 #[cfg(feature = "sandbox")]
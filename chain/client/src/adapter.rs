@@ -12,6 +12,7 @@ use near_primitives::errors::InvalidTxError;
 use near_primitives::hash::CryptoHash;
 use near_primitives::network::{AnnounceAccount, PeerId};
 use near_primitives::sharding::PartialEncodedChunk;
+use near_primitives::syncing::{EpochSyncFinalizationResponse, EpochSyncResponse};
 use near_primitives::transaction::SignedTransaction;
 use near_primitives::types::{AccountId, EpochId, ShardId};
 use near_primitives::views::FinalExecutionOutcomeView;
@@ -57,6 +58,29 @@ pub(crate) struct BlockHeadersRequest(pub Vec<CryptoHash>);
 #[rtype(result = "Result<(),ReasonForBan>")]
 pub(crate) struct BlockHeadersResponse(pub Vec<BlockHeader>, pub PeerId);
 
+/// Request light client block of given epoch (epoch sync).
+#[derive(actix::Message)]
+#[rtype(result = "Option<Box<EpochSyncResponse>>")]
+pub(crate) struct EpochSyncRequest(pub EpochId);
+
+/// Epoch sync response.
+#[derive(actix::Message, Debug)]
+#[rtype(result = "()")]
+pub(crate) struct EpochSyncResponseMessage(pub Box<EpochSyncResponse>, pub PeerId);
+
+/// Request data needed to finish epoch sync at given epoch.
+#[derive(actix::Message)]
+#[rtype(result = "Option<Box<EpochSyncFinalizationResponse>>")]
+pub(crate) struct EpochSyncFinalizationRequest(pub EpochId);
+
+/// Epoch sync finalization response.
+#[derive(actix::Message, Debug)]
+#[rtype(result = "()")]
+pub(crate) struct EpochSyncFinalizationResponseMessage(
+    pub Box<EpochSyncFinalizationResponse>,
+    pub PeerId,
+);
+
 /// State request header.
 #[derive(actix::Message)]
 #[rtype(result = "Option<StateResponse>")]
@@ -134,6 +158,9 @@ pub enum ProcessTxResponse {
     /// The node being queried does not track the shard needed and therefore cannot provide userful
     /// response.
     DoesNotTrackShard,
+    /// The transaction pool is full and the transaction was rejected.  The caller should retry
+    /// later.
+    TransactionPoolFull,
 }
 
 pub struct Adapter {
@@ -366,6 +393,59 @@ impl near_network::client::Client for Adapter {
         }
     }
 
+    async fn epoch_sync_request(&self, epoch_id: EpochId) -> Option<Box<EpochSyncResponse>> {
+        match self.view_client_addr.send(EpochSyncRequest(epoch_id).with_span_context()).await {
+            Ok(res) => res,
+            Err(err) => {
+                tracing::error!("mailbox error: {err}");
+                None
+            }
+        }
+    }
+
+    async fn epoch_sync_response(&self, resp: Box<EpochSyncResponse>, peer_id: PeerId) {
+        match self
+            .client_addr
+            .send(EpochSyncResponseMessage(resp, peer_id).with_span_context())
+            .await
+        {
+            Ok(()) => {}
+            Err(err) => tracing::error!("mailbox error: {err}"),
+        }
+    }
+
+    async fn epoch_sync_finalization_request(
+        &self,
+        epoch_id: EpochId,
+    ) -> Option<Box<EpochSyncFinalizationResponse>> {
+        match self
+            .view_client_addr
+            .send(EpochSyncFinalizationRequest(epoch_id).with_span_context())
+            .await
+        {
+            Ok(res) => res,
+            Err(err) => {
+                tracing::error!("mailbox error: {err}");
+                None
+            }
+        }
+    }
+
+    async fn epoch_sync_finalization_response(
+        &self,
+        resp: Box<EpochSyncFinalizationResponse>,
+        peer_id: PeerId,
+    ) {
+        match self
+            .client_addr
+            .send(EpochSyncFinalizationResponseMessage(resp, peer_id).with_span_context())
+            .await
+        {
+            Ok(()) => {}
+            Err(err) => tracing::error!("mailbox error: {err}"),
+        }
+    }
+
     async fn network_info(&self, info: NetworkInfo) {
         match self.client_addr.send(SetNetworkInfo(info).with_span_context()).await {
             Ok(()) => {}
@@ -30,6 +30,7 @@ use near_chunks::ShardsManager;
 use near_network::types::{
     HighestHeightPeerInfo, NetworkRequests, PeerManagerAdapter, ReasonForBan,
 };
+use near_pool::InsertTransactionResult;
 use near_primitives::block::{Approval, ApprovalInner, ApprovalMessage, Block, BlockHeader, Tip};
 use near_primitives::challenge::{Challenge, ChallengeBody};
 use near_primitives::hash::CryptoHash;
@@ -41,7 +42,9 @@ use near_primitives::sharding::{
 };
 use near_primitives::transaction::SignedTransaction;
 use near_primitives::types::chunk_extra::ChunkExtra;
-use near_primitives::types::{AccountId, ApprovalStake, BlockHeight, EpochId, NumBlocks, ShardId};
+use near_primitives::types::{
+    AccountId, ApprovalStake, BlockHeight, EpochHeight, EpochId, NumBlocks, ShardId,
+};
 use near_primitives::unwrap_or_return;
 use near_primitives::utils::MaybeValidated;
 use near_primitives::validator_signer::ValidatorSigner;
@@ -51,6 +54,7 @@ use crate::debug::BlockProductionTracker;
 use crate::debug::PRODUCTION_TIMES_CACHE_SIZE;
 use crate::sync::block::BlockSync;
 use crate::sync::epoch::EpochSync;
+use crate::sync::external::{new_external_state_part_source, ExternalStatePartSource};
 use crate::sync::header::HeaderSync;
 use crate::sync::state::{StateSync, StateSyncResult};
 use crate::{metrics, SyncStatus};
@@ -68,11 +72,7 @@ const CHUNK_HEADERS_FOR_INCLUSION_CACHE_SIZE: usize = 2048;
 const NUM_EPOCH_CHUNK_PRODUCERS_TO_KEEP_IN_BLOCKLIST: usize = 1000;
 
 /// The time we wait for the response to a Epoch Sync request before retrying
-// TODO #3488 set 30_000
-pub const EPOCH_SYNC_REQUEST_TIMEOUT: Duration = Duration::from_millis(1_000);
-/// How frequently a Epoch Sync response can be sent to a particular peer
-// TODO #3488 set 60_000
-pub const EPOCH_SYNC_PEER_TIMEOUT: Duration = Duration::from_millis(10);
+pub const EPOCH_SYNC_REQUEST_TIMEOUT: Duration = Duration::from_millis(10_000);
 /// Drop blocks whose height are beyond head + horizon if it is not in the current epoch.
 const BLOCK_HORIZON: u64 = 500;
 
@@ -93,6 +93,10 @@ pub struct Client {
     /// Fast Forward accrued delta height used to calculate fast forwarded timestamps for each block.
     #[cfg(feature = "sandbox")]
     pub(crate) accrued_fastforward_delta: near_primitives::types::BlockHeightDelta,
+    /// Named sandbox snapshots together with the fast forward delta accrued when they were taken.
+    #[cfg(feature = "sandbox")]
+    sandbox_snapshots:
+        HashMap<String, (near_chain::SandboxSnapshot, near_primitives::types::BlockHeightDelta)>,
 
     pub config: ClientConfig,
     pub sync_status: SyncStatus,
@@ -126,6 +130,9 @@ pub struct Client {
     pub block_sync: BlockSync,
     /// Keeps track of syncing state.
     pub state_sync: StateSync,
+    /// Source of state parts tried before peers, shared by state sync and
+    /// catchups.
+    external_state_part_source: Option<Arc<dyn ExternalStatePartSource>>,
     /// List of currently accumulated challenges.
     pub challenges: HashMap<CryptoHash, Challenge>,
     /// A ReedSolomon instance to reconstruct shard.
@@ -192,7 +199,7 @@ impl Client {
             &chain_genesis,
             doomslug_threshold_mode,
             ChainConfig {
-                save_trie_changes: !config.archive,
+                save_trie_changes: !config.archive || config.split_storage,
                 background_migration_threads: config.client_background_migration_threads,
             },
         )?;
@@ -205,13 +212,13 @@ impl Client {
             chain.store().new_read_only_chunks_store(),
             chain.head().ok(),
         );
-        let sharded_tx_pool = ShardedTransactionPool::new(rng_seed);
+        let sharded_tx_pool =
+            ShardedTransactionPool::new(rng_seed, config.transaction_pool.clone());
         let sync_status = SyncStatus::AwaitingPeers;
         let genesis_block = chain.genesis_block();
         let epoch_sync = EpochSync::new(
             network_adapter.clone(),
             genesis_block.header().epoch_id().clone(),
-            genesis_block.header().next_epoch_id().clone(),
             runtime_adapter
                 .get_epoch_block_producers_ordered(
                     genesis_block.header().epoch_id(),
@@ -221,7 +228,7 @@ impl Client {
                 .map(|x| x.0.clone())
                 .collect(),
             EPOCH_SYNC_REQUEST_TIMEOUT,
-            EPOCH_SYNC_PEER_TIMEOUT,
+            config.epoch_sync_enabled && !config.archive,
         );
         let header_sync = HeaderSync::new(
             network_adapter.clone(),
@@ -232,7 +239,13 @@ impl Client {
         );
         let block_sync =
             BlockSync::new(network_adapter.clone(), config.block_fetch_horizon, config.archive);
-        let state_sync = StateSync::new(network_adapter.clone(), config.state_sync_timeout);
+        let external_state_part_source =
+            config.state_sync_external_parts.as_ref().map(new_external_state_part_source);
+        let state_sync = StateSync::new(
+            network_adapter.clone(),
+            config.state_sync_timeout,
+            external_state_part_source.clone(),
+        );
         let num_block_producer_seats = config.num_block_producer_seats as usize;
         let data_parts = runtime_adapter.num_data_parts();
         let parity_parts = runtime_adapter.num_total_parts() - data_parts;
@@ -257,6 +270,8 @@ impl Client {
             produce_invalid_tx_in_chunks: false,
             #[cfg(feature = "sandbox")]
             accrued_fastforward_delta: 0,
+            #[cfg(feature = "sandbox")]
+            sandbox_snapshots: HashMap::new(),
             config,
             sync_status,
             chain,
@@ -279,6 +294,7 @@ impl Client {
             header_sync,
             block_sync,
             state_sync,
+            external_state_part_source,
             challenges: Default::default(),
             rs_for_chunk_production: ReedSolomonWrapper::new(data_parts, parity_parts),
             rebroadcasted_blocks: lru::LruCache::new(NUM_REBROADCAST_BLOCKS),
@@ -1336,6 +1352,38 @@ impl Client {
         Ok(())
     }
 
+    /// Records the current chain head under `name`, replacing any snapshot with the same name.
+    #[cfg(feature = "sandbox")]
+    pub fn sandbox_snapshot(&mut self, name: String) -> Result<(), Error> {
+        let snapshot = self.chain.sandbox_snapshot()?;
+        self.sandbox_snapshots.insert(name, (snapshot, self.accrued_fastforward_delta));
+        Ok(())
+    }
+
+    /// Rewinds the chain head to the snapshot recorded under `name`, drops all pending
+    /// transactions and restores the fast forward delta accrued at the time of the snapshot.
+    #[cfg(feature = "sandbox")]
+    pub fn sandbox_restore(&mut self, name: &str) -> Result<(), Error> {
+        let (snapshot, delta) = self
+            .sandbox_snapshots
+            .get(name)
+            .cloned()
+            .ok_or_else(|| Error::Other(format!("sandbox snapshot {} not found", name)))?;
+        self.chain.sandbox_restore(&snapshot)?;
+        self.accrued_fastforward_delta = delta;
+        self.sharded_tx_pool.clear();
+
+        // Heights up to the latest known one may already have been signed, so new blocks are
+        // produced above it to avoid double signing.
+        let latest_known = self.chain.store().get_latest_known()?;
+        let new_latest_known = near_chain::types::LatestKnown {
+            height: latest_known.height + 1,
+            seen: near_primitives::utils::to_timestamp(Clock::utc() + self.sandbox_delta_time()),
+        };
+        self.chain.mut_store().save_latest_known(new_latest_known.clone())?;
+        self.sandbox_update_tip(new_latest_known.height)
+    }
+
     /// Gets the advanced timestamp delta in nanoseconds for sandbox once it has been fast-forwarded
     #[cfg(feature = "sandbox")]
     pub fn sandbox_delta_time(&self) -> chrono::Duration {
@@ -1434,15 +1482,37 @@ impl Client {
                 .entered();
                 let _gc_timer = metrics::GC_TIME.start_timer();
 
-                let result = if self.config.archive {
-                    self.chain.clear_archive_data(self.config.gc.gc_blocks_limit)
+                // With split storage, hot storage is treated as an archive
+                // until some data is copied to cold storage.
+                let gc_hot_storage = self.config.split_storage
+                    && matches!(self.chain.store().cold_head(), Ok(Some(_)));
+                // gc_blocks_limit can be changed at runtime.
+                let mut gc_config = self.config.gc.clone();
+                if let Some(configs) = &*near_dyn_configs::UPDATEABLE_CONFIGS.borrow() {
+                    gc_config.gc_blocks_limit = configs.gc_blocks_limit;
+                }
+                let result = if self.config.archive && !gc_hot_storage {
+                    self.chain.clear_archive_data(gc_config.gc_blocks_limit)
                 } else {
                     let tries = self.runtime_adapter.get_tries();
-                    self.chain.clear_data(tries, &self.config.gc)
+                    self.chain.clear_data(tries, &gc_config)
                 };
                 log_assert!(result.is_ok(), "Can't clear old data, {:?}", result);
             }
 
+            // tracked_accounts can be changed at runtime.
+            let tracked_accounts = near_dyn_configs::UPDATEABLE_CONFIGS
+                .borrow()
+                .as_ref()
+                .map(|configs| configs.tracked_accounts.clone());
+            if let Some(tracked_accounts) = tracked_accounts {
+                if tracked_accounts != self.config.tracked_accounts {
+                    if let Err(err) = self.update_tracked_accounts(tracked_accounts) {
+                        error!(target: "client", "Failed to update tracked accounts: {err}");
+                    }
+                }
+            }
+
             // send_network_chain_info should be called whenever the chain head changes.
             // See send_network_chain_info() for more details.
             if let Err(err) = self.send_network_chain_info() {
@@ -2004,8 +2074,22 @@ impl Client {
                 // TODO #6713: Transactions don't need to be recorded if the node is not a validator
                 // for the shard.
                 // If I'm not an active validator I should forward tx to next validators.
-                self.sharded_tx_pool.insert_transaction(shard_id, tx.clone());
-                trace!(target: "client", shard_id, "Recorded a transaction.");
+                match self.sharded_tx_pool.insert_transaction(shard_id, tx.clone()) {
+                    InsertTransactionResult::Success => {
+                        trace!(target: "client", shard_id, "Recorded a transaction.");
+                    }
+                    InsertTransactionResult::Duplicate => {
+                        trace!(target: "client", shard_id, "Duplicate transaction, not recording it.");
+                    }
+                    InsertTransactionResult::NoSpaceLeft
+                    | InsertTransactionResult::SignerLimitExceeded => {
+                        if active_validator {
+                            debug!(target: "client", shard_id, "Transaction pool is full, rejecting the transaction.");
+                            return Ok(ProcessTxResponse::TransactionPoolFull);
+                        }
+                        trace!(target: "client", shard_id, "Transaction pool is full, not recording the transaction.");
+                    }
+                }
 
                 // Active validator:
                 //   possibly forward to next epoch validators
@@ -2067,6 +2151,22 @@ impl Client {
         Ok(false)
     }
 
+    /// Changes the accounts whose shards the client tracks. The newly tracked shards are
+    /// caught up during the next epoch, and tracked from the epoch after it, whose height
+    /// is returned. Returns None if the client tracks all shards.
+    pub fn update_tracked_accounts(
+        &mut self,
+        tracked_accounts: Vec<AccountId>,
+    ) -> Result<Option<EpochHeight>, Error> {
+        let head = self.chain.head()?;
+        let epoch_height = self
+            .runtime_adapter
+            .update_tracked_accounts(tracked_accounts.clone(), &head.epoch_id)?;
+        info!(target: "client", ?tracked_accounts, ?epoch_height, "Updated tracked accounts");
+        self.config.tracked_accounts = tracked_accounts;
+        Ok(epoch_height)
+    }
+
     /// Walks through all the ongoing state syncs for future epochs and processes them
     pub fn run_catchup(
         &mut self,
@@ -2118,11 +2218,16 @@ impl Client {
                 }
             };
             let state_sync_timeout = self.config.state_sync_timeout;
+            let external_source = &self.external_state_part_source;
             let epoch_id = self.chain.get_block(&sync_hash)?.header().epoch_id().clone();
             let (state_sync, new_shard_sync, blocks_catch_up_state) =
                 self.catchup_state_syncs.entry(sync_hash).or_insert_with(|| {
                     (
-                        StateSync::new(network_adapter1, state_sync_timeout),
+                        StateSync::new(
+                            network_adapter1,
+                            state_sync_timeout,
+                            external_source.clone(),
+                        ),
                         new_shard_sync,
                         BlocksCatchUpState::new(sync_hash, epoch_id),
                     )
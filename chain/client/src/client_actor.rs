@@ -6,9 +6,10 @@
 //! https://github.com/near/nearcore/issues/7899
 
 use crate::adapter::{
-    BlockApproval, BlockHeadersResponse, BlockResponse, ProcessTxRequest, ProcessTxResponse,
-    RecvChallenge, RecvPartialEncodedChunk, RecvPartialEncodedChunkForward,
-    RecvPartialEncodedChunkRequest, RecvPartialEncodedChunkResponse, SetNetworkInfo, StateResponse,
+    BlockApproval, BlockHeadersResponse, BlockResponse, EpochSyncFinalizationResponseMessage,
+    EpochSyncResponseMessage, ProcessTxRequest, ProcessTxResponse, RecvChallenge,
+    RecvPartialEncodedChunk, RecvPartialEncodedChunkForward, RecvPartialEncodedChunkRequest,
+    RecvPartialEncodedChunkResponse, SetNetworkInfo, StateResponse,
 };
 use crate::client::{Client, EPOCH_START_INFO_BLOCKS};
 use crate::debug::new_network_info_view;
@@ -486,6 +487,38 @@ impl Handler<WithSpanContext<BlockHeadersResponse>> for ClientActor {
     }
 }
 
+impl Handler<WithSpanContext<EpochSyncResponseMessage>> for ClientActor {
+    type Result = ();
+
+    fn handle(&mut self, msg: WithSpanContext<EpochSyncResponseMessage>, ctx: &mut Context<Self>) {
+        self.wrap(msg, ctx, "EpochSyncResponse", |this, msg| {
+            let EpochSyncResponseMessage(response, peer_id) = msg;
+            this.client.epoch_sync.on_response(*response, peer_id);
+        })
+    }
+}
+
+impl Handler<WithSpanContext<EpochSyncFinalizationResponseMessage>> for ClientActor {
+    type Result = ();
+
+    fn handle(
+        &mut self,
+        msg: WithSpanContext<EpochSyncFinalizationResponseMessage>,
+        ctx: &mut Context<Self>,
+    ) {
+        self.wrap(msg, ctx, "EpochSyncFinalizationResponse", |this, msg| {
+            let EpochSyncFinalizationResponseMessage(response, peer_id) = msg;
+            if let Err(err) = this.client.epoch_sync.on_finalization_response(
+                *response,
+                peer_id,
+                &mut this.client.chain,
+            ) {
+                error!(target: "sync", ?err, "Failed to finish epoch sync");
+            }
+        })
+    }
+}
+
 impl Handler<WithSpanContext<BlockApproval>> for ClientActor {
     type Result = ();
 
@@ -656,6 +689,18 @@ impl Handler<WithSpanContext<near_client_primitives::types::SandboxMessage>> for
                 );
                 near_client_primitives::types::SandboxResponse::SandboxNoResponse
             }
+            near_client_primitives::types::SandboxMessage::SandboxReplaceAccounts(
+                account_ids,
+                records,
+            ) => {
+                self.client.chain.patch_state(
+                    near_primitives::sandbox::state_patch::SandboxStatePatch::replace_accounts(
+                        account_ids,
+                        records,
+                    ),
+                );
+                near_client_primitives::types::SandboxResponse::SandboxNoResponse
+            }
             near_client_primitives::types::SandboxMessage::SandboxPatchStateStatus => {
                 near_client_primitives::types::SandboxResponse::SandboxPatchStateFinished(
                     !self.client.chain.patch_state_in_progress(),
@@ -675,6 +720,35 @@ impl Handler<WithSpanContext<near_client_primitives::types::SandboxMessage>> for
                     self.fastforward_delta == 0,
                 )
             }
+            near_client_primitives::types::SandboxMessage::SandboxSnapshot(name) => {
+                match self.client.sandbox_snapshot(name) {
+                    Ok(()) => near_client_primitives::types::SandboxResponse::SandboxNoResponse,
+                    Err(err) => {
+                        near_client_primitives::types::SandboxResponse::SandboxSnapshotFailed(
+                            err.to_string(),
+                        )
+                    }
+                }
+            }
+            near_client_primitives::types::SandboxMessage::SandboxRestore(name) => {
+                match self.client.sandbox_restore(&name) {
+                    Ok(()) => {
+                        // A fast forward in progress was relative to the abandoned head.
+                        self.fastforward_delta = 0;
+                        near_client_primitives::types::SandboxResponse::SandboxNoResponse
+                    }
+                    Err(err) => {
+                        near_client_primitives::types::SandboxResponse::SandboxRestoreFailed(
+                            err.to_string(),
+                        )
+                    }
+                }
+            }
+            near_client_primitives::types::SandboxMessage::SandboxGasTrace(receipt_id) => {
+                near_client_primitives::types::SandboxResponse::SandboxGasTrace(
+                    self.client.runtime_adapter.get_gas_traces(&receipt_id),
+                )
+            }
         }
     }
 }
@@ -1598,6 +1672,16 @@ impl ClientActor {
                 self.check_send_announce_account(head.prev_block_hash);
             }
         } else {
+            // Epoch sync (if needed) runs before any other kind of sync.
+            if unwrap_and_report!(self.client.epoch_sync.run(
+                &mut self.client.sync_status,
+                &self.client.chain,
+                highest_height,
+                &self.network_info.highest_height_peers,
+                self.client.config.epoch_length,
+            )) {
+                return;
+            }
             // Run each step of syncing separately.
             unwrap_and_report!(self.client.header_sync.run(
                 &mut self.client.sync_status,
@@ -1,7 +1,7 @@
 //! Structs in this file are used for debug purposes, and might change at any time
 //! without backwards compatibility.
 use crate::ClientActor;
-use actix::{Context, Handler};
+use actix::{Context, Handler, Message, ResponseFuture};
 use borsh::BorshSerialize;
 use itertools::Itertools;
 use near_chain::crypto_hash_timer::CryptoHashTimer;
@@ -25,9 +25,11 @@ use near_primitives::{
     types::EpochId,
     views::ValidatorInfo,
 };
+use near_store::backup::BackupInfo;
 use near_store::DBCol;
 use std::cmp::{max, min};
 use std::collections::{HashMap, HashSet};
+use std::path::{Component, Path};
 
 use near_client_primitives::debug::{DebugBlockStatus, DebugChunkStatus};
 use near_network::types::{ConnectedPeerInfo, NetworkInfo, PeerType};
@@ -717,3 +719,88 @@ pub(crate) fn new_network_info_view(chain: &Chain, network_info: &NetworkInfo) -
             .collect::<Vec<_>>(),
     }
 }
+
+// Request to create a checkpoint of node's database in `name` subdirectory of
+// the configured checkpoints directory.  Sent by the POST
+// /debug/api/checkpoint endpoint.
+#[derive(serde::Deserialize, Debug)]
+pub struct CreateCheckpoint {
+    pub name: String,
+}
+
+impl Message for CreateCheckpoint {
+    type Result = Result<BackupInfo, StatusError>;
+}
+
+// Request to compact node's database.  Sent by the POST /debug/api/compact
+// endpoint.
+#[derive(Debug)]
+pub struct CompactDatabase;
+
+impl Message for CompactDatabase {
+    type Result = Result<(), StatusError>;
+}
+
+/// Runs a blocking database operation on a thread of the blocking pool so that
+/// the client actor keeps processing messages while it’s in progress.
+fn run_blocking<T: Send + 'static>(
+    f: impl FnOnce() -> anyhow::Result<T> + Send + 'static,
+) -> ResponseFuture<Result<T, StatusError>> {
+    Box::pin(async move {
+        tokio::task::spawn_blocking(f)
+            .await
+            .map_err(|err| StatusError::Unreachable { error_message: err.to_string() })?
+            .map_err(|err| StatusError::InternalError { error_message: format!("{err:#}") })
+    })
+}
+
+impl Handler<WithSpanContext<CreateCheckpoint>> for ClientActor {
+    type Result = ResponseFuture<Result<BackupInfo, StatusError>>;
+
+    #[perf]
+    fn handle(
+        &mut self,
+        msg: WithSpanContext<CreateCheckpoint>,
+        _ctx: &mut Context<Self>,
+    ) -> Self::Result {
+        let (_span, msg) = handler_debug_span!(target: "client", msg);
+        let checkpoints_dir = match &self.client.config.checkpoints_dir {
+            Some(dir) => dir,
+            None => {
+                return Box::pin(futures::future::err(StatusError::InternalError {
+                    error_message: "checkpoints_dir is not configured".to_string(),
+                }))
+            }
+        };
+        // Checkpoints can only be created directly in the configured directory.
+        let mut components = Path::new(&msg.name).components();
+        if !matches!((components.next(), components.next()), (Some(Component::Normal(_)), None)) {
+            return Box::pin(futures::future::err(StatusError::InternalError {
+                error_message: format!("{:?}: invalid checkpoint name", msg.name),
+            }));
+        }
+        let path = checkpoints_dir.join(&msg.name);
+        let store = self.client.chain.store().store().clone();
+        run_blocking(move || near_store::backup::create_backup(&store, &path))
+    }
+}
+
+impl Handler<WithSpanContext<CompactDatabase>> for ClientActor {
+    type Result = ResponseFuture<Result<(), StatusError>>;
+
+    #[perf]
+    fn handle(
+        &mut self,
+        msg: WithSpanContext<CompactDatabase>,
+        _ctx: &mut Context<Self>,
+    ) -> Self::Result {
+        let (_span, _msg) = handler_debug_span!(target: "client", msg);
+        let store = self.client.chain.store().store().clone();
+        run_blocking(move || {
+            tracing::info!(target: "client", "Compacting database");
+            store.compact()?;
+            tracing::info!(target: "client", "Database compacted");
+            Ok(())
+        })
+    }
+}
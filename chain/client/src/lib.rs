@@ -4,7 +4,7 @@ pub use near_client_primitives::types::{
     GetMaintenanceWindows, GetNetworkInfo, GetNextLightClientBlock, GetProtocolConfig, GetReceipt,
     GetStateChanges, GetStateChangesInBlock, GetStateChangesWithCauseInBlock,
     GetStateChangesWithCauseInBlockForTrackedShards, GetValidatorInfo, GetValidatorOrdered, Query,
-    QueryError, Status, StatusResponse, SyncStatus, TxStatus, TxStatusError,
+    QueryError, SimulateTransaction, Status, StatusResponse, SyncStatus, TxStatus, TxStatusError,
 };
 
 pub use near_client_primitives::debug::DebugStatus;
@@ -14,6 +14,7 @@ pub use crate::adapter::{
 };
 pub use crate::client::Client;
 pub use crate::client_actor::{start_client, ClientActor};
+pub use crate::debug::{CompactDatabase, CreateCheckpoint};
 pub use crate::view_client::{start_view_client, ViewClientActor};
 
 pub mod adapter;
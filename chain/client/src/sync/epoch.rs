@@ -1,87 +1,359 @@
-use std::collections::{HashMap, HashSet};
-
+//! Epoch sync lets a node which is far behind the network skip downloading
+//! headers of all the epochs it has missed.
+//!
+//! Starting from genesis, the node requests light client block of each epoch
+//! and validates it against block producers of the epoch (see
+//! [`near_chain::validate_light_client_block`]).  A valid light client block
+//! carries block producers of the next epoch which lets the node move on to
+//! the next epoch.  Once a peer reports that it’s in the epoch the node has
+//! reached, the node requests data about the last epoch boundary it has
+//! crossed: headers around the boundary, block merkle tree and epoch manager
+//! data.  After validating it, the node saves the data and makes the last
+//! block before the boundary its header head so that header sync and state
+//! sync only need to cover the last two epochs.
+use std::collections::VecDeque;
 use std::sync::Arc;
 use std::time::Duration as TimeDuration;
 
 use chrono::{DateTime, Duration};
+use rand::seq::SliceRandom;
+use rand::thread_rng;
+use tracing::{debug, info, warn};
 
-use near_network::types::PeerManagerAdapter;
-
+use near_chain::{validate_light_client_block, Chain};
+use near_client_primitives::types::SyncStatus;
+use near_network::types::{
+    HighestHeightPeerInfo, NetworkRequests, PeerManagerAdapter, PeerManagerMessageRequest,
+    ReasonForBan,
+};
+use near_o11y::WithSpanContextExt;
+use near_primitives::block::BlockHeader;
 use near_primitives::hash::CryptoHash;
 use near_primitives::network::PeerId;
-
+use near_primitives::syncing::{EpochSyncFinalizationResponse, EpochSyncResponse};
 use near_primitives::time::{Clock, Utc};
 use near_primitives::types::validator_stake::ValidatorStake;
-use near_primitives::types::EpochId;
+use near_primitives::types::{BlockHeight, BlockHeightDelta, EpochId};
+use near_primitives::views::validator_stake_view::ValidatorStakeView;
+
+/// Number of requests in a row which may fail (time out or get an invalid
+/// response) before epoch sync is abandoned in favour of header sync.
+const MAX_FAILED_REQUESTS: u32 = 10;
+
+/// Epoch whose light client block has been validated.
+struct SyncedEpoch {
+    epoch_id: EpochId,
+    /// Block producers of the epoch.
+    block_producers: Vec<ValidatorStake>,
+    /// Hash of the block the light client block of the epoch was created for.
+    light_client_block_hash: CryptoHash,
+}
 
 /// Helper to keep track of the Epoch Sync
-// TODO #3488
-#[allow(dead_code)]
 pub struct EpochSync {
     network_adapter: Arc<dyn PeerManagerAdapter>,
-    /// Datastructure to keep track of when the last request to each peer was made.
-    /// Peers do not respond to Epoch Sync requests more frequently than once per a certain time
-    /// interval, thus there's no point in requesting more frequently.
-    peer_to_last_request_time: HashMap<PeerId, DateTime<Utc>>,
-    /// Tracks all the peers who have reported that we are already up to date
-    peers_reporting_up_to_date: HashSet<PeerId>,
-    /// The last epoch we are synced to
-    current_epoch_id: EpochId,
-    /// The next epoch id we need to sync
-    next_epoch_id: EpochId,
-    /// The block producers set to validate the light client block view for the next epoch
-    next_block_producers: Vec<ValidatorStake>,
-    /// The last epoch id that we have requested
-    requested_epoch_id: EpochId,
-    /// When and to whom was the last request made
-    last_request_time: DateTime<Utc>,
-    last_request_peer_id: Option<PeerId>,
-
-    /// How long to wait for a response before re-requesting the same light client block view
+    /// Epoch whose light client block is going to be requested next.
+    epoch_id: EpochId,
+    /// Block producers of `epoch_id` used to validate its light client block.
+    block_producers: Vec<ValidatorStake>,
+    /// Last two epochs whose light client blocks have been validated.
+    synced_epochs: VecDeque<SyncedEpoch>,
+    /// Number of epochs synced so far.
+    epoch_ord: u64,
+    /// Whether a peer has reported `epoch_id` as its current epoch in which
+    /// case finalisation data is requested rather than light client blocks.
+    finalizing: bool,
+    /// Peer the outstanding request was sent to and when.
+    request: Option<(PeerId, DateTime<Utc>)>,
+    /// How long to wait for a response before re-requesting from another peer.
     request_timeout: Duration,
-    /// How frequently to send request to the same peer
-    peer_timeout: Duration,
-
-    /// True, if all peers agreed that we're at the last Epoch.
-    /// Only finalization is needed.
-    have_all_epochs: bool,
-    /// Whether the Epoch Sync was performed to completion previously.
-    /// Current state machine allows for only one Epoch Sync.
+    /// Number of consecutive requests which got no valid response.
+    num_failed_requests: u32,
+    /// Whether it has been checked that the node is far enough behind for
+    /// epoch sync to be worth it.
+    started: bool,
+    /// Whether the Epoch Sync was performed to completion previously (or
+    /// wasn’t needed at all).  Current state machine allows for only one
+    /// Epoch Sync.
     pub done: bool,
-
-    pub sync_hash: CryptoHash,
-
-    received_epoch: bool,
-
-    is_just_started: bool,
 }
 
 impl EpochSync {
     pub fn new(
         network_adapter: Arc<dyn PeerManagerAdapter>,
         genesis_epoch_id: EpochId,
-        genesis_next_epoch_id: EpochId,
         first_epoch_block_producers: Vec<ValidatorStake>,
         request_timeout: TimeDuration,
-        peer_timeout: TimeDuration,
+        enabled: bool,
     ) -> Self {
         Self {
             network_adapter,
-            peer_to_last_request_time: HashMap::new(),
-            peers_reporting_up_to_date: HashSet::new(),
-            current_epoch_id: genesis_epoch_id.clone(),
-            next_epoch_id: genesis_next_epoch_id,
-            next_block_producers: first_epoch_block_producers,
-            requested_epoch_id: genesis_epoch_id,
-            last_request_time: Clock::utc(),
-            last_request_peer_id: None,
+            epoch_id: genesis_epoch_id,
+            block_producers: first_epoch_block_producers,
+            synced_epochs: VecDeque::with_capacity(2),
+            epoch_ord: 0,
+            finalizing: false,
+            request: None,
             request_timeout: Duration::from_std(request_timeout).unwrap(),
-            peer_timeout: Duration::from_std(peer_timeout).unwrap(),
-            received_epoch: false,
-            have_all_epochs: false,
-            done: false,
-            sync_hash: CryptoHash::default(),
-            is_just_started: true,
+            num_failed_requests: 0,
+            started: false,
+            done: !enabled,
+        }
+    }
+
+    /// Runs a step of epoch sync.
+    ///
+    /// Returns whether epoch sync is in progress in which case other kinds of
+    /// sync must not be run.
+    pub fn run(
+        &mut self,
+        sync_status: &mut SyncStatus,
+        chain: &Chain,
+        highest_height: BlockHeight,
+        highest_height_peers: &[HighestHeightPeerInfo],
+        epoch_length: BlockHeightDelta,
+    ) -> Result<bool, near_chain::Error> {
+        if self.done {
+            return Ok(false);
+        }
+        let _span = tracing::debug_span!(target: "sync", "run", sync = "EpochSync").entered();
+        if !self.started {
+            // Epoch sync only makes sense for a node which has nothing but
+            // genesis and is more than a couple of epochs behind.  Otherwise
+            // header sync is good enough.
+            let header_head = chain.header_head()?;
+            if header_head.height != chain.genesis().height()
+                || highest_height <= header_head.height + 2 * epoch_length
+            {
+                debug!(target: "sync", header_head = header_head.height, highest_height,
+                       "Epoch sync not needed");
+                self.done = true;
+                return Ok(false);
+            }
+            info!(target: "sync", highest_height, "Starting epoch sync");
+            self.started = true;
+        }
+
+        let now = Clock::utc();
+        if let Some((peer_id, sent_at)) = &self.request {
+            if now < *sent_at + self.request_timeout {
+                return Ok(true);
+            }
+            debug!(target: "sync", %peer_id, "Epoch sync request timed out");
+            self.request = None;
+            self.record_failed_request();
+            if self.done {
+                return Ok(false);
+            }
+        }
+
+        *sync_status = SyncStatus::EpochSync { epoch_ord: self.epoch_ord };
+        let peer_id = match highest_height_peers.choose(&mut thread_rng()) {
+            Some(peer) => peer.peer_info.id.clone(),
+            None => return Ok(true),
+        };
+        let request = if self.finalizing {
+            let epoch_id = self.synced_epochs.back().unwrap().epoch_id.clone();
+            debug!(target: "sync", ?epoch_id, %peer_id, "Requesting epoch sync finalization");
+            NetworkRequests::EpochSyncFinalizationRequest { epoch_id, peer_id: peer_id.clone() }
+        } else {
+            let epoch_id = self.epoch_id.clone();
+            debug!(target: "sync", ?epoch_id, %peer_id, "Requesting light client block");
+            NetworkRequests::EpochSyncRequest { epoch_id, peer_id: peer_id.clone() }
+        };
+        self.network_adapter
+            .do_send(PeerManagerMessageRequest::NetworkRequests(request).with_span_context());
+        self.request = Some((peer_id, now));
+        Ok(true)
+    }
+
+    /// Processes response to a light client block request.
+    pub fn on_response(&mut self, response: EpochSyncResponse, peer_id: PeerId) {
+        if self.done || self.finalizing || !self.is_awaited(&peer_id) {
+            debug!(target: "sync", %peer_id, "Ignoring unexpected epoch sync response");
+            return;
+        }
+        self.request = None;
+        match response {
+            EpochSyncResponse::UpToDate => {
+                // Finalisation requires headers of the epoch before the last
+                // synced one (see `validate_finalization`) so there must be
+                // at least two synced epochs.
+                if self.synced_epochs.len() < 2 {
+                    info!(target: "sync", epoch_id = ?self.epoch_id,
+                          "Peer is too close to genesis for epoch sync; falling back to header sync");
+                    self.done = true;
+                    return;
+                }
+                info!(target: "sync", epoch_id = ?self.epoch_id, epoch_ord = self.epoch_ord,
+                      "Reached current epoch; finalizing epoch sync");
+                self.finalizing = true;
+            }
+            EpochSyncResponse::Advance { light_client_block_view } => {
+                let light_client_block_hash = match validate_light_client_block(
+                    &light_client_block_view,
+                    &self.epoch_id,
+                    &self.block_producers,
+                ) {
+                    Ok(hash) => hash,
+                    Err(err) => {
+                        warn!(target: "sync", %peer_id, ?err, "Received invalid light client block");
+                        self.ban_peer(peer_id);
+                        self.record_failed_request();
+                        return;
+                    }
+                };
+                let next_block_producers = light_client_block_view
+                    .next_bps
+                    .into_iter()
+                    .flatten()
+                    .map(ValidatorStakeView::into_validator_stake)
+                    .collect();
+                let next_epoch_id = EpochId(light_client_block_view.inner_lite.next_epoch_id);
+                let synced = SyncedEpoch {
+                    epoch_id: std::mem::replace(&mut self.epoch_id, next_epoch_id),
+                    block_producers: std::mem::replace(
+                        &mut self.block_producers,
+                        next_block_producers,
+                    ),
+                    light_client_block_hash,
+                };
+                if self.synced_epochs.len() == 2 {
+                    self.synced_epochs.pop_front();
+                }
+                self.synced_epochs.push_back(synced);
+                self.epoch_ord += 1;
+                self.num_failed_requests = 0;
+            }
+        }
+    }
+
+    /// Processes response to a finalization request.  If the response is
+    /// valid, saves it in the chain and finishes epoch sync.
+    pub fn on_finalization_response(
+        &mut self,
+        response: EpochSyncFinalizationResponse,
+        peer_id: PeerId,
+        chain: &mut Chain,
+    ) -> Result<(), near_chain::Error> {
+        if self.done || !self.finalizing || !self.is_awaited(&peer_id) {
+            debug!(target: "sync", %peer_id, "Ignoring unexpected epoch sync finalization response");
+            return Ok(());
         }
+        self.request = None;
+        if let Err(err) = self.validate_finalization(&response) {
+            warn!(target: "sync", %peer_id, ?err, "Received invalid epoch sync finalization response");
+            self.ban_peer(peer_id);
+            self.record_failed_request();
+            return Ok(());
+        }
+        chain.finish_epoch_sync(response)?;
+        self.done = true;
+        Ok(())
+    }
+
+    /// Checks that finalization response describes the boundary between the
+    /// last two synced epochs.
+    ///
+    /// Headers in the response must form a chain going from the block of the
+    /// light client block of the earlier epoch up to the first block of the
+    /// later epoch.  The first block must be signed by its block producer
+    /// and commit to the epoch manager data and block merkle tree from the
+    /// response.
+    fn validate_finalization(
+        &self,
+        response: &EpochSyncFinalizationResponse,
+    ) -> Result<(), near_chain::Error> {
+        let prev_epoch = &self.synced_epochs[0];
+        let cur_epoch = &self.synced_epochs[1];
+        let init_header = &response.header_sync_init_header;
+        let cur_header = &response.cur_epoch_header;
+
+        let headers: Vec<&BlockHeader> = response
+            .prev_epoch_headers
+            .iter()
+            .chain(std::iter::once(init_header))
+            .chain(std::iter::once(cur_header))
+            .collect();
+        if response.prev_epoch_headers.first().map(BlockHeader::hash)
+            != Some(&prev_epoch.light_client_block_hash)
+        {
+            return Err(near_chain::Error::Other(
+                "headers don't start at light client block".to_string(),
+            ));
+        }
+        if headers.windows(2).any(|pair| pair[1].prev_hash() != pair[0].hash()) {
+            return Err(near_chain::Error::Other("headers don't form a chain".to_string()));
+        }
+        if headers[..headers.len() - 1]
+            .iter()
+            .any(|header| header.epoch_id() != &prev_epoch.epoch_id)
+            || cur_header.epoch_id() != &cur_epoch.epoch_id
+        {
+            return Err(near_chain::Error::InvalidEpochHash);
+        }
+
+        let cur_epoch_info = &response.cur_epoch_info;
+        let producer =
+            cur_epoch_info.get_validator(cur_epoch_info.sample_block_producer(cur_header.height()));
+        let is_known_producer = cur_epoch.block_producers.iter().any(|bp| {
+            bp.account_id() == producer.account_id() && bp.public_key() == producer.public_key()
+        });
+        if !is_known_producer || !cur_header.verify_block_producer(producer.public_key()) {
+            return Err(near_chain::Error::InvalidSignature);
+        }
+
+        let epoch_sync_data_hash = CryptoHash::hash_borsh((
+            &response.prev_epoch_first_block_info,
+            &response.prev_epoch_prev_last_block_info,
+            &response.prev_epoch_last_block_info,
+            &response.prev_epoch_info,
+            &response.cur_epoch_info,
+            &response.next_epoch_info,
+        ));
+        if cur_header.epoch_sync_data_hash() != Some(epoch_sync_data_hash) {
+            return Err(near_chain::Error::Other("epoch sync data hash mismatch".to_string()));
+        }
+        if response.prev_epoch_last_block_info.hash() != init_header.hash()
+            || response.prev_epoch_prev_last_block_info.hash() != init_header.prev_hash()
+            || response.prev_epoch_first_block_info.hash()
+                != response.prev_epoch_last_block_info.epoch_first_block()
+        {
+            return Err(near_chain::Error::Other("block infos don't match headers".to_string()));
+        }
+
+        let mut block_merkle_tree = response.header_sync_init_header_tree.clone();
+        if &block_merkle_tree.root() != init_header.block_merkle_root() {
+            return Err(near_chain::Error::InvalidBlockMerkleRoot);
+        }
+        block_merkle_tree.insert(*init_header.hash());
+        if &block_merkle_tree.root() != cur_header.block_merkle_root() {
+            return Err(near_chain::Error::InvalidBlockMerkleRoot);
+        }
+        Ok(())
+    }
+
+    /// Whether a response from given peer is awaited.
+    fn is_awaited(&self, peer_id: &PeerId) -> bool {
+        matches!(&self.request, Some((requested, _)) if requested == peer_id)
+    }
+
+    fn record_failed_request(&mut self) {
+        self.num_failed_requests += 1;
+        if self.num_failed_requests >= MAX_FAILED_REQUESTS {
+            warn!(target: "sync", "Epoch sync failed {} times in a row; falling back to header sync",
+                  self.num_failed_requests);
+            self.done = true;
+        }
+    }
+
+    fn ban_peer(&self, peer_id: PeerId) {
+        self.network_adapter.do_send(
+            PeerManagerMessageRequest::NetworkRequests(NetworkRequests::BanPeer {
+                peer_id,
+                ban_reason: ReasonForBan::BadBlockHeader,
+            })
+            .with_span_context(),
+        );
     }
 }
@@ -0,0 +1,103 @@
+//! External sources of state parts.
+//!
+//! Instead of requesting every state part from peers, state sync can first
+//! look for it in an external location populated by nodes which dump their
+//! state parts (e.g. with `state-viewer dump-state-parts`).  Parts obtained
+//! this way are validated against the state root in the same way as parts
+//! received from peers and parts missing from the external location are
+//! requested from peers.
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use near_chain_configs::ExternalStatePartsConfig;
+use near_primitives::types::{EpochId, ShardId};
+
+/// A source of state parts other than peers.
+pub trait ExternalStatePartSource: Send + Sync {
+    /// Returns part `part_id` of the state of shard `shard_id` as of the
+    /// beginning of epoch `epoch_id`, or `None` if the source doesn’t have it.
+    fn get_part(
+        &self,
+        epoch_id: &EpochId,
+        shard_id: ShardId,
+        part_id: u64,
+    ) -> std::io::Result<Option<Vec<u8>>>;
+}
+
+/// Creates the state part source described by the configuration.
+pub fn new_external_state_part_source(
+    config: &ExternalStatePartsConfig,
+) -> Arc<dyn ExternalStatePartSource> {
+    match config {
+        ExternalStatePartsConfig::Filesystem { root_dir } => {
+            Arc::new(FilesystemStatePartSource::new(root_dir.clone()))
+        }
+    }
+}
+
+/// Returns path of the directory with all state parts of the given shard and
+/// epoch within a directory with state parts of all epochs.
+pub fn state_parts_dir(root_dir: &Path, epoch_id: &EpochId, shard_id: ShardId) -> PathBuf {
+    root_dir.join(epoch_id.0.to_string()).join(shard_id.to_string())
+}
+
+/// Returns name of the file a state part is stored in.
+pub fn state_part_file_name(part_id: u64) -> String {
+    format!("state_part_{:06}", part_id)
+}
+
+/// Reads state parts from a local directory.
+///
+/// Parts are expected at `<root_dir>/<epoch_id>/<shard_id>/state_part_<part_id>`
+/// which makes it possible to populate the directory by running
+/// `state-viewer dump-state-parts` for each epoch and shard.
+pub struct FilesystemStatePartSource {
+    root_dir: PathBuf,
+}
+
+impl FilesystemStatePartSource {
+    pub fn new(root_dir: PathBuf) -> Self {
+        Self { root_dir }
+    }
+}
+
+impl ExternalStatePartSource for FilesystemStatePartSource {
+    fn get_part(
+        &self,
+        epoch_id: &EpochId,
+        shard_id: ShardId,
+        part_id: u64,
+    ) -> std::io::Result<Option<Vec<u8>>> {
+        let path =
+            state_parts_dir(&self.root_dir, epoch_id, shard_id).join(state_part_file_name(part_id));
+        match std::fs::read(path) {
+            Ok(data) => Ok(Some(data)),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use near_primitives::hash::hash;
+
+    #[test]
+    fn test_filesystem_state_part_source() {
+        let root_dir = tempfile::tempdir().unwrap();
+        let epoch_id = EpochId(hash(b"epoch"));
+        let dir = state_parts_dir(root_dir.path(), &epoch_id, 1);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("state_part_000002"), b"part").unwrap();
+
+        let source = new_external_state_part_source(&ExternalStatePartsConfig::Filesystem {
+            root_dir: root_dir.path().to_path_buf(),
+        });
+        assert_eq!(source.get_part(&epoch_id, 1, 2).unwrap(), Some(b"part".to_vec()));
+        assert_eq!(source.get_part(&epoch_id, 1, 3).unwrap(), None);
+        assert_eq!(source.get_part(&epoch_id, 0, 2).unwrap(), None);
+        assert_eq!(source.get_part(&EpochId::default(), 1, 2).unwrap(), None);
+    }
+}
@@ -254,8 +254,13 @@ impl HeaderSync {
         let ordinals = get_locator_ordinals(final_head_ordinal, tip_ordinal);
         let mut locator: Vec<CryptoHash> = vec![];
         for ordinal in &ordinals {
-            let block_hash = store.get_block_hash_from_ordinal(*ordinal)?;
-            locator.push(block_hash);
+            // After epoch sync, blocks between genesis and the header the sync
+            // finished at are unknown.  Simply skip them.
+            match store.get_block_hash_from_ordinal(*ordinal) {
+                Ok(block_hash) => locator.push(block_hash),
+                Err(near_chain::Error::DBNotFoundErr(_)) => {}
+                Err(err) => return Err(err),
+            }
         }
         debug!(target: "sync", "Sync: locator: {:?} ordinals: {:?}", locator, ordinals);
         Ok(locator)
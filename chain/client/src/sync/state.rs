@@ -14,6 +14,10 @@
 //! Then for each part that we're missing, we're 'randomly' picking a target from whom we'll request it - but we make
 //! sure to not request more than MAX_STATE_PART_REQUESTS from each.
 //!
+//! If an external source of state parts is configured (see the `external`
+//! module), each part is first looked up there and only the parts missing
+//! from the source are requested from peers.
+//!
 //! WARNING: with the current design, we're putting quite a load on the validators - as we request a lot of data from
 //!         them (if you assume that we have 100 validators and 30 peers - we send 100/130 of requests to validators).
 //!         Currently validators defend against it, by having a rate limiters - but we should improve the algorithm
@@ -22,10 +26,10 @@
 
 use near_chain::{near_chain_primitives, Error};
 use near_primitives::state_part::PartId;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::ops::Add;
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use std::time::Duration as TimeDuration;
 
 use ansi_term::Color::{Purple, Yellow};
@@ -42,7 +46,7 @@ use near_network::types::{
 use near_primitives::hash::CryptoHash;
 use near_primitives::syncing::{get_num_state_parts, ShardStateSyncResponse};
 use near_primitives::time::{Clock, Utc};
-use near_primitives::types::{AccountId, ShardId, StateRoot};
+use near_primitives::types::{AccountId, EpochId, ShardId, StateRoot};
 
 use near_chain::chain::{ApplyStatePartsRequest, StateSplitRequest};
 use near_client_primitives::types::{
@@ -53,11 +57,15 @@ use near_network::types::PeerManagerMessageRequest;
 use near_o11y::WithSpanContextExt;
 use near_primitives::shard_layout::ShardUId;
 
+use crate::sync::external::ExternalStatePartSource;
+
 /// Maximum number of state parts to request per peer on each round when node is trying to download the state.
 pub const MAX_STATE_PART_REQUEST: u64 = 16;
 /// Number of state parts already requested stored as pending.
 /// This number should not exceed MAX_STATE_PART_REQUEST times (number of peers in the network).
 pub const MAX_PENDING_PART: u64 = MAX_STATE_PART_REQUEST * 10000;
+/// Maximum number of state parts being downloaded from the external source at the same time.
+pub const MAX_EXTERNAL_STATE_PART_REQUESTS: usize = 16;
 
 pub enum StateSyncResult {
     /// No shard has changed its status
@@ -75,6 +83,14 @@ struct PendingRequestStatus {
     wait_until: DateTime<Utc>,
 }
 
+/// Result of downloading a state part from the external source.
+struct ExternalPartResponse {
+    sync_hash: CryptoHash,
+    shard_id: ShardId,
+    part_id: u64,
+    result: std::io::Result<Option<Vec<u8>>>,
+}
+
 impl PendingRequestStatus {
     fn new(timeout: Duration) -> Self {
         Self { missing_parts: 1, wait_until: Clock::utc().add(timeout) }
@@ -116,10 +132,24 @@ pub struct StateSync {
 
     /// Maps shard_id to result of splitting state for resharding
     split_state_roots: HashMap<ShardId, Result<HashMap<ShardUId, StateRoot>, Error>>,
+
+    /// Source to download state parts from before requesting them from peers.
+    external_source: Option<Arc<dyn ExternalStatePartSource>>,
+    /// Number of parts currently being downloaded from the external source.
+    num_external_requests: usize,
+    /// Parts downloaded from the external source which haven't been saved yet.
+    external_responses: Arc<Mutex<Vec<ExternalPartResponse>>>,
+    /// Parts (identified by sync hash, shard id and part id) which couldn't be
+    /// downloaded from the external source and are requested from peers instead.
+    external_missing_parts: HashSet<(CryptoHash, ShardId, u64)>,
 }
 
 impl StateSync {
-    pub fn new(network_adapter: Arc<dyn PeerManagerAdapter>, timeout: TimeDuration) -> Self {
+    pub fn new(
+        network_adapter: Arc<dyn PeerManagerAdapter>,
+        timeout: TimeDuration,
+        external_source: Option<Arc<dyn ExternalStatePartSource>>,
+    ) -> Self {
         StateSync {
             network_adapter,
             last_time_block_requested: None,
@@ -128,6 +158,10 @@ impl StateSync {
             timeout: Duration::from_std(timeout).unwrap(),
             state_parts_apply_results: HashMap::new(),
             split_state_roots: HashMap::new(),
+            external_source,
+            num_external_requests: 0,
+            external_responses: Default::default(),
+            external_missing_parts: HashSet::new(),
         }
     }
 
@@ -225,9 +259,10 @@ impl StateSync {
                             get_num_state_parts(shard_state_header.state_root_node().memory_usage);
                         // If the header was downloaded succesfully - move to phase 2 (downloading parts).
                         // Create the vector with entry for each part.
+                        // Every part gets its own `run_me` flag.
                         *shard_sync_download = ShardSyncDownload {
-                            downloads: vec![
-                                DownloadStatus {
+                            downloads: (0..state_num_parts)
+                                .map(|_| DownloadStatus {
                                     start_time: now,
                                     prev_update_time: now,
                                     run_me: Arc::new(AtomicBool::new(true)),
@@ -235,9 +270,8 @@ impl StateSync {
                                     done: false,
                                     state_requests_count: 0,
                                     last_target: None,
-                                };
-                                state_num_parts as usize
-                            ],
+                                })
+                                .collect(),
                             status: ShardSyncStatus::StateDownloadParts,
                         };
                         need_shard = true;
@@ -588,9 +622,22 @@ impl StateSync {
         chain: &Chain,
         runtime_adapter: &Arc<dyn RuntimeAdapter>,
         sync_hash: CryptoHash,
-        shard_sync_download: ShardSyncDownload,
+        mut shard_sync_download: ShardSyncDownload,
         highest_height_peers: &[HighestHeightPeerInfo],
     ) -> Result<ShardSyncDownload, near_chain::Error> {
+        if shard_sync_download.status == ShardSyncStatus::StateDownloadParts {
+            if let Some(source) = self.external_source.clone() {
+                let epoch_id = chain.get_block_header(&sync_hash)?.epoch_id().clone();
+                self.request_external_parts(
+                    source,
+                    epoch_id,
+                    shard_id,
+                    sync_hash,
+                    &mut shard_sync_download,
+                );
+            }
+        }
+
         let possible_targets = self.possible_targets(
             me,
             shard_id,
@@ -697,6 +744,102 @@ impl StateSync {
         Ok(new_shard_sync_download)
     }
 
+    /// Starts downloading parts which still need to be requested from the
+    /// external source, unless the source is known not to have them.
+    /// Downloaded parts are saved by `process_external_parts`.
+    fn request_external_parts(
+        &mut self,
+        source: Arc<dyn ExternalStatePartSource>,
+        epoch_id: EpochId,
+        shard_id: ShardId,
+        sync_hash: CryptoHash,
+        shard_sync_download: &mut ShardSyncDownload,
+    ) {
+        let max_requests =
+            MAX_EXTERNAL_STATE_PART_REQUESTS.saturating_sub(self.num_external_requests);
+        let parts_to_request = shard_sync_download
+            .downloads
+            .iter()
+            .enumerate()
+            .filter(|(part_id, download)| {
+                download.run_me.load(Ordering::SeqCst)
+                    && !self.external_missing_parts.contains(&(
+                        sync_hash,
+                        shard_id,
+                        *part_id as u64,
+                    ))
+            })
+            .map(|(part_id, _)| part_id)
+            .take(max_requests)
+            .collect::<Vec<_>>();
+        for part_id in parts_to_request {
+            let download = &mut shard_sync_download.downloads[part_id];
+            download.run_me.store(false, Ordering::SeqCst);
+            download.state_requests_count += 1;
+            download.last_target = None;
+            self.num_external_requests += 1;
+
+            let source = source.clone();
+            let epoch_id = epoch_id.clone();
+            let responses = self.external_responses.clone();
+            let part_id = part_id as u64;
+            tokio::task::spawn_blocking(move || {
+                let result = source.get_part(&epoch_id, shard_id, part_id);
+                responses.lock().unwrap().push(ExternalPartResponse {
+                    sync_hash,
+                    shard_id,
+                    part_id,
+                    result,
+                });
+            });
+        }
+    }
+
+    /// Saves parts downloaded from the external source.  Parts which the
+    /// source doesn't have or which are invalid are requested from peers.
+    fn process_external_parts(
+        &mut self,
+        current_sync_hash: CryptoHash,
+        new_shard_sync: &mut HashMap<u64, ShardSyncDownload>,
+        chain: &mut Chain,
+    ) {
+        let responses = std::mem::take(&mut *self.external_responses.lock().unwrap());
+        for ExternalPartResponse { sync_hash, shard_id, part_id, result } in responses {
+            self.num_external_requests -= 1;
+            if sync_hash != current_sync_hash {
+                continue;
+            }
+            let shard_sync_download = match new_shard_sync.get_mut(&shard_id) {
+                Some(shard_sync_download)
+                    if shard_sync_download.status == ShardSyncStatus::StateDownloadParts =>
+                {
+                    shard_sync_download
+                }
+                _ => continue,
+            };
+            let num_parts = shard_sync_download.downloads.len() as u64;
+            let download = match shard_sync_download.downloads.get_mut(part_id as usize) {
+                Some(download) if !download.done => download,
+                _ => continue,
+            };
+            let result = match result {
+                Ok(Some(data)) => chain
+                    .set_state_part(shard_id, sync_hash, PartId::new(part_id, num_parts), &data)
+                    .map_err(|err| err.to_string()),
+                Ok(None) => Err("part not found".to_string()),
+                Err(err) => Err(err.to_string()),
+            };
+            match result {
+                Ok(()) => download.done = true,
+                Err(err) => {
+                    debug!(target: "sync", shard_id, part_id, %sync_hash, %err, "Failed to download state part from the external source, requesting it from peers");
+                    self.external_missing_parts.insert((sync_hash, shard_id, part_id));
+                    download.run_me.store(true, Ordering::SeqCst);
+                }
+            }
+        }
+    }
+
     /// The main 'step' function that should be called periodically to check and update the sync process.
     /// The current state/progress information is mostly kept within 'new_shard_sync' object.
     ///
@@ -724,6 +867,10 @@ impl StateSync {
         // Seems it is related to some bug with block getting orphaned after state sync? but not sure.
         let (request_block, have_block) = self.sync_block_status(&prev_hash, chain, now)?;
 
+        if self.external_source.is_some() {
+            self.process_external_parts(sync_hash, new_shard_sync, chain);
+        }
+
         if tracking_shards.is_empty() {
             // This case is possible if a validator cares about the same shards in the new epoch as
             //    in the previous (or about a subset of them), return success right away
@@ -903,16 +1050,14 @@ mod test {
     };
 
     use near_chain::test_utils;
+    use near_chain::test_utils::KeyValueRuntime;
 
-    use super::*;
+    use crate::sync::external::{state_part_file_name, state_parts_dir, FilesystemStatePartSource};
 
-    #[test]
-    // Start a new state sync - and check that it asks for a header.
-    fn test_ask_for_header() {
-        let mock_peer_manager = Arc::new(MockPeerManagerAdapter::default());
-        let mut state_sync = StateSync::new(mock_peer_manager.clone(), TimeDuration::from_secs(1));
-        let mut new_shard_sync = HashMap::new();
+    use super::*;
 
+    /// Creates a chain whose head is the first block of the second epoch.
+    fn setup_chain() -> (Chain, Arc<KeyValueRuntime>) {
         let (mut chain, kv, signer) = test_utils::setup();
 
         // TODO: lower the epoch length
@@ -941,6 +1086,18 @@ mod test {
             )
             .unwrap();
         }
+        (chain, kv)
+    }
+
+    #[test]
+    // Start a new state sync - and check that it asks for a header.
+    fn test_ask_for_header() {
+        let mock_peer_manager = Arc::new(MockPeerManagerAdapter::default());
+        let mut state_sync =
+            StateSync::new(mock_peer_manager.clone(), TimeDuration::from_secs(1), None);
+        let mut new_shard_sync = HashMap::new();
+
+        let (mut chain, kv) = setup_chain();
 
         let request_hash = &chain.head().unwrap().last_block_hash;
         let state_sync_header = chain.get_state_response_header(0, *request_hash).unwrap();
@@ -1023,4 +1180,105 @@ mod test {
             System::current().stop()
         });
     }
+
+    #[test]
+    // Download state parts from the external source and fall back to peers if they are missing.
+    fn test_external_state_parts() {
+        let (mut chain, kv) = setup_chain();
+        let sync_hash = chain.head().unwrap().last_block_hash;
+        let epoch_id = chain.get_block_header(&sync_hash).unwrap().epoch_id().clone();
+        let header = chain.get_state_response_header(0, sync_hash).unwrap();
+        let state_root = header.chunk_prev_state_root();
+        let num_parts = get_num_state_parts(header.state_root_node().memory_usage);
+        chain.set_state_header(0, sync_hash, header).unwrap();
+        let runtime_adapter = kv as Arc<dyn RuntimeAdapter>;
+
+        let new_shard_sync = || {
+            let now = Clock::utc();
+            let downloads = (0..num_parts)
+                .map(|_| DownloadStatus {
+                    start_time: now,
+                    prev_update_time: now,
+                    run_me: Arc::new(AtomicBool::new(true)),
+                    error: false,
+                    done: false,
+                    state_requests_count: 0,
+                    last_target: None,
+                })
+                .collect();
+            HashMap::from([(
+                0,
+                ShardSyncDownload { downloads, status: ShardSyncStatus::StateDownloadParts },
+            )])
+        };
+        let root_dir = tempfile::tempdir().unwrap();
+        let mock_peer_manager = Arc::new(MockPeerManagerAdapter::default());
+        let new_state_sync = || {
+            StateSync::new(
+                mock_peer_manager.clone(),
+                TimeDuration::from_secs(60),
+                Some(Arc::new(FilesystemStatePartSource::new(root_dir.path().to_path_buf()))),
+            )
+        };
+        let run = |state_sync: &mut StateSync,
+                   new_shard_sync: &mut HashMap<u64, ShardSyncDownload>,
+                   chain: &mut Chain| {
+            state_sync
+                .run(
+                    &None,
+                    sync_hash,
+                    new_shard_sync,
+                    chain,
+                    &runtime_adapter,
+                    &[],
+                    vec![0],
+                    &|_| {},
+                    &|_| {},
+                )
+                .unwrap();
+        };
+
+        run_actix(async {
+            // The source doesn't have any parts, so they are requested from peers.
+            let mut state_sync = new_state_sync();
+            let mut shard_sync = new_shard_sync();
+            run(&mut state_sync, &mut shard_sync, &mut chain);
+            assert!(mock_peer_manager.pop().is_none());
+            let mut requested_parts = vec![];
+            while requested_parts.len() < num_parts as usize {
+                tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+                run(&mut state_sync, &mut shard_sync, &mut chain);
+                while let Some(request) = mock_peer_manager.pop() {
+                    match request.as_network_requests() {
+                        NetworkRequests::StateRequestPart { shard_id: 0, part_id, .. } => {
+                            requested_parts.push(part_id)
+                        }
+                        request => panic!("unexpected request {:?}", request),
+                    }
+                }
+            }
+            requested_parts.sort();
+            assert_eq!(requested_parts, (0..num_parts).collect::<Vec<_>>());
+
+            // Once the parts are available, they are downloaded from the source only.
+            let parts_dir = state_parts_dir(root_dir.path(), &epoch_id, 0);
+            std::fs::create_dir_all(&parts_dir).unwrap();
+            for part_id in 0..num_parts {
+                let part = runtime_adapter
+                    .obtain_state_part(0, &sync_hash, &state_root, PartId::new(part_id, num_parts))
+                    .unwrap();
+                std::fs::write(parts_dir.join(state_part_file_name(part_id)), part).unwrap();
+            }
+            let mut state_sync = new_state_sync();
+            let mut shard_sync = new_shard_sync();
+            while shard_sync[&0].status == ShardSyncStatus::StateDownloadParts {
+                run(&mut state_sync, &mut shard_sync, &mut chain);
+                tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+            }
+            assert_eq!(shard_sync[&0].status, ShardSyncStatus::StateDownloadScheduling);
+            assert!(mock_peer_manager.pop().is_none());
+
+            System::current().stop()
+        });
+    }
 }
@@ -1007,7 +1007,9 @@ pub fn setup_mock_all_validators(
                         NetworkRequests::ForwardTx(_, _)
                         | NetworkRequests::BanPeer { .. }
                         | NetworkRequests::TxStatus(_, _, _)
-                        | NetworkRequests::Challenge(_) => {}
+                        | NetworkRequests::Challenge(_)
+                        | NetworkRequests::EpochSyncRequest { .. }
+                        | NetworkRequests::EpochSyncFinalizationRequest { .. } => {}
                     };
                 }
                 resp
@@ -24,7 +24,8 @@ use near_client_primitives::types::{
     GetMaintenanceWindowsError, GetNextLightClientBlockError, GetProtocolConfig,
     GetProtocolConfigError, GetReceipt, GetReceiptError, GetStateChangesError,
     GetStateChangesWithCauseInBlock, GetStateChangesWithCauseInBlockForTrackedShards,
-    GetValidatorInfoError, Query, QueryError, TxStatus, TxStatusError,
+    GetValidatorInfoError, Query, QueryError, SimulateTransaction, SimulateTransactionError,
+    SimulateTransactionResponse, TxStatus, TxStatusError,
 };
 #[cfg(feature = "test_features")]
 use near_network::types::NetworkAdversarialMessage;
@@ -41,8 +42,8 @@ use near_primitives::merkle::{merklize, PartialMerkleTree};
 use near_primitives::network::AnnounceAccount;
 use near_primitives::sharding::ShardChunk;
 use near_primitives::syncing::{
-    ShardStateSyncResponse, ShardStateSyncResponseHeader, ShardStateSyncResponseV1,
-    ShardStateSyncResponseV2,
+    EpochSyncFinalizationResponse, EpochSyncResponse, ShardStateSyncResponse,
+    ShardStateSyncResponseHeader, ShardStateSyncResponseV1, ShardStateSyncResponseV2,
 };
 use near_primitives::types::{
     AccountId, BlockHeight, BlockId, BlockReference, EpochReference, Finality, MaybeBlockId,
@@ -57,8 +58,9 @@ use near_primitives::views::{
 };
 
 use crate::adapter::{
-    AnnounceAccountRequest, BlockHeadersRequest, BlockRequest, StateRequestHeader,
-    StateRequestPart, StateResponse, TxStatusRequest, TxStatusResponse,
+    AnnounceAccountRequest, BlockHeadersRequest, BlockRequest, EpochSyncFinalizationRequest,
+    EpochSyncRequest, StateRequestHeader, StateRequestPart, StateResponse, TxStatusRequest,
+    TxStatusResponse,
 };
 use crate::{
     metrics, sync, GetChunk, GetExecutionOutcomeResponse, GetNextLightClientBlock, GetStateChanges,
@@ -298,6 +300,25 @@ impl ViewClientActor {
         Ok(windows)
     }
 
+    /// With split storage, state of the blocks garbage collected from hot
+    /// storage is read from cold storage, which has the blocks up to its head
+    /// only.  Queries for blocks past it would fail on missing trie nodes.
+    fn check_cold_head(&self, header: &BlockHeader, tip: &Tip) -> Result<(), QueryError> {
+        let cold_head = match self.chain.store().cold_head() {
+            Ok(Some(cold_head)) => cold_head,
+            Ok(None) => return Ok(()),
+            Err(err) => return Err(QueryError::InternalError { error_message: err.to_string() }),
+        };
+        let gc_stop_height = self.runtime_adapter.get_gc_stop_height(&tip.last_block_hash);
+        if header.height() < gc_stop_height && header.height() > cold_head.height {
+            return Err(QueryError::GarbageCollectedBlock {
+                block_height: header.height(),
+                block_hash: *header.hash(),
+            });
+        }
+        Ok(())
+    }
+
     fn handle_query(&mut self, msg: Query) -> Result<QueryResponse, QueryError> {
         let header = self.get_block_header_by_reference(&msg.block_reference);
         let header = match header {
@@ -330,6 +351,9 @@ impl ViewClientActor {
             .map_err(|err| QueryError::InternalError { error_message: err.to_string() })?;
 
         let tip = self.chain.head();
+        if let Ok(tip) = &tip {
+            self.check_cold_head(&header, tip)?;
+        }
         let chunk_extra =
             self.chain.get_chunk_extra(header.hash(), &shard_uid).map_err(|err| match err {
                 near_chain::near_chain_primitives::Error::DBNotFoundErr(_) => match tip {
@@ -828,6 +852,9 @@ impl Handler<WithSpanContext<GetStateChangesWithCauseInBlockForTrackedShards>> f
                     return Err(GetStateChangesError::IOError { error_message: err.to_string() })
                 }
             };
+            if msg.shard_ids.as_ref().map_or(false, |shard_ids| !shard_ids.contains(&shard_id)) {
+                continue;
+            }
 
             let state_changes =
                 state_changes_with_cause_split_by_shard_id.entry(shard_id).or_default();
@@ -1189,6 +1216,54 @@ impl Handler<WithSpanContext<BlockHeadersRequest>> for ViewClientActor {
     }
 }
 
+impl Handler<WithSpanContext<EpochSyncRequest>> for ViewClientActor {
+    type Result = Option<Box<EpochSyncResponse>>;
+
+    #[perf]
+    fn handle(
+        &mut self,
+        msg: WithSpanContext<EpochSyncRequest>,
+        _ctx: &mut Self::Context,
+    ) -> Self::Result {
+        let (_span, msg) = handler_debug_span!(target: "client", msg);
+        let _timer = metrics::VIEW_CLIENT_MESSAGE_TIME
+            .with_label_values(&["EpochSyncRequest"])
+            .start_timer();
+        let EpochSyncRequest(epoch_id) = msg;
+        match self.chain.get_epoch_sync_response(&epoch_id) {
+            Ok(response) => Some(Box::new(response)),
+            Err(err) => {
+                debug!(target: "sync", ?epoch_id, ?err, "Cannot respond to epoch sync request");
+                None
+            }
+        }
+    }
+}
+
+impl Handler<WithSpanContext<EpochSyncFinalizationRequest>> for ViewClientActor {
+    type Result = Option<Box<EpochSyncFinalizationResponse>>;
+
+    #[perf]
+    fn handle(
+        &mut self,
+        msg: WithSpanContext<EpochSyncFinalizationRequest>,
+        _ctx: &mut Self::Context,
+    ) -> Self::Result {
+        let (_span, msg) = handler_debug_span!(target: "client", msg);
+        let _timer = metrics::VIEW_CLIENT_MESSAGE_TIME
+            .with_label_values(&["EpochSyncFinalizationRequest"])
+            .start_timer();
+        let EpochSyncFinalizationRequest(epoch_id) = msg;
+        match self.chain.get_epoch_sync_finalization_response(&epoch_id) {
+            Ok(response) => Some(Box::new(response)),
+            Err(err) => {
+                debug!(target: "sync", ?epoch_id, ?err, "Cannot respond to epoch sync finalization request");
+                None
+            }
+        }
+    }
+}
+
 impl Handler<WithSpanContext<StateRequestHeader>> for ViewClientActor {
     type Result = Option<StateResponse>;
 
@@ -1422,6 +1497,95 @@ impl Handler<WithSpanContext<GetMaintenanceWindows>> for ViewClientActor {
     }
 }
 
+impl Handler<WithSpanContext<SimulateTransaction>> for ViewClientActor {
+    type Result = Result<SimulateTransactionResponse, SimulateTransactionError>;
+
+    #[perf]
+    fn handle(
+        &mut self,
+        msg: WithSpanContext<SimulateTransaction>,
+        _ctx: &mut Self::Context,
+    ) -> Self::Result {
+        let (_span, msg) = handler_debug_span!(target: "client", msg);
+        let _timer = metrics::VIEW_CLIENT_MESSAGE_TIME
+            .with_label_values(&["SimulateTransaction"])
+            .start_timer();
+        let tx = msg.signed_transaction;
+        let head_header = self.chain.head_header()?;
+        let head_hash = *head_header.hash();
+        self.chain
+            .store()
+            .check_transaction_validity_period(
+                &head_header,
+                &tx.transaction.block_hash,
+                self.chain.transaction_validity_period,
+            )
+            .map_err(SimulateTransactionError::InvalidTransaction)?;
+
+        let epoch_id = self.runtime_adapter.get_epoch_id_from_prev_block(&head_hash)?;
+        let protocol_version = self.runtime_adapter.get_epoch_protocol_version(&epoch_id)?;
+        let mut state_roots = HashMap::new();
+        for shard_id in 0..self.runtime_adapter.num_shards(&epoch_id)? {
+            if !self.runtime_adapter.cares_about_shard(
+                self.validator_account_id.as_ref(),
+                &head_hash,
+                shard_id,
+                true,
+            ) {
+                continue;
+            }
+            let shard_uid = self.runtime_adapter.shard_id_to_uid(shard_id, &epoch_id)?;
+            // The node may have not caught up with the shards of the next epoch yet.
+            if let Ok(chunk_extra) = self.chain.get_chunk_extra(&head_hash, &shard_uid) {
+                state_roots.insert(shard_id, *chunk_extra.state_root());
+            }
+        }
+        let shard_id =
+            self.runtime_adapter.account_id_to_shard_id(&tx.transaction.signer_id, &epoch_id)?;
+        let state_root =
+            *state_roots.get(&shard_id).ok_or(SimulateTransactionError::DoesNotTrackShard)?;
+
+        let gas_price = head_header.gas_price();
+        if let Some(err) = self.runtime_adapter.validate_tx(
+            gas_price,
+            Some(state_root),
+            &tx,
+            true,
+            &epoch_id,
+            protocol_version,
+        )? {
+            return Err(SimulateTransactionError::InvalidTransaction(err));
+        }
+        let result = self.runtime_adapter.simulate_transaction(
+            &state_roots,
+            head_header.height() + 1,
+            head_header.raw_timestamp(),
+            &head_hash,
+            gas_price,
+            None,
+            &tx,
+        )?;
+
+        // There is no block including the outcomes, hence no block hash and proofs.
+        let mut outcomes = result.outcomes.into_iter().map(|outcome| ExecutionOutcomeWithIdView {
+            proof: vec![],
+            block_hash: CryptoHash::default(),
+            id: outcome.id,
+            outcome: outcome.outcome.into(),
+        });
+        let transaction_outcome = outcomes.next().ok_or_else(|| {
+            SimulateTransactionError::Unreachable("Transaction has no outcome".to_string())
+        })?;
+        Ok(SimulateTransactionResponse {
+            block_hash: head_hash,
+            transaction_outcome,
+            receipts_outcome: outcomes.collect(),
+            state_changes: result.state_changes.into_iter().map(Into::into).collect(),
+            unprocessed_receipts: result.unprocessed_receipts.into_iter().map(Into::into).collect(),
+        })
+    }
+}
+
 /// Starts the View Client in a new arbiter (thread).
 pub fn start_view_client(
     validator_account_id: Option<AccountId>,
@@ -302,16 +302,24 @@ impl EpochManager {
         next_epoch_info: EpochInfo,
     ) -> Result<StoreUpdate, EpochError> {
         let mut store_update = self.store.store_update();
+        let prev_epoch_start = prev_epoch_first_block_info.height();
+        let last_block_hash = *prev_epoch_last_block_info.hash();
+        let last_block_height = prev_epoch_last_block_info.height();
         self.save_block_info(&mut store_update, Arc::new(prev_epoch_first_block_info))?;
         self.save_block_info(&mut store_update, Arc::new(prev_epoch_prev_last_block_info))?;
         self.save_block_info(&mut store_update, Arc::new(prev_epoch_last_block_info))?;
         self.save_epoch_info(&mut store_update, prev_epoch_id, Arc::new(prev_epoch_info))?;
         self.save_epoch_info(&mut store_update, epoch_id, Arc::new(epoch_info))?;
         self.save_epoch_info(&mut store_update, next_epoch_id, Arc::new(next_epoch_info))?;
-        // TODO #3488
-        // put unreachable! here to avoid warnings
-        unreachable!();
-        // Ok(store_update)
+        self.save_epoch_start(&mut store_update, prev_epoch_id, prev_epoch_start)?;
+        // Statistics of the previous epoch are not known (and not needed since
+        // the epoch has already been finalised).  Start aggregating from the
+        // first block of `epoch_id` which will be processed next.
+        self.epoch_info_aggregator =
+            EpochInfoAggregator::new(prev_epoch_id.clone(), last_block_hash);
+        store_update.set_ser(DBCol::EpochInfo, AGGREGATOR_KEY, &self.epoch_info_aggregator)?;
+        self.largest_final_height = last_block_height;
+        Ok(store_update)
     }
 
     /// When computing validators to kickout, we exempt some validators first so that
@@ -0,0 +1,39 @@
+use std::sync::Arc;
+
+use anyhow::Context;
+use rocksdb::DB;
+
+use near_primitives::types::BlockHeight;
+
+const LAST_SYNCED_BLOCK_HEIGHT_KEY: &[u8] = b"last_synced_block_height";
+
+/// Height of the last block handled by the indexer, persisted in the indexer's
+/// own database next to the node storage. Used by `SyncModeEnum::FromInterruption`.
+#[derive(Clone)]
+pub(crate) struct Checkpoint {
+    db: Arc<DB>,
+}
+
+impl Checkpoint {
+    pub(crate) fn open(
+        home_dir: &std::path::Path,
+        store_config: &near_store::StoreConfig,
+    ) -> anyhow::Result<Self> {
+        let path =
+            near_store::NodeStorage::opener(home_dir, store_config, None).path().join("indexer");
+        let db = DB::open_default(&path)
+            .with_context(|| format!("failed to open indexer database at {}", path.display()))?;
+        Ok(Self { db: Arc::new(db) })
+    }
+
+    pub(crate) fn last_synced_block_height(&self) -> anyhow::Result<Option<BlockHeight>> {
+        match self.db.get(LAST_SYNCED_BLOCK_HEIGHT_KEY)? {
+            Some(value) => Ok(Some(String::from_utf8(value)?.parse()?)),
+            None => Ok(None),
+        }
+    }
+
+    pub(crate) fn set_last_synced_block_height(&self, height: BlockHeight) -> anyhow::Result<()> {
+        Ok(self.db.put(LAST_SYNCED_BLOCK_HEIGHT_KEY, &height.to_string())?)
+    }
+}
@@ -0,0 +1,213 @@
+use std::collections::HashSet;
+
+use near_indexer_primitives::IndexerShard;
+use near_primitives::shard_layout::{account_id_to_shard_id, ShardLayout};
+use near_primitives::types::{AccountId, ShardId};
+use near_primitives::views;
+
+/// Kind of a state change, see `near_primitives::views::StateChangeValueView`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StateChangeKind {
+    AccountUpdate,
+    AccountDeletion,
+    AccessKeyUpdate,
+    AccessKeyDeletion,
+    DataUpdate,
+    DataDeletion,
+    ContractCodeUpdate,
+    ContractCodeDeletion,
+}
+
+impl StateChangeKind {
+    fn of(value: &views::StateChangeValueView) -> Self {
+        match value {
+            views::StateChangeValueView::AccountUpdate { .. } => Self::AccountUpdate,
+            views::StateChangeValueView::AccountDeletion { .. } => Self::AccountDeletion,
+            views::StateChangeValueView::AccessKeyUpdate { .. } => Self::AccessKeyUpdate,
+            views::StateChangeValueView::AccessKeyDeletion { .. } => Self::AccessKeyDeletion,
+            views::StateChangeValueView::DataUpdate { .. } => Self::DataUpdate,
+            views::StateChangeValueView::DataDeletion { .. } => Self::DataDeletion,
+            views::StateChangeValueView::ContractCodeUpdate { .. } => Self::ContractCodeUpdate,
+            views::StateChangeValueView::ContractCodeDeletion { .. } => Self::ContractCodeDeletion,
+        }
+    }
+}
+
+/// Limits which parts of a block end up in a `StreamerMessage`. An empty list
+/// doesn't restrict anything, so the default filter keeps everything.
+///
+/// Transactions are matched by their signer or receiver, receipts and their
+/// execution outcomes by their receiver and state changes by the changed
+/// account. Receipts, outcomes and state changes live on the shard of the
+/// matched account, so when only exact account ids are listed they are not
+/// fetched for other shards at all.
+#[derive(Debug, Clone, Default)]
+pub struct IndexerFilter {
+    /// Accounts to keep. An entry starting with `*.` matches every sub-account
+    /// of the rest, e.g. `*.pool.near` matches `aurora.pool.near` but not
+    /// `pool.near` itself.
+    pub account_ids: Vec<String>,
+    /// Transactions and receipts are kept only if they have a `FunctionCall`
+    /// action calling one of these methods.
+    pub method_names: Vec<String>,
+    /// Kinds of state changes to keep.
+    pub state_change_kinds: Vec<StateChangeKind>,
+}
+
+impl IndexerFilter {
+    fn is_empty(&self) -> bool {
+        self.account_ids.is_empty()
+            && self.method_names.is_empty()
+            && self.state_change_kinds.is_empty()
+    }
+
+    fn matches_account(&self, account_id: &AccountId) -> bool {
+        self.account_ids.is_empty()
+            || self.account_ids.iter().any(|pattern| match pattern.strip_prefix('*') {
+                Some(suffix) => suffix.starts_with('.') && account_id.as_ref().ends_with(suffix),
+                None => pattern == account_id.as_ref(),
+            })
+    }
+
+    fn matches_actions(&self, actions: &[views::ActionView]) -> bool {
+        self.method_names.is_empty()
+            || actions.iter().any(|action| match action {
+                views::ActionView::FunctionCall { method_name, .. } => {
+                    self.method_names.contains(method_name)
+                }
+                _ => false,
+            })
+    }
+
+    fn matches_transaction(&self, transaction: &views::SignedTransactionView) -> bool {
+        (self.matches_account(&transaction.signer_id)
+            || self.matches_account(&transaction.receiver_id))
+            && self.matches_actions(&transaction.actions)
+    }
+
+    fn matches_receipt(&self, receipt: &views::ReceiptView) -> bool {
+        self.matches_account(&receipt.receiver_id)
+            && match &receipt.receipt {
+                views::ReceiptEnumView::Action { actions, .. } => self.matches_actions(actions),
+                views::ReceiptEnumView::Data { .. } => self.method_names.is_empty(),
+            }
+    }
+
+    fn matches_state_change(&self, state_change: &views::StateChangeWithCauseView) -> bool {
+        let account_id = match &state_change.value {
+            views::StateChangeValueView::AccountUpdate { account_id, .. }
+            | views::StateChangeValueView::AccountDeletion { account_id }
+            | views::StateChangeValueView::AccessKeyUpdate { account_id, .. }
+            | views::StateChangeValueView::AccessKeyDeletion { account_id, .. }
+            | views::StateChangeValueView::DataUpdate { account_id, .. }
+            | views::StateChangeValueView::DataDeletion { account_id, .. }
+            | views::StateChangeValueView::ContractCodeUpdate { account_id, .. }
+            | views::StateChangeValueView::ContractCodeDeletion { account_id } => account_id,
+        };
+        self.matches_account(account_id)
+            && (self.state_change_kinds.is_empty()
+                || self.state_change_kinds.contains(&StateChangeKind::of(&state_change.value)))
+    }
+
+    /// Returns the shards whose receipts, execution outcomes and state changes
+    /// may match the filter, or `None` if any shard may.
+    pub(crate) fn shards_to_fetch(&self, shard_layout: &ShardLayout) -> Option<HashSet<ShardId>> {
+        if self.account_ids.is_empty() || self.account_ids.iter().any(|id| id.starts_with('*')) {
+            return None;
+        }
+        Some(
+            self.account_ids
+                .iter()
+                // An invalid account id can't match anything.
+                .filter_map(|account_id| account_id.parse::<AccountId>().ok())
+                .map(|account_id| account_id_to_shard_id(&account_id, shard_layout))
+                .collect(),
+        )
+    }
+
+    /// Drops everything not matching the filter from the shards.
+    pub(crate) fn apply(&self, shards: &mut [IndexerShard]) {
+        if self.is_empty() {
+            return;
+        }
+        for shard in shards {
+            if let Some(chunk) = &mut shard.chunk {
+                chunk.transactions.retain(|tx| self.matches_transaction(&tx.transaction));
+                chunk.receipts.retain(|receipt| self.matches_receipt(receipt));
+            }
+            shard
+                .receipt_execution_outcomes
+                .retain(|outcome| self.matches_receipt(&outcome.receipt));
+            shard.state_changes.retain(|state_change| self.matches_state_change(state_change));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use near_crypto::{InMemorySigner, KeyType};
+    use near_primitives::hash::CryptoHash;
+    use near_primitives::transaction::SignedTransaction;
+
+    fn filter(account_ids: &[&str]) -> IndexerFilter {
+        IndexerFilter {
+            account_ids: account_ids.iter().map(|id| id.to_string()).collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_matches_account() {
+        let filter = filter(&["alice.near", "*.pool.near"]);
+        for (account_id, expected) in [
+            ("alice.near", true),
+            ("bob.near", false),
+            ("aurora.pool.near", true),
+            ("a.b.pool.near", true),
+            ("pool.near", false),
+            ("notpool.near", false),
+        ] {
+            assert_eq!(
+                filter.matches_account(&account_id.parse().unwrap()),
+                expected,
+                "{}",
+                account_id
+            );
+        }
+        assert!(IndexerFilter::default().matches_account(&"bob.near".parse().unwrap()));
+    }
+
+    #[test]
+    fn test_matches_transaction() {
+        let signer = InMemorySigner::from_seed("alice.near".parse().unwrap(), KeyType::ED25519, "");
+        let tx: views::SignedTransactionView = SignedTransaction::send_money(
+            1,
+            "alice.near".parse().unwrap(),
+            "contract.near".parse().unwrap(),
+            &signer,
+            100,
+            CryptoHash::default(),
+        )
+        .into();
+        assert!(filter(&["alice.near"]).matches_transaction(&tx));
+        assert!(filter(&["contract.near"]).matches_transaction(&tx));
+        assert!(!filter(&["bob.near"]).matches_transaction(&tx));
+    }
+
+    #[test]
+    fn test_shards_to_fetch() {
+        let shard_layout = ShardLayout::v1(vec![], vec!["m".parse().unwrap()], None, 1);
+        assert_eq!(filter(&[]).shards_to_fetch(&shard_layout), None);
+        assert_eq!(filter(&["a.near", "*.pool.near"]).shards_to_fetch(&shard_layout), None);
+        assert_eq!(
+            filter(&["a.near", "b.near"]).shards_to_fetch(&shard_layout),
+            Some(HashSet::from([0]))
+        );
+        assert_eq!(
+            filter(&["a.near", "z.near"]).shards_to_fetch(&shard_layout),
+            Some(HashSet::from([0, 1]))
+        );
+    }
+}
@@ -5,6 +5,7 @@ use tokio::sync::mpsc;
 
 use near_chain_configs::GenesisValidationMode;
 pub use near_primitives;
+use near_primitives::epoch_manager::AllEpochConfig;
 use near_primitives::types::Gas;
 pub use nearcore::{get_default_home, init_configs, NearConfig};
 
@@ -14,6 +15,12 @@ pub use near_indexer_primitives::{
     StreamerMessage,
 };
 
+pub use filter::{IndexerFilter, StateChangeKind};
+pub use sink::{JsonLinesFileSink, RotatingFileSink, Sink};
+
+mod checkpoint;
+mod filter;
+mod sink;
 mod streamer;
 
 pub const INDEXER: &str = "indexer";
@@ -55,10 +62,13 @@ pub struct InitConfigArgs {
 pub enum SyncModeEnum {
     /// Real-time syncing, always taking the latest finalized block to stream
     LatestSynced,
-    /// Starts syncing from the block NEAR Indexer was interrupted last time
+    /// Starts syncing from the block NEAR Indexer was interrupted last time. When streaming into
+    /// a `Sink`, that is the last block the sink acknowledged.
     FromInterruption,
     /// Specific block height to start syncing from
     BlockHeight(u64),
+    /// Backfill blocks from `start` to `end` (both inclusive) and stop streaming afterwards
+    BlockRange { start: u64, end: u64 },
 }
 
 /// Enum to define whether await for node to be fully synced or stream while syncing (useful for indexing from genesis)
@@ -81,6 +91,26 @@ pub struct IndexerConfig {
     pub await_for_node_synced: AwaitForNodeSyncedEnum,
     /// Tells whether to validate the genesis file before starting
     pub validate_genesis: bool,
+    /// Number of blocks fetched concurrently while catching up. `StreamerMessage`s are
+    /// still emitted in order of block height
+    pub fetch_concurrency: usize,
+    /// Limits which accounts, methods and state changes end up in `StreamerMessage`s
+    pub filter: IndexerFilter,
+}
+
+impl Default for IndexerConfig {
+    /// Streams every block starting from the latest one once the node in the
+    /// default home directory is synced, fetching blocks one by one.
+    fn default() -> Self {
+        Self {
+            home_dir: get_default_home(),
+            sync_mode: SyncModeEnum::LatestSynced,
+            await_for_node_synced: AwaitForNodeSyncedEnum::WaitForFullSync,
+            validate_genesis: true,
+            fetch_concurrency: 1,
+            filter: IndexerFilter::default(),
+        }
+    }
 }
 
 /// This is the core component, which handles `nearcore` and internal `streamer`.
@@ -89,6 +119,7 @@ pub struct Indexer {
     near_config: nearcore::NearConfig,
     view_client: actix::Addr<near_client::ViewClientActor>,
     client: actix::Addr<near_client::ClientActor>,
+    checkpoint: checkpoint::Checkpoint,
 }
 
 impl Indexer {
@@ -116,23 +147,61 @@ impl Indexer {
             ",
             indexer_config.home_dir.join("config.json").display()
         );
+        let checkpoint =
+            checkpoint::Checkpoint::open(&indexer_config.home_dir, &near_config.config.store)?;
         let nearcore::NearNode { client, view_client, .. } =
             nearcore::start_with_config(&indexer_config.home_dir, near_config.clone())
                 .with_context(|| "start_with_config")?;
-        Ok(Self { view_client, client, near_config, indexer_config })
+        Ok(Self { view_client, client, near_config, indexer_config, checkpoint })
     }
 
     /// Boots up `near_indexer::streamer`, so it monitors the new blocks with chunks, transactions, receipts, and execution outcomes inside. The returned stream handler should be drained and handled on the user side.
-    pub fn streamer(&self) -> mpsc::Receiver<StreamerMessage> {
+    /// The stream ends if the streamer stops on an error, which is logged.
+    pub fn streamer(&self) -> anyhow::Result<mpsc::Receiver<StreamerMessage>> {
+        let (sender, receiver) = mpsc::channel(100);
+        let streamer = streamer::start(
+            self.view_client.clone(),
+            self.client.clone(),
+            self.indexer_config.clone(),
+            AllEpochConfig::from(&self.near_config.genesis.config),
+            self.checkpoint.clone(),
+            true,
+            sender,
+        );
+        actix::spawn(async move {
+            if let Err(err) = streamer.await {
+                tracing::error!(target: INDEXER, "Streamer stopped: {:#}", err);
+            }
+        });
+        Ok(receiver)
+    }
+
+    /// Boots up `near_indexer::streamer` and writes every `StreamerMessage` into the given sink.
+    /// A block is recorded as synced only once the sink acknowledges the write, so with
+    /// `SyncModeEnum::FromInterruption` every block is delivered at least once across restarts.
+    /// The returned handle resolves when streaming stops, with the sink or the streamer error if
+    /// there was one.
+    pub fn stream_into<S: Sink + 'static>(
+        &self,
+        sink: S,
+    ) -> anyhow::Result<tokio::task::JoinHandle<anyhow::Result<()>>> {
         let (sender, receiver) = mpsc::channel(100);
-        actix::spawn(streamer::start(
+        let streamer = actix::spawn(streamer::start(
             self.view_client.clone(),
             self.client.clone(),
             self.indexer_config.clone(),
-            self.near_config.config.store.clone(),
+            AllEpochConfig::from(&self.near_config.genesis.config),
+            self.checkpoint.clone(),
+            false,
             sender,
         ));
-        receiver
+        let checkpoint = self.checkpoint.clone();
+        Ok(actix::spawn(async move {
+            // The sink stops once the streamer drops the sender, and the
+            // streamer stops once the sink drops the receiver.
+            sink::run(receiver, sink, checkpoint).await?;
+            streamer.await?
+        }))
     }
 
     /// Expose neard config
@@ -0,0 +1,189 @@
+use std::path::{Path, PathBuf};
+
+use tokio::fs::{File, OpenOptions};
+use tokio::io::AsyncWriteExt;
+
+use near_indexer_primitives::StreamerMessage;
+use near_primitives::types::BlockHeight;
+
+use super::Sink;
+
+const SEGMENT_EXTENSION: &str = "jsonl";
+
+fn to_json_line(message: &StreamerMessage) -> anyhow::Result<Vec<u8>> {
+    let mut line = serde_json::to_vec(message)?;
+    line.push(b'\n');
+    Ok(line)
+}
+
+async fn open_for_append(path: &Path) -> std::io::Result<File> {
+    OpenOptions::new().create(true).append(true).open(path).await
+}
+
+/// Appends every message as a line of JSON to a single file.
+pub struct JsonLinesFileSink {
+    file: File,
+}
+
+impl JsonLinesFileSink {
+    /// Opens the file for appending, creating it if it doesn't exist.
+    pub async fn open(path: &Path) -> std::io::Result<Self> {
+        Ok(Self { file: open_for_append(path).await? })
+    }
+}
+
+#[async_trait::async_trait]
+impl Sink for JsonLinesFileSink {
+    async fn write(&mut self, message: &StreamerMessage) -> anyhow::Result<()> {
+        self.file.write_all(&to_json_line(message)?).await?;
+        self.file.sync_data().await?;
+        Ok(())
+    }
+}
+
+/// Segment of `RotatingFileSink` currently being written to.
+struct Segment {
+    file: File,
+    size: u64,
+}
+
+/// Writes messages as lines of JSON into segment files in a directory.
+///
+/// A new segment is started once the current one reaches `max_segment_size`
+/// bytes. Segments are named after the height of their first block, so they
+/// sort in block order, and only the newest `max_segments` are kept if set.
+pub struct RotatingFileSink {
+    dir: PathBuf,
+    max_segment_size: u64,
+    max_segments: Option<usize>,
+    current: Option<Segment>,
+}
+
+impl RotatingFileSink {
+    /// Creates the directory if it doesn't exist. The first message written
+    /// always starts a new segment.
+    pub async fn new(
+        dir: PathBuf,
+        max_segment_size: u64,
+        max_segments: Option<usize>,
+    ) -> std::io::Result<Self> {
+        tokio::fs::create_dir_all(&dir).await?;
+        Ok(Self { dir, max_segment_size, max_segments, current: None })
+    }
+
+    fn segment_path(&self, first_height: BlockHeight) -> PathBuf {
+        self.dir.join(format!("{:020}.{}", first_height, SEGMENT_EXTENSION))
+    }
+
+    /// Lists segment files in the directory, oldest first.
+    async fn segments(&self) -> std::io::Result<Vec<PathBuf>> {
+        let mut segments = vec![];
+        let mut entries = tokio::fs::read_dir(&self.dir).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path();
+            if path.extension().map_or(false, |ext| ext == SEGMENT_EXTENSION) {
+                segments.push(path);
+            }
+        }
+        segments.sort();
+        Ok(segments)
+    }
+
+    async fn start_segment(&mut self, first_height: BlockHeight) -> std::io::Result<()> {
+        let file = open_for_append(&self.segment_path(first_height)).await?;
+        let size = file.metadata().await?.len();
+        self.current = Some(Segment { file, size });
+        if let Some(max_segments) = self.max_segments {
+            let segments = self.segments().await?;
+            let excess = segments.len().saturating_sub(max_segments.max(1));
+            for path in &segments[..excess] {
+                tokio::fs::remove_file(path).await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn write_line(&mut self, height: BlockHeight, line: &[u8]) -> std::io::Result<()> {
+        let rotate = match &self.current {
+            Some(segment) => segment.size >= self.max_segment_size,
+            None => true,
+        };
+        if rotate {
+            self.start_segment(height).await?;
+        }
+        let segment = self.current.as_mut().expect("segment has just been started");
+        segment.file.write_all(line).await?;
+        segment.file.sync_data().await?;
+        segment.size += line.len() as u64;
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl Sink for RotatingFileSink {
+    async fn write(&mut self, message: &StreamerMessage) -> anyhow::Result<()> {
+        let line = to_json_line(message)?;
+        Ok(self.write_line(message.block.header.height, &line).await?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn read_segments(sink: &RotatingFileSink) -> Vec<(String, String)> {
+        let mut res = vec![];
+        for path in sink.segments().await.unwrap() {
+            let name = path.file_name().unwrap().to_str().unwrap().to_string();
+            res.push((name, std::fs::read_to_string(&path).unwrap()));
+        }
+        res
+    }
+
+    #[tokio::test]
+    async fn test_rotating_file_sink_rotates_segments() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut sink = RotatingFileSink::new(dir.path().to_path_buf(), 8, None).await.unwrap();
+        for height in 1..=5 {
+            sink.write_line(height, format!("block{}\n", height).as_bytes()).await.unwrap();
+        }
+        assert_eq!(
+            read_segments(&sink).await,
+            vec![
+                ("00000000000000000001.jsonl".to_string(), "block1\nblock2\n".to_string()),
+                ("00000000000000000003.jsonl".to_string(), "block3\nblock4\n".to_string()),
+                ("00000000000000000005.jsonl".to_string(), "block5\n".to_string()),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_rotating_file_sink_keeps_newest_segments() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut sink = RotatingFileSink::new(dir.path().to_path_buf(), 1, Some(2)).await.unwrap();
+        for height in 1..=4 {
+            sink.write_line(height, format!("block{}\n", height).as_bytes()).await.unwrap();
+        }
+        assert_eq!(
+            read_segments(&sink).await,
+            vec![
+                ("00000000000000000003.jsonl".to_string(), "block3\n".to_string()),
+                ("00000000000000000004.jsonl".to_string(), "block4\n".to_string()),
+            ]
+        );
+    }
+
+    /// After a restart the first re-delivered block is appended to the segment it started.
+    #[tokio::test]
+    async fn test_rotating_file_sink_appends_after_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut sink = RotatingFileSink::new(dir.path().to_path_buf(), 100, None).await.unwrap();
+        sink.write_line(1, b"block1\n").await.unwrap();
+        let mut sink = RotatingFileSink::new(dir.path().to_path_buf(), 100, None).await.unwrap();
+        sink.write_line(1, b"block1\n").await.unwrap();
+        assert_eq!(
+            read_segments(&sink).await,
+            vec![("00000000000000000001.jsonl".to_string(), "block1\nblock1\n".to_string())]
+        );
+    }
+}
@@ -0,0 +1,107 @@
+//! Destinations the indexer can write `StreamerMessage`s to instead of handing
+//! out a channel receiver, see [`crate::Indexer::stream_into`].
+use anyhow::Context;
+use tokio::sync::mpsc;
+use tracing::debug;
+
+use near_indexer_primitives::StreamerMessage;
+
+use crate::checkpoint::Checkpoint;
+use crate::INDEXER;
+
+pub use self::file::{JsonLinesFileSink, RotatingFileSink};
+
+mod file;
+
+/// Destination for `StreamerMessage`s, e.g. a file, a message queue or a database.
+#[async_trait::async_trait]
+pub trait Sink: Send {
+    /// Writes the message. Returning `Ok` acknowledges that the message is
+    /// durably written: only then the indexer records its block as synced, so
+    /// `SyncModeEnum::FromInterruption` resumes after the last acknowledged
+    /// block. A message may be written again after a restart, so sinks should
+    /// tolerate duplicates.
+    async fn write(&mut self, message: &StreamerMessage) -> anyhow::Result<()>;
+}
+
+/// Writes every received message into the sink and records its block in the
+/// checkpoint once the sink acknowledges the write. Stops on the first sink
+/// error, which drops the receiver and terminates the streamer.
+pub(crate) async fn run<S: Sink>(
+    mut messages: mpsc::Receiver<StreamerMessage>,
+    mut sink: S,
+    checkpoint: Checkpoint,
+) -> anyhow::Result<()> {
+    while let Some(message) = messages.recv().await {
+        let height = message.block.header.height;
+        sink.write(&message)
+            .await
+            .with_context(|| format!("sink failed to write block #{}", height))?;
+        checkpoint.set_last_synced_block_height(height)?;
+        debug!(target: INDEXER, "Block #{} written to the sink", height);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use near_primitives::block::Block;
+    use near_primitives::hash::CryptoHash;
+    use near_primitives::types::BlockHeight;
+    use near_primitives::version::PROTOCOL_VERSION;
+    use near_primitives::views::BlockView;
+
+    fn message(height: BlockHeight) -> StreamerMessage {
+        let block = Block::genesis(
+            PROTOCOL_VERSION,
+            vec![],
+            near_primitives::utils::from_timestamp(0),
+            height,
+            0,
+            0,
+            CryptoHash::default(),
+        );
+        StreamerMessage {
+            block: BlockView::from_author_block("test".parse().unwrap(), block),
+            shards: vec![],
+        }
+    }
+
+    /// Checks that the block is not yet recorded in the checkpoint when the
+    /// sink is asked to write it, and fails on `fail_at`.
+    struct CheckingSink {
+        checkpoint: Checkpoint,
+        fail_at: BlockHeight,
+    }
+
+    #[async_trait::async_trait]
+    impl Sink for CheckingSink {
+        async fn write(&mut self, message: &StreamerMessage) -> anyhow::Result<()> {
+            let height = message.block.header.height;
+            assert_eq!(
+                self.checkpoint.last_synced_block_height().unwrap(),
+                height.checked_sub(1).filter(|prev| *prev > 0)
+            );
+            anyhow::ensure!(height != self.fail_at, "failed to write block #{}", height);
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_checkpoint_advances_after_sink_acknowledges() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join("data")).unwrap();
+        let checkpoint = Checkpoint::open(dir.path(), &near_store::StoreConfig::default()).unwrap();
+        let (sender, receiver) = mpsc::channel(10);
+        for height in 1..=4 {
+            sender.send(message(height)).await.unwrap();
+        }
+        drop(sender);
+
+        let sink = CheckingSink { checkpoint: checkpoint.clone(), fail_at: 3 };
+        assert!(run(receiver, sink, checkpoint.clone()).await.is_err());
+        assert_eq!(checkpoint.last_synced_block_height().unwrap(), Some(2));
+    }
+}
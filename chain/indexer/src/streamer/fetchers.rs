@@ -1,6 +1,6 @@
 //! Streamer watches the network and collects all the blocks and related chunks
 //! into one struct and pushes in in to the given queue
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 use actix::Addr;
 use futures::stream::StreamExt;
@@ -67,15 +67,22 @@ pub(crate) async fn fetch_block(
         .map_err(|err| FailedToFetchData::String(err.to_string()))
 }
 
+/// Fetches state changes of the given block split by shard. If `shard_ids` is
+/// set, only changes of these shards are fetched.
 pub(crate) async fn fetch_state_changes(
     client: &Addr<near_client::ViewClientActor>,
     block_hash: CryptoHash,
     epoch_id: near_primitives::types::EpochId,
+    shard_ids: Option<HashSet<types::ShardId>>,
 ) -> Result<HashMap<near_primitives::types::ShardId, views::StateChangesView>, FailedToFetchData> {
     client
         .send(
-            near_client::GetStateChangesWithCauseInBlockForTrackedShards { block_hash, epoch_id }
-                .with_span_context(),
+            near_client::GetStateChangesWithCauseInBlockForTrackedShards {
+                block_hash,
+                epoch_id,
+                shard_ids,
+            }
+            .with_span_context(),
         )
         .await?
         .map_err(|err| FailedToFetchData::String(err.to_string()))
@@ -83,9 +90,12 @@ pub(crate) async fn fetch_state_changes(
 
 /// Fetch all ExecutionOutcomeWithId for current block
 /// Returns a HashMap where the key is shard id IndexerExecutionOutcomeWithOptionalReceipt
+/// Receipts are fetched only for the outcomes of shards accepted by `is_shard_fetched`,
+/// outcomes of other shards are returned without them.
 pub(crate) async fn fetch_outcomes(
     client: &Addr<near_client::ViewClientActor>,
     block_hash: CryptoHash,
+    is_shard_fetched: impl Fn(&types::ShardId) -> bool,
 ) -> Result<
     HashMap<near_primitives::types::ShardId, Vec<IndexerExecutionOutcomeWithOptionalReceipt>>,
     FailedToFetchData,
@@ -102,6 +112,13 @@ pub(crate) async fn fetch_outcomes(
     for (shard_id, shard_outcomes) in outcomes {
         let mut outcomes_with_receipts: Vec<IndexerExecutionOutcomeWithOptionalReceipt> = vec![];
         for outcome in shard_outcomes {
+            if !is_shard_fetched(&shard_id) {
+                outcomes_with_receipts.push(IndexerExecutionOutcomeWithOptionalReceipt {
+                    execution_outcome: outcome,
+                    receipt: None,
+                });
+                continue;
+            }
             let receipt = match fetch_receipt_by_id(&client, outcome.id).await {
                 Ok(res) => res,
                 Err(e) => {
@@ -1,8 +1,9 @@
+use std::ops::RangeInclusive;
 use std::time::Duration;
 
 use actix::Addr;
 use async_recursion::async_recursion;
-use rocksdb::DB;
+use futures::StreamExt;
 use tokio::sync::mpsc;
 use tokio::time;
 use tracing::{debug, info};
@@ -12,10 +13,13 @@ use near_indexer_primitives::{
     IndexerExecutionOutcomeWithReceipt, IndexerShard, IndexerTransactionWithOutcome,
     StreamerMessage,
 };
+use near_primitives::epoch_manager::AllEpochConfig;
 use near_primitives::hash::CryptoHash;
+use near_primitives::types::BlockHeight;
 use near_primitives::views;
 
-use crate::{AwaitForNodeSyncedEnum, IndexerConfig};
+use crate::checkpoint::Checkpoint;
+use crate::{AwaitForNodeSyncedEnum, IndexerConfig, IndexerFilter, SyncModeEnum};
 
 use self::errors::FailedToFetchData;
 use self::fetchers::{
@@ -65,24 +69,37 @@ fn test_problematic_blocks_hash() {
 
 /// This function supposed to return the entire `StreamerMessage`.
 /// It fetches the block and all related parts (chunks, outcomes, state changes etc.)
-/// and returns everything together in one struct
+/// and returns everything together in one struct.
+/// Only the parts matching `filter` are kept. Receipts, execution outcomes and state
+/// changes of shards which can't match it are not fetched at all.
 #[async_recursion]
 async fn build_streamer_message(
     client: &Addr<near_client::ViewClientActor>,
     block: views::BlockView,
+    filter: &IndexerFilter,
+    all_epoch_config: &AllEpochConfig,
 ) -> Result<StreamerMessage, FailedToFetchData> {
     let _timer = metrics::BUILD_STREAMER_MESSAGE_TIME.start_timer();
-    let chunks = fetch_block_chunks(&client, &block).await?;
-
     let protocol_config_view = fetch_protocol_config(&client, block.header.hash).await?;
     let num_shards = protocol_config_view.num_block_producer_seats_per_shard.len()
         as near_primitives::types::NumShards;
+    let shard_layout =
+        all_epoch_config.for_protocol_version(protocol_config_view.protocol_version).shard_layout;
+    let shards_to_fetch = filter.shards_to_fetch(&shard_layout);
+    let is_shard_fetched = |shard_id: &near_primitives::types::ShardId| {
+        shards_to_fetch.as_ref().map_or(true, |shards| shards.contains(shard_id))
+    };
+
+    // Chunks of every shard are fetched because transactions are also matched by
+    // their receiver, who may live on any shard.
+    let chunks = fetch_block_chunks(&client, &block).await?;
 
-    let mut shards_outcomes = fetch_outcomes(&client, block.header.hash).await?;
+    let mut shards_outcomes = fetch_outcomes(&client, block.header.hash, is_shard_fetched).await?;
     let mut state_changes = fetch_state_changes(
         &client,
         block.header.hash,
         near_primitives::types::EpochId(block.header.epoch_id.clone()),
+        shards_to_fetch.clone(),
     )
     .await?;
     let mut indexer_shards = (0..num_shards)
@@ -106,6 +123,11 @@ async fn build_streamer_message(
 
         // Take execution outcomes for receipts from the vec and keep only the ones for transactions
         let mut receipt_outcomes = outcomes.split_off(transactions.len());
+        let is_receiver_shard_fetched = is_shard_fetched(&header.shard_id);
+        if !is_receiver_shard_fetched {
+            // Receipts of this shard can't match the filter, so they weren't fetched.
+            receipt_outcomes.clear();
+        }
 
         let indexer_transactions = transactions
             .into_iter()
@@ -124,7 +146,10 @@ async fn build_streamer_message(
             &protocol_config_view,
             indexer_transactions
                 .iter()
-                .filter(|tx| tx.transaction.signer_id == tx.transaction.receiver_id)
+                .filter(|tx| {
+                    is_receiver_shard_fetched
+                        && tx.transaction.signer_id == tx.transaction.receiver_id
+                })
                 .collect::<Vec<&IndexerTransactionWithOutcome>>(),
             &block,
         )
@@ -222,6 +247,9 @@ async fn build_streamer_message(
     // chunks and we end up with non-empty `shards_outcomes` we want to be sure we put them into IndexerShard
     // That might happen before the fix https://github.com/near/nearcore/pull/4228
     for (shard_id, outcomes) in shards_outcomes {
+        if !is_shard_fetched(&shard_id) {
+            continue;
+        }
         indexer_shards[shard_id as usize].receipt_execution_outcomes.extend(
             outcomes.into_iter().map(|outcome| IndexerExecutionOutcomeWithReceipt {
                 execution_outcome: outcome.execution_outcome,
@@ -230,6 +258,8 @@ async fn build_streamer_message(
         )
     }
 
+    filter.apply(&mut indexer_shards);
+
     Ok(StreamerMessage { block, shards: indexer_shards })
 }
 
@@ -241,7 +271,7 @@ async fn find_local_receipt_by_id_in_block(
     block: views::BlockView,
     receipt_id: near_primitives::hash::CryptoHash,
 ) -> Result<Option<views::ReceiptView>, FailedToFetchData> {
-    let chunks = fetch_block_chunks(&client, &block).await?;
+    let chunks = fetch_block_chunks(&client, &block, |_| true).await?;
 
     let mut shards_outcomes = fetch_outcomes(&client, block.header.hash).await?;
 
@@ -282,22 +312,22 @@ async fn find_local_receipt_by_id_in_block(
 /// compares to already fetched block height and in case it differs fetches new block of given height.
 ///
 /// We have to pass `client: Addr<near_client::ClientActor>` and `view_client: Addr<near_client::ViewClientActor>`.
+///
+/// If `save_checkpoint` is false, the caller is responsible for recording handled blocks in
+/// `checkpoint`, e.g. once they are durably written by a `Sink`.
+///
+/// Stops with an error if the checkpoint can't be read or written.
 pub(crate) async fn start(
     view_client: Addr<near_client::ViewClientActor>,
     client: Addr<near_client::ClientActor>,
     indexer_config: IndexerConfig,
-    store_config: near_store::StoreConfig,
+    all_epoch_config: AllEpochConfig,
+    checkpoint: Checkpoint,
+    save_checkpoint: bool,
     blocks_sink: mpsc::Sender<StreamerMessage>,
-) {
+) -> anyhow::Result<()> {
     info!(target: INDEXER, "Starting Streamer...");
-    let indexer_db_path =
-        near_store::NodeStorage::opener(&indexer_config.home_dir, &store_config, None)
-            .path()
-            .join("indexer");
-
-    // TODO: implement proper error handling
-    let db = DB::open_default(indexer_db_path).unwrap();
-    let mut last_synced_block_height: Option<near_primitives::types::BlockHeight> = None;
+    let mut last_synced_block_height: Option<BlockHeight> = None;
 
     'main: loop {
         time::sleep(INTERVAL).await;
@@ -320,22 +350,14 @@ pub(crate) async fn start(
         };
 
         let latest_block_height = block.header.height;
-        let start_syncing_block_height = if let Some(last_synced_block_height) =
-            last_synced_block_height
-        {
-            last_synced_block_height + 1
-        } else {
-            match indexer_config.sync_mode {
-                crate::SyncModeEnum::FromInterruption => {
-                    match db.get(b"last_synced_block_height").unwrap() {
-                        Some(value) => String::from_utf8(value).unwrap().parse::<u64>().unwrap(),
-                        None => latest_block_height,
-                    }
-                }
-                crate::SyncModeEnum::LatestSynced => latest_block_height,
-                crate::SyncModeEnum::BlockHeight(height) => height,
-            }
-        };
+        let heights = heights_to_sync(
+            &indexer_config.sync_mode,
+            last_synced_block_height,
+            || checkpoint.last_synced_block_height(),
+            latest_block_height,
+        )?;
+        let (start_syncing_block_height, end_syncing_block_height) =
+            (*heights.start(), *heights.end());
 
         debug!(
             target: INDEXER,
@@ -345,35 +367,153 @@ pub(crate) async fn start(
         );
         metrics::START_BLOCK_HEIGHT.set(start_syncing_block_height as i64);
         metrics::LATEST_BLOCK_HEIGHT.set(latest_block_height as i64);
-        for block_height in start_syncing_block_height..=latest_block_height {
+        // Blocks are fetched and assembled concurrently, but `buffered` yields
+        // them in the order of heights, so messages are still sent in order.
+        let mut streamer_messages = futures::stream::iter(heights)
+            .map(|block_height| {
+                let view_client = &view_client;
+                let filter = &indexer_config.filter;
+                let all_epoch_config = &all_epoch_config;
+                async move {
+                    let response = match fetch_block_by_height(view_client, block_height).await {
+                        Ok(block) => Some(
+                            build_streamer_message(view_client, block, filter, all_epoch_config)
+                                .await,
+                        ),
+                        Err(_) => None,
+                    };
+                    (block_height, response)
+                }
+            })
+            .buffered(std::cmp::max(indexer_config.fetch_concurrency, 1));
+        while let Some((block_height, response)) = streamer_messages.next().await {
             metrics::CURRENT_BLOCK_HEIGHT.set(block_height as i64);
-            if let Ok(block) = fetch_block_by_height(&view_client, block_height).await {
-                let response = build_streamer_message(&view_client, block).await;
-
-                match response {
-                    Ok(streamer_message) => {
-                        debug!(target: INDEXER, "{:#?}", &streamer_message);
-                        if blocks_sink.send(streamer_message).await.is_err() {
-                            info!(
-                                target: INDEXER,
-                                "Unable to send StreamerMessage to listener, listener doesn't listen. terminating..."
-                            );
-                            break 'main;
-                        } else {
-                            metrics::NUM_STREAMER_MESSAGES_SENT.inc();
-                        }
-                    }
-                    Err(err) => {
-                        debug!(
+            match response {
+                Some(Ok(streamer_message)) => {
+                    debug!(target: INDEXER, "{:#?}", &streamer_message);
+                    if blocks_sink.send(streamer_message).await.is_err() {
+                        info!(
                             target: INDEXER,
-                            "Missing data, skipping block #{}...", block_height
+                            "Unable to send StreamerMessage to listener, listener doesn't listen. terminating..."
                         );
-                        debug!(target: INDEXER, "{:#?}", err);
+                        break 'main;
+                    } else {
+                        metrics::NUM_STREAMER_MESSAGES_SENT.inc();
                     }
                 }
+                Some(Err(err)) => {
+                    debug!(target: INDEXER, "Missing data, skipping block #{}...", block_height);
+                    debug!(target: INDEXER, "{:#?}", err);
+                }
+                None => {}
+            }
+            if save_checkpoint {
+                checkpoint.set_last_synced_block_height(block_height)?;
             }
-            db.put(b"last_synced_block_height", &block_height.to_string()).unwrap();
             last_synced_block_height = Some(block_height);
         }
+
+        if is_range_synced(&indexer_config.sync_mode, end_syncing_block_height) {
+            info!(target: INDEXER, "Reached the end of the block range, stopping");
+            break 'main;
+        }
+    }
+    Ok(())
+}
+
+/// Heights of the blocks the streamer handles in one iteration of its loop.
+///
+/// `interrupted_at` is queried only for `SyncModeEnum::FromInterruption` on the
+/// first iteration and returns the height recorded in the checkpoint.  The range
+/// is empty if there are no new blocks.
+fn heights_to_sync(
+    sync_mode: &SyncModeEnum,
+    last_synced_block_height: Option<BlockHeight>,
+    interrupted_at: impl FnOnce() -> anyhow::Result<Option<BlockHeight>>,
+    latest_block_height: BlockHeight,
+) -> anyhow::Result<RangeInclusive<BlockHeight>> {
+    let start = match (last_synced_block_height, sync_mode) {
+        (Some(last_synced_block_height), _) => last_synced_block_height + 1,
+        (None, SyncModeEnum::FromInterruption) => interrupted_at()?.unwrap_or(latest_block_height),
+        (None, SyncModeEnum::LatestSynced) => latest_block_height,
+        (None, SyncModeEnum::BlockHeight(height)) => *height,
+        (None, SyncModeEnum::BlockRange { start, .. }) => *start,
+    };
+    let end = match sync_mode {
+        SyncModeEnum::BlockRange { end, .. } => std::cmp::min(*end, latest_block_height),
+        _ => latest_block_height,
+    };
+    Ok(start..=end)
+}
+
+/// Whether the streamer has handled every block it was asked for, given the
+/// height it has synced up to.  Only `SyncModeEnum::BlockRange` ever finishes.
+fn is_range_synced(sync_mode: &SyncModeEnum, synced_block_height: BlockHeight) -> bool {
+    match sync_mode {
+        SyncModeEnum::BlockRange { end, .. } => synced_block_height >= *end,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn range(start: BlockHeight, end: BlockHeight) -> SyncModeEnum {
+        SyncModeEnum::BlockRange { start, end }
+    }
+
+    fn heights(
+        sync_mode: &SyncModeEnum,
+        last_synced_block_height: Option<BlockHeight>,
+        interrupted_at: Option<BlockHeight>,
+        latest_block_height: BlockHeight,
+    ) -> RangeInclusive<BlockHeight> {
+        heights_to_sync(
+            sync_mode,
+            last_synced_block_height,
+            || Ok(interrupted_at),
+            latest_block_height,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_block_range_starts_at_range_start() {
+        assert_eq!(heights(&range(10, 20), None, Some(5), 100), 10..=20);
+        // The range isn't clamped by a checkpoint left over from a previous run.
+        assert_eq!(heights(&range(10, 20), None, Some(15), 100), 10..=20);
+    }
+
+    #[test]
+    fn test_block_range_waits_for_the_chain_to_reach_range_end() {
+        assert_eq!(heights(&range(10, 20), None, None, 15), 10..=15);
+        assert!(!is_range_synced(&range(10, 20), 15));
+        assert_eq!(heights(&range(10, 20), Some(15), None, 15).count(), 0);
+        assert_eq!(heights(&range(10, 20), Some(15), None, 30), 16..=20);
+        assert!(is_range_synced(&range(10, 20), 20));
+    }
+
+    #[test]
+    fn test_block_range_ahead_of_the_chain() {
+        assert_eq!(heights(&range(50, 60), None, None, 20).count(), 0);
+        assert!(!is_range_synced(&range(50, 60), 20));
+    }
+
+    #[test]
+    fn test_other_sync_modes_never_finish() {
+        assert_eq!(heights(&SyncModeEnum::LatestSynced, None, None, 7), 7..=7);
+        assert_eq!(heights(&SyncModeEnum::BlockHeight(3), None, None, 7), 3..=7);
+        assert_eq!(heights(&SyncModeEnum::FromInterruption, None, Some(4), 7), 4..=7);
+        assert_eq!(heights(&SyncModeEnum::FromInterruption, Some(5), None, 7), 6..=7);
+        assert!(!is_range_synced(&SyncModeEnum::LatestSynced, u64::MAX));
+    }
+
+    #[test]
+    fn test_checkpoint_error() {
+        let failing = || Err(anyhow::anyhow!("checkpoint is unavailable"));
+        assert!(heights_to_sync(&SyncModeEnum::FromInterruption, None, failing, 7).is_err());
+        // The checkpoint is read only when resuming from an interruption.
+        assert_eq!(heights_to_sync(&SyncModeEnum::LatestSynced, None, failing, 7).unwrap(), 7..=7);
     }
 }
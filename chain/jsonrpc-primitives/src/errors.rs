@@ -93,6 +93,11 @@ impl RpcError {
         }
     }
 
+    /// Create an Invalid Request error.
+    pub fn invalid_request(e: String) -> Self {
+        RpcError::new(-32_600, "Invalid Request".to_owned(), Some(Value::String(e)))
+    }
+
     pub fn serialization_error(e: String) -> Self {
         RpcError::new_internal_error(Some(Value::String(e.clone())), e)
     }
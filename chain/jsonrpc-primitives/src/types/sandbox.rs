@@ -1,5 +1,7 @@
+use near_primitives::gas_trace::GasTraceFormat;
+use near_primitives::hash::CryptoHash;
 use near_primitives::state_record::StateRecord;
-use near_primitives::types::BlockHeightDelta;
+use near_primitives::types::{AccountId, BlockHeightDelta, BlockReference};
 use serde::{Deserialize, Serialize};
 
 #[derive(Deserialize, Serialize, Debug)]
@@ -61,3 +63,159 @@ impl From<RpcSandboxFastForwardError> for crate::errors::RpcError {
         Self::new_internal_or_handler_error(Some(error_data.clone()), error_data)
     }
 }
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct RpcSandboxSnapshotRequest {
+    pub name: String,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct RpcSandboxSnapshotResponse {}
+
+#[derive(thiserror::Error, Debug, Serialize, Deserialize)]
+#[serde(tag = "name", content = "info", rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum RpcSandboxSnapshotError {
+    #[error("The node reached its limits. Try again later. More details: {error_message}")]
+    InternalError { error_message: String },
+}
+
+impl From<RpcSandboxSnapshotError> for crate::errors::RpcError {
+    fn from(error: RpcSandboxSnapshotError) -> Self {
+        let error_data = match serde_json::to_value(error) {
+            Ok(value) => value,
+            Err(err) => {
+                return Self::new_internal_error(
+                    None,
+                    format!("Failed to serialize RpcSandboxSnapshotError: {:?}", err),
+                )
+            }
+        };
+        Self::new_internal_or_handler_error(Some(error_data.clone()), error_data)
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct RpcSandboxRestoreRequest {
+    pub name: String,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct RpcSandboxRestoreResponse {}
+
+#[derive(thiserror::Error, Debug, Serialize, Deserialize)]
+#[serde(tag = "name", content = "info", rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum RpcSandboxRestoreError {
+    #[error("The node reached its limits. Try again later. More details: {error_message}")]
+    InternalError { error_message: String },
+}
+
+impl From<RpcSandboxRestoreError> for crate::errors::RpcError {
+    fn from(error: RpcSandboxRestoreError) -> Self {
+        let error_data = match serde_json::to_value(error) {
+            Ok(value) => value,
+            Err(err) => {
+                return Self::new_internal_error(
+                    None,
+                    format!("Failed to serialize RpcSandboxRestoreError: {:?}", err),
+                )
+            }
+        };
+        Self::new_internal_or_handler_error(Some(error_data.clone()), error_data)
+    }
+}
+
+/// Where `sandbox_import_accounts` reads the state of the accounts from.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(rename_all = "snake_case")]
+pub enum RpcSandboxImportSource {
+    /// JSON RPC endpoint of a node tracking the shards of the accounts.
+    RpcUrl(String),
+    /// Database directory of a stopped node on the same machine, e.g.
+    /// `~/.near/data`.
+    DataDir(std::path::PathBuf),
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct RpcSandboxImportAccountsRequest {
+    #[serde(flatten)]
+    pub source: RpcSandboxImportSource,
+    /// Accounts to import.  Their existing state on the sandbox node, including
+    /// access keys and contract data, is replaced by the imported one.
+    pub account_ids: Vec<AccountId>,
+    #[serde(flatten)]
+    pub block_reference: BlockReference,
+    /// If set, the access keys of the imported accounts are replaced with
+    /// a single full access key with this public key.
+    #[serde(default)]
+    pub access_key: Option<near_crypto::PublicKey>,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct RpcSandboxImportAccountsResponse {
+    /// Number of state records patched in.
+    pub records: usize,
+}
+
+#[derive(thiserror::Error, Debug, Serialize, Deserialize)]
+#[serde(tag = "name", content = "info", rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum RpcSandboxImportAccountsError {
+    #[error("Account {account_id} does not exist at the requested block")]
+    UnknownAccount { account_id: AccountId },
+    #[error("Failed to read state from the source: {error_message}")]
+    SourceError { error_message: String },
+    #[error("The node reached its limits. Try again later. More details: {error_message}")]
+    InternalError { error_message: String },
+}
+
+impl From<RpcSandboxImportAccountsError> for crate::errors::RpcError {
+    fn from(error: RpcSandboxImportAccountsError) -> Self {
+        let error_data = match serde_json::to_value(error) {
+            Ok(value) => value,
+            Err(err) => {
+                return Self::new_internal_error(
+                    None,
+                    format!("Failed to serialize RpcSandboxImportAccountsError: {:?}", err),
+                )
+            }
+        };
+        Self::new_internal_or_handler_error(Some(error_data.clone()), error_data)
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct RpcSandboxGasTraceRequest {
+    pub receipt_id: CryptoHash,
+    #[serde(default)]
+    pub format: GasTraceFormat,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct RpcSandboxGasTraceResponse {
+    /// Gas traces of the function calls of the receipt in the requested
+    /// format.
+    pub trace: serde_json::Value,
+}
+
+#[derive(thiserror::Error, Debug, Serialize, Deserialize)]
+#[serde(tag = "name", content = "info", rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum RpcSandboxGasTraceError {
+    #[error("Receipt {receipt_id} has not been recently executed by this node with `sandbox_gas_traces` enabled")]
+    UnknownReceipt { receipt_id: CryptoHash },
+    #[error("The node reached its limits. Try again later. More details: {error_message}")]
+    InternalError { error_message: String },
+}
+
+impl From<RpcSandboxGasTraceError> for crate::errors::RpcError {
+    fn from(error: RpcSandboxGasTraceError) -> Self {
+        let error_data = match serde_json::to_value(error) {
+            Ok(value) => value,
+            Err(err) => {
+                return Self::new_internal_error(
+                    None,
+                    format!("Failed to serialize RpcSandboxGasTraceError: {:?}", err),
+                )
+            }
+        };
+        Self::new_internal_or_handler_error(Some(error_data.clone()), error_data)
+    }
+}
@@ -38,6 +38,8 @@ pub enum RpcTransactionError {
     InternalError { debug_info: String },
     #[error("Timeout")]
     TimeoutError,
+    #[error("Transaction pool of the node is full. Try again later")]
+    TransactionPoolFull,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -51,6 +53,15 @@ pub struct RpcBroadcastTxSyncResponse {
     pub transaction_hash: near_primitives::hash::CryptoHash,
 }
 
+#[derive(Serialize, Deserialize, Debug)]
+pub struct RpcSimulateTransactionResponse {
+    pub block_hash: near_primitives::hash::CryptoHash,
+    pub transaction_outcome: near_primitives::views::ExecutionOutcomeWithIdView,
+    pub receipts_outcome: Vec<near_primitives::views::ExecutionOutcomeWithIdView>,
+    pub state_changes: near_primitives::views::StateChangesView,
+    pub unprocessed_receipts: Vec<near_primitives::views::ReceiptView>,
+}
+
 impl From<RpcTransactionError> for crate::errors::RpcError {
     fn from(error: RpcTransactionError) -> Self {
         let error_data = match &error {
@@ -36,19 +36,13 @@ const PAYLOAD_LIMIT: usize = 100 * 1024 * 1024;
 type HttpRequest<T> = LocalBoxFuture<'static, Result<T, String>>;
 type RpcRequest<T> = LocalBoxFuture<'static, Result<T, RpcError>>;
 
-/// Prepare a `RPCRequest` with a given client, server address, method and parameters.
-fn call_method<P, R>(client: &Client, server_addr: &str, method: &str, params: P) -> RpcRequest<R>
-where
-    P: Serialize,
-    R: serde::de::DeserializeOwned + 'static,
-{
-    let request =
-        Message::request(method.to_string(), Some(serde_json::to_value(&params).unwrap()));
+/// Sends a JSON RPC message to the server and returns the message it responds with.
+fn send_message(client: &Client, server_addr: &str, message: &Message) -> RpcRequest<Message> {
     // TODO: simplify this.
     client
         .post(server_addr)
         .insert_header(("Content-Type", "application/json"))
-        .send_json(&request)
+        .send_json(message)
         .map_err(|err| RpcError::new_internal_error(None, format!("{:?}", err)))
         .and_then(|mut response| {
             response.body().limit(PAYLOAD_LIMIT).map(|body| match body {
@@ -60,6 +54,18 @@ where
                 }
             })
         })
+        .boxed_local()
+}
+
+/// Prepare a `RPCRequest` with a given client, server address, method and parameters.
+fn call_method<P, R>(client: &Client, server_addr: &str, method: &str, params: P) -> RpcRequest<R>
+where
+    P: Serialize,
+    R: serde::de::DeserializeOwned + 'static,
+{
+    let request =
+        Message::request(method.to_string(), Some(serde_json::to_value(&params).unwrap()));
+    send_message(client, server_addr, &request)
         .and_then(|message| {
             future::ready(match message {
                 Message::Response(resp) => resp.result.and_then(|x| {
@@ -72,6 +78,62 @@ where
         .boxed_local()
 }
 
+/// Calls sent to the server together as a single JSON RPC batch request, see
+/// [`JsonRpcClient::batch`].
+pub struct BatchRequest {
+    server_addr: String,
+    client: Client,
+    requests: Vec<Message>,
+}
+
+impl BatchRequest {
+    /// Adds a call of the method with the given parameters to the batch.
+    pub fn call<P: Serialize>(mut self, method: &str, params: P) -> Self {
+        self.requests.push(Message::request(
+            method.to_string(),
+            Some(serde_json::to_value(&params).unwrap()),
+        ));
+        self
+    }
+
+    /// Sends the batch.  Results of the calls are returned in the order the
+    /// calls were added and each of them succeeds or fails on its own.  The
+    /// whole request fails only if the server rejects the batch itself, e.g.
+    /// when it's larger than the server allows.
+    pub fn send(self) -> RpcRequest<Vec<Result<serde_json::Value, RpcError>>> {
+        let ids: Vec<_> = self.requests.iter().map(Message::id).collect();
+        send_message(&self.client, &self.server_addr, &Message::Batch(self.requests))
+            .and_then(move |message| {
+                future::ready(match message {
+                    Message::Batch(responses) => Ok(ids
+                        .iter()
+                        .map(|id| {
+                            responses
+                                .iter()
+                                .find_map(|response| match response {
+                                    Message::Response(response) if &response.id == id => {
+                                        Some(response.result.clone())
+                                    }
+                                    _ => None,
+                                })
+                                .unwrap_or_else(|| {
+                                    Err(RpcError::parse_error(format!(
+                                        "No response for request {}",
+                                        id
+                                    )))
+                                })
+                        })
+                        .collect()),
+                    Message::Response(response) => response.result.and_then(|_| {
+                        Err(RpcError::parse_error(format!("Expected a batch response")))
+                    }),
+                    _ => Err(RpcError::parse_error(format!("Failed to parse JSON RPC response"))),
+                })
+            })
+            .boxed_local()
+    }
+}
+
 /// Prepare a `HttpRequest` with a given client, server address and parameters.
 fn call_http_get<R, P>(
     client: &Client,
@@ -194,6 +256,8 @@ jsonrpc_client!(pub struct JsonRpcClient {
     pub fn EXPERIMENTAL_broadcast_tx_sync(&self, tx: String) -> RpcRequest<serde_json::Value>;
     #[allow(non_snake_case)]
     pub fn EXPERIMENTAL_tx_status(&self, tx: String) -> RpcRequest<serde_json::Value>;
+    #[allow(non_snake_case)]
+    pub fn EXPERIMENTAL_simulate_tx(&self, tx: String) -> RpcRequest<serde_json::Value>;
     pub fn health(&self) -> RpcRequest<()>;
     pub fn tx(&self, hash: String, account_id: AccountId) -> RpcRequest<FinalExecutionOutcomeView>;
     pub fn chunk(&self, id: ChunkId) -> RpcRequest<ChunkView>;
@@ -202,6 +266,15 @@ jsonrpc_client!(pub struct JsonRpcClient {
 });
 
 impl JsonRpcClient {
+    /// Starts a batch of calls to be sent to the server in a single request.
+    pub fn batch(&self) -> BatchRequest {
+        BatchRequest {
+            server_addr: self.server_addr.clone(),
+            client: self.client.clone(),
+            requests: Vec::new(),
+        }
+    }
+
     /// This is a soft-deprecated method to do query RPC request with a path and data positional
     /// parameters.
     pub fn query_by_path(
@@ -258,6 +331,13 @@ impl JsonRpcClient {
     ) -> RpcRequest<near_jsonrpc_primitives::types::config::RpcProtocolConfigResponse> {
         call_method(&self.client, &self.server_addr, "EXPERIMENTAL_protocol_config", request)
     }
+
+    pub fn sandbox_import_accounts(
+        &self,
+        request: near_jsonrpc_primitives::types::sandbox::RpcSandboxImportAccountsRequest,
+    ) -> RpcRequest<near_jsonrpc_primitives::types::sandbox::RpcSandboxImportAccountsResponse> {
+        call_method(&self.client, &self.server_addr, "sandbox_import_accounts", request)
+    }
 }
 
 fn create_client() -> Client {
@@ -22,10 +22,27 @@ pub fn start_all(node_type: NodeType) -> (Addr<ViewClientActor>, String) {
     start_all_with_validity_period_and_no_epoch_sync(node_type, 100, false)
 }
 
+/// Like [`start_all`], but lets the test adjust the RPC server config.
+pub fn start_all_with_rpc_config(
+    node_type: NodeType,
+    configure: impl FnOnce(&mut RpcConfig),
+) -> (Addr<ViewClientActor>, String) {
+    start_all_impl(node_type, 100, false, configure)
+}
+
 pub fn start_all_with_validity_period_and_no_epoch_sync(
     node_type: NodeType,
     transaction_validity_period: NumBlocks,
     enable_doomslug: bool,
+) -> (Addr<ViewClientActor>, String) {
+    start_all_impl(node_type, transaction_validity_period, enable_doomslug, |_| {})
+}
+
+fn start_all_impl(
+    node_type: NodeType,
+    transaction_validity_period: NumBlocks,
+    enable_doomslug: bool,
+    configure: impl FnOnce(&mut RpcConfig),
 ) -> (Addr<ViewClientActor>, String) {
     let (client_addr, view_client_addr) = setup_no_network_with_validity_period_and_no_epoch_sync(
         vec!["test1".parse().unwrap(), "test2".parse().unwrap()],
@@ -40,8 +57,10 @@ pub fn start_all_with_validity_period_and_no_epoch_sync(
     );
 
     let addr = format!("127.0.0.1:{}", open_port());
+    let mut rpc_config = RpcConfig::new(&addr);
+    configure(&mut rpc_config);
     start_http(
-        RpcConfig::new(&addr),
+        rpc_config,
         TEST_GENESIS_CONFIG.clone(),
         client_addr,
         view_client_addr.clone(),
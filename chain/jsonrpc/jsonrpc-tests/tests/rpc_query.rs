@@ -582,3 +582,36 @@ fn test_get_chunk_with_object_in_params() {
         assert_eq!(chunk.header.chunk_hash, same_chunk.header.chunk_hash);
     });
 }
+
+/// Sends several requests in a single batch, one of which fails.
+#[test]
+fn test_batch() {
+    test_with_client!(test_utils::NodeType::NonValidator, client, async move {
+        let results = client
+            .batch()
+            .call("block", json!({"block_id": 0}))
+            .call("no_such_method", json!([]))
+            .call("gas_price", json!([null]))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(results.len(), 3);
+        let block: near_primitives::views::BlockView =
+            serde_json::from_value(results[0].clone().unwrap()).unwrap();
+        assert_eq!(block.header.height, 0);
+        assert_eq!(results[1].as_ref().unwrap_err().code, -32_601);
+        let gas_price: near_primitives::views::GasPriceView =
+            serde_json::from_value(results[2].clone().unwrap()).unwrap();
+        assert!(gas_price.gas_price > 0);
+    });
+}
+
+/// Batches larger than `max_batch_size` are rejected as a whole.
+#[test]
+fn test_batch_too_large() {
+    test_with_client!(test_utils::NodeType::NonValidator, client, async move {
+        let batch = (0..101).fold(client.batch(), |batch, _| batch.call("status", json!([])));
+        let err = batch.send().await.unwrap_err();
+        assert_eq!(err.code, -32_600);
+    });
+}
@@ -0,0 +1,140 @@
+use std::fmt::Debug;
+use std::time::Duration;
+
+use actix::System;
+use futures::{Sink, SinkExt, Stream, StreamExt};
+use serde_json::{json, Value};
+
+use near_actix_test_utils::run_actix;
+use near_o11y::testonly::init_test_logger;
+use near_primitives::hash::CryptoHash;
+
+use near_jsonrpc_tests as test_utils;
+
+/// Receives the next JSON message from the socket, skipping control frames.
+async fn recv<S, E>(ws: &mut S) -> Value
+where
+    S: Stream<Item = Result<awc::ws::Frame, E>> + Unpin,
+    E: Debug,
+{
+    loop {
+        match ws.next().await.expect("connection closed").unwrap() {
+            awc::ws::Frame::Text(text) => return serde_json::from_slice(&text).unwrap(),
+            awc::ws::Frame::Ping(_) | awc::ws::Frame::Pong(_) => {}
+            frame => panic!("unexpected frame {:?}", frame),
+        }
+    }
+}
+
+/// Calls the method and returns its result.  Subscription notifications
+/// received in the meantime are dropped.
+async fn call<S, E>(ws: &mut S, method: &str, params: Value) -> Value
+where
+    S: Sink<awc::ws::Message> + Stream<Item = Result<awc::ws::Frame, E>> + Unpin,
+    <S as Sink<awc::ws::Message>>::Error: Debug,
+    E: Debug,
+{
+    let request = json!({"jsonrpc": "2.0", "id": method, "method": method, "params": params});
+    ws.send(awc::ws::Message::Text(request.to_string().into())).await.unwrap();
+    loop {
+        let message = recv(ws).await;
+        if message["id"] == method {
+            return message["result"].clone();
+        }
+    }
+}
+
+/// Receives the next notification of the subscription.
+async fn next_notification<S, E>(ws: &mut S, subscription: &Value) -> Value
+where
+    S: Stream<Item = Result<awc::ws::Frame, E>> + Unpin,
+    E: Debug,
+{
+    loop {
+        let message = recv(ws).await;
+        if message["method"] == "subscription" && &message["params"]["subscription"] == subscription
+        {
+            return message["params"].clone();
+        }
+    }
+}
+
+#[test]
+fn test_subscribe_new_heads() {
+    init_test_logger();
+
+    run_actix(async {
+        let (_, addr) = test_utils::start_all(test_utils::NodeType::Validator);
+
+        actix::spawn(async move {
+            let (_, mut ws) =
+                awc::Client::new().ws(format!("ws://{}/ws", addr)).connect().await.unwrap();
+            let subscription = call(&mut ws, "subscribe_new_heads", json!([])).await;
+            assert!(subscription.is_u64(), "{}", subscription);
+
+            let first = next_notification(&mut ws, &subscription).await;
+            let second = next_notification(&mut ws, &subscription).await;
+            let height = |params: &Value| params["result"]["header"]["height"].as_u64().unwrap();
+            assert!(height(&second) > height(&first));
+
+            assert_eq!(call(&mut ws, "unsubscribe", json!([subscription])).await, json!(true));
+            assert_eq!(call(&mut ws, "unsubscribe", json!([subscription])).await, json!(false));
+            System::current().stop();
+        });
+    });
+}
+
+#[test]
+fn test_subscribe_tx_status_expires() {
+    init_test_logger();
+
+    run_actix(async {
+        let (_, addr) =
+            test_utils::start_all_with_rpc_config(test_utils::NodeType::Validator, |config| {
+                config.websocket_config.tx_status_timeout = Duration::from_secs(1)
+            });
+
+        actix::spawn(async move {
+            let (_, mut ws) =
+                awc::Client::new().ws(format!("ws://{}/ws", addr)).connect().await.unwrap();
+            let subscription = call(
+                &mut ws,
+                "subscribe_tx_status",
+                json!([CryptoHash::default().to_string(), "test1"]),
+            )
+            .await;
+            assert!(subscription.is_u64(), "{}", subscription);
+
+            let notification = next_notification(&mut ws, &subscription).await;
+            assert!(notification["error"].is_object(), "{}", notification);
+            assert_eq!(notification["result"], Value::Null);
+            // The subscription is already closed.
+            assert_eq!(call(&mut ws, "unsubscribe", json!([subscription])).await, json!(false));
+            System::current().stop();
+        });
+    });
+}
+
+#[test]
+fn test_too_many_subscriptions() {
+    init_test_logger();
+
+    run_actix(async {
+        let (_, addr) =
+            test_utils::start_all_with_rpc_config(test_utils::NodeType::Validator, |config| {
+                config.websocket_config.max_subscriptions_per_connection = 1
+            });
+
+        actix::spawn(async move {
+            let (_, mut ws) =
+                awc::Client::new().ws(format!("ws://{}/ws", addr)).connect().await.unwrap();
+            let subscription = call(&mut ws, "subscribe_final_blocks", json!([])).await;
+            assert!(subscription.is_u64(), "{}", subscription);
+            assert_eq!(call(&mut ws, "subscribe_new_heads", json!([])).await, Value::Null);
+
+            assert_eq!(call(&mut ws, "unsubscribe", json!([subscription])).await, json!(true));
+            assert!(call(&mut ws, "subscribe_new_heads", json!([])).await.is_u64());
+            System::current().stop();
+        });
+    });
+}
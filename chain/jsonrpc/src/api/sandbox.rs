@@ -2,8 +2,10 @@ use serde_json::Value;
 
 use near_jsonrpc_primitives::errors::RpcParseError;
 use near_jsonrpc_primitives::types::sandbox::{
-    RpcSandboxFastForwardError, RpcSandboxFastForwardRequest, RpcSandboxPatchStateError,
-    RpcSandboxPatchStateRequest,
+    RpcSandboxFastForwardError, RpcSandboxFastForwardRequest, RpcSandboxGasTraceError,
+    RpcSandboxGasTraceRequest, RpcSandboxImportAccountsRequest, RpcSandboxPatchStateError,
+    RpcSandboxPatchStateRequest, RpcSandboxRestoreError, RpcSandboxRestoreRequest,
+    RpcSandboxSnapshotError, RpcSandboxSnapshotRequest,
 };
 
 use super::{parse_params, RpcFrom, RpcRequest};
@@ -20,6 +22,30 @@ impl RpcRequest for RpcSandboxFastForwardRequest {
     }
 }
 
+impl RpcRequest for RpcSandboxSnapshotRequest {
+    fn parse(value: Option<Value>) -> Result<Self, RpcParseError> {
+        parse_params::<Self>(value)
+    }
+}
+
+impl RpcRequest for RpcSandboxRestoreRequest {
+    fn parse(value: Option<Value>) -> Result<Self, RpcParseError> {
+        parse_params::<Self>(value)
+    }
+}
+
+impl RpcRequest for RpcSandboxImportAccountsRequest {
+    fn parse(value: Option<Value>) -> Result<Self, RpcParseError> {
+        parse_params::<Self>(value)
+    }
+}
+
+impl RpcRequest for RpcSandboxGasTraceRequest {
+    fn parse(value: Option<Value>) -> Result<Self, RpcParseError> {
+        parse_params::<Self>(value)
+    }
+}
+
 impl RpcFrom<actix::MailboxError> for RpcSandboxPatchStateError {
     fn rpc_from(error: actix::MailboxError) -> Self {
         Self::InternalError { error_message: error.to_string() }
@@ -31,3 +57,21 @@ impl RpcFrom<actix::MailboxError> for RpcSandboxFastForwardError {
         Self::InternalError { error_message: error.to_string() }
     }
 }
+
+impl RpcFrom<actix::MailboxError> for RpcSandboxSnapshotError {
+    fn rpc_from(error: actix::MailboxError) -> Self {
+        Self::InternalError { error_message: error.to_string() }
+    }
+}
+
+impl RpcFrom<actix::MailboxError> for RpcSandboxRestoreError {
+    fn rpc_from(error: actix::MailboxError) -> Self {
+        Self::InternalError { error_message: error.to_string() }
+    }
+}
+
+impl RpcFrom<actix::MailboxError> for RpcSandboxGasTraceError {
+    fn rpc_from(error: actix::MailboxError) -> Self {
+        Self::InternalError { error_message: error.to_string() }
+    }
+}
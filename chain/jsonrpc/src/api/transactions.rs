@@ -1,10 +1,12 @@
 use serde_json::Value;
 
-use near_client_primitives::types::TxStatusError;
+use near_client_primitives::types::{
+    SimulateTransactionError, SimulateTransactionResponse, TxStatusError,
+};
 use near_jsonrpc_primitives::errors::RpcParseError;
 use near_jsonrpc_primitives::types::transactions::{
-    RpcBroadcastTransactionRequest, RpcTransactionError, RpcTransactionResponse,
-    RpcTransactionStatusCommonRequest, TransactionInfo,
+    RpcBroadcastTransactionRequest, RpcSimulateTransactionResponse, RpcTransactionError,
+    RpcTransactionResponse, RpcTransactionStatusCommonRequest, TransactionInfo,
 };
 use near_primitives::hash::CryptoHash;
 use near_primitives::types::AccountId;
@@ -53,6 +55,37 @@ impl RpcFrom<TxStatusError> for RpcTransactionError {
     }
 }
 
+impl RpcFrom<SimulateTransactionError> for RpcTransactionError {
+    fn rpc_from(error: SimulateTransactionError) -> Self {
+        match error {
+            SimulateTransactionError::InvalidTransaction(context) => {
+                Self::InvalidTransaction { context }
+            }
+            SimulateTransactionError::DoesNotTrackShard => Self::DoesNotTrackShard,
+            SimulateTransactionError::IOError(debug_info) => Self::InternalError { debug_info },
+            SimulateTransactionError::Unreachable(ref error_message) => {
+                tracing::warn!(target: "jsonrpc", "Unreachable error occurred: {}", error_message);
+                crate::metrics::RPC_UNREACHABLE_ERROR_COUNT
+                    .with_label_values(&["RpcTransactionError"])
+                    .inc();
+                Self::InternalError { debug_info: error.to_string() }
+            }
+        }
+    }
+}
+
+impl RpcFrom<SimulateTransactionResponse> for RpcSimulateTransactionResponse {
+    fn rpc_from(response: SimulateTransactionResponse) -> Self {
+        Self {
+            block_hash: response.block_hash,
+            transaction_outcome: response.transaction_outcome,
+            receipts_outcome: response.receipts_outcome,
+            state_changes: response.state_changes,
+            unprocessed_receipts: response.unprocessed_receipts,
+        }
+    }
+}
+
 impl RpcFrom<FinalExecutionOutcomeViewEnum> for RpcTransactionResponse {
     fn rpc_from(final_execution_outcome: FinalExecutionOutcomeViewEnum) -> Self {
         Self { final_execution_outcome }
@@ -18,10 +18,11 @@ use tracing::info;
 
 use near_chain_configs::GenesisConfig;
 use near_client::{
-    ClientActor, DebugStatus, GetBlock, GetBlockProof, GetChunk, GetExecutionOutcome, GetGasPrice,
-    GetMaintenanceWindows, GetNetworkInfo, GetNextLightClientBlock, GetProtocolConfig, GetReceipt,
-    GetStateChanges, GetStateChangesInBlock, GetValidatorInfo, GetValidatorOrdered,
-    ProcessTxRequest, ProcessTxResponse, Query, Status, TxStatus, ViewClientActor,
+    ClientActor, CompactDatabase, CreateCheckpoint, DebugStatus, GetBlock, GetBlockProof, GetChunk,
+    GetExecutionOutcome, GetGasPrice, GetMaintenanceWindows, GetNetworkInfo,
+    GetNextLightClientBlock, GetProtocolConfig, GetReceipt, GetStateChanges,
+    GetStateChangesInBlock, GetValidatorInfo, GetValidatorOrdered, ProcessTxRequest,
+    ProcessTxResponse, Query, SimulateTransaction, Status, TxStatus, ViewClientActor,
 };
 pub use near_jsonrpc_client as client;
 use near_jsonrpc_primitives::errors::RpcError;
@@ -35,6 +36,9 @@ use near_primitives::views::FinalExecutionOutcomeViewEnum;
 
 mod api;
 mod metrics;
+#[cfg(feature = "sandbox")]
+mod sandbox_import;
+mod subscriptions;
 
 use api::RpcRequest;
 pub use api::{RpcFrom, RpcInto};
@@ -59,11 +63,46 @@ impl Default for RpcPollingConfig {
 pub struct RpcLimitsConfig {
     /// Maximum byte size of the json payload.
     pub json_payload_max_size: usize,
+    /// Maximum number of requests in a single batch.
+    #[serde(default = "default_max_batch_size")]
+    pub max_batch_size: usize,
+}
+
+fn default_max_batch_size() -> usize {
+    100
 }
 
 impl Default for RpcLimitsConfig {
     fn default() -> Self {
-        Self { json_payload_max_size: 10 * 1024 * 1024 }
+        Self { json_payload_max_size: 10 * 1024 * 1024, max_batch_size: default_max_batch_size() }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct RpcWebSocketConfig {
+    /// Maximum number of active subscriptions of a single WebSocket connection.
+    pub max_subscriptions_per_connection: usize,
+    /// Number of blocks buffered for a subscriber which doesn't keep up with
+    /// the updates.  Once it falls further behind, its subscriptions are closed.
+    pub max_pending_blocks: usize,
+    /// Maximum number of bytes of notifications and responses waiting to be
+    /// written to the socket of a connection.  Once a connection has more
+    /// pending, it is closed.
+    pub max_pending_bytes: usize,
+    /// How long a `subscribe_tx_status` subscription waits for the final
+    /// outcome of the transaction before it's closed with an error.
+    pub tx_status_timeout: Duration,
+}
+
+impl Default for RpcWebSocketConfig {
+    fn default() -> Self {
+        Self {
+            max_subscriptions_per_connection: 16,
+            max_pending_blocks: 64,
+            max_pending_bytes: 16 * 1024 * 1024,
+            tx_status_timeout: Duration::from_secs(600),
+        }
     }
 }
 
@@ -80,6 +119,8 @@ pub struct RpcConfig {
     pub polling_config: RpcPollingConfig,
     #[serde(default)]
     pub limits_config: RpcLimitsConfig,
+    #[serde(default)]
+    pub websocket_config: RpcWebSocketConfig,
     // If true, enable some debug RPC endpoints (like one to get the latest block).
     // We disable it by default, as some of those endpoints might be quite CPU heavy.
     #[serde(default = "default_enable_debug_rpc")]
@@ -98,6 +139,7 @@ impl Default for RpcConfig {
             cors_allowed_origins: vec!["*".to_owned()],
             polling_config: Default::default(),
             limits_config: Default::default(),
+            websocket_config: Default::default(),
             enable_debug_rpc: false,
             experimental_debug_pages_src_path: None,
         }
@@ -142,6 +184,7 @@ impl near_jsonrpc_primitives::types::transactions::RpcTransactionError {
         match resp {
             ProcessTxResponse::InvalidTx(context) => Self::InvalidTransaction { context },
             ProcessTxResponse::NoResponse => Self::TimeoutError,
+            ProcessTxResponse::TransactionPoolFull => Self::TransactionPoolFull,
             ProcessTxResponse::DoesNotTrackShard | ProcessTxResponse::RequestRouted => {
                 Self::DoesNotTrackShard
             }
@@ -222,24 +265,73 @@ struct JsonRpcHandler {
     view_client_addr: Addr<ViewClientActor>,
     peer_manager_addr: Option<Addr<PeerManagerActor>>,
     polling_config: RpcPollingConfig,
+    limits_config: RpcLimitsConfig,
+    websocket_config: RpcWebSocketConfig,
+    block_watcher: subscriptions::BlockWatcher,
     genesis_config: GenesisConfig,
     enable_debug_rpc: bool,
     debug_pages_src_path: Option<PathBuf>,
 }
 
 impl JsonRpcHandler {
+    /// Returns the limits currently in effect.
+    /// They can be changed at runtime, see `near_dyn_configs::UpdateableConfigs`.
+    fn limits(&self) -> RpcLimitsConfig {
+        match &*near_dyn_configs::UPDATEABLE_CONFIGS.borrow() {
+            Some(near_dyn_configs::UpdateableConfigs { rpc_limits: Some(limits), .. }) => {
+                RpcLimitsConfig {
+                    json_payload_max_size: limits.json_payload_max_size,
+                    max_batch_size: limits.max_batch_size,
+                }
+            }
+            _ => self.limits_config.clone(),
+        }
+    }
+
     pub async fn process(&self, message: Message) -> Result<Message, HttpError> {
         let id = message.id();
         match message {
             Message::Request(request) => {
                 Ok(Message::response(id, self.process_request(request).await))
             }
+            Message::Batch(messages) => Ok(self.process_batch(messages).await),
             _ => Ok(Message::error(RpcError::parse_error(
                 "JSON RPC Request format was expected".to_owned(),
             ))),
         }
     }
 
+    /// Processes requests of a batch concurrently.  Every element of the batch
+    /// gets its own response, in the same order, so one failing request
+    /// doesn't affect the others.
+    async fn process_batch(&self, messages: Vec<Message>) -> Message {
+        if messages.is_empty() {
+            return Message::error(RpcError::invalid_request("Empty batch".to_owned()));
+        }
+        let max_batch_size = self.limits().max_batch_size;
+        if messages.len() > max_batch_size {
+            return Message::error(RpcError::invalid_request(format!(
+                "Batch of {} requests exceeds the limit of {}",
+                messages.len(),
+                max_batch_size
+            )));
+        }
+        metrics::RPC_BATCH_SIZE.observe(messages.len() as f64);
+        let responses = futures::future::join_all(messages.into_iter().map(|message| async move {
+            match message {
+                Message::Request(request) => {
+                    let id = request.id.clone();
+                    Message::response(id, self.process_request(request).await)
+                }
+                _ => Message::error(RpcError::invalid_request(
+                    "JSON RPC Request format was expected".to_owned(),
+                )),
+            }
+        }))
+        .await;
+        Message::Batch(responses)
+    }
+
     // `process_request` increments affected metrics but the request processing is done by
     // `process_request_internal`.
     async fn process_request(&self, request: Request) -> Result<Value, RpcError> {
@@ -343,6 +435,9 @@ impl JsonRpcHandler {
             "EXPERIMENTAL_receipt" => {
                 process_method_call(request, |params| self.receipt(params)).await
             }
+            "EXPERIMENTAL_simulate_tx" => {
+                process_method_call(request, |params| self.simulate_tx(params)).await
+            }
             "EXPERIMENTAL_tx_status" => {
                 process_method_call(request, |params| self.tx_status_common(params, true)).await
             }
@@ -360,6 +455,22 @@ impl JsonRpcHandler {
             "sandbox_fast_forward" => {
                 process_method_call(request, |params| self.sandbox_fast_forward(params)).await
             }
+            #[cfg(feature = "sandbox")]
+            "sandbox_snapshot" => {
+                process_method_call(request, |params| self.sandbox_snapshot(params)).await
+            }
+            #[cfg(feature = "sandbox")]
+            "sandbox_restore" => {
+                process_method_call(request, |params| self.sandbox_restore(params)).await
+            }
+            #[cfg(feature = "sandbox")]
+            "sandbox_import_accounts" => {
+                process_method_call(request, |params| self.sandbox_import_accounts(params)).await
+            }
+            #[cfg(feature = "sandbox")]
+            "sandbox_gas_trace" => {
+                process_method_call(request, |params| self.sandbox_gas_trace(params)).await
+            }
             _ => Err(RpcError::method_not_found(request.method)),
         }
     }
@@ -685,6 +796,21 @@ impl JsonRpcHandler {
         }
     }
 
+    async fn simulate_tx(
+        &self,
+        request_data: near_jsonrpc_primitives::types::transactions::RpcBroadcastTransactionRequest,
+    ) -> Result<
+        near_jsonrpc_primitives::types::transactions::RpcSimulateTransactionResponse,
+        near_jsonrpc_primitives::types::transactions::RpcTransactionError,
+    > {
+        let response = self
+            .view_client_send(SimulateTransaction {
+                signed_transaction: request_data.signed_transaction,
+            })
+            .await?;
+        Ok(response.rpc_into())
+    }
+
     async fn send_tx_commit(
         &self,
         request_data: near_jsonrpc_primitives::types::transactions::RpcBroadcastTransactionRequest,
@@ -833,6 +959,37 @@ impl JsonRpcHandler {
         }
     }
 
+    /// Creates a checkpoint of node’s database in requested subdirectory of
+    /// the configured checkpoints directory.
+    ///
+    /// Returns `None` if debug RPC is disabled.
+    pub async fn debug_create_checkpoint(
+        &self,
+        request: CreateCheckpoint,
+    ) -> Result<
+        Option<near_store::backup::BackupInfo>,
+        near_jsonrpc_primitives::types::status::RpcStatusError,
+    > {
+        if self.enable_debug_rpc {
+            Ok(Some(self.client_send(request).await?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Compacts node’s database.  Returns once compaction has finished.
+    ///
+    /// Returns `None` if debug RPC is disabled.
+    pub async fn debug_compact_database(
+        &self,
+    ) -> Result<Option<()>, near_jsonrpc_primitives::types::status::RpcStatusError> {
+        if self.enable_debug_rpc {
+            Ok(Some(self.client_send(CompactDatabase).await?))
+        } else {
+            Ok(None)
+        }
+    }
+
     pub async fn protocol_config(
         &self,
         request_data: near_jsonrpc_primitives::types::config::RpcProtocolConfigRequest,
@@ -1100,15 +1257,21 @@ impl JsonRpcHandler {
         near_jsonrpc_primitives::types::sandbox::RpcSandboxPatchStateResponse,
         near_jsonrpc_primitives::types::sandbox::RpcSandboxPatchStateError,
     > {
-        self.client_addr
-            .send(
-                near_client_primitives::types::SandboxMessage::SandboxPatchState(
-                    patch_state_request.records,
-                )
-                .with_span_context(),
-            )
-            .await
-            .map_err(RpcFrom::rpc_from)?;
+        self.send_state_patch(near_client_primitives::types::SandboxMessage::SandboxPatchState(
+            patch_state_request.records,
+        ))
+        .await
+    }
+
+    /// Sends the state patch to the client and waits until it is applied.
+    async fn send_state_patch(
+        &self,
+        patch: near_client_primitives::types::SandboxMessage,
+    ) -> Result<
+        near_jsonrpc_primitives::types::sandbox::RpcSandboxPatchStateResponse,
+        near_jsonrpc_primitives::types::sandbox::RpcSandboxPatchStateError,
+    > {
+        self.client_addr.send(patch.with_span_context()).await.map_err(RpcFrom::rpc_from)?;
 
         timeout(self.polling_config.polling_timeout, async {
             loop {
@@ -1190,6 +1353,155 @@ impl JsonRpcHandler {
 
         Ok(near_jsonrpc_primitives::types::sandbox::RpcSandboxFastForwardResponse {})
     }
+
+    async fn sandbox_snapshot(
+        &self,
+        snapshot_request: near_jsonrpc_primitives::types::sandbox::RpcSandboxSnapshotRequest,
+    ) -> Result<
+        near_jsonrpc_primitives::types::sandbox::RpcSandboxSnapshotResponse,
+        near_jsonrpc_primitives::types::sandbox::RpcSandboxSnapshotError,
+    > {
+        use near_client_primitives::types::SandboxResponse;
+
+        let response = self
+            .client_addr
+            .send(
+                near_client_primitives::types::SandboxMessage::SandboxSnapshot(
+                    snapshot_request.name,
+                )
+                .with_span_context(),
+            )
+            .await
+            .map_err(RpcFrom::rpc_from)?;
+
+        if let SandboxResponse::SandboxSnapshotFailed(err) = response {
+            return Err(
+                near_jsonrpc_primitives::types::sandbox::RpcSandboxSnapshotError::InternalError {
+                    error_message: format!("sandbox failed to take a snapshot due to: {}", err),
+                },
+            );
+        }
+
+        Ok(near_jsonrpc_primitives::types::sandbox::RpcSandboxSnapshotResponse {})
+    }
+
+    async fn sandbox_restore(
+        &self,
+        restore_request: near_jsonrpc_primitives::types::sandbox::RpcSandboxRestoreRequest,
+    ) -> Result<
+        near_jsonrpc_primitives::types::sandbox::RpcSandboxRestoreResponse,
+        near_jsonrpc_primitives::types::sandbox::RpcSandboxRestoreError,
+    > {
+        use near_client_primitives::types::SandboxResponse;
+
+        let response = self
+            .client_addr
+            .send(
+                near_client_primitives::types::SandboxMessage::SandboxRestore(restore_request.name)
+                    .with_span_context(),
+            )
+            .await
+            .map_err(RpcFrom::rpc_from)?;
+
+        if let SandboxResponse::SandboxRestoreFailed(err) = response {
+            return Err(
+                near_jsonrpc_primitives::types::sandbox::RpcSandboxRestoreError::InternalError {
+                    error_message: format!("sandbox failed to restore a snapshot due to: {}", err),
+                },
+            );
+        }
+
+        Ok(near_jsonrpc_primitives::types::sandbox::RpcSandboxRestoreResponse {})
+    }
+
+    async fn sandbox_gas_trace(
+        &self,
+        trace_request: near_jsonrpc_primitives::types::sandbox::RpcSandboxGasTraceRequest,
+    ) -> Result<
+        near_jsonrpc_primitives::types::sandbox::RpcSandboxGasTraceResponse,
+        near_jsonrpc_primitives::types::sandbox::RpcSandboxGasTraceError,
+    > {
+        use near_client_primitives::types::SandboxResponse;
+        use near_jsonrpc_primitives::types::sandbox::RpcSandboxGasTraceError;
+
+        let response = self
+            .client_addr
+            .send(
+                near_client_primitives::types::SandboxMessage::SandboxGasTrace(
+                    trace_request.receipt_id,
+                )
+                .with_span_context(),
+            )
+            .await
+            .map_err(RpcFrom::rpc_from)?;
+
+        match response {
+            SandboxResponse::SandboxGasTrace(Some(traces)) => {
+                Ok(near_jsonrpc_primitives::types::sandbox::RpcSandboxGasTraceResponse {
+                    trace: near_primitives::gas_trace::export(&traces, trace_request.format),
+                })
+            }
+            SandboxResponse::SandboxGasTrace(None) => {
+                Err(RpcSandboxGasTraceError::UnknownReceipt {
+                    receipt_id: trace_request.receipt_id,
+                })
+            }
+            response => Err(RpcSandboxGasTraceError::InternalError {
+                error_message: format!("unexpected response {:?}", response),
+            }),
+        }
+    }
+
+    async fn sandbox_import_accounts(
+        &self,
+        import_request: near_jsonrpc_primitives::types::sandbox::RpcSandboxImportAccountsRequest,
+    ) -> Result<
+        near_jsonrpc_primitives::types::sandbox::RpcSandboxImportAccountsResponse,
+        near_jsonrpc_primitives::types::sandbox::RpcSandboxImportAccountsError,
+    > {
+        use near_jsonrpc_primitives::types::sandbox::{
+            RpcSandboxImportAccountsError, RpcSandboxImportSource, RpcSandboxPatchStateError,
+        };
+
+        let account_ids = import_request.account_ids;
+        let block_reference = import_request.block_reference;
+        let mut records = match import_request.source {
+            RpcSandboxImportSource::RpcUrl(url) => {
+                sandbox_import::fetch_from_rpc(&url, &account_ids, block_reference).await?
+            }
+            RpcSandboxImportSource::DataDir(data_dir) => {
+                let account_ids = account_ids.clone();
+                tokio::task::spawn_blocking(move || {
+                    sandbox_import::read_from_data_dir(&data_dir, &account_ids, block_reference)
+                })
+                .await
+                .map_err(|err| {
+                    RpcSandboxImportAccountsError::InternalError { error_message: err.to_string() }
+                })??
+            }
+        };
+        if let Some(public_key) = &import_request.access_key {
+            sandbox_import::replace_access_keys(&mut records, &account_ids, public_key);
+        }
+
+        let num_records = records.len();
+        self.send_state_patch(
+            near_client_primitives::types::SandboxMessage::SandboxReplaceAccounts(
+                account_ids,
+                records,
+            ),
+        )
+        .await
+        .map_err(|err| match err {
+            RpcSandboxPatchStateError::InternalError { error_message } => {
+                RpcSandboxImportAccountsError::InternalError { error_message }
+            }
+        })?;
+
+        Ok(near_jsonrpc_primitives::types::sandbox::RpcSandboxImportAccountsResponse {
+            records: num_records,
+        })
+    }
 }
 
 #[cfg(feature = "test_features")]
@@ -1306,14 +1618,21 @@ impl JsonRpcHandler {
 }
 
 fn rpc_handler(
-    message: web::Json<Message>,
+    req: HttpRequest,
+    payload: web::Payload,
     handler: web::Data<JsonRpcHandler>,
 ) -> impl Future<Output = Result<HttpResponse, HttpError>> {
     let response = async move {
-        let message = handler.process(message.0).await?;
+        // Equivalent of the web::Json extractor, but json_payload_max_size
+        // can be changed at runtime.
+        let message: Message = web::JsonBody::new(&req, &mut payload.into_inner(), None, true)
+            .limit(handler.limits().json_payload_max_size)
+            .await?;
+        let message = handler.process(message).await?;
         Ok(HttpResponse::Ok().json(&message))
     };
-    response.boxed()
+    // HttpRequest and Payload are not Send.
+    response.boxed_local()
 }
 
 fn status_handler(
@@ -1360,6 +1679,27 @@ async fn debug_block_status_handler(
     }
 }
 
+async fn debug_checkpoint_handler(
+    request: web::Json<CreateCheckpoint>,
+    handler: web::Data<JsonRpcHandler>,
+) -> Result<HttpResponse, HttpError> {
+    match handler.debug_create_checkpoint(request.into_inner()).await {
+        Ok(Some(value)) => Ok(HttpResponse::Ok().json(&value)),
+        Ok(None) => Ok(HttpResponse::MethodNotAllowed().finish()),
+        Err(err) => Ok(HttpResponse::InternalServerError().json(&err)),
+    }
+}
+
+async fn debug_compact_handler(
+    handler: web::Data<JsonRpcHandler>,
+) -> Result<HttpResponse, HttpError> {
+    match handler.debug_compact_database().await {
+        Ok(Some(())) => Ok(HttpResponse::Ok().finish()),
+        Ok(None) => Ok(HttpResponse::MethodNotAllowed().finish()),
+        Err(err) => Ok(HttpResponse::InternalServerError().json(&err)),
+    }
+}
+
 fn health_handler(
     handler: web::Data<JsonRpcHandler>,
 ) -> impl Future<Output = Result<HttpResponse, HttpError>> {
@@ -1490,6 +1830,7 @@ pub fn start_http(
         cors_allowed_origins,
         polling_config,
         limits_config,
+        websocket_config,
         enable_debug_rpc,
         experimental_debug_pages_src_path: debug_pages_src_path,
     } = config;
@@ -1497,6 +1838,11 @@ pub fn start_http(
     let cors_allowed_origins_clone = cors_allowed_origins.clone();
     info!(target:"network", "Starting http server at {}", addr);
     let mut servers = Vec::new();
+    let block_watcher = subscriptions::BlockWatcher::spawn(
+        view_client_addr.clone(),
+        polling_config.polling_interval,
+        websocket_config.max_pending_blocks,
+    );
     let server = HttpServer::new(move || {
         App::new()
             .wrap(get_cors(&cors_allowed_origins))
@@ -1505,6 +1851,9 @@ pub fn start_http(
                 view_client_addr: view_client_addr.clone(),
                 peer_manager_addr: peer_manager_addr.clone(),
                 polling_config,
+                limits_config: limits_config.clone(),
+                websocket_config: websocket_config.clone(),
+                block_watcher: block_watcher.clone(),
                 genesis_config: genesis_config.clone(),
                 enable_debug_rpc,
                 debug_pages_src_path: debug_pages_src_path.clone().map(Into::into),
@@ -1512,6 +1861,7 @@ pub fn start_http(
             .app_data(web::JsonConfig::default().limit(limits_config.json_payload_max_size))
             .wrap(middleware::Logger::default())
             .service(web::resource("/").route(web::post().to(rpc_handler)))
+            .service(web::resource("/ws").route(web::get().to(subscriptions::ws_handler)))
             .service(
                 web::resource("/status")
                     .route(web::get().to(status_handler))
@@ -1528,6 +1878,13 @@ pub fn start_http(
                     .route(web::get().to(tier1_network_info_handler)),
             )
             .service(web::resource("/metrics").route(web::get().to(prometheus_handler)))
+            .service(
+                web::resource("/debug/api/checkpoint")
+                    .route(web::post().to(debug_checkpoint_handler)),
+            )
+            .service(
+                web::resource("/debug/api/compact").route(web::post().to(debug_compact_handler)),
+            )
             .service(web::resource("/debug/api/{api}").route(web::get().to(debug_handler)))
             .service(
                 web::resource("/debug/api/block_status/{starting_height}")
@@ -1,4 +1,6 @@
-use near_o11y::metrics::{exponential_buckets, HistogramVec, IntCounter, IntCounterVec};
+use near_o11y::metrics::{
+    exponential_buckets, Histogram, HistogramVec, IntCounter, IntCounterVec, IntGauge,
+};
 use once_cell::sync::Lazy;
 
 pub static RPC_PROCESSING_TIME: Lazy<HistogramVec> = Lazy::new(|| {
@@ -55,3 +57,46 @@ pub static RPC_UNREACHABLE_ERROR_COUNT: Lazy<IntCounterVec> = Lazy::new(|| {
     )
     .unwrap()
 });
+pub static RPC_WEBSOCKET_CONNECTIONS: Lazy<IntGauge> = Lazy::new(|| {
+    near_o11y::metrics::try_create_int_gauge(
+        "near_rpc_websocket_connections",
+        "Number of open JSON RPC WebSocket connections",
+    )
+    .unwrap()
+});
+pub static RPC_WEBSOCKET_SUBSCRIPTIONS: Lazy<IntGauge> = Lazy::new(|| {
+    near_o11y::metrics::try_create_int_gauge(
+        "near_rpc_websocket_subscriptions",
+        "Number of active JSON RPC WebSocket subscriptions",
+    )
+    .unwrap()
+});
+pub static RPC_WEBSOCKET_LAGGED_SUBSCRIPTIONS_TOTAL: Lazy<IntCounter> = Lazy::new(|| {
+    near_o11y::metrics::try_create_int_counter(
+        "near_rpc_websocket_lagged_subscriptions_total",
+        "Total count of JSON RPC WebSocket subscriptions closed because the subscriber fell behind",
+    )
+    .unwrap()
+});
+pub static RPC_WEBSOCKET_EXPIRED_SUBSCRIPTIONS_TOTAL: Lazy<IntCounter> = Lazy::new(|| {
+    near_o11y::metrics::try_create_int_counter(
+        "near_rpc_websocket_expired_subscriptions_total",
+        "Total count of JSON RPC WebSocket transaction status subscriptions closed because the outcome didn't appear in time",
+    )
+    .unwrap()
+});
+pub static RPC_WEBSOCKET_SLOW_CONNECTIONS_TOTAL: Lazy<IntCounter> = Lazy::new(|| {
+    near_o11y::metrics::try_create_int_counter(
+        "near_rpc_websocket_slow_connections_total",
+        "Total count of JSON RPC WebSocket connections closed because the client didn't keep up with the messages",
+    )
+    .unwrap()
+});
+pub static RPC_BATCH_SIZE: Lazy<Histogram> = Lazy::new(|| {
+    near_o11y::metrics::try_create_histogram_with_buckets(
+        "near_rpc_batch_size",
+        "Number of requests in JSON RPC batches",
+        exponential_buckets(1.0, 2.0, 10).unwrap(),
+    )
+    .unwrap()
+});
@@ -0,0 +1,389 @@
+//! Reading the state of accounts of another chain for `sandbox_import_accounts`.
+use std::path::Path;
+
+use near_crypto::PublicKey;
+use near_jsonrpc_primitives::errors::{RpcError, RpcErrorKind};
+use near_jsonrpc_primitives::types::query::{QueryResponseKind, RpcQueryRequest};
+use near_jsonrpc_primitives::types::sandbox::RpcSandboxImportAccountsError;
+use near_primitives::account::AccessKey;
+use near_primitives::block::Tip;
+use near_primitives::hash::CryptoHash;
+use near_primitives::shard_layout::get_block_shard_uid_rev;
+use near_primitives::state_record::StateRecord;
+use near_primitives::trie_key::{trie_key_parsers, TrieKey};
+use near_primitives::types::chunk_extra::ChunkExtra;
+use near_primitives::types::{AccountId, BlockId, BlockReference, Finality};
+use near_primitives::views::QueryRequest;
+use near_store::{
+    DBCol, Mode, NodeStorage, Store, StoreConfig, Temperature, Trie, TrieDBStorage, FINAL_HEAD_KEY,
+    HEAD_KEY,
+};
+
+fn source_error(error: impl std::fmt::Display) -> RpcSandboxImportAccountsError {
+    RpcSandboxImportAccountsError::SourceError { error_message: error.to_string() }
+}
+
+/// Fetches the state of the accounts from the JSON RPC of another node.
+///
+/// All queries are made against the same block.  Contracts whose state is
+/// larger than the `trie_viewer_state_size_limit` of the source node cannot be
+/// imported this way.
+pub(crate) async fn fetch_from_rpc(
+    url: &str,
+    account_ids: &[AccountId],
+    block_reference: BlockReference,
+) -> Result<Vec<StateRecord>, RpcSandboxImportAccountsError> {
+    let client = near_jsonrpc_client::new_client(url);
+    let block = client.block(block_reference).await.map_err(source_error)?;
+    let block_reference = BlockReference::BlockId(BlockId::Hash(block.header.hash));
+
+    let mut records = Vec::new();
+    for account_id in account_ids {
+        let query = |request| {
+            client.query(RpcQueryRequest { block_reference: block_reference.clone(), request })
+        };
+        let account =
+            match query(QueryRequest::ViewAccount { account_id: account_id.clone() }).await {
+                Ok(response) => match response.kind {
+                    QueryResponseKind::ViewAccount(account) => account,
+                    kind => return Err(source_error(format!("unexpected response {:?}", kind))),
+                },
+                Err(err) if is_unknown_account(&err) => {
+                    return Err(RpcSandboxImportAccountsError::UnknownAccount {
+                        account_id: account_id.clone(),
+                    })
+                }
+                Err(err) => return Err(source_error(err)),
+            };
+        let has_contract = account.code_hash != CryptoHash::default();
+        records
+            .push(StateRecord::Account { account_id: account_id.clone(), account: account.into() });
+
+        if has_contract {
+            match query(QueryRequest::ViewCode { account_id: account_id.clone() })
+                .await
+                .map_err(source_error)?
+                .kind
+            {
+                QueryResponseKind::ViewCode(code) => records.push(StateRecord::Contract {
+                    account_id: account_id.clone(),
+                    code: code.code,
+                }),
+                kind => return Err(source_error(format!("unexpected response {:?}", kind))),
+            }
+        }
+
+        match query(QueryRequest::ViewAccessKeyList { account_id: account_id.clone() })
+            .await
+            .map_err(source_error)?
+            .kind
+        {
+            QueryResponseKind::AccessKeyList(list) => {
+                records.extend(list.keys.into_iter().map(|key| StateRecord::AccessKey {
+                    account_id: account_id.clone(),
+                    public_key: key.public_key,
+                    access_key: key.access_key.into(),
+                }))
+            }
+            kind => return Err(source_error(format!("unexpected response {:?}", kind))),
+        }
+
+        match query(QueryRequest::ViewState {
+            account_id: account_id.clone(),
+            prefix: vec![].into(),
+            include_proof: false,
+        })
+        .await
+        .map_err(source_error)?
+        .kind
+        {
+            QueryResponseKind::ViewState(state) => {
+                records.extend(state.values.into_iter().map(|item| StateRecord::Data {
+                    account_id: account_id.clone(),
+                    data_key: item.key,
+                    value: item.value,
+                }))
+            }
+            kind => return Err(source_error(format!("unexpected response {:?}", kind))),
+        }
+    }
+    Ok(records)
+}
+
+fn is_unknown_account(error: &RpcError) -> bool {
+    match &error.error_struct {
+        Some(RpcErrorKind::HandlerError(value)) => value["name"] == "UNKNOWN_ACCOUNT",
+        _ => false,
+    }
+}
+
+/// Reads the state of the accounts from the database of a stopped node.
+///
+/// Only shards whose state the node has for the block are searched, so the
+/// node has to track the shards of the accounts.
+pub(crate) fn read_from_data_dir(
+    data_dir: &Path,
+    account_ids: &[AccountId],
+    block_reference: BlockReference,
+) -> Result<Vec<StateRecord>, RpcSandboxImportAccountsError> {
+    let data_dir = std::fs::canonicalize(data_dir).map_err(source_error)?;
+    let store_config = StoreConfig { path: Some(data_dir.clone()), ..StoreConfig::default() };
+    let store = NodeStorage::opener(&data_dir, &store_config, None)
+        .open_in_mode(Mode::ReadOnly)
+        .map_err(source_error)?
+        .get_store(Temperature::Hot);
+
+    let block_hash = get_block_hash(&store, block_reference)?;
+    let mut tries = Vec::new();
+    for item in store.iter_prefix_ser::<ChunkExtra>(DBCol::ChunkExtra, block_hash.as_ref()) {
+        let (key, chunk_extra) = item.map_err(source_error)?;
+        let (_, shard_uid) = get_block_shard_uid_rev(&key).map_err(source_error)?;
+        let storage = TrieDBStorage::new(store.clone(), shard_uid);
+        tries.push(Trie::new(Box::new(storage), *chunk_extra.state_root(), None));
+    }
+    if tries.is_empty() {
+        return Err(source_error(format!("no state is available for block {}", block_hash)));
+    }
+
+    let mut records = Vec::new();
+    for account_id in account_ids {
+        let account_key = TrieKey::Account { account_id: account_id.clone() }.to_vec();
+        let mut found = false;
+        for trie in &tries {
+            if trie.get(&account_key).map_err(source_error)?.is_none() {
+                continue;
+            }
+            read_account_records(trie, account_id, &mut records)?;
+            found = true;
+            break;
+        }
+        if !found {
+            return Err(RpcSandboxImportAccountsError::UnknownAccount {
+                account_id: account_id.clone(),
+            });
+        }
+    }
+    Ok(records)
+}
+
+fn get_block_hash(
+    store: &Store,
+    block_reference: BlockReference,
+) -> Result<CryptoHash, RpcSandboxImportAccountsError> {
+    let head_key: &[u8] = match block_reference {
+        BlockReference::BlockId(BlockId::Hash(hash)) => return Ok(hash),
+        BlockReference::BlockId(BlockId::Height(height)) => {
+            return store
+                .get_ser(DBCol::BlockHeight, &near_primitives::utils::index_to_bytes(height))
+                .map_err(source_error)?
+                .ok_or_else(|| source_error(format!("no block at height {}", height)));
+        }
+        BlockReference::Finality(Finality::None) => HEAD_KEY,
+        // The data directory doesn’t record doomslug final head separately.
+        BlockReference::Finality(Finality::DoomSlug | Finality::Final) => FINAL_HEAD_KEY,
+        BlockReference::SyncCheckpoint(_) => {
+            return Err(source_error("sync checkpoints are not supported for data directories"))
+        }
+    };
+    let tip: Tip = store
+        .get_ser(DBCol::BlockMisc, head_key)
+        .map_err(source_error)?
+        .ok_or_else(|| source_error("the database has no chain head"))?;
+    Ok(tip.last_block_hash)
+}
+
+fn read_account_records(
+    trie: &Trie,
+    account_id: &AccountId,
+    records: &mut Vec<StateRecord>,
+) -> Result<(), RpcSandboxImportAccountsError> {
+    for key in [
+        TrieKey::Account { account_id: account_id.clone() }.to_vec(),
+        TrieKey::ContractCode { account_id: account_id.clone() }.to_vec(),
+    ] {
+        if let Some(value) = trie.get(&key).map_err(source_error)? {
+            records.extend(StateRecord::from_raw_key_value(key, value));
+        }
+    }
+    for prefix in [
+        trie_key_parsers::get_raw_prefix_for_access_keys(account_id),
+        trie_key_parsers::get_raw_prefix_for_contract_data(account_id, &[]),
+    ] {
+        let mut iter = trie.iter().map_err(source_error)?;
+        iter.seek_prefix(&prefix).map_err(source_error)?;
+        for item in iter {
+            let (key, value) = item.map_err(source_error)?;
+            records.extend(StateRecord::from_raw_key_value(key, value));
+        }
+    }
+    Ok(())
+}
+
+/// Replaces the access keys of the accounts with a single full access key.
+pub(crate) fn replace_access_keys(
+    records: &mut Vec<StateRecord>,
+    account_ids: &[AccountId],
+    public_key: &PublicKey,
+) {
+    records.retain(|record| !matches!(record, StateRecord::AccessKey { .. }));
+    records.extend(account_ids.iter().map(|account_id| StateRecord::AccessKey {
+        account_id: account_id.clone(),
+        public_key: public_key.clone(),
+        access_key: AccessKey::full_access(),
+    }));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use near_primitives::account::Account;
+    use near_primitives::borsh::BorshSerialize;
+    use near_primitives::hash::hash;
+    use near_primitives::shard_layout::{get_block_shard_uid, ShardUId};
+    use near_primitives::types::EpochId;
+    use near_store::test_utils::test_populate_trie;
+    use near_store::ShardTries;
+
+    fn account_id(id: &str) -> AccountId {
+        id.parse().unwrap()
+    }
+
+    #[test]
+    fn test_read_from_data_dir() {
+        let (dir, opener) = NodeStorage::test_opener();
+        let alice = account_id("alice.near");
+        let bob = account_id("bob.near");
+        let code = b"code".to_vec();
+        let public_key = PublicKey::empty(near_crypto::KeyType::ED25519);
+        let records = vec![
+            StateRecord::Account {
+                account_id: alice.clone(),
+                account: Account::new(10, 0, hash(&code), 100),
+            },
+            StateRecord::Contract { account_id: alice.clone(), code: code.clone() },
+            StateRecord::AccessKey {
+                account_id: alice.clone(),
+                public_key: public_key.clone(),
+                access_key: AccessKey::full_access(),
+            },
+            StateRecord::Data {
+                account_id: alice.clone(),
+                data_key: b"k1".to_vec(),
+                value: b"v1".to_vec(),
+            },
+            StateRecord::Data {
+                account_id: alice.clone(),
+                data_key: b"k2".to_vec(),
+                value: b"v2".to_vec(),
+            },
+        ];
+        let bob_records = vec![
+            StateRecord::Account {
+                account_id: bob.clone(),
+                account: Account::new(20, 0, CryptoHash::default(), 100),
+            },
+            StateRecord::Data {
+                account_id: bob.clone(),
+                data_key: b"k1".to_vec(),
+                value: b"bob".to_vec(),
+            },
+        ];
+        let changes = records
+            .iter()
+            .chain(&bob_records)
+            .map(|record| {
+                let (key, value) = match record {
+                    StateRecord::Account { account_id, account } => (
+                        TrieKey::Account { account_id: account_id.clone() },
+                        account.try_to_vec().unwrap(),
+                    ),
+                    StateRecord::Contract { account_id, code } => {
+                        (TrieKey::ContractCode { account_id: account_id.clone() }, code.clone())
+                    }
+                    StateRecord::AccessKey { account_id, public_key, access_key } => (
+                        TrieKey::AccessKey {
+                            account_id: account_id.clone(),
+                            public_key: public_key.clone(),
+                        },
+                        access_key.try_to_vec().unwrap(),
+                    ),
+                    StateRecord::Data { account_id, data_key, value } => (
+                        TrieKey::ContractData {
+                            account_id: account_id.clone(),
+                            key: data_key.clone(),
+                        },
+                        value.clone(),
+                    ),
+                    _ => unreachable!(),
+                };
+                (key.to_vec(), Some(value))
+            })
+            .collect();
+
+        let block_hash = hash(b"block");
+        {
+            let store = opener.open().unwrap().get_store(Temperature::Hot);
+            let tries = ShardTries::test(store.clone(), 1);
+            let shard_uid = ShardUId::single_shard();
+            let state_root = test_populate_trie(&tries, &Trie::EMPTY_ROOT, shard_uid, changes);
+            let chunk_extra = ChunkExtra::new(&state_root, CryptoHash::default(), vec![], 0, 0, 0);
+            let tip = Tip {
+                height: 5,
+                last_block_hash: block_hash,
+                prev_block_hash: CryptoHash::default(),
+                epoch_id: EpochId::default(),
+                next_epoch_id: EpochId::default(),
+            };
+            let mut update = store.store_update();
+            update
+                .set_ser(
+                    DBCol::ChunkExtra,
+                    &get_block_shard_uid(&block_hash, &shard_uid),
+                    &chunk_extra,
+                )
+                .unwrap();
+            update.set_ser(DBCol::BlockMisc, HEAD_KEY, &tip).unwrap();
+            update
+                .set_ser(
+                    DBCol::BlockHeight,
+                    &near_primitives::utils::index_to_bytes(5),
+                    &block_hash,
+                )
+                .unwrap();
+            update.commit().unwrap();
+        }
+
+        // Only the state of the requested account is read.
+        let data_dir = dir.path().join("data");
+        let got = read_from_data_dir(
+            &data_dir,
+            &[alice.clone()],
+            BlockReference::Finality(Finality::None),
+        )
+        .unwrap();
+        let to_json = |records: &[StateRecord]| serde_json::to_value(records).unwrap();
+        assert_eq!(to_json(&records), to_json(&got));
+
+        let got = read_from_data_dir(
+            &data_dir,
+            &[bob.clone(), alice.clone()],
+            BlockReference::BlockId(BlockId::Height(5)),
+        )
+        .unwrap();
+        let expected: Vec<_> = bob_records.into_iter().chain(records).collect();
+        assert_eq!(to_json(&expected), to_json(&got));
+
+        assert!(matches!(
+            read_from_data_dir(
+                &data_dir,
+                &[account_id("carol.near")],
+                BlockReference::Finality(Finality::None),
+            ),
+            Err(RpcSandboxImportAccountsError::UnknownAccount { .. })
+        ));
+        assert!(matches!(
+            read_from_data_dir(&data_dir, &[alice], BlockReference::BlockId(BlockId::Height(6))),
+            Err(RpcSandboxImportAccountsError::SourceError { .. })
+        ));
+    }
+}
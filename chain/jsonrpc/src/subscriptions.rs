@@ -0,0 +1,462 @@
+//! WebSocket endpoint serving JSON-RPC subscriptions.
+//!
+//! A client subscribes with one of the `subscribe_*` methods and gets back a
+//! subscription id.  Updates are then pushed as `subscription` notifications
+//! with `{"subscription": id, "result": ...}` params until the client calls
+//! `unsubscribe` with that id.  A `subscribe_tx_status` subscription ends by
+//! itself once the final transaction outcome is sent.  If a subscription fails,
+//! the notification carries an `error` instead of a `result` and the
+//! subscription is closed.  Any other method, as well as batches of requests,
+//! is handled as a regular JSON-RPC request.
+//!
+//! New blocks are polled from the view client once for all the connections by
+//! [`BlockWatcher`] and fanned out over bounded broadcast channels.  A
+//! subscription which falls behind the channel is closed.  Besides, every
+//! session counts the bytes it queued which weren't taken by the socket yet and
+//! closes the connection once there are more than
+//! [`crate::RpcWebSocketConfig::max_pending_bytes`] of them, so a slow client
+//! can't make the node buffer updates without a limit.
+
+use std::collections::HashMap;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use actix::{
+    Actor, ActorContext, ActorFutureExt, Addr, AsyncContext, SpawnHandle, StreamHandler, WrapFuture,
+};
+use actix_web::{web, Error as HttpError, HttpRequest, HttpResponse};
+use actix_web_actors::ws;
+use futures::stream::{self, Stream, StreamExt};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tokio::sync::broadcast;
+use tokio::time::sleep;
+
+use near_client::{GetBlock, GetStateChanges, TxStatus, ViewClientActor};
+use near_jsonrpc_primitives::errors::RpcError;
+use near_jsonrpc_primitives::message::{self, Message};
+use near_jsonrpc_primitives::types::changes::{
+    RpcStateChangesError, RpcStateChangesInBlockResponse,
+};
+use near_jsonrpc_primitives::types::transactions::{
+    RpcTransactionError, RpcTransactionResponse, RpcTransactionStatusCommonRequest, TransactionInfo,
+};
+use near_o11y::WithSpanContextExt;
+use near_primitives::types::{AccountId, BlockId, BlockReference, Finality};
+use near_primitives::views::{BlockView, StateChangesRequestView, StateChangesView};
+
+use crate::api::{parse_params, RpcRequest};
+use crate::{metrics, serialize_response, JsonRpcHandler};
+
+/// Polls the view client for new blocks and broadcasts them to all the
+/// subscribed sessions.
+#[derive(Clone)]
+pub(crate) struct BlockWatcher {
+    new_heads: broadcast::Sender<Arc<BlockView>>,
+    final_blocks: broadcast::Sender<Arc<BlockView>>,
+}
+
+impl BlockWatcher {
+    /// Spawns the polling task.  `capacity` is the number of blocks buffered
+    /// for a subscriber before it's considered to be lagging behind.
+    pub(crate) fn spawn(
+        view_client_addr: Addr<ViewClientActor>,
+        polling_interval: Duration,
+        capacity: usize,
+    ) -> Self {
+        let (new_heads, _) = broadcast::channel(capacity);
+        let (final_blocks, _) = broadcast::channel(capacity);
+        let watcher = Self { new_heads, final_blocks };
+        tokio::spawn(watcher.clone().run(view_client_addr, polling_interval));
+        watcher
+    }
+
+    async fn run(self, view_client_addr: Addr<ViewClientActor>, polling_interval: Duration) {
+        let mut last_head = None;
+        let mut last_final_height = None;
+        loop {
+            sleep(polling_interval).await;
+            if self.new_heads.receiver_count() > 0 {
+                let head = fetch_block(&view_client_addr, Finality::None.into()).await;
+                if let Some(head) = head {
+                    if last_head != Some(head.header.hash) {
+                        last_head = Some(head.header.hash);
+                        let _ = self.new_heads.send(Arc::new(head));
+                    }
+                }
+            }
+
+            if self.final_blocks.receiver_count() == 0 {
+                last_final_height = None;
+                continue;
+            }
+            let block = match fetch_block(&view_client_addr, Finality::Final.into()).await {
+                Some(block) => block,
+                None => continue,
+            };
+            let height = block.header.height;
+            if last_final_height.map_or(false, |last_height| last_height >= height) {
+                continue;
+            }
+            // Don't skip blocks which became final between two polls, the
+            // subscriptions to changes rely on seeing every final block.
+            if let Some(last_height) = last_final_height {
+                for height in last_height + 1..height {
+                    let block = fetch_block(&view_client_addr, BlockId::Height(height).into());
+                    if let Some(block) = block.await {
+                        let _ = self.final_blocks.send(Arc::new(block));
+                    }
+                }
+            }
+            last_final_height = Some(height);
+            let _ = self.final_blocks.send(Arc::new(block));
+        }
+    }
+}
+
+fn subscription_error(message: String) -> RpcError {
+    RpcError::new(-32_000, "Server error".to_owned(), Some(Value::String(message)))
+}
+
+async fn fetch_block(
+    view_client_addr: &Addr<ViewClientActor>,
+    block_reference: BlockReference,
+) -> Option<BlockView> {
+    view_client_addr.send(GetBlock(block_reference).with_span_context()).await.ok()?.ok()
+}
+
+/// Yields the blocks sent to the channel.  Fails and ends if the subscriber
+/// lagged behind and some blocks were dropped.
+fn blocks(
+    receiver: broadcast::Receiver<Arc<BlockView>>,
+) -> impl Stream<Item = Result<Arc<BlockView>, RpcError>> {
+    stream::unfold(Some(receiver), |receiver| async move {
+        let mut receiver = receiver?;
+        match receiver.recv().await {
+            Ok(block) => Some((Ok(block), Some(receiver))),
+            Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                metrics::RPC_WEBSOCKET_LAGGED_SUBSCRIPTIONS_TOTAL.inc();
+                let error =
+                    format!("Subscriber is lagging behind, {} blocks were dropped", skipped);
+                Some((Err(subscription_error(error)), None))
+            }
+            Err(broadcast::error::RecvError::Closed) => None,
+        }
+    })
+}
+
+type Updates = Pin<Box<dyn Stream<Item = Result<Value, RpcError>>>>;
+
+fn block_updates(receiver: broadcast::Receiver<Arc<BlockView>>) -> Updates {
+    blocks(receiver).map(|block| serialize_response(&*block?)).boxed_local()
+}
+
+/// Sends the final outcome of the transaction once it's available and ends.
+/// Fails if the outcome isn't available within `timeout`.
+fn tx_status_updates(
+    handler: web::Data<JsonRpcHandler>,
+    receiver: broadcast::Receiver<Arc<BlockView>>,
+    tx_hash: near_primitives::hash::CryptoHash,
+    signer_account_id: AccountId,
+    timeout: Duration,
+) -> Updates {
+    let expired = stream::once(async move {
+        sleep(timeout).await;
+        metrics::RPC_WEBSOCKET_EXPIRED_SUBSCRIPTIONS_TOTAL.inc();
+        Err(subscription_error(format!(
+            "Final outcome of transaction {} is not available after {:?}",
+            tx_hash, timeout
+        )))
+    });
+    let outcome = blocks(receiver)
+        .then(move |block| {
+            let handler = handler.clone();
+            let signer_account_id = signer_account_id.clone();
+            async move {
+                block?;
+                let status: Result<_, RpcTransactionError> = handler
+                    .view_client_send(TxStatus { tx_hash, signer_account_id, fetch_receipt: false })
+                    .await;
+                match status {
+                    Ok(Some(final_execution_outcome)) => {
+                        serialize_response(RpcTransactionResponse { final_execution_outcome })
+                            .map(Some)
+                    }
+                    Ok(None) | Err(RpcTransactionError::UnknownTransaction { .. }) => Ok(None),
+                    Err(err) => Err(err.into()),
+                }
+            }
+        })
+        .filter_map(|update| futures::future::ready(update.transpose()));
+    stream::select(outcome, expired).take(1).boxed_local()
+}
+
+/// Sends the changes of the accounts in every final block which changed them.
+fn account_changes_updates(
+    handler: web::Data<JsonRpcHandler>,
+    receiver: broadcast::Receiver<Arc<BlockView>>,
+    account_ids: Vec<AccountId>,
+) -> Updates {
+    blocks(receiver)
+        .then(move |block| {
+            let handler = handler.clone();
+            let account_ids = account_ids.clone();
+            async move {
+                let block_hash = block?.header.hash;
+                let changes: Result<StateChangesView, RpcStateChangesError> = handler
+                    .view_client_send(GetStateChanges {
+                        block_hash,
+                        state_changes_request: StateChangesRequestView::AccountChanges {
+                            account_ids,
+                        },
+                    })
+                    .await;
+                match changes {
+                    Ok(changes) if changes.is_empty() => Ok(None),
+                    Ok(changes) => {
+                        serialize_response(RpcStateChangesInBlockResponse { block_hash, changes })
+                            .map(Some)
+                    }
+                    Err(err) => Err(err.into()),
+                }
+            }
+        })
+        .filter_map(|update| futures::future::ready(update.transpose()))
+        .boxed_local()
+}
+
+#[derive(Deserialize)]
+struct RpcSubscribeAccountChangesRequest {
+    account_ids: Vec<AccountId>,
+}
+
+enum SubscriptionEvent {
+    Update(u64, Result<Value, RpcError>),
+    Finished(u64),
+}
+
+/// A single WebSocket connection.
+pub(crate) struct WsSession {
+    handler: web::Data<JsonRpcHandler>,
+    subscriptions: HashMap<u64, SpawnHandle>,
+    next_subscription_id: u64,
+    /// Bytes queued with `ctx.text` which weren't taken by the socket yet.
+    pending_bytes: Arc<AtomicUsize>,
+}
+
+impl WsSession {
+    fn send(&mut self, ctx: &mut ws::WebsocketContext<Self>, message: Message) {
+        if ctx.state().stopping() {
+            return;
+        }
+        let text: String = message.into();
+        let pending_bytes = self.pending_bytes.fetch_add(text.len(), Ordering::Relaxed);
+        if pending_bytes + text.len() > self.handler.websocket_config.max_pending_bytes {
+            metrics::RPC_WEBSOCKET_SLOW_CONNECTIONS_TOTAL.inc();
+            for (_, handle) in self.subscriptions.drain() {
+                ctx.cancel_future(handle);
+                metrics::RPC_WEBSOCKET_SUBSCRIPTIONS.dec();
+            }
+            ctx.close(Some(ws::CloseReason {
+                code: ws::CloseCode::Policy,
+                description: Some("Client doesn't keep up with the messages".to_owned()),
+            }));
+            ctx.stop();
+            return;
+        }
+        ctx.text(text);
+    }
+
+    fn handle_text(&mut self, text: &str, ctx: &mut ws::WebsocketContext<Self>) {
+        let request = match message::from_str(text) {
+            Ok(Message::Request(request)) => request,
+            Ok(Message::Batch(messages)) => {
+                let handler = self.handler.clone();
+                ctx.spawn(
+                    async move { handler.process_batch(messages).await }
+                        .into_actor(self)
+                        .map(|response, act, ctx| act.send(ctx, response)),
+                );
+                return;
+            }
+            Ok(_) => {
+                let error =
+                    RpcError::parse_error("JSON RPC Request format was expected".to_owned());
+                return self.send(ctx, Message::error(error));
+            }
+            Err(broken) => return self.send(ctx, broken.reply()),
+        };
+        let id = request.id.clone();
+        let result = match request.method.as_ref() {
+            "subscribe_new_heads"
+            | "subscribe_final_blocks"
+            | "subscribe_tx_status"
+            | "subscribe_account_changes" => {
+                self.subscribe(&request.method, request.params, ctx).map(|id| json!(id))
+            }
+            "unsubscribe" => parse_params::<(u64,)>(request.params)
+                .map(|(id,)| json!(self.unsubscribe(id, ctx)))
+                .map_err(RpcError::from),
+            _ => {
+                let handler = self.handler.clone();
+                ctx.spawn(
+                    async move { handler.process_request(request).await }
+                        .into_actor(self)
+                        .map(move |result, act, ctx| act.send(ctx, Message::response(id, result))),
+                );
+                return;
+            }
+        };
+        self.send(ctx, Message::response(id, result));
+    }
+
+    fn subscribe(
+        &mut self,
+        method: &str,
+        params: Option<Value>,
+        ctx: &mut ws::WebsocketContext<Self>,
+    ) -> Result<u64, RpcError> {
+        let max_subscriptions = self.handler.websocket_config.max_subscriptions_per_connection;
+        if self.subscriptions.len() >= max_subscriptions {
+            return Err(subscription_error(format!(
+                "Too many subscriptions, at most {} are allowed per connection",
+                max_subscriptions
+            )));
+        }
+        let block_watcher = &self.handler.block_watcher;
+        let updates = match method {
+            "subscribe_new_heads" => {
+                <()>::parse(params)?;
+                block_updates(block_watcher.new_heads.subscribe())
+            }
+            "subscribe_final_blocks" => {
+                <()>::parse(params)?;
+                block_updates(block_watcher.final_blocks.subscribe())
+            }
+            "subscribe_tx_status" => {
+                let (tx_hash, signer_account_id) =
+                    match RpcTransactionStatusCommonRequest::parse(params)?.transaction_info {
+                        TransactionInfo::Transaction(tx) => {
+                            (tx.get_hash(), tx.transaction.signer_id)
+                        }
+                        TransactionInfo::TransactionId { hash, account_id } => (hash, account_id),
+                    };
+                tx_status_updates(
+                    self.handler.clone(),
+                    block_watcher.final_blocks.subscribe(),
+                    tx_hash,
+                    signer_account_id,
+                    self.handler.websocket_config.tx_status_timeout,
+                )
+            }
+            "subscribe_account_changes" => {
+                let RpcSubscribeAccountChangesRequest { account_ids } = parse_params(params)?;
+                account_changes_updates(
+                    self.handler.clone(),
+                    block_watcher.final_blocks.subscribe(),
+                    account_ids,
+                )
+            }
+            _ => unreachable!(),
+        };
+
+        let id = self.next_subscription_id;
+        self.next_subscription_id += 1;
+        let events = updates
+            .map(move |update| SubscriptionEvent::Update(id, update))
+            .chain(stream::once(async move { SubscriptionEvent::Finished(id) }));
+        self.subscriptions.insert(id, ctx.add_stream(events));
+        metrics::RPC_WEBSOCKET_SUBSCRIPTIONS.inc();
+        Ok(id)
+    }
+
+    fn unsubscribe(&mut self, id: u64, ctx: &mut ws::WebsocketContext<Self>) -> bool {
+        match self.subscriptions.remove(&id) {
+            Some(handle) => {
+                ctx.cancel_future(handle);
+                metrics::RPC_WEBSOCKET_SUBSCRIPTIONS.dec();
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+impl Actor for WsSession {
+    type Context = ws::WebsocketContext<Self>;
+
+    fn started(&mut self, _ctx: &mut Self::Context) {
+        metrics::RPC_WEBSOCKET_CONNECTIONS.inc();
+    }
+
+    fn stopped(&mut self, _ctx: &mut Self::Context) {
+        metrics::RPC_WEBSOCKET_CONNECTIONS.dec();
+        metrics::RPC_WEBSOCKET_SUBSCRIPTIONS.sub(self.subscriptions.len() as i64);
+    }
+}
+
+impl StreamHandler<Result<ws::Message, ws::ProtocolError>> for WsSession {
+    fn handle(&mut self, message: Result<ws::Message, ws::ProtocolError>, ctx: &mut Self::Context) {
+        match message {
+            Ok(ws::Message::Text(text)) => self.handle_text(&text, ctx),
+            Ok(ws::Message::Ping(bytes)) => ctx.pong(&bytes),
+            Ok(ws::Message::Close(reason)) => {
+                ctx.close(reason);
+                ctx.stop();
+            }
+            Ok(_) => {}
+            Err(_) => ctx.stop(),
+        }
+    }
+}
+
+impl StreamHandler<SubscriptionEvent> for WsSession {
+    fn handle(&mut self, event: SubscriptionEvent, ctx: &mut Self::Context) {
+        let params = match event {
+            SubscriptionEvent::Update(id, Ok(result)) => {
+                json!({"subscription": id, "result": result})
+            }
+            SubscriptionEvent::Update(id, Err(error)) => {
+                self.unsubscribe(id, ctx);
+                json!({"subscription": id, "error": error})
+            }
+            SubscriptionEvent::Finished(id) => {
+                if self.subscriptions.remove(&id).is_some() {
+                    metrics::RPC_WEBSOCKET_SUBSCRIPTIONS.dec();
+                }
+                return;
+            }
+        };
+        self.send(ctx, Message::notification("subscription".to_owned(), Some(params)));
+    }
+
+    // A finished subscription must not stop the whole session.
+    fn finished(&mut self, _ctx: &mut Self::Context) {}
+}
+
+pub(crate) async fn ws_handler(
+    request: HttpRequest,
+    stream: web::Payload,
+    handler: web::Data<JsonRpcHandler>,
+) -> Result<HttpResponse, HttpError> {
+    let pending_bytes = Arc::new(AtomicUsize::new(0));
+    let session = WsSession {
+        handler,
+        subscriptions: HashMap::new(),
+        next_subscription_id: 0,
+        pending_bytes: pending_bytes.clone(),
+    };
+    let mut response = ws::handshake(&request)?;
+    // Frames are taken from the context only once the socket can accept them,
+    // so whatever was queued but not taken yet is pending.
+    let frames = ws::WebsocketContext::create(session, stream).inspect(move |frame| {
+        if let Ok(frame) = frame {
+            let _ = pending_bytes.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |pending| {
+                Some(pending.saturating_sub(frame.len()))
+            });
+        }
+    });
+    Ok(response.streaming(frames))
+}
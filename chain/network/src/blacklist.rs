@@ -1,4 +1,3 @@
-use std::collections::HashSet;
 use std::net;
 
 /// Only IPv6 addresses are stored.  IPv4 addresses are mapped to IPv6 before being added.
@@ -11,31 +10,74 @@ use std::net;
 pub enum Entry {
     Ip(net::Ipv6Addr),
     IpPort(net::Ipv6Addr, u16),
+    /// CIDR range: network address with the host bits cleared and the prefix length.
+    /// IPv4 ranges are mapped to IPv6, i.e. `a.b.c.d/n` is stored as `::ffff:a.b.c.d/(96+n)`.
+    Subnet(net::Ipv6Addr, u8),
+}
+
+fn to_ipv6(ip: net::IpAddr) -> net::Ipv6Addr {
+    match ip {
+        net::IpAddr::V4(ip) => ip.to_ipv6_mapped(),
+        net::IpAddr::V6(ip) => ip,
+    }
+}
+
+fn prefix_mask(len: u8) -> u128 {
+    u128::MAX.checked_shl(128 - len as u32).unwrap_or(0)
 }
 
 impl Entry {
     pub fn from_ip(ip: net::IpAddr) -> Entry {
-        Entry::Ip(match ip {
-            net::IpAddr::V4(ip) => ip.to_ipv6_mapped(),
-            net::IpAddr::V6(ip) => ip,
-        })
+        Entry::Ip(to_ipv6(ip))
     }
 
     pub fn from_addr(addr: net::SocketAddr) -> Entry {
-        Entry::IpPort(
-            match addr.ip() {
-                net::IpAddr::V4(ip) => ip.to_ipv6_mapped(),
-                net::IpAddr::V6(ip) => ip,
-            },
-            addr.port(),
-        )
+        Entry::IpPort(to_ipv6(addr.ip()), addr.port())
     }
+
+    /// Constructs a CIDR range entry. Host bits of `ip` are ignored.
+    /// Returns None if `len` exceeds the length of the address.
+    pub fn from_subnet(ip: net::IpAddr, len: u8) -> Option<Entry> {
+        let len = match ip {
+            net::IpAddr::V4(_) if len <= 32 => len + 96,
+            net::IpAddr::V6(_) if len <= 128 => len,
+            _ => return None,
+        };
+        let ip = u128::from(to_ipv6(ip)) & prefix_mask(len);
+        Some(Entry::Subnet(ip.into(), len))
+    }
+
+    /// Returns whether `addr` matches the entry.
+    pub fn contains(&self, addr: net::SocketAddr) -> bool {
+        let ip = to_ipv6(addr.ip());
+        match *self {
+            Entry::Ip(want) => ip == want,
+            Entry::IpPort(want, port) => ip == want && addr.port() == port,
+            Entry::Subnet(net, len) => u128::from(ip) & prefix_mask(len) == u128::from(net),
+        }
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum ParseEntryError {
+    #[error("invalid address: {0}")]
+    Addr(#[from] net::AddrParseError),
+    #[error("invalid CIDR prefix length: {0}")]
+    PrefixLen(String),
 }
 
 impl std::str::FromStr for Entry {
-    type Err = std::net::AddrParseError;
+    type Err = ParseEntryError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some((ip, len)) = s.split_once('/') {
+            let ip = ip.parse::<net::IpAddr>()?;
+            return len
+                .parse::<u8>()
+                .ok()
+                .and_then(|len| Entry::from_subnet(ip, len))
+                .ok_or_else(|| ParseEntryError::PrefixLen(len.to_string()));
+        }
         match s.parse::<std::net::IpAddr>() {
             Ok(ip) => Ok(Entry::from_ip(ip)),
             Err(_) => Ok(Entry::from_addr(s.parse::<net::SocketAddr>()?)),
@@ -43,15 +85,41 @@ impl std::str::FromStr for Entry {
     }
 }
 
-/// A blacklist for socket addresses.  Supports adding individual IP:port tuples
-/// to the blacklist or entire IPs.
-#[derive(Debug, Default, Clone)]
-pub struct Blacklist(HashSet<Entry>);
+/// A single rule of the blacklist. In the config, allow rules are prefixed with `!`.
+#[derive(Debug, Hash, PartialEq, Eq, Clone, Copy)]
+pub enum Rule {
+    Deny(Entry),
+    Allow(Entry),
+}
+
+impl std::str::FromStr for Rule {
+    type Err = ParseEntryError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.strip_prefix('!') {
+            Some(s) => Ok(Rule::Allow(s.parse()?)),
+            None => Ok(Rule::Deny(s.parse()?)),
+        }
+    }
+}
+
+/// A blacklist for socket addresses.  Supports blacklisting individual IP:port tuples,
+/// entire IPs and CIDR ranges, as well as exceptions from the blacklisted ranges.
+/// Rules are evaluated in order and the first matching rule decides, so an allow rule
+/// has to precede the deny rule it makes an exception from.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Blacklist(Vec<Rule>);
 
 // TODO(CP-34): merge Blacklist with whitelist functionality and replace them with sth
 // like AuthorizationConfig.
 impl FromIterator<Entry> for Blacklist {
     fn from_iter<I: IntoIterator<Item = Entry>>(i: I) -> Self {
+        Self(i.into_iter().map(Rule::Deny).collect())
+    }
+}
+
+impl FromIterator<Rule> for Blacklist {
+    fn from_iter<I: IntoIterator<Item = Rule>>(i: I) -> Self {
         Self(i.into_iter().collect())
     }
 }
@@ -59,7 +127,14 @@ impl FromIterator<Entry> for Blacklist {
 impl Blacklist {
     /// Returns whether given address is on the blacklist.
     pub fn contains(&self, addr: net::SocketAddr) -> bool {
-        self.0.contains(&Entry::from_ip(addr.ip())) || self.0.contains(&Entry::from_addr(addr))
+        for rule in &self.0 {
+            match rule {
+                Rule::Deny(e) if e.contains(addr) => return true,
+                Rule::Allow(e) if e.contains(addr) => return false,
+                _ => {}
+            }
+        }
+        false
     }
 }
 
@@ -78,7 +153,9 @@ mod test {
 
         assert_eq!(None, parse("foo"));
         assert_eq!(None, parse("192.0.2.*"));
-        assert_eq!(None, parse("192.0.2.0/24"));
+        assert_eq!(None, parse("192.0.2.0/33"));
+        assert_eq!(None, parse("192.0.2.0/"));
+        assert_eq!(None, parse("::/129"));
         assert_eq!(None, parse("192.0.2.4.5"));
         assert_eq!(None, parse("192.0.2.4:424242"));
 
@@ -94,6 +171,16 @@ mod test {
             Entry::from_addr(net::SocketAddr::new(LO4, 42)),
             parse("[::ffff:127.0.0.1]:42").unwrap()
         );
+
+        assert_eq!(parse("::ffff:192.0.2.0/120").unwrap(), parse("192.0.2.0/24").unwrap());
+        // Host bits are ignored.
+        assert_eq!(parse("192.0.2.0/24").unwrap(), parse("192.0.2.77/24").unwrap());
+        assert_eq!(
+            Entry::Subnet("::ffff:192.0.2.0".parse().unwrap(), 120),
+            parse("192.0.2.0/24").unwrap()
+        );
+        assert_eq!(Entry::Subnet(net::Ipv6Addr::UNSPECIFIED, 0), parse("::/0").unwrap());
+        assert_eq!(Entry::Subnet(net::Ipv6Addr::LOCALHOST, 128), parse("::1/128").unwrap());
     }
 
     #[test]
@@ -124,4 +211,40 @@ mod test {
         assert!(blacklist.contains(SocketAddr::new(mapped_ip, 42)));
         assert!(!blacklist.contains(SocketAddr::new(mapped_ip, 8080)));
     }
+
+    #[test]
+    fn test_blacklist_subnets() {
+        use std::net::*;
+
+        fn addr(s: &str) -> SocketAddr {
+            s.parse().unwrap()
+        }
+
+        let blacklist: Blacklist = ["!192.0.2.7", "!192.0.2.8:42", "192.0.2.0/24", "2001:db8::/32"]
+            .iter()
+            .map(|r| r.parse::<Rule>().unwrap())
+            .collect();
+
+        assert!(blacklist.contains(addr("192.0.2.1:42")));
+        assert!(blacklist.contains(addr("192.0.2.255:42")));
+        assert!(blacklist.contains(addr("[::ffff:192.0.2.1]:42")));
+        assert!(!blacklist.contains(addr("192.0.3.1:42")));
+        // Exceptions.
+        assert!(!blacklist.contains(addr("192.0.2.7:42")));
+        assert!(!blacklist.contains(addr("192.0.2.8:42")));
+        assert!(blacklist.contains(addr("192.0.2.8:43")));
+        // IPv6.
+        assert!(blacklist.contains(addr("[2001:db8:1::1]:42")));
+        assert!(!blacklist.contains(addr("[2001:db9::1]:42")));
+
+        // The first matching rule decides.
+        let blacklist: Blacklist =
+            ["192.0.2.0/24", "!192.0.2.7"].iter().map(|r| r.parse::<Rule>().unwrap()).collect();
+        assert!(blacklist.contains(addr("192.0.2.7:42")));
+
+        let everything: Blacklist =
+            [Entry::from_subnet(Ipv6Addr::UNSPECIFIED.into(), 0).unwrap()].into_iter().collect();
+        assert!(everything.contains(addr("192.0.2.7:42")));
+        assert!(everything.contains(addr("[::1]:42")));
+    }
 }
@@ -1,3 +1,5 @@
+use crate::time;
+
 /// Config of a rate limiter algorithm, which behaves like a semaphore
 /// - with maximal capacity `burst`
 /// - with a new ticket added automatically every 1/qps seconds (qps stands for "queries per
@@ -25,3 +27,32 @@ impl Limit {
         Ok(())
     }
 }
+
+/// Non-blocking rate limiter enforcing a `Limit` (token bucket algorithm).
+/// The bucket starts full. Requests which do not fit into the
+/// currently available capacity are rejected rather than delayed.
+pub struct Limiter {
+    limit: Limit,
+    tokens: f64,
+    updated: time::Instant,
+}
+
+impl Limiter {
+    pub fn new(clock: &time::Clock, limit: Limit) -> Self {
+        Self { limit, tokens: limit.burst as f64, updated: clock.now() }
+    }
+
+    /// Acquires `n` tickets, if available. Otherwise returns false and doesn't acquire anything.
+    /// Note that a request for more than `burst` tickets never succeeds.
+    pub fn try_acquire(&mut self, clock: &time::Clock, n: u64) -> bool {
+        let now = clock.now();
+        let elapsed = (now - self.updated).as_seconds_f64();
+        self.tokens = (self.tokens + elapsed * self.limit.qps).min(self.limit.burst as f64);
+        self.updated = now;
+        if self.tokens < n as f64 {
+            return false;
+        }
+        self.tokens -= n as f64;
+        true
+    }
+}
@@ -2,6 +2,7 @@ use crate::concurrency::arc_mutex::ArcMutex;
 use crate::concurrency::demux;
 use crate::concurrency::rate;
 use crate::concurrency::rayon;
+use crate::time;
 
 // drop a trivial future without completion => panic (in debug mode at least).
 #[tokio::test]
@@ -111,3 +112,22 @@ fn arc_mutex_update() {
     );
     assert_eq!(v3, *m.load());
 }
+
+#[test]
+fn test_limiter() {
+    let clock = time::FakeClock::default();
+    let mut l = rate::Limiter::new(&clock.clock(), rate::Limit { qps: 2., burst: 10 });
+    // The bucket starts full.
+    assert!(l.try_acquire(&clock.clock(), 7));
+    assert!(!l.try_acquire(&clock.clock(), 4));
+    assert!(l.try_acquire(&clock.clock(), 3));
+    assert!(!l.try_acquire(&clock.clock(), 1));
+    // Tickets are refilled at qps rate.
+    clock.advance(time::Duration::milliseconds(1500));
+    assert!(l.try_acquire(&clock.clock(), 3));
+    assert!(!l.try_acquire(&clock.clock(), 1));
+    // Up to burst tickets are accumulated.
+    clock.advance(time::Duration::seconds(100));
+    assert!(!l.try_acquire(&clock.clock(), 11));
+    assert!(l.try_acquire(&clock.clock(), 10));
+}
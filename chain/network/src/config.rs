@@ -1,9 +1,12 @@
 use crate::blacklist;
 use crate::concurrency::rate;
+use crate::config_json;
 use crate::network_protocol::PeerAddr;
 use crate::network_protocol::PeerInfo;
+use crate::network_protocol::{PeerMessage, RoutedMessageBody};
 use crate::peer_manager::peer_manager_actor::Event;
 use crate::peer_manager::peer_store;
+use crate::recorder;
 use crate::sink::Sink;
 use crate::time;
 use crate::types::ROUTED_MESSAGE_TTL;
@@ -13,9 +16,10 @@ use near_primitives::network::PeerId;
 use near_primitives::test_utils::create_test_signer;
 use near_primitives::types::AccountId;
 use near_primitives::validator_signer::ValidatorSigner;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4};
 use std::sync::Arc;
+use strum::VariantNames as _;
 
 /// How much height horizon to give to consider peer up to date.
 pub const HIGHEST_PEER_HORIZON: u64 = 5;
@@ -88,6 +92,24 @@ pub struct Tier1 {
     pub enable_outbound: bool,
 }
 
+/// Quotas of the inbound traffic of a single message type.
+#[derive(Clone, Copy)]
+pub struct MessageRateLimits {
+    pub messages: Option<rate::Limit>,
+    pub bytes: Option<rate::Limit>,
+}
+
+/// Per-connection quotas of the inbound traffic.
+/// See `config_json::InboundRateLimits`.
+#[derive(Clone, Default)]
+pub struct InboundRateLimits {
+    /// If true, a peer exceeding a quota gets banned.
+    /// Otherwise the messages exceeding the quota are just dropped.
+    pub ban: bool,
+    /// Quotas keyed by `PeerMessage::msg_variant()`.
+    pub messages: HashMap<&'static str, MessageRateLimits>,
+}
+
 /// Validated configuration for the peer-to-peer manager.
 #[derive(Clone)]
 pub struct NetworkConfig {
@@ -158,12 +180,79 @@ pub struct NetworkConfig {
     //   * ignoring received deleted edges as well
     pub skip_tombstones: Option<time::Duration>,
 
+    /// Whether to negotiate the encrypted transport with peers.
+    /// A connection is encrypted iff both sides have it enabled, which is
+    /// decided by the `noise` field of the Handshakes alone. Otherwise the
+    /// connection falls back to plaintext.
+    pub encrypted_transport: bool,
+    /// Whether to refuse connections with peers which haven't negotiated
+    /// the encrypted transport. Requires `encrypted_transport`.
+    pub require_encryption: bool,
+
+    /// If set, all the messages exchanged with peers are recorded to disk.
+    /// See `recorder` module.
+    pub traffic_recorder: Option<recorder::Config>,
+
+    /// Quotas of the inbound traffic, enforced separately for every connection.
+    pub inbound_rate_limits: InboundRateLimits,
+
     /// TEST-ONLY
     /// TODO(gprusak): make it pub(crate), once all integration tests
     /// are merged into near_network.
     pub event_sink: Sink<Event>,
 }
 
+/// Parses the `blacklist` field of the JSON config.
+pub fn parse_blacklist(blacklist: &[String]) -> anyhow::Result<blacklist::Blacklist> {
+    blacklist
+        .iter()
+        .map(|e| e.parse::<blacklist::Rule>())
+        .collect::<Result<_, _>>()
+        .context("failed to parse blacklist")
+}
+
+/// Parses the `whitelist_nodes` field of the JSON config.
+pub fn parse_whitelist_nodes(whitelist_nodes: &str) -> anyhow::Result<Vec<PeerInfo>> {
+    if whitelist_nodes.is_empty() {
+        return Ok(vec![]);
+    }
+    whitelist_nodes
+        .split(',')
+        .map(|peer| match peer.parse::<PeerInfo>() {
+            Ok(peer) if peer.addr.is_none() => {
+                anyhow::bail!("whitelist_nodes are required to specify both PeerId and IP:port")
+            }
+            Ok(peer) => Ok(peer),
+            Err(err) => Err(err.into()),
+        })
+        .collect::<anyhow::Result<_>>()
+        .context("whitelist_nodes")
+}
+
+/// Parses the `inbound_rate_limits` field of the JSON config.
+/// Message types are matched against the `PeerMessage::msg_variant()` values,
+/// so that typos are detected at startup.
+pub fn parse_inbound_rate_limits(
+    cfg: &config_json::InboundRateLimits,
+) -> anyhow::Result<InboundRateLimits> {
+    let limit =
+        |l: Option<config_json::RateLimit>| l.map(|l| rate::Limit { burst: l.burst, qps: l.qps });
+    let mut messages = HashMap::new();
+    for (name, limits) in &cfg.messages {
+        // Routed messages are identified by the type of their body.
+        let name: &'static str = *PeerMessage::VARIANTS
+            .iter()
+            .chain(RoutedMessageBody::VARIANTS)
+            .find(|v| **v == name.as_str() && **v != "Routed")
+            .with_context(|| format!("inbound_rate_limits: unknown message type {name:?}"))?;
+        messages.insert(
+            name,
+            MessageRateLimits { messages: limit(limits.messages), bytes: limit(limits.bytes) },
+        );
+    }
+    Ok(InboundRateLimits { ban: cfg.action == config_json::RateLimitAction::Ban, messages })
+}
+
 impl NetworkConfig {
     pub fn new(
         cfg: crate::config_json::Config,
@@ -229,31 +318,12 @@ impl NetworkConfig {
                         .collect::<Result<_, _>>()
                         .context("boot_nodes")?
                 },
-                blacklist: cfg
-                    .blacklist
-                    .iter()
-                    .map(|e| e.parse())
-                    .collect::<Result<_, _>>()
-                    .context("failed to parse blacklist")?,
+                blacklist: parse_blacklist(&cfg.blacklist)?,
                 connect_only_to_boot_nodes: cfg.experimental.connect_only_to_boot_nodes,
                 ban_window: cfg.ban_window.try_into()?,
                 peer_expiration_duration: cfg.peer_expiration_duration.try_into()?,
             },
-            whitelist_nodes: if cfg.whitelist_nodes.is_empty() {
-                vec![]
-            } else {
-                cfg.whitelist_nodes
-                    .split(',')
-                    .map(|peer| match peer.parse::<PeerInfo>() {
-                        Ok(peer) if peer.addr.is_none() => anyhow::bail!(
-                            "whitelist_nodes are required to specify both PeerId and IP:port"
-                        ),
-                        Ok(peer) => Ok(peer),
-                        Err(err) => Err(err.into()),
-                    })
-                    .collect::<anyhow::Result<_>>()
-                    .context("whitelist_nodes")?
-            },
+            whitelist_nodes: parse_whitelist_nodes(&cfg.whitelist_nodes)?,
             handshake_timeout: cfg.handshake_timeout.try_into()?,
             monitor_peers_max_period: cfg.monitor_peers_max_period.try_into()?,
             max_num_peers: cfg.max_num_peers,
@@ -287,6 +357,14 @@ impl NetworkConfig {
             } else {
                 None
             },
+            encrypted_transport: cfg.experimental.encrypted_transport,
+            require_encryption: cfg.experimental.require_encryption,
+            traffic_recorder: cfg.experimental.traffic_recorder.map(|cfg| recorder::Config {
+                dir: cfg.dir,
+                max_file_size: cfg.max_file_size_bytes,
+                max_files: cfg.max_files,
+            }),
+            inbound_rate_limits: parse_inbound_rate_limits(&cfg.inbound_rate_limits)?,
             event_sink: Sink::null(),
         };
         Ok(this)
@@ -350,6 +428,10 @@ impl NetworkConfig {
                 enable_outbound: true,
             }),
             skip_tombstones: None,
+            encrypted_transport: false,
+            require_encryption: false,
+            traffic_recorder: None,
+            inbound_rate_limits: InboundRateLimits::default(),
             event_sink: Sink::null(),
         }
     }
@@ -384,12 +466,20 @@ impl NetworkConfig {
                 self.peer_recent_time_window, UPDATE_INTERVAL_LAST_TIME_RECEIVED_MESSAGE
             );
         }
+        if self.require_encryption && !self.encrypted_transport {
+            anyhow::bail!("require_encryption is set, but encrypted_transport is disabled");
+        }
         self.accounts_data_broadcast_rate_limit
             .validate()
             .context("accounts_Data_broadcast_rate_limit")?;
         self.routing_table_update_rate_limit
             .validate()
             .context("routing_table_update_rate_limit")?;
+        for (name, limits) in &self.inbound_rate_limits.messages {
+            for limit in [limits.messages, limits.bytes].iter().flatten() {
+                limit.validate().with_context(|| format!("inbound_rate_limits[{name}]"))?;
+            }
+        }
         Ok(VerifiedConfig { node_id: self.node_id(), inner: self })
     }
 }
@@ -423,7 +513,9 @@ impl std::ops::Deref for VerifiedConfig {
 #[cfg(test)]
 mod test {
     use super::UPDATE_INTERVAL_LAST_TIME_RECEIVED_MESSAGE;
+    use crate::concurrency::rate;
     use crate::config;
+    use crate::config_json;
     use crate::network_protocol;
     use crate::network_protocol::testonly as data;
     use crate::network_protocol::AccountData;
@@ -452,6 +544,49 @@ mod test {
         assert!(nc.verify().is_err());
     }
 
+    #[test]
+    fn test_inbound_rate_limits() {
+        let limit = |burst, qps| Some(config_json::RateLimit { burst, qps });
+        let cfg = config_json::InboundRateLimits {
+            action: config_json::RateLimitAction::Ban,
+            messages: [
+                (
+                    "PeersRequest".to_string(),
+                    config_json::MessageRateLimits { messages: limit(10, 0.1), bytes: None },
+                ),
+                (
+                    "StateRequestPart".to_string(),
+                    config_json::MessageRateLimits { messages: None, bytes: limit(1000, 10.) },
+                ),
+            ]
+            .into(),
+        };
+        let limits = config::parse_inbound_rate_limits(&cfg).unwrap();
+        assert!(limits.ban);
+        assert_eq!(2, limits.messages.len());
+        assert_eq!(10, limits.messages["PeersRequest"].messages.unwrap().burst);
+        assert!(limits.messages["StateRequestPart"].messages.is_none());
+        assert_eq!(1000, limits.messages["StateRequestPart"].bytes.unwrap().burst);
+
+        // Unknown message types are rejected.
+        for name in ["Routed", "NoSuchMessage"] {
+            let mut cfg = cfg.clone();
+            cfg.messages.insert(name.to_string(), Default::default());
+            assert!(config::parse_inbound_rate_limits(&cfg).is_err());
+        }
+
+        // Invalid limits are rejected.
+        let mut nc = config::NetworkConfig::from_seed("123", 213);
+        nc.inbound_rate_limits.messages.insert(
+            "PeersRequest",
+            config::MessageRateLimits {
+                messages: Some(rate::Limit { burst: 10, qps: 0. }),
+                bytes: None,
+            },
+        );
+        assert!(nc.verify().is_err());
+    }
+
     // Check that MAX_PEER_ADDRS limit is consistent with the
     // network_protocol::MAX_ACCOUNT_DATA_SIZE_BYTES limit
     #[test]
@@ -1,5 +1,7 @@
 use crate::network_protocol::PeerAddr;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
 use std::time::Duration;
 
 /// Time to persist Accounts Id in the router without removing them in seconds.
@@ -69,6 +71,7 @@ pub struct Config {
     pub boot_nodes: String,
     /// Comma separated list of whitelisted nodes. Inbound connections from the nodes on
     /// the whitelist are accepted even if the limit of the inbound connection has been reached.
+    /// Can be changed at runtime via dyn_config.json.
     /// For each whitelisted node specifying both PeerId and one of IP:port or Host:port is required:
     /// Examples:
     ///   ed25519:86EtEy7epneKyrcJwSWP7zsisTkfDRH5CFVszt4qiQYw@31.192.22.209:24567
@@ -105,7 +108,12 @@ pub struct Config {
     /// Ban window for peers who misbehave.
     pub ban_window: Duration,
     /// List of addresses that will not be accepted as valid neighbors.
-    /// It can be IP:Port or IP (to blacklist all connections coming from this address).
+    /// It can be IP:Port, IP (to blacklist all connections coming from this address)
+    /// or a CIDR range, like 192.0.2.0/24 or 2001:db8::/32.
+    /// Entries prefixed with `!` are exceptions: they allow the matching addresses.
+    /// Entries are evaluated in order and the first matching one decides,
+    /// e.g. `["!192.0.2.7", "192.0.2.0/24"]` blocks the whole range except for 192.0.2.7.
+    /// Can be changed at runtime via dyn_config.json.
     #[serde(default)]
     pub blacklist: Vec<String>,
     /// Time to persist Accounts Id in the router without removing them in seconds.
@@ -164,12 +172,69 @@ pub struct Config {
     // TODO: unskip, once the functionality is implemented.
     #[serde(skip)] // TODO: add a default list.
     pub trusted_stun_servers: Vec<String>,
+    /// Per-connection quotas of the inbound traffic, by message type.
+    #[serde(default)]
+    pub inbound_rate_limits: InboundRateLimits,
     // Experimental part of the JSON config. Regular users/validators should not have to set any values there.
     // Field names in here can change/disappear at any moment without warning.
     #[serde(default)]
     pub experimental: ExperimentalConfig,
 }
 
+/// Quotas of the inbound traffic, enforced separately for every connection.
+/// Example:
+///   {
+///     "action": "ban",
+///     "messages": {
+///       "PeersRequest": {"messages": {"burst": 10, "qps": 0.1}},
+///       "StateRequestPart": {"messages": {"burst": 30, "qps": 3}, "bytes": {"burst": 1000000, "qps": 10000}}
+///     }
+///   }
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct InboundRateLimits {
+    /// What to do with a message exceeding the quota.
+    #[serde(default)]
+    pub action: RateLimitAction,
+    /// Quotas keyed by the message type, which is the name of a `PeerMessage` variant,
+    /// or, for routed messages, the name of a `RoutedMessageBody` variant.
+    /// Message types without an entry are not limited.
+    #[serde(default)]
+    pub messages: BTreeMap<String, MessageRateLimits>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RateLimitAction {
+    /// Drop the message.
+    Drop,
+    /// Drop the message, disconnect and ban the peer.
+    Ban,
+}
+
+impl Default for RateLimitAction {
+    fn default() -> Self {
+        RateLimitAction::Drop
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+pub struct MessageRateLimits {
+    /// Quota on the number of messages.
+    #[serde(default)]
+    pub messages: Option<RateLimit>,
+    /// Quota on the total size of messages in bytes.
+    /// Note that a message larger than `burst` always exceeds the quota.
+    #[serde(default)]
+    pub bytes: Option<RateLimit>,
+}
+
+/// See `near_network::concurrency::rate::Limit`.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct RateLimit {
+    pub burst: u64,
+    pub qps: f64,
+}
+
 fn default_tier1_enable_inbound() -> bool {
     true
 }
@@ -219,6 +284,39 @@ pub struct ExperimentalConfig {
     /// See `near_network::config::Tier1::new_connections_per_attempt`.
     #[serde(default = "default_tier1_new_connections_per_attempt")]
     pub tier1_new_connections_per_attempt: u64,
+
+    /// See `near_network::config::NetworkConfig::encrypted_transport`.
+    #[serde(default)]
+    pub encrypted_transport: bool,
+
+    /// See `near_network::config::NetworkConfig::require_encryption`.
+    #[serde(default)]
+    pub require_encryption: bool,
+
+    /// If set, all the messages exchanged with peers are recorded to disk,
+    /// so that they can be replayed with the mock node.
+    #[serde(default)]
+    pub traffic_recorder: Option<TrafficRecorderConfig>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct TrafficRecorderConfig {
+    /// Directory to store the recording in, relative to the home dir.
+    pub dir: PathBuf,
+    /// A new file is started once the current one reaches this size.
+    #[serde(default = "default_traffic_recorder_max_file_size_bytes")]
+    pub max_file_size_bytes: u64,
+    /// Number of files to keep. The oldest files are deleted.
+    #[serde(default = "default_traffic_recorder_max_files")]
+    pub max_files: usize,
+}
+
+fn default_traffic_recorder_max_file_size_bytes() -> u64 {
+    100_000_000
+}
+
+fn default_traffic_recorder_max_files() -> usize {
+    10
 }
 
 impl Default for ExperimentalConfig {
@@ -231,6 +329,9 @@ impl Default for ExperimentalConfig {
             tier1_enable_outbound: default_tier1_enable_outbound(),
             tier1_connect_interval: default_tier1_connect_interval(),
             tier1_new_connections_per_attempt: default_tier1_new_connections_per_attempt(),
+            encrypted_transport: false,
+            require_encryption: false,
+            traffic_recorder: None,
         }
     }
 }
@@ -259,6 +360,7 @@ impl Default for Config {
             public_addrs: vec![],
             allow_private_ip_in_public_addrs: false,
             trusted_stun_servers: vec![],
+            inbound_rate_limits: Default::default(),
             experimental: Default::default(),
         }
     }
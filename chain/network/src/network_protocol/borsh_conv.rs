@@ -15,6 +15,7 @@ impl From<&net::Handshake> for mem::Handshake {
             sender_chain_info: x.sender_chain_info.clone(),
             partial_edge_info: x.partial_edge_info.clone(),
             owned_account: None,
+            noise: None,
         }
     }
 }
@@ -186,6 +187,14 @@ impl From<&mem::PeerMessage> for net::PeerMessage {
             mem::PeerMessage::Routed(r) => net::PeerMessage::Routed(Box::new(r.msg.clone())),
             mem::PeerMessage::Disconnect => net::PeerMessage::Disconnect,
             mem::PeerMessage::Challenge(c) => net::PeerMessage::Challenge(c),
+
+            // These messages are not supported, we translate them to an empty RoutingTableUpdate.
+            mem::PeerMessage::EpochSyncRequest(_)
+            | mem::PeerMessage::EpochSyncResponse(_)
+            | mem::PeerMessage::EpochSyncFinalizationRequest(_)
+            | mem::PeerMessage::EpochSyncFinalizationResponse(_) => {
+                net::PeerMessage::SyncRoutingTable(net::RoutingTableUpdate::default())
+            }
         }
     }
 }
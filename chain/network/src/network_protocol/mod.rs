@@ -35,10 +35,13 @@ use near_primitives::network::{AnnounceAccount, PeerId};
 use near_primitives::sharding::{
     ChunkHash, PartialEncodedChunk, PartialEncodedChunkPart, ReceiptProof, ShardChunkHeader,
 };
-use near_primitives::syncing::{ShardStateSyncResponse, ShardStateSyncResponseV1};
+use near_primitives::syncing::{
+    EpochSyncFinalizationResponse, EpochSyncResponse, ShardStateSyncResponse,
+    ShardStateSyncResponseV1,
+};
 use near_primitives::transaction::SignedTransaction;
 use near_primitives::types::AccountId;
-use near_primitives::types::{BlockHeight, ShardId};
+use near_primitives::types::{BlockHeight, EpochId, ShardId};
 use near_primitives::validator_signer::ValidatorSigner;
 use near_primitives::views::FinalExecutionOutcomeView;
 use protobuf::Message as _;
@@ -266,6 +269,16 @@ pub struct Handshake {
     pub(crate) partial_edge_info: PartialEdgeInfo,
     /// Account owned by the sender.
     pub(crate) owned_account: Option<SignedOwnedAccount>,
+    /// Noise handshake message of the sender, offering/accepting the encrypted transport.
+    pub(crate) noise: Option<SignedNoiseMessage>,
+}
+
+/// Noise handshake message, signed by the node key of the sender.
+/// See `crate::peer::noise`.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct SignedNoiseMessage {
+    pub(crate) message: Vec<u8>,
+    pub(crate) signature: Signature,
 }
 
 #[derive(PartialEq, Eq, Clone, Debug, strum::IntoStaticStr)]
@@ -312,6 +325,15 @@ pub enum PeerMessage {
     /// Gracefully disconnect from other peer.
     Disconnect,
     Challenge(Challenge),
+
+    /// Request for the light client block of given epoch.  Sent during epoch
+    /// sync, see `near_client::sync::epoch`.
+    EpochSyncRequest(EpochId),
+    EpochSyncResponse(Box<EpochSyncResponse>),
+    /// Request for data needed to start header sync at given epoch, which is
+    /// the last one reached by epoch sync.
+    EpochSyncFinalizationRequest(EpochId),
+    EpochSyncFinalizationResponse(Box<EpochSyncFinalizationResponse>),
 }
 
 impl fmt::Display for PeerMessage {
@@ -384,7 +406,13 @@ impl PeerMessage {
 
 // TODO(#1313): Use Box
 #[derive(
-    borsh::BorshSerialize, borsh::BorshDeserialize, PartialEq, Eq, Clone, strum::IntoStaticStr,
+    borsh::BorshSerialize,
+    borsh::BorshDeserialize,
+    PartialEq,
+    Eq,
+    Clone,
+    strum::IntoStaticStr,
+    strum::EnumVariantNames,
 )]
 pub enum RoutedMessageBody {
     BlockApproval(Approval),
@@ -9,7 +9,9 @@ use crate::time::error::ComponentRange;
 use borsh::{BorshDeserialize as _, BorshSerialize as _};
 use near_primitives::block::{Block, BlockHeader};
 use near_primitives::challenge::Challenge;
+use near_primitives::syncing::{EpochSyncFinalizationResponse, EpochSyncResponse};
 use near_primitives::transaction::SignedTransaction;
+use near_primitives::types::EpochId;
 use protobuf::MessageField as MF;
 use std::sync::Arc;
 
@@ -149,6 +151,30 @@ impl From<&PeerMessage> for proto::PeerMessage {
                     borsh: r.try_to_vec().unwrap(),
                     ..Default::default()
                 }),
+                PeerMessage::EpochSyncRequest(epoch_id) => {
+                    ProtoMT::EpochSyncRequest(proto::EpochSyncRequest {
+                        epoch_id: MF::some((&epoch_id.0).into()),
+                        ..Default::default()
+                    })
+                }
+                PeerMessage::EpochSyncResponse(r) => {
+                    ProtoMT::EpochSyncResponse(proto::EpochSyncResponse {
+                        borsh: r.try_to_vec().unwrap(),
+                        ..Default::default()
+                    })
+                }
+                PeerMessage::EpochSyncFinalizationRequest(epoch_id) => {
+                    ProtoMT::EpochSyncFinalizationRequest(proto::EpochSyncFinalizationRequest {
+                        epoch_id: MF::some((&epoch_id.0).into()),
+                        ..Default::default()
+                    })
+                }
+                PeerMessage::EpochSyncFinalizationResponse(r) => {
+                    ProtoMT::EpochSyncFinalizationResponse(proto::EpochSyncFinalizationResponse {
+                        borsh: r.try_to_vec().unwrap(),
+                        ..Default::default()
+                    })
+                }
             }),
             ..Default::default()
         }
@@ -158,6 +184,7 @@ impl From<&PeerMessage> for proto::PeerMessage {
 pub type ParseTransactionError = borsh::maybestd::io::Error;
 pub type ParseRoutedError = borsh::maybestd::io::Error;
 pub type ParseChallengeError = borsh::maybestd::io::Error;
+pub type ParseEpochSyncResponseError = borsh::maybestd::io::Error;
 
 #[derive(thiserror::Error, Debug)]
 pub enum ParsePeerMessageError {
@@ -195,6 +222,14 @@ pub enum ParsePeerMessageError {
     RoutedCreatedAtTimestamp(ComponentRange),
     #[error("sync_accounts_data: {0}")]
     SyncAccountsData(ParseVecError<ParseSignedAccountDataError>),
+    #[error("epoch_sync_request: {0}")]
+    EpochSyncRequest(ParseRequiredError<ParseCryptoHashError>),
+    #[error("epoch_sync_response: {0}")]
+    EpochSyncResponse(ParseEpochSyncResponseError),
+    #[error("epoch_sync_finalization_request: {0}")]
+    EpochSyncFinalizationRequest(ParseRequiredError<ParseCryptoHashError>),
+    #[error("epoch_sync_finalization_response: {0}")]
+    EpochSyncFinalizationResponse(ParseEpochSyncResponseError),
 }
 
 impl TryFrom<&proto::PeerMessage> for PeerMessage {
@@ -271,6 +306,25 @@ impl TryFrom<&proto::PeerMessage> for PeerMessage {
             ProtoMT::Challenge(c) => PeerMessage::Challenge(
                 Challenge::try_from_slice(&c.borsh).map_err(Self::Error::Challenge)?,
             ),
+            ProtoMT::EpochSyncRequest(r) => PeerMessage::EpochSyncRequest(EpochId(
+                try_from_required(&r.epoch_id).map_err(Self::Error::EpochSyncRequest)?,
+            )),
+            ProtoMT::EpochSyncResponse(r) => PeerMessage::EpochSyncResponse(Box::new(
+                EpochSyncResponse::try_from_slice(&r.borsh)
+                    .map_err(Self::Error::EpochSyncResponse)?,
+            )),
+            ProtoMT::EpochSyncFinalizationRequest(r) => {
+                PeerMessage::EpochSyncFinalizationRequest(EpochId(
+                    try_from_required(&r.epoch_id)
+                        .map_err(Self::Error::EpochSyncFinalizationRequest)?,
+                ))
+            }
+            ProtoMT::EpochSyncFinalizationResponse(r) => {
+                PeerMessage::EpochSyncFinalizationResponse(Box::new(
+                    EpochSyncFinalizationResponse::try_from_slice(&r.borsh)
+                        .map_err(Self::Error::EpochSyncFinalizationResponse)?,
+                ))
+            }
         })
     }
 }
@@ -1,3 +1,4 @@
+pub(crate) mod noise;
 pub(crate) mod peer_actor;
 mod stream;
 mod tracker;
@@ -0,0 +1,272 @@
+//! Encrypted peer transport, based on the Noise protocol framework
+//! (https://noiseprotocol.org/noise.html), as implemented by the `snow` crate.
+//!
+//! Peers run the Noise_NN_25519_ChaChaPoly_SHA256 handshake. Its two messages are
+//! carried in the `Handshake` messages: the outbound peer (initiator) sends its
+//! ephemeral key, and the inbound peer (responder) replies with its own.
+//! The NN pattern doesn't authenticate the peers by itself, so every Noise message
+//! is signed with the node key of the sender (see `SignedNoiseMessage`), and the
+//! PeerIds of both sides together with the edge nonce are passed to Noise as the
+//! prologue. A man in the middle is therefore unable to substitute the ephemeral keys,
+//! and the session keys are bound to the connection they have been negotiated for.
+//!
+//! Once the handshake is complete, every frame is encrypted with ChaCha20-Poly1305.
+//! Noise messages are limited to 64KiB, so a frame is split into chunks,
+//! which are encrypted with consecutive nonces.
+use crate::network_protocol::SignedNoiseMessage;
+use near_crypto::SecretKey;
+use near_primitives::hash::CryptoHash;
+use near_primitives::network::PeerId;
+use std::sync::Arc;
+
+/// Size of the authentication tag appended to every encrypted chunk.
+const TAG_LEN: usize = 16;
+
+/// Maximal size of a Noise message, including the authentication tag.
+const MAX_CHUNK_LEN: usize = 65535;
+
+/// Maximal size of the plaintext encrypted into a single Noise message.
+const MAX_PLAINTEXT_CHUNK_LEN: usize = MAX_CHUNK_LEN - TAG_LEN;
+
+/// Bit of the frame length prefix marking an encrypted frame.
+/// Frames are way smaller than 2^31 bytes, so it is never set for plaintext frames.
+pub(crate) const ENCRYPTED_FRAME_BIT: u32 = 1 << 31;
+
+const NOISE_PARAMS: &str = "Noise_NN_25519_ChaChaPoly_SHA256";
+
+/// Domain separator of the prologue and the signatures.
+const PROTOCOL_NAME: &str = "near-network/noise/v1";
+
+#[derive(thiserror::Error, Debug)]
+pub(crate) enum Error {
+    #[error("noise: {0}")]
+    Noise(#[source] snow::Error),
+    #[error("invalid signature of the noise message")]
+    InvalidSignature,
+    #[error("peer sent a noise message, but encryption has not been offered")]
+    UnexpectedNoiseMessage,
+    #[error("encryption is required, but peer doesn't support it")]
+    EncryptionRequired,
+    #[error("failed to decrypt frame")]
+    Decrypt,
+}
+
+impl From<snow::Error> for Error {
+    fn from(err: snow::Error) -> Self {
+        Self::Noise(err)
+    }
+}
+
+fn builder(prologue: &[u8]) -> snow::Builder<'_> {
+    snow::Builder::new(NOISE_PARAMS.parse().expect("valid noise params")).prologue(prologue)
+}
+
+/// Binds the session to the connection between `initiator` and `responder`,
+/// established with the given edge nonce.
+fn prologue(initiator: &PeerId, responder: &PeerId, nonce: u64) -> CryptoHash {
+    CryptoHash::hash_borsh((PROTOCOL_NAME, initiator, responder, nonce))
+}
+
+fn signed_hash(prologue: &CryptoHash, sender: &PeerId, message: &[u8]) -> CryptoHash {
+    CryptoHash::hash_borsh((prologue, sender, message))
+}
+
+fn sign(prologue: &CryptoHash, node_key: &SecretKey, message: Vec<u8>) -> SignedNoiseMessage {
+    let sender = PeerId::new(node_key.public_key());
+    let signature = node_key.sign(signed_hash(prologue, &sender, &message).as_ref());
+    SignedNoiseMessage { message, signature }
+}
+
+fn verify(prologue: &CryptoHash, sender: &PeerId, msg: &SignedNoiseMessage) -> Result<(), Error> {
+    let hash = signed_hash(prologue, sender, &msg.message);
+    if !msg.signature.verify(hash.as_ref(), sender.public_key()) {
+        return Err(Error::InvalidSignature);
+    }
+    Ok(())
+}
+
+/// Outbound side of the handshake, waiting for the response of the inbound peer.
+pub(crate) struct Initiator {
+    prologue: CryptoHash,
+    state: snow::HandshakeState,
+}
+
+impl std::fmt::Debug for Initiator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Initiator").field("prologue", &self.prologue).finish()
+    }
+}
+
+impl Initiator {
+    /// Starts the handshake of a connection to `peer_id` with the given edge nonce.
+    /// Returns the message offering encryption, to be sent in our Handshake.
+    pub fn new(
+        node_key: &SecretKey,
+        peer_id: &PeerId,
+        nonce: u64,
+    ) -> Result<(Self, SignedNoiseMessage), Error> {
+        let prologue = prologue(&PeerId::new(node_key.public_key()), peer_id, nonce);
+        let mut state = builder(prologue.as_ref()).build_initiator()?;
+        let mut buf = vec![0; MAX_CHUNK_LEN];
+        let n = state.write_message(&[], &mut buf)?;
+        buf.truncate(n);
+        Ok((Self { prologue, state }, sign(&prologue, node_key, buf)))
+    }
+
+    /// Completes the handshake, given the message from the Handshake of `peer_id`.
+    pub fn finish(
+        mut self,
+        peer_id: &PeerId,
+        msg: &SignedNoiseMessage,
+    ) -> Result<SessionKeys, Error> {
+        verify(&self.prologue, peer_id, msg)?;
+        self.state.read_message(&msg.message, &mut vec![0; MAX_CHUNK_LEN])?;
+        SessionKeys::new(self.state)
+    }
+}
+
+/// Accepts the encryption offered by `peer_id` in its Handshake (`msg`),
+/// established with the given edge nonce.
+/// Returns the response, to be sent in our Handshake, and the session keys.
+pub(crate) fn respond(
+    node_key: &SecretKey,
+    peer_id: &PeerId,
+    nonce: u64,
+    msg: &SignedNoiseMessage,
+) -> Result<(SignedNoiseMessage, SessionKeys), Error> {
+    let prologue = prologue(peer_id, &PeerId::new(node_key.public_key()), nonce);
+    verify(&prologue, peer_id, msg)?;
+    let mut state = builder(prologue.as_ref()).build_responder()?;
+    let mut buf = vec![0; MAX_CHUNK_LEN];
+    state.read_message(&msg.message, &mut buf)?;
+    let n = state.write_message(&[], &mut buf)?;
+    buf.truncate(n);
+    Ok((sign(&prologue, node_key, buf), SessionKeys::new(state)?))
+}
+
+/// Size of the frame of `n` bytes, once encrypted.
+pub(crate) fn encrypted_len(n: usize) -> usize {
+    let chunks = std::cmp::max(1, (n + MAX_PLAINTEXT_CHUNK_LEN - 1) / MAX_PLAINTEXT_CHUNK_LEN);
+    n + chunks * TAG_LEN
+}
+
+/// Encrypts/decrypts a single direction of the connection.
+pub(crate) struct Cipher {
+    transport: Arc<snow::StatelessTransportState>,
+    nonce: u64,
+}
+
+impl Cipher {
+    fn next_nonce(&mut self) -> u64 {
+        self.nonce += 1;
+        self.nonce - 1
+    }
+
+    /// Returns `data` encrypted and authenticated with the next nonces.
+    pub fn encrypt(&mut self, data: &[u8]) -> Vec<u8> {
+        let mut out = vec![0; encrypted_len(data.len())];
+        let (mut i, mut o) = (0, 0);
+        // An empty frame is still encrypted as a single (empty) chunk.
+        loop {
+            let chunk = &data[i..std::cmp::min(data.len(), i + MAX_PLAINTEXT_CHUNK_LEN)];
+            let nonce = self.next_nonce();
+            o += self
+                .transport
+                .write_message(nonce, chunk, &mut out[o..])
+                .expect("encryption of a chunk within the size limit cannot fail");
+            i += chunk.len();
+            if i == data.len() {
+                return out;
+            }
+        }
+    }
+
+    /// Decrypts `data` with the next nonces.
+    /// Frames are expected in order: a dropped, replayed or reordered frame
+    /// fails the authentication.
+    pub fn decrypt(&mut self, data: &[u8]) -> Result<Vec<u8>, Error> {
+        if data.is_empty() {
+            return Err(Error::Decrypt);
+        }
+        let mut out = vec![0; data.len()];
+        let mut o = 0;
+        for chunk in data.chunks(MAX_CHUNK_LEN) {
+            let nonce = self.next_nonce();
+            o += self
+                .transport
+                .read_message(nonce, chunk, &mut out[o..])
+                .map_err(|_| Error::Decrypt)?;
+        }
+        out.truncate(o);
+        Ok(out)
+    }
+}
+
+/// Ciphers of both directions of a connection.
+pub(crate) struct SessionKeys {
+    pub send: Cipher,
+    pub recv: Cipher,
+}
+
+impl SessionKeys {
+    fn new(state: snow::HandshakeState) -> Result<Self, Error> {
+        let transport = Arc::new(state.into_stateless_transport_mode()?);
+        Ok(Self {
+            send: Cipher { transport: transport.clone(), nonce: 0 },
+            recv: Cipher { transport, nonce: 0 },
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_matches::assert_matches;
+    use near_crypto::KeyType;
+
+    fn key(seed: &str) -> SecretKey {
+        SecretKey::from_seed(KeyType::ED25519, seed)
+    }
+
+    #[test]
+    fn test_session_keys() {
+        let (a, b, c) = (key("a"), key("b"), key("c"));
+        let a_id = PeerId::new(a.public_key());
+        let b_id = PeerId::new(b.public_key());
+        let (init, offer) = Initiator::new(&a, &b_id, 7).unwrap();
+        let (resp, mut kb) = respond(&b, &a_id, 7, &offer).unwrap();
+        let mut ka = init.finish(&b_id, &resp).unwrap();
+        for msg in [&b"hello"[..], b"", &[7; 1000], &[8; 3 * MAX_PLAINTEXT_CHUNK_LEN + 5]] {
+            let x = ka.send.encrypt(msg);
+            assert_eq!(encrypted_len(msg.len()), x.len());
+            assert_eq!(msg, &kb.recv.decrypt(&x).unwrap()[..]);
+            let y = kb.send.encrypt(msg);
+            assert_eq!(msg, &ka.recv.decrypt(&y).unwrap()[..]);
+        }
+
+        // Replayed frame.
+        let x = ka.send.encrypt(b"x");
+        kb.recv.decrypt(&x).unwrap();
+        assert_matches!(kb.recv.decrypt(&x), Err(Error::Decrypt));
+
+        // A node without b's key cannot answer on b's behalf.
+        let (init, offer) = Initiator::new(&a, &b_id, 8).unwrap();
+        let (resp, _) = respond(&c, &a_id, 8, &offer).unwrap();
+        assert_matches!(init.finish(&b_id, &resp), Err(Error::InvalidSignature));
+
+        // A node without a's key cannot offer on a's behalf.
+        let (_, offer) = Initiator::new(&c, &b_id, 9).unwrap();
+        assert_matches!(respond(&b, &a_id, 9, &offer), Err(Error::InvalidSignature));
+
+        // Messages are bound to the edge nonce.
+        let (_, offer) = Initiator::new(&a, &b_id, 10).unwrap();
+        assert_matches!(respond(&b, &a_id, 11, &offer), Err(Error::InvalidSignature));
+
+        // A man in the middle cannot replace the ephemeral key, even with a valid signature
+        // from another session.
+        let (init, _) = Initiator::new(&a, &b_id, 12).unwrap();
+        let (_, other_offer) = Initiator::new(&a, &b_id, 12).unwrap();
+        let (resp, _) = respond(&b, &a_id, 12, &other_offer).unwrap();
+        assert_matches!(init.finish(&b_id, &resp), Err(Error::Noise(_)));
+    }
+}
@@ -1,17 +1,22 @@
 use crate::accounts_data;
+use crate::client;
 use crate::concurrency::atomic_cell::AtomicCell;
 use crate::concurrency::demux;
+use crate::concurrency::rate;
+use crate::config;
 use crate::network_protocol::{
     Edge, EdgeState, Encoding, OwnedAccount, ParsePeerMessageError, PartialEdgeInfo,
     PeerChainInfoV2, PeerIdOrHash, PeerInfo, RawRoutedMessage, RoutedMessageBody, RoutedMessageV2,
-    RoutingTableUpdate, SyncAccountsData,
+    RoutingTableUpdate, SignedNoiseMessage, SyncAccountsData,
 };
+use crate::peer::noise;
 use crate::peer::stream;
 use crate::peer::tracker::Tracker;
 use crate::peer_manager::connection;
 use crate::peer_manager::network_state::{NetworkState, PRUNE_EDGES_AFTER};
 use crate::peer_manager::peer_manager_actor::Event;
 use crate::private_actix::{RegisterPeerError, SendMessage};
+use crate::recorder;
 use crate::routing::edge::verify_nonce;
 use crate::stats::metrics;
 use crate::tcp;
@@ -33,6 +38,7 @@ use near_primitives::version::{
     ProtocolVersion, PEER_MIN_ALLOWED_PROTOCOL_VERSION, PROTOCOL_VERSION,
 };
 use parking_lot::Mutex;
+use std::collections::HashMap;
 use std::fmt::Debug;
 use std::io;
 use std::net::SocketAddr;
@@ -78,6 +84,8 @@ pub struct HandshakeCompletedEvent {
     pub(crate) stream_id: tcp::StreamId,
     pub(crate) edge: Edge,
     pub(crate) tier: tcp::Tier,
+    /// Whether the encrypted transport has been negotiated.
+    pub(crate) encrypted: bool,
 }
 
 #[derive(thiserror::Error, Clone, PartialEq, Eq, Debug)]
@@ -111,6 +119,37 @@ pub(crate) enum ClosingReason {
     Unknown,
 }
 
+/// Limiters enforcing `config::InboundRateLimits` on a single connection.
+/// The limiters of a message type are created on the first message of that type.
+#[derive(Default)]
+struct InboundRateLimiters(HashMap<&'static str, (Option<rate::Limiter>, Option<rate::Limiter>)>);
+
+impl InboundRateLimiters {
+    /// Accounts a received message of the given type and size.
+    /// Returns false iff the message exceeds the quota of its type.
+    fn check(
+        &mut self,
+        clock: &time::Clock,
+        cfg: &config::InboundRateLimits,
+        msg_type: &'static str,
+        msg_len: usize,
+    ) -> bool {
+        let limits = match cfg.messages.get(msg_type) {
+            Some(limits) => limits,
+            None => return true,
+        };
+        let (messages, bytes) = self.0.entry(msg_type).or_insert_with(|| {
+            (
+                limits.messages.map(|l| rate::Limiter::new(clock, l)),
+                limits.bytes.map(|l| rate::Limiter::new(clock, l)),
+            )
+        });
+        let messages_ok = messages.as_mut().map_or(true, |l| l.try_acquire(clock, 1));
+        let bytes_ok = bytes.as_mut().map_or(true, |l| l.try_acquire(clock, msg_len as u64));
+        messages_ok && bytes_ok
+    }
+}
+
 pub(crate) struct PeerActor {
     clock: time::Clock,
 
@@ -149,6 +188,11 @@ pub(crate) struct PeerActor {
     // TODO: move it to ConnectingStatus::Outbound.
     // When ready, use connection.peer_info instead.
     peer_info: DisplayOption<PeerInfo>,
+    /// Noise handshake state of the encrypted transport offered in our Handshake.
+    /// Present only for outbound connections, until the peer's Handshake is received.
+    noise_initiator: Option<noise::Initiator>,
+    /// Enforces `config.inbound_rate_limits` on the received messages.
+    inbound_rate_limiters: InboundRateLimiters,
 }
 
 impl Debug for PeerActor {
@@ -164,6 +208,18 @@ struct HandshakeSpec {
     tier: tcp::Tier,
     protocol_version: ProtocolVersion,
     partial_edge_info: PartialEdgeInfo,
+    noise: NoiseSpec,
+}
+
+/// What to put into the `noise` field of the Handshake.
+#[derive(Clone, Debug)]
+enum NoiseSpec {
+    /// Plaintext transport.
+    Disabled,
+    /// Offer the encrypted transport (outbound peer).
+    Offer,
+    /// Accept the encrypted transport offered by the peer (inbound peer).
+    Accept(SignedNoiseMessage),
 }
 
 type HandshakeSignalSender = tokio::sync::oneshot::Sender<std::convert::Infallible>;
@@ -214,6 +270,14 @@ impl PeerActor {
         force_encoding: Option<Encoding>,
         network_state: Arc<NetworkState>,
     ) -> Result<(actix::Addr<Self>, HandshakeSignal), ClosingReason> {
+        // Override force_encoding for outbound Tier1 connections,
+        // since Tier1Handshake is supported only with proto encoding.
+        let force_encoding = match &stream.type_ {
+            tcp::StreamType::Outbound { tier, .. } if tier == &tcp::Tier::T1 => {
+                Some(Encoding::Proto)
+            }
+            _ => force_encoding,
+        };
         let connecting_status = match &stream.type_ {
             tcp::StreamType::Inbound => ConnectingStatus::Inbound(
                 network_state
@@ -248,17 +312,17 @@ impl PeerActor {
                     protocol_version: PROTOCOL_VERSION,
                     tier: *tier,
                     peer_id: peer_id.clone(),
+                    // The noise message is sent only in the proto encoding.
+                    noise: if network_state.config.encrypted_transport
+                        && force_encoding != Some(Encoding::Borsh)
+                    {
+                        NoiseSpec::Offer
+                    } else {
+                        NoiseSpec::Disabled
+                    },
                 },
             },
         };
-        // Override force_encoding for outbound Tier1 connections,
-        // since Tier1Handshake is supported only with proto encoding.
-        let force_encoding = match &stream.type_ {
-            tcp::StreamType::Outbound { tier, .. } if tier == &tcp::Tier::T1 => {
-                Some(Encoding::Proto)
-            }
-            _ => force_encoding,
-        };
         let my_node_info = PeerInfo {
             id: network_state.config.node_id(),
             addr: network_state.config.node_addr.clone(),
@@ -301,6 +365,8 @@ impl PeerActor {
                         }),
                     }
                     .into(),
+                    noise_initiator: None,
+                    inbound_rate_limiters: InboundRateLimiters::default(),
                     network_state,
                 }
             }),
@@ -344,6 +410,15 @@ impl PeerActor {
         if let (PeerStatus::Ready(conn), PeerMessage::PeersRequest) = (&self.peer_status, msg) {
             conn.last_time_peer_requested.store(Some(self.clock.now()));
         }
+        if let Some(recorder) = &self.network_state.recorder {
+            recorder.record(
+                &self.clock,
+                self.other_peer_id(),
+                recorder::Direction::Outbound,
+                false,
+                msg,
+            );
+        }
         if let Some(enc) = self.encoding() {
             return self.send_message_with_encoding(msg, enc);
         }
@@ -381,7 +456,7 @@ impl PeerActor {
             .inc_by(bytes_len as u64);
     }
 
-    fn send_handshake(&self, spec: HandshakeSpec) {
+    fn send_handshake(&mut self, spec: HandshakeSpec) {
         let (height, tracked_shards) =
             if let Some(chain_info) = self.network_state.chain_info.load().as_ref() {
                 (chain_info.block.header().height(), chain_info.tracked_shards.clone())
@@ -401,6 +476,27 @@ impl PeerActor {
                 tracked_shards,
                 archival: self.network_state.config.archive,
             },
+            noise: match spec.noise {
+                NoiseSpec::Offer => {
+                    // Every attempt (i.e. every nonce) gets a fresh noise handshake.
+                    match noise::Initiator::new(
+                        &self.network_state.config.node_key,
+                        &spec.peer_id,
+                        spec.partial_edge_info.nonce,
+                    ) {
+                        Ok((initiator, msg)) => {
+                            self.noise_initiator = Some(initiator);
+                            Some(msg)
+                        }
+                        Err(err) => {
+                            tracing::error!(target: "network", "Failed to start noise handshake: {err}");
+                            None
+                        }
+                    }
+                }
+                NoiseSpec::Accept(msg) => Some(msg),
+                NoiseSpec::Disabled => None,
+            },
             partial_edge_info: spec.partial_edge_info,
             owned_account: self.network_state.config.validator.as_ref().map(|vc| {
                 OwnedAccount {
@@ -436,6 +532,43 @@ impl PeerActor {
         self.peer_info.as_ref().as_ref().map(|peer_info| &peer_info.id)
     }
 
+    /// Establishes the session keys of the encrypted transport, if both sides
+    /// agreed to use it. For inbound connections it also returns the response
+    /// to send in our Handshake. Returns an error if the handshake is inconsistent
+    /// with what we have offered, or if we require encryption and the peer doesn't
+    /// support it.
+    fn negotiate_encryption(
+        &self,
+        cs: &ConnectingStatus,
+        initiator: Option<noise::Initiator>,
+        handshake: &Handshake,
+    ) -> Result<Option<(noise::SessionKeys, Option<SignedNoiseMessage>)>, noise::Error> {
+        let config = &self.network_state.config;
+        let res = match (cs, &handshake.noise) {
+            (ConnectingStatus::Outbound { .. }, Some(msg)) => {
+                let initiator = initiator.ok_or(noise::Error::UnexpectedNoiseMessage)?;
+                Some((initiator.finish(&handshake.sender_peer_id, msg)?, None))
+            }
+            // Support of the encrypted transport isn't tied to a protocol version:
+            // peers which don't know the `noise` field of the Handshake ignore it,
+            // so its presence alone decides.
+            (ConnectingStatus::Inbound { .. }, Some(msg)) if config.encrypted_transport => {
+                let (response, keys) = noise::respond(
+                    &config.node_key,
+                    &handshake.sender_peer_id,
+                    handshake.partial_edge_info.nonce,
+                    msg,
+                )?;
+                Some((keys, Some(response)))
+            }
+            _ => None,
+        };
+        if res.is_none() && config.require_encryption {
+            return Err(noise::Error::EncryptionRequired);
+        }
+        Ok(res)
+    }
+
     fn process_handshake(
         &mut self,
         ctx: &mut <PeerActor as actix::Actor>::Context,
@@ -549,6 +682,29 @@ impl PeerActor {
             }
         }
 
+        let initiator = self.noise_initiator.take();
+        let (mut session_keys, noise_response) = match self
+            .negotiate_encryption(cs, initiator, &handshake)
+        {
+            Ok(Some((keys, response))) => (Some(keys), response),
+            Ok(None) => (None, None),
+            Err(err) => {
+                tracing::warn!(target: "network", "Failed to negotiate encrypted transport with peer {}: {err}", handshake.sender_peer_id);
+                self.stop(ctx, ClosingReason::HandshakeFailed);
+                return;
+            }
+        };
+        let encrypted = session_keys.is_some();
+        // The inbound peer has already switched to the encrypted transport, right after
+        // sending its Handshake, so the outbound peer switches immediately. The inbound peer
+        // switches once it sends its Handshake, which happens only after the connection
+        // has been registered.
+        if let ConnectingStatus::Outbound { .. } = cs {
+            if let Some(keys) = session_keys.take() {
+                self.framed.enable_encryption(keys);
+            }
+        }
+
         // Merge partial edges.
         let nonce = handshake.partial_edge_info.nonce;
         let partial_edge_info = match cs {
@@ -595,6 +751,7 @@ impl PeerActor {
             _peer_connections_metric: metrics::PEER_CONNECTIONS.new_point(&metrics::Connection {
                 type_: self.peer_type,
                 encoding: self.encoding(),
+                encrypted,
             }),
             last_time_peer_requested: AtomicCell::new(None),
             last_time_received_message: AtomicCell::new(now),
@@ -671,7 +828,14 @@ impl PeerActor {
                                 tier,
                                 protocol_version: handshake.protocol_version,
                                 partial_edge_info: partial_edge_info,
+                                noise: match noise_response {
+                                    Some(msg) => NoiseSpec::Accept(msg),
+                                    None => NoiseSpec::Disabled,
+                                },
                             });
+                            if let Some(keys) = session_keys {
+                                act.framed.enable_encryption(keys);
+                            }
                         }
                         // TIER1 is strictly reserved for BFT consensensus messages,
                         // so all kinds of periodical syncs happen only on TIER2 connections.
@@ -767,6 +931,7 @@ impl PeerActor {
                             stream_id: act.stream_id,
                             edge,
                             tier: conn.tier,
+                            encrypted,
                         }));
                     },
                     Err(err) => {
@@ -886,60 +1051,57 @@ impl PeerActor {
         }
     }
 
-    async fn receive_routed_message(
+    pub(crate) async fn receive_routed_message(
         clock: &time::Clock,
-        network_state: &NetworkState,
+        client: &dyn client::Client,
         peer_id: PeerId,
         msg_hash: CryptoHash,
         body: RoutedMessageBody,
     ) -> Result<Option<RoutedMessageBody>, ReasonForBan> {
         let _span = tracing::trace_span!(target: "network", "receive_routed_message").entered();
         Ok(match body {
-            RoutedMessageBody::TxStatusRequest(account_id, tx_hash) => network_state
-                .client
+            RoutedMessageBody::TxStatusRequest(account_id, tx_hash) => client
                 .tx_status_request(account_id, tx_hash)
                 .await
                 .map(|v| RoutedMessageBody::TxStatusResponse(*v)),
             RoutedMessageBody::TxStatusResponse(tx_result) => {
-                network_state.client.tx_status_response(tx_result).await;
+                client.tx_status_response(tx_result).await;
                 None
             }
-            RoutedMessageBody::StateRequestHeader(shard_id, sync_hash) => network_state
-                .client
+            RoutedMessageBody::StateRequestHeader(shard_id, sync_hash) => client
                 .state_request_header(shard_id, sync_hash)
                 .await?
                 .map(RoutedMessageBody::VersionedStateResponse),
-            RoutedMessageBody::StateRequestPart(shard_id, sync_hash, part_id) => network_state
-                .client
+            RoutedMessageBody::StateRequestPart(shard_id, sync_hash, part_id) => client
                 .state_request_part(shard_id, sync_hash, part_id)
                 .await?
                 .map(RoutedMessageBody::VersionedStateResponse),
             RoutedMessageBody::VersionedStateResponse(info) => {
-                network_state.client.state_response(info).await;
+                client.state_response(info).await;
                 None
             }
             RoutedMessageBody::BlockApproval(approval) => {
-                network_state.client.block_approval(approval, peer_id).await;
+                client.block_approval(approval, peer_id).await;
                 None
             }
             RoutedMessageBody::ForwardTx(transaction) => {
-                network_state.client.transaction(transaction, /*is_forwarded=*/ true).await;
+                client.transaction(transaction, /*is_forwarded=*/ true).await;
                 None
             }
             RoutedMessageBody::PartialEncodedChunkRequest(request) => {
-                network_state.client.partial_encoded_chunk_request(request, msg_hash).await;
+                client.partial_encoded_chunk_request(request, msg_hash).await;
                 None
             }
             RoutedMessageBody::PartialEncodedChunkResponse(response) => {
-                network_state.client.partial_encoded_chunk_response(response, clock.now()).await;
+                client.partial_encoded_chunk_response(response, clock.now()).await;
                 None
             }
             RoutedMessageBody::VersionedPartialEncodedChunk(chunk) => {
-                network_state.client.partial_encoded_chunk(chunk).await;
+                client.partial_encoded_chunk(chunk).await;
                 None
             }
             RoutedMessageBody::PartialEncodedChunkForward(msg) => {
-                network_state.client.partial_encoded_chunk_forward(msg).await;
+                client.partial_encoded_chunk_forward(msg).await;
                 None
             }
             RoutedMessageBody::ReceiptOutcomeRequest(_) => {
@@ -995,7 +1157,7 @@ impl PeerActor {
             Ok(match msg {
                 PeerMessage::Routed(msg) => {
                     let msg_hash = msg.hash();
-                    Self::receive_routed_message(&clock, &network_state, peer_id, msg_hash, msg.msg.body).await?.map(
+                    Self::receive_routed_message(&clock, &*network_state.client, peer_id, msg_hash, msg.msg.body).await?.map(
                         |body| {
                             PeerMessage::Routed(network_state.sign_message(
                                 &clock,
@@ -1026,6 +1188,20 @@ impl PeerActor {
                     network_state.client.challenge(challenge).await;
                     None
                 }
+                PeerMessage::EpochSyncRequest(epoch_id) => {
+                    network_state.client.epoch_sync_request(epoch_id).await.map(PeerMessage::EpochSyncResponse)
+                }
+                PeerMessage::EpochSyncResponse(resp) => {
+                    network_state.client.epoch_sync_response(resp, peer_id).await;
+                    None
+                }
+                PeerMessage::EpochSyncFinalizationRequest(epoch_id) => {
+                    network_state.client.epoch_sync_finalization_request(epoch_id).await.map(PeerMessage::EpochSyncFinalizationResponse)
+                }
+                PeerMessage::EpochSyncFinalizationResponse(resp) => {
+                    network_state.client.epoch_sync_finalization_response(resp, peer_id).await;
+                    None
+                }
                 msg => {
                     tracing::error!(target: "network", "Peer received unexpected type: {:?}", msg);
                     None
@@ -1352,7 +1528,8 @@ impl actix::Actor for PeerActor {
         if let PeerStatus::Connecting(_, ConnectingStatus::Outbound { handshake_spec, .. }) =
             &self.peer_status
         {
-            self.send_handshake(handshake_spec.clone());
+            let spec = handshake_spec.clone();
+            self.send_handshake(spec);
         }
         self.network_state
             .config
@@ -1420,6 +1597,10 @@ impl actix::Handler<stream::Error> for PeerActor {
             }
             // It is expected in a sense that the peer might be just slow.
             stream::Error::Send(stream::SendError::QueueOverflow { .. }) => true,
+            // Corrupted frames might have been injected by a third party,
+            // so we don't ban the peer for them.
+            stream::Error::Recv(stream::RecvError::UnexpectedEncryptedFrame)
+            | stream::Error::Recv(stream::RecvError::Decrypt(_)) => true,
             stream::Error::Recv(stream::RecvError::IO(err))
             | stream::Error::Send(stream::SendError::IO(err)) => match err.kind() {
                 // Connection has been closed.
@@ -1476,6 +1657,19 @@ impl actix::Handler<stream::Frame> for PeerActor {
         };
 
         tracing::trace!(target: "network", "Received message: {}", peer_msg);
+        if let Some(recorder) = &self.network_state.recorder {
+            let was_requested = match &peer_msg {
+                PeerMessage::Block(block) => self.tracker.lock().has_request(block.hash()),
+                _ => false,
+            };
+            recorder.record(
+                &self.clock,
+                self.other_peer_id(),
+                recorder::Direction::Inbound,
+                was_requested,
+                &peer_msg,
+            );
+        }
 
         {
             let labels = [peer_msg.msg_variant()];
@@ -1500,6 +1694,28 @@ impl actix::Handler<stream::Frame> for PeerActor {
                     self.stop(ctx, ClosingReason::DisallowedMessage);
                     return;
                 }
+                let msg_type = peer_msg.msg_variant();
+                let inbound_rate_limits = &self.network_state.config.inbound_rate_limits;
+                if !self.inbound_rate_limiters.check(
+                    &self.clock,
+                    inbound_rate_limits,
+                    msg_type,
+                    msg.len(),
+                ) {
+                    if inbound_rate_limits.ban {
+                        metrics::PEER_MESSAGE_RATE_LIMITED
+                            .with_label_values(&[msg_type, "ban"])
+                            .inc();
+                        tracing::warn!(target: "network", "Received {} exceeding the inbound rate limit, banning {}", msg_type, self.peer_info);
+                        self.stop(ctx, ClosingReason::Ban(ReasonForBan::Abusive));
+                    } else {
+                        metrics::PEER_MESSAGE_RATE_LIMITED
+                            .with_label_values(&[msg_type, "drop"])
+                            .inc();
+                        tracing::debug!(target: "network", "Received {} exceeding the inbound rate limit, dropping", msg_type);
+                    }
+                    return;
+                }
 
                 // Optionally, ignore any received tombstones after startup. This is to
                 // prevent overload from too much accumulated deleted edges.
@@ -1,9 +1,11 @@
+use crate::peer::noise;
 use crate::peer_manager::connection;
 use crate::stats::metrics;
 use crate::tcp;
 use actix::fut::future::wrap_future;
 use actix::AsyncContext as _;
 use bytesize::{GIB, MIB};
+use parking_lot::Mutex;
 use std::io;
 use std::net::SocketAddr;
 use std::sync::atomic::Ordering;
@@ -34,12 +36,22 @@ pub(crate) enum RecvError {
     IO(#[source] io::Error),
     #[error("message too large: got {got_bytes}B, want <={want_max_bytes}B")]
     MessageTooLarge { got_bytes: usize, want_max_bytes: usize },
+    #[error("encrypted frame received before the session keys were established")]
+    UnexpectedEncryptedFrame,
+    #[error("decryption: {0}")]
+    Decrypt(#[source] noise::Error),
 }
 
 #[derive(actix::Message, PartialEq, Eq, Clone, Debug)]
 #[rtype(result = "()")]
 pub(crate) struct Frame(pub Vec<u8>);
 
+/// Frame as written to the TCP stream.
+struct WireFrame {
+    data: Vec<u8>,
+    encrypted: bool,
+}
+
 /// Stream critical error.
 /// Actor is responsible for calling ctx.stop() after receiving stream::Error.
 /// Actor might receive more than 1 stream::Error, but should call ctx.stop() just after the
@@ -57,7 +69,11 @@ pub(crate) enum Error {
 }
 
 pub(crate) struct FramedStream<Actor: actix::Actor> {
-    queue_send: tokio::sync::mpsc::UnboundedSender<Frame>,
+    queue_send: tokio::sync::mpsc::UnboundedSender<WireFrame>,
+    /// Cipher for the outgoing frames, set once the encrypted transport has been negotiated.
+    send_cipher: Mutex<Option<noise::Cipher>>,
+    /// Cipher for the incoming frames, shared with the recv loop.
+    recv_cipher: Arc<Mutex<Option<noise::Cipher>>>,
     stats: Arc<connection::Stats>,
     send_buf_size_metric: Arc<metrics::IntGaugeGuard>,
     addr: actix::Addr<Actor>,
@@ -90,26 +106,55 @@ where
                 }
             }
         }));
+        let recv_cipher = Arc::new(Mutex::new(None));
         ctx.spawn(wrap_future({
             let addr = ctx.address();
             let stats = stats.clone();
+            let recv_cipher = recv_cipher.clone();
             async move {
-                if let Err(err) =
-                    Self::run_recv_loop(stream.peer_addr, tcp_recv, addr.clone(), stats).await
+                if let Err(err) = Self::run_recv_loop(
+                    stream.peer_addr,
+                    tcp_recv,
+                    addr.clone(),
+                    stats,
+                    recv_cipher,
+                )
+                .await
                 {
                     addr.do_send(Error::Recv(err));
                 }
             }
         }));
-        Self { queue_send, stats, send_buf_size_metric, addr: ctx.address() }
+        Self {
+            queue_send,
+            send_cipher: Mutex::new(None),
+            recv_cipher,
+            stats,
+            send_buf_size_metric,
+            addr: ctx.address(),
+        }
+    }
+
+    /// Switches the stream to the encrypted transport.
+    /// All the frames passed to send() afterwards are encrypted.
+    /// All the frames received afterwards are expected to be encrypted, except for
+    /// the plaintext frames which the peer has sent before it established the session
+    /// keys on its side (i.e. before it received our Handshake); these are dropped.
+    pub fn enable_encryption(&self, keys: noise::SessionKeys) {
+        *self.send_cipher.lock() = Some(keys.send);
+        *self.recv_cipher.lock() = Some(keys.recv);
     }
 
     /// Pushes `msg` to the send queue.
     /// Silently drops message if the connection has been closed.
     /// If the message is too large, it will be silently dropped inside run_send_loop.
     /// Emits a critical error to Actor if send queue is full.
-    pub fn send(&self, frame: Frame) {
-        let msg = &frame.0;
+    pub fn send(&self, Frame(msg): Frame) {
+        let frame = match self.send_cipher.lock().as_mut() {
+            Some(cipher) => WireFrame { data: cipher.encrypt(&msg), encrypted: true },
+            None => WireFrame { data: msg, encrypted: false },
+        };
+        let msg = &frame.data;
         let mut buf_size =
             self.stats.bytes_to_send.fetch_add(msg.len() as u64, Ordering::Acquire) as usize;
         buf_size += msg.len();
@@ -141,6 +186,7 @@ where
         read: ReadHalf,
         addr: actix::Addr<Actor>,
         stats: Arc<connection::Stats>,
+        cipher: Arc<Mutex<Option<noise::Cipher>>>,
     ) -> Result<(), RecvError> {
         const READ_BUFFER_CAPACITY: usize = 8 * 1024;
         let mut read = tokio::io::BufReader::with_capacity(READ_BUFFER_CAPACITY, read);
@@ -152,12 +198,12 @@ where
             vec![peer_addr.to_string()],
         );
         loop {
-            let n = read.read_u32_le().await.map_err(RecvError::IO)? as usize;
-            if n > NETWORK_MESSAGE_MAX_SIZE_BYTES {
-                return Err(RecvError::MessageTooLarge {
-                    got_bytes: n,
-                    want_max_bytes: NETWORK_MESSAGE_MAX_SIZE_BYTES,
-                });
+            let n = read.read_u32_le().await.map_err(RecvError::IO)?;
+            let encrypted = n & noise::ENCRYPTED_FRAME_BIT != 0;
+            let n = (n & !noise::ENCRYPTED_FRAME_BIT) as usize;
+            let max_size = max_frame_size(encrypted);
+            if n > max_size {
+                return Err(RecvError::MessageTooLarge { got_bytes: n, want_max_bytes: max_size });
             }
             msg_size_metric.observe(n as f64);
             buf_size_metric.set(n as i64);
@@ -168,6 +214,15 @@ where
             buf_size_metric.set(0);
             stats.received_messages.fetch_add(1, Ordering::Relaxed);
             stats.received_bytes.fetch_add(n as u64, Ordering::Relaxed);
+            let buf = match (cipher.lock().as_mut(), encrypted) {
+                (Some(cipher), true) => cipher.decrypt(&buf).map_err(RecvError::Decrypt)?,
+                (None, true) => return Err(RecvError::UnexpectedEncryptedFrame),
+                (Some(_), false) => {
+                    metrics::PEER_PLAINTEXT_FRAMES_DROPPED.inc();
+                    continue;
+                }
+                (None, false) => buf,
+            };
             if let Err(_) = addr.send(Frame(buf)).await {
                 // We got mailbox error, which means that Actor has stopped,
                 // so we should just close the stream.
@@ -177,28 +232,33 @@ where
     }
     async fn run_send_loop(
         tcp_send: WriteHalf,
-        mut queue_recv: tokio::sync::mpsc::UnboundedReceiver<Frame>,
+        mut queue_recv: tokio::sync::mpsc::UnboundedReceiver<WireFrame>,
         stats: Arc<connection::Stats>,
         buf_size_metric: Arc<metrics::IntGaugeGuard>,
     ) -> io::Result<()> {
         const WRITE_BUFFER_CAPACITY: usize = 8 * 1024;
         let mut writer = tokio::io::BufWriter::with_capacity(WRITE_BUFFER_CAPACITY, tcp_send);
-        while let Some(Frame(mut msg)) = queue_recv.recv().await {
+        while let Some(mut frame) = queue_recv.recv().await {
             // Try writing a batch of messages and flush once at the end.
             loop {
                 // TODO(gprusak): sending a too large message should probably be treated as a bug,
                 // since dropping messages may lead to hard-to-debug high-level issues.
-                if msg.len() > NETWORK_MESSAGE_MAX_SIZE_BYTES {
+                let msg = &frame.data;
+                if msg.len() > max_frame_size(frame.encrypted) {
                     metrics::MessageDropped::InputTooLong.inc_unknown_msg();
                 } else {
-                    writer.write_u32_le(msg.len() as u32).await?;
+                    let mut n = msg.len() as u32;
+                    if frame.encrypted {
+                        n |= noise::ENCRYPTED_FRAME_BIT;
+                    }
+                    writer.write_u32_le(n).await?;
                     writer.write_all(&msg[..]).await?;
                 }
                 stats.messages_to_send.fetch_sub(1, Ordering::Release);
                 stats.bytes_to_send.fetch_sub(msg.len() as u64, Ordering::Release);
                 buf_size_metric.sub(msg.len() as i64);
-                msg = match queue_recv.try_recv() {
-                    Ok(Frame(it)) => it,
+                frame = match queue_recv.try_recv() {
+                    Ok(it) => it,
                     Err(_) => break,
                 };
             }
@@ -213,3 +273,11 @@ where
         Ok(())
     }
 }
+
+fn max_frame_size(encrypted: bool) -> usize {
+    if encrypted {
+        noise::encrypted_len(NETWORK_MESSAGE_MAX_SIZE_BYTES)
+    } else {
+        NETWORK_MESSAGE_MAX_SIZE_BYTES
+    }
+}
@@ -108,6 +108,7 @@ impl PeerHandle {
             cfg.chain.genesis_id.clone(),
             fc,
             vec![],
+            None,
         ));
         let actix = ActixSystem::spawn({
             let clock = clock.clone();
@@ -2,6 +2,8 @@ use crate::network_protocol::testonly as data;
 use crate::network_protocol::{
     Encoding, Handshake, HandshakeFailureReason, PartialEdgeInfo, PeerMessage, RoutedMessageBody,
 };
+use crate::peer::noise;
+use crate::peer::peer_actor::ClosingReason;
 use crate::peer::testonly::{Event, PeerConfig, PeerHandle};
 use crate::peer_manager::peer_manager_actor::Event as PME;
 use crate::tcp;
@@ -207,6 +209,7 @@ async fn test_handshake(outbound_encoding: Option<Encoding>, inbound_encoding: O
         sender_chain_info: outbound_cfg.chain.get_peer_chain_info(),
         partial_edge_info: outbound_cfg.partial_edge_info(&inbound.cfg.id(), 1),
         owned_account: None,
+        noise: None,
     };
     // We will also introduce chain_id mismatch, but ProtocolVersionMismatch is expected to take priority.
     handshake.sender_chain_info.genesis_id.chain_id = "unknown_chain".to_string();
@@ -263,3 +266,245 @@ async fn handshake() -> anyhow::Result<()> {
     }
     Ok(())
 }
+
+#[tokio::test]
+// Verifies that the encrypted transport is negotiated and used after the handshake.
+async fn encrypted_handshake() {
+    init_test_logger();
+    let mut rng = make_rng(89028037453);
+    let mut clock = time::FakeClock::default();
+
+    let chain = Arc::new(data::Chain::make(&mut clock, &mut rng, 12));
+    let mut inbound_cfg = PeerConfig {
+        network: chain.make_config(&mut rng),
+        chain: chain.clone(),
+        force_encoding: None,
+    };
+    inbound_cfg.network.encrypted_transport = true;
+    let outbound_cfg = PeerConfig {
+        network: chain.make_config(&mut rng),
+        chain: chain.clone(),
+        force_encoding: None,
+    };
+    let (outbound_stream, inbound_stream) =
+        tcp::Stream::loopback(inbound_cfg.id(), tcp::Tier::T2).await;
+    let inbound = PeerHandle::start_endpoint(clock.clock(), inbound_cfg, inbound_stream).await;
+    let outbound_port = outbound_stream.local_addr.port();
+    let mut outbound = Stream::new(Some(Encoding::Proto), outbound_stream);
+
+    let (initiator, offer) =
+        noise::Initiator::new(&outbound_cfg.network.node_key, &inbound.cfg.id(), 1).unwrap();
+    let handshake = Handshake {
+        protocol_version: PROTOCOL_VERSION,
+        oldest_supported_version: PROTOCOL_VERSION,
+        sender_peer_id: outbound_cfg.id(),
+        target_peer_id: inbound.cfg.id(),
+        sender_listen_port: Some(outbound_port),
+        sender_chain_info: chain.get_peer_chain_info(),
+        partial_edge_info: outbound_cfg.partial_edge_info(&inbound.cfg.id(), 1),
+        owned_account: None,
+        noise: Some(offer),
+    };
+    outbound.write(&PeerMessage::Tier2Handshake(handshake)).await;
+    let response = match outbound.read().await.unwrap() {
+        PeerMessage::Tier2Handshake(h) => h.noise.unwrap(),
+        msg => panic!("unexpected message {msg}"),
+    };
+    outbound.enable_encryption(initiator.finish(&inbound.cfg.id(), &response).unwrap());
+    // Inbound peer syncs the routing table right after the handshake.
+    assert_matches!(outbound.read().await.unwrap(), PeerMessage::SyncRoutingTable(_));
+
+    let mut events = inbound.events.from_now();
+    outbound.write(&PeerMessage::PeersRequest).await;
+    events
+        .recv_until(|ev| match ev {
+            Event::Network(PME::MessageProcessed(_, PeerMessage::PeersRequest)) => Some(()),
+            _ => None,
+        })
+        .await;
+}
+
+/// Awaits the end of the handshake. Returns whether the connection is encrypted.
+async fn wait_for_handshake(peer: &mut PeerHandle) -> Result<bool, ClosingReason> {
+    peer.events
+        .recv_until(|ev| match ev {
+            Event::Network(PME::HandshakeCompleted(ev)) => Some(Ok(ev.encrypted)),
+            Event::Network(PME::ConnectionClosed(ev)) => Some(Err(ev.reason)),
+            _ => None,
+        })
+        .await
+}
+
+/// Connects 2 PeerActors with the given (encrypted_transport,require_encryption) settings.
+/// Returns the results of the handshake on the outbound and inbound side.
+async fn test_encryption_negotiation(
+    outbound_encryption: (bool, bool),
+    inbound_encryption: (bool, bool),
+) -> (Result<bool, ClosingReason>, Result<bool, ClosingReason>) {
+    let mut rng = make_rng(89028037453);
+    let mut clock = time::FakeClock::default();
+
+    let chain = Arc::new(data::Chain::make(&mut clock, &mut rng, 12));
+    let mut inbound_cfg = PeerConfig {
+        network: chain.make_config(&mut rng),
+        chain: chain.clone(),
+        force_encoding: None,
+    };
+    inbound_cfg.network.encrypted_transport = inbound_encryption.0;
+    inbound_cfg.network.require_encryption = inbound_encryption.1;
+    let mut outbound_cfg = PeerConfig {
+        network: chain.make_config(&mut rng),
+        chain: chain.clone(),
+        force_encoding: None,
+    };
+    outbound_cfg.network.encrypted_transport = outbound_encryption.0;
+    outbound_cfg.network.require_encryption = outbound_encryption.1;
+    let (outbound_stream, inbound_stream) =
+        tcp::Stream::loopback(inbound_cfg.id(), tcp::Tier::T2).await;
+    let mut inbound = PeerHandle::start_endpoint(clock.clock(), inbound_cfg, inbound_stream).await;
+    let mut outbound =
+        PeerHandle::start_endpoint(clock.clock(), outbound_cfg, outbound_stream).await;
+    let outbound_res = wait_for_handshake(&mut outbound).await;
+    let inbound_res = wait_for_handshake(&mut inbound).await;
+    if let (Ok(_), Ok(_)) = (&outbound_res, &inbound_res) {
+        // Check that the connection is usable.
+        let mut events = inbound.events.from_now();
+        outbound.send(PeerMessage::PeersRequest).await;
+        events
+            .recv_until(|ev| match ev {
+                Event::Network(PME::MessageProcessed(_, PeerMessage::PeersRequest)) => Some(()),
+                _ => None,
+            })
+            .await;
+    }
+    (outbound_res, inbound_res)
+}
+
+#[tokio::test]
+// Verifies that 2 PeerActors negotiate the encrypted transport iff both support it.
+async fn encryption_negotiation() {
+    init_test_logger();
+    assert_eq!(
+        (Ok(true), Ok(true)),
+        test_encryption_negotiation((true, false), (true, false)).await
+    );
+    assert_eq!((Ok(true), Ok(true)), test_encryption_negotiation((true, true), (true, true)).await);
+    assert_eq!(
+        (Ok(false), Ok(false)),
+        test_encryption_negotiation((true, false), (false, false)).await
+    );
+    assert_eq!(
+        (Ok(false), Ok(false)),
+        test_encryption_negotiation((false, false), (true, false)).await
+    );
+    // A peer requiring encryption refuses a plaintext peer.
+    assert_matches!(
+        test_encryption_negotiation((true, true), (false, false)).await.0,
+        Err(ClosingReason::HandshakeFailed)
+    );
+    assert_matches!(
+        test_encryption_negotiation((false, false), (true, true)).await.1,
+        Err(ClosingReason::HandshakeFailed)
+    );
+}
+
+#[tokio::test]
+// Verifies that a man in the middle can neither strip nor replace the encryption offer
+// of a peer which requires encryption.
+async fn encryption_downgrade() {
+    init_test_logger();
+    let mut rng = make_rng(89028037453);
+    let mut clock = time::FakeClock::default();
+    let chain = Arc::new(data::Chain::make(&mut clock, &mut rng, 12));
+
+    // Inbound peer requiring encryption receives a Handshake with a stripped offer.
+    let mut inbound_cfg = PeerConfig {
+        network: chain.make_config(&mut rng),
+        chain: chain.clone(),
+        force_encoding: None,
+    };
+    inbound_cfg.network.encrypted_transport = true;
+    inbound_cfg.network.require_encryption = true;
+    let outbound_cfg = PeerConfig {
+        network: chain.make_config(&mut rng),
+        chain: chain.clone(),
+        force_encoding: None,
+    };
+    let (outbound_stream, inbound_stream) =
+        tcp::Stream::loopback(inbound_cfg.id(), tcp::Tier::T2).await;
+    let mut inbound = PeerHandle::start_endpoint(clock.clock(), inbound_cfg, inbound_stream).await;
+    let outbound_port = outbound_stream.local_addr.port();
+    let mut outbound = Stream::new(Some(Encoding::Proto), outbound_stream);
+    let mut handshake = Handshake {
+        protocol_version: PROTOCOL_VERSION,
+        oldest_supported_version: PROTOCOL_VERSION,
+        sender_peer_id: outbound_cfg.id(),
+        target_peer_id: inbound.cfg.id(),
+        sender_listen_port: Some(outbound_port),
+        sender_chain_info: chain.get_peer_chain_info(),
+        partial_edge_info: outbound_cfg.partial_edge_info(&inbound.cfg.id(), 1),
+        owned_account: None,
+        noise: None,
+    };
+    outbound.write(&PeerMessage::Tier2Handshake(handshake.clone())).await;
+    assert_eq!(Err(ClosingReason::HandshakeFailed), wait_for_handshake(&mut inbound).await);
+
+    // Inbound peer receives an offer, replaced by a third party.
+    let (outbound_stream, inbound_stream) =
+        tcp::Stream::loopback(inbound.cfg.id(), tcp::Tier::T2).await;
+    let mut inbound = PeerHandle::start_endpoint(
+        clock.clock(),
+        PeerConfig {
+            network: inbound.cfg.network.clone(),
+            chain: chain.clone(),
+            force_encoding: None,
+        },
+        inbound_stream,
+    )
+    .await;
+    let mut outbound = Stream::new(Some(Encoding::Proto), outbound_stream);
+    let mitm_key = data::make_secret_key(&mut rng);
+    let (_, offer) = noise::Initiator::new(&mitm_key, &inbound.cfg.id(), 1).unwrap();
+    handshake.noise = Some(offer);
+    outbound.write(&PeerMessage::Tier2Handshake(handshake)).await;
+    assert_eq!(Err(ClosingReason::HandshakeFailed), wait_for_handshake(&mut inbound).await);
+
+    // Outbound peer requiring encryption receives a response with a stripped offer.
+    let inbound_cfg = PeerConfig {
+        network: chain.make_config(&mut rng),
+        chain: chain.clone(),
+        force_encoding: None,
+    };
+    let mut outbound_cfg = PeerConfig {
+        network: chain.make_config(&mut rng),
+        chain: chain.clone(),
+        force_encoding: None,
+    };
+    outbound_cfg.network.encrypted_transport = true;
+    outbound_cfg.network.require_encryption = true;
+    let (outbound_stream, inbound_stream) =
+        tcp::Stream::loopback(inbound_cfg.id(), tcp::Tier::T2).await;
+    let mut outbound =
+        PeerHandle::start_endpoint(clock.clock(), outbound_cfg, outbound_stream).await;
+    let mut inbound = Stream::new(Some(Encoding::Proto), inbound_stream);
+    let handshake = match inbound.read().await.unwrap() {
+        PeerMessage::Tier2Handshake(h) => h,
+        msg => panic!("unexpected message {msg}"),
+    };
+    assert!(handshake.noise.is_some());
+    inbound
+        .write(&PeerMessage::Tier2Handshake(Handshake {
+            protocol_version: handshake.protocol_version,
+            oldest_supported_version: handshake.protocol_version,
+            sender_peer_id: inbound_cfg.id(),
+            target_peer_id: outbound.cfg.id(),
+            sender_listen_port: None,
+            sender_chain_info: chain.get_peer_chain_info(),
+            partial_edge_info: inbound_cfg
+                .partial_edge_info(&outbound.cfg.id(), handshake.partial_edge_info.nonce),
+            owned_account: None,
+            noise: None,
+        }))
+        .await;
+    assert_eq!(Err(ClosingReason::HandshakeFailed), wait_for_handshake(&mut outbound).await);
+}
@@ -1,4 +1,5 @@
 use crate::accounts_data;
+use crate::blacklist;
 use crate::client;
 use crate::concurrency::demux;
 use crate::concurrency::runtime::Runtime;
@@ -12,6 +13,7 @@ use crate::peer_manager::connection;
 use crate::peer_manager::peer_manager_actor::Event;
 use crate::peer_manager::peer_store;
 use crate::private_actix::RegisterPeerError;
+use crate::recorder;
 use crate::routing::route_back_cache::RouteBackCache;
 use crate::stats::metrics;
 use crate::store;
@@ -121,14 +123,19 @@ pub(crate) struct NetworkState {
     pub txns_since_last_block: AtomicUsize,
 
     /// Whitelisted nodes, which are allowed to connect even if the connection limit has been
-    /// reached.
-    whitelist_nodes: Vec<WhitelistNode>,
+    /// reached. It can be updated at runtime, see `set_access_lists`.
+    whitelist_nodes: ArcSwap<Vec<WhitelistNode>>,
     /// Maximal allowed number of peer connections.
-    /// It is initialized with config.max_num_peers and is mutable
-    /// only so that it can be changed in tests.
-    /// TODO(gprusak): determine why tests need to change that dynamically
-    /// in the first place.
+    /// It is initialized with config.max_num_peers and can be changed at runtime,
+    /// see `set_connection_limits`.
     pub max_num_peers: AtomicU32,
+    /// Bounds of the ideal number of connections.
+    /// Initialized with config.ideal_connections_lo and config.ideal_connections_hi,
+    /// can be changed at runtime, see `set_connection_limits`.
+    pub ideal_connections_lo: AtomicU32,
+    pub ideal_connections_hi: AtomicU32,
+    /// Recorder of the messages exchanged with peers, if enabled in the config.
+    pub recorder: Option<recorder::Recorder>,
 
     /// Mutex which prevents overlapping calls to tier1_advertise_proxies.
     tier1_advertise_proxies_mutex: tokio::sync::Mutex<()>,
@@ -149,6 +156,7 @@ impl NetworkState {
         genesis_id: GenesisId,
         client: Arc<dyn client::Client>,
         whitelist_nodes: Vec<WhitelistNode>,
+        recorder: Option<recorder::Recorder>,
     ) -> Self {
         Self {
             runtime: Runtime::new(),
@@ -173,8 +181,11 @@ impl NetworkState {
                 RECENT_ROUTED_MESSAGES_CACHE_SIZE,
             )),
             txns_since_last_block: AtomicUsize::new(0),
-            whitelist_nodes,
+            whitelist_nodes: ArcSwap::new(Arc::new(whitelist_nodes)),
             max_num_peers: AtomicU32::new(config.max_num_peers),
+            ideal_connections_lo: AtomicU32::new(config.ideal_connections_lo),
+            ideal_connections_hi: AtomicU32::new(config.ideal_connections_hi),
+            recorder,
             add_edges_demux: demux::Demux::new(config.routing_table_update_rate_limit),
             set_chain_info_mutex: Mutex::new(()),
             config,
@@ -221,12 +232,47 @@ impl NetworkState {
     /// been reached. This predicate should be evaluated AFTER the Handshake.
     pub fn is_peer_whitelisted(&self, peer_info: &PeerInfo) -> bool {
         self.whitelist_nodes
+            .load()
             .iter()
             .filter(|wn| wn.id == peer_info.id)
             .filter(|wn| Some(wn.addr) == peer_info.addr)
             .any(|wn| wn.account_id.is_none() || wn.account_id == peer_info.account_id)
     }
 
+    /// Replaces the limits on the number of connections.
+    /// Excess connections are closed gradually, by the regular connection monitoring.
+    pub fn set_connection_limits(
+        &self,
+        max_num_peers: u32,
+        ideal_connections_lo: u32,
+        ideal_connections_hi: u32,
+    ) {
+        self.max_num_peers.store(max_num_peers, Ordering::Relaxed);
+        self.ideal_connections_lo.store(ideal_connections_lo, Ordering::Relaxed);
+        self.ideal_connections_hi.store(ideal_connections_hi, Ordering::Relaxed);
+    }
+
+    /// Replaces the blacklist and the whitelisted nodes.
+    /// The ready connections to peers which are blacklisted by the new rules are closed.
+    pub fn set_access_lists(
+        &self,
+        blacklist: blacklist::Blacklist,
+        whitelist_nodes: Vec<WhitelistNode>,
+    ) {
+        if let Err(err) = self.peer_store.set_blacklist(blacklist) {
+            tracing::error!(target: "network", ?err, "Failed to remove blacklisted peers from the peer store");
+        }
+        self.whitelist_nodes.store(Arc::new(whitelist_nodes));
+        for pool in [&self.tier1, &self.tier2] {
+            for conn in pool.load().ready.values() {
+                if conn.peer_info.addr.map_or(false, |addr| self.peer_store.is_blacklisted(&addr)) {
+                    tracing::info!(target: "network", peer_info = ?conn.peer_info, "Disconnecting peer, its address has been blacklisted");
+                    conn.stop(None);
+                }
+            }
+        }
+    }
+
     /// predicate checking whether we should allow an inbound connection from peer_info.
     fn is_inbound_allowed(&self, peer_info: &PeerInfo) -> bool {
         // Check if we have spare inbound connections capacity.
@@ -1,3 +1,4 @@
+use crate::blacklist;
 use crate::client;
 use crate::config;
 use crate::debug::{DebugStatus, GetDebugStatus};
@@ -9,6 +10,7 @@ use crate::peer::peer_actor::PeerActor;
 use crate::peer_manager::connection;
 use crate::peer_manager::network_state::{NetworkState, WhitelistNode};
 use crate::peer_manager::peer_store;
+use crate::recorder;
 use crate::stats::metrics;
 use crate::store;
 use crate::tcp;
@@ -159,6 +161,25 @@ impl actix::Actor for PeerManagerActor {
     }
 }
 
+/// Parses the access lists updated at runtime.
+/// Lists which are not set default to the ones from the config.
+fn parse_access_lists(
+    config: &config::NetworkConfig,
+    lists: &near_dyn_configs::NetworkAccessLists,
+) -> anyhow::Result<(blacklist::Blacklist, Vec<WhitelistNode>)> {
+    let blacklist = match &lists.blacklist {
+        Some(blacklist) => config::parse_blacklist(blacklist)?,
+        None => config.peer_store.blacklist.clone(),
+    };
+    let whitelist_nodes = match &lists.whitelist_nodes {
+        Some(whitelist_nodes) => config::parse_whitelist_nodes(whitelist_nodes)?,
+        None => config.whitelist_nodes.clone(),
+    };
+    let whitelist_nodes =
+        whitelist_nodes.iter().map(WhitelistNode::from_peer_info).collect::<anyhow::Result<_>>()?;
+    Ok((blacklist, whitelist_nodes))
+}
+
 impl PeerManagerActor {
     pub fn spawn(
         clock: time::Clock,
@@ -186,6 +207,10 @@ impl PeerManagerActor {
             v
         };
         let my_peer_id = config.node_id();
+        let recorder = (config.traffic_recorder.clone())
+            .map(|cfg| recorder::Recorder::new(cfg, my_peer_id.clone()))
+            .transpose()
+            .context("traffic recorder")?;
         let arbiter = actix::Arbiter::new().handle();
         let clock = clock.clone();
         let state = Arc::new(NetworkState::new(
@@ -196,6 +221,7 @@ impl PeerManagerActor {
             genesis_id,
             client,
             whitelist_nodes,
+            recorder,
         ));
         arbiter.spawn({
             let arbiter = arbiter.clone();
@@ -261,6 +287,42 @@ impl PeerManagerActor {
                         }
                     });
                 }
+                // Apply the connection limits updated at runtime.
+                arbiter.spawn({
+                    let state = state.clone();
+                    let mut updates = near_dyn_configs::UPDATEABLE_CONFIGS.subscribe();
+                    async move {
+                        while updates.changed().await.is_ok() {
+                            let configs = updates.borrow().clone();
+                            if let Some(configs) = configs {
+                                state.set_connection_limits(
+                                    configs.max_num_peers,
+                                    configs.ideal_connections_lo,
+                                    configs.ideal_connections_hi,
+                                );
+                            }
+                        }
+                    }
+                });
+                // Apply the network access lists updated at runtime.
+                arbiter.spawn({
+                    let state = state.clone();
+                    let mut updates = near_dyn_configs::NETWORK_ACCESS_LISTS.subscribe();
+                    async move {
+                        while updates.changed().await.is_ok() {
+                            let lists = updates.borrow().clone();
+                            match parse_access_lists(&state.config, &lists) {
+                                Ok((blacklist, whitelist_nodes)) => {
+                                    tracing::info!(target: "network", ?blacklist, "Updating network access lists");
+                                    state.set_access_lists(blacklist, whitelist_nodes);
+                                }
+                                Err(err) => {
+                                    tracing::warn!(target: "network", ?err, "Ignoring invalid network access lists")
+                                }
+                            }
+                        }
+                    }
+                });
             }
         });
         Ok(Self::start_in_arbiter(&arbiter, move |_ctx| Self {
@@ -325,7 +387,7 @@ impl PeerManagerActor {
             tier2.ready.values().filter(|peer| peer.peer_type == PeerType::Outbound).count()
                 + tier2.outbound_handshakes.len();
 
-        (total_connections < self.state.config.ideal_connections_lo as usize
+        (total_connections < self.state.ideal_connections_lo.load(Ordering::Relaxed) as usize
             || (total_connections < self.state.max_num_peers.load(Ordering::Relaxed) as usize
                 && potential_outbound_connections
                     < self.state.config.minimum_outbound_peers as usize))
@@ -419,7 +481,8 @@ impl PeerManagerActor {
         safe_set.extend(whitelisted_peers);
 
         // If there is not enough non-whitelisted peers, return without disconnecting anyone.
-        if tier2.ready.len() - safe_set.len() <= self.state.config.ideal_connections_hi as usize {
+        let ideal_connections_hi = self.state.ideal_connections_hi.load(Ordering::Relaxed);
+        if tier2.ready.len() - safe_set.len() <= ideal_connections_hi as usize {
             return;
         }
 
@@ -469,7 +532,7 @@ impl PeerManagerActor {
         if let Some(p) = candidates.choose(&mut rand::thread_rng()) {
             tracing::debug!(target: "network", id = ?p.peer_info.id,
                 tier2_len = tier2.ready.len(),
-                ideal_connections_hi = self.state.config.ideal_connections_hi,
+                ideal_connections_hi,
                 "Stop active connection"
             );
             p.stop(None);
@@ -703,6 +766,27 @@ impl PeerManagerActor {
                     NetworkResponses::RouteNotFound
                 }
             }
+            NetworkRequests::EpochSyncRequest { epoch_id, peer_id } => {
+                if self
+                    .state
+                    .tier2
+                    .send_message(peer_id, Arc::new(PeerMessage::EpochSyncRequest(epoch_id)))
+                {
+                    NetworkResponses::NoResponse
+                } else {
+                    NetworkResponses::RouteNotFound
+                }
+            }
+            NetworkRequests::EpochSyncFinalizationRequest { epoch_id, peer_id } => {
+                if self.state.tier2.send_message(
+                    peer_id,
+                    Arc::new(PeerMessage::EpochSyncFinalizationRequest(epoch_id)),
+                ) {
+                    NetworkResponses::NoResponse
+                } else {
+                    NetworkResponses::RouteNotFound
+                }
+            }
             NetworkRequests::StateRequestHeader { shard_id, sync_hash, target } => {
                 if self.send_message_to_account_or_peer_or_hash(
                     &target,
@@ -405,6 +405,24 @@ impl PeerStore {
         self.0.lock().config.blacklist.contains(*addr)
     }
 
+    /// Replaces the blacklist and removes the peers which are blacklisted by it.
+    /// Boot nodes are kept, as they are when the store is loaded.
+    pub fn set_blacklist(&self, blacklist: blacklist::Blacklist) -> anyhow::Result<()> {
+        let mut inner = self.0.lock();
+        let to_delete: Vec<_> = (inner.peer_states.iter())
+            .filter(|(peer_id, state)| {
+                !inner.boot_nodes.contains(*peer_id)
+                    && state.peer_info.addr.map_or(false, |addr| blacklist.contains(addr))
+            })
+            .map(|(peer_id, _)| peer_id.clone())
+            .collect();
+        for peer_id in &to_delete {
+            tracing::info!(target: "network", "Removing {} because address is blacklisted", peer_id);
+        }
+        inner.config.blacklist = blacklist;
+        inner.delete_peers(&to_delete)
+    }
+
     pub fn len(&self) -> usize {
         self.0.lock().peer_states.len()
     }
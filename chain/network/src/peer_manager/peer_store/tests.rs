@@ -494,8 +494,10 @@ fn check_ignore_blacklisted_peers() {
 
     // Blacklist one of the existing peers and one new peer.
     {
-        let blacklist: blacklist::Blacklist =
-            ["127.0.0.1:2", "127.0.0.1:5"].iter().map(|e| e.parse().unwrap()).collect();
+        let blacklist: blacklist::Blacklist = ["127.0.0.1:2", "127.0.0.1:5"]
+            .iter()
+            .map(|e| e.parse::<blacklist::Entry>().unwrap())
+            .collect();
         let peer_store =
             PeerStore::new(&clock.clock(), make_config(&[], blacklist, false), store).unwrap();
         // Peer 127.0.0.1:2 is removed since it's blacklisted.
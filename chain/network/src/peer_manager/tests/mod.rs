@@ -1,5 +1,6 @@
 mod accounts_data;
 mod connection_pool;
 mod nonce;
+mod rate_limits;
 mod routing;
 mod tier1;
@@ -0,0 +1,119 @@
+use crate::concurrency::rate;
+use crate::config;
+use crate::network_protocol::testonly as data;
+use crate::network_protocol::PeerMessage;
+use crate::peer::peer_actor::ClosingReason;
+use crate::peer_manager;
+use crate::peer_manager::peer_manager_actor::Event as PME;
+use crate::peer_manager::testonly::Event;
+use crate::testonly::fake_client;
+use crate::testonly::make_rng;
+use crate::time;
+use crate::types::ReasonForBan;
+use near_o11y::testonly::init_test_logger;
+use std::sync::Arc;
+
+fn block_request_limits(ban: bool) -> config::InboundRateLimits {
+    config::InboundRateLimits {
+        ban,
+        messages: [(
+            "BlockRequest",
+            config::MessageRateLimits {
+                messages: Some(rate::Limit { burst: 2, qps: 1. }),
+                bytes: None,
+            },
+        )]
+        .into(),
+    }
+}
+
+#[tokio::test]
+async fn inbound_rate_limit_drop() {
+    init_test_logger();
+    let mut rng = make_rng(921853233);
+    let rng = &mut rng;
+    let mut clock = time::FakeClock::default();
+    let chain = Arc::new(data::Chain::make(&mut clock, rng, 10));
+
+    let mut cfg = chain.make_config(rng);
+    cfg.inbound_rate_limits = block_request_limits(false);
+    let pm = peer_manager::testonly::start(
+        clock.clock(),
+        near_store::db::TestDB::new(),
+        cfg,
+        chain.clone(),
+    )
+    .await;
+    let peer = pm
+        .start_inbound(chain.clone(), chain.make_config(rng))
+        .await
+        .handshake(&clock.clock())
+        .await;
+
+    tracing::info!(target:"test", "exceed the burst, then send a message of another type");
+    let hashes: Vec<_> = (0..3).map(|_| data::make_hash(rng)).collect();
+    let tx = data::make_signed_transaction(rng);
+    let mut events = pm.events.from_now();
+    for h in &hashes {
+        peer.send(PeerMessage::BlockRequest(*h)).await;
+    }
+    peer.send(PeerMessage::Transaction(tx.clone())).await;
+
+    tracing::info!(target:"test", "the message exceeding the burst should be dropped");
+    let mut got = vec![];
+    loop {
+        match events
+            .recv_until(|ev| match ev {
+                Event::Client(ev @ fake_client::Event::BlockRequest(_)) => Some(ev),
+                Event::Client(ev @ fake_client::Event::Transaction(_)) => Some(ev),
+                Event::PeerManager(PME::ConnectionClosed(_)) => panic!("connection closed"),
+                _ => None,
+            })
+            .await
+        {
+            fake_client::Event::BlockRequest(h) => got.push(h),
+            ev => {
+                assert_eq!(fake_client::Event::Transaction(tx), ev);
+                break;
+            }
+        }
+    }
+    assert_eq!(hashes[..2], got);
+}
+
+#[tokio::test]
+async fn inbound_rate_limit_ban() {
+    init_test_logger();
+    let mut rng = make_rng(921853233);
+    let rng = &mut rng;
+    let mut clock = time::FakeClock::default();
+    let chain = Arc::new(data::Chain::make(&mut clock, rng, 10));
+
+    let mut cfg = chain.make_config(rng);
+    cfg.inbound_rate_limits = block_request_limits(true);
+    let pm = peer_manager::testonly::start(
+        clock.clock(),
+        near_store::db::TestDB::new(),
+        cfg,
+        chain.clone(),
+    )
+    .await;
+    let peer = pm
+        .start_inbound(chain.clone(), chain.make_config(rng))
+        .await
+        .handshake(&clock.clock())
+        .await;
+
+    tracing::info!(target:"test", "exceed the burst, expect the peer to be banned");
+    let mut events = pm.events.from_now();
+    for _ in 0..3 {
+        peer.send(PeerMessage::BlockRequest(data::make_hash(rng))).await;
+    }
+    let reason = events
+        .recv_until(|ev| match ev {
+            Event::PeerManager(PME::ConnectionClosed(ev)) => Some(ev.reason),
+            _ => None,
+        })
+        .await;
+    assert_eq!(ClosingReason::Ban(ReasonForBan::Abusive), reason);
+}
@@ -1,5 +1,6 @@
 use crate::blacklist;
 use crate::broadcast;
+use crate::config;
 use crate::config::NetworkConfig;
 use crate::network_protocol::testonly as data;
 use crate::network_protocol::{Edge, Encoding, Ping, Pong, RoutedMessageBody, RoutingTableUpdate};
@@ -777,6 +778,38 @@ async fn blacklist_all() {
     pm1.wait_for_routing_table(&[]).await;
 }
 
+// test node 0 blacklisting node 1 at runtime, while they are connected
+#[tokio::test]
+async fn blacklist_reload() {
+    init_test_logger();
+    let mut rng = make_rng(921853233);
+    let rng = &mut rng;
+    let mut clock = time::FakeClock::default();
+    let chain = Arc::new(data::Chain::make(&mut clock, rng, 10));
+
+    let cfgs = make_configs(&chain, rng, 2, 0, false);
+    let pm0 = start_pm(clock.clock(), TestDB::new(), cfgs[0].clone(), chain.clone()).await;
+    let pm1 = start_pm(clock.clock(), TestDB::new(), cfgs[1].clone(), chain.clone()).await;
+    let id1 = pm1.cfg.node_id();
+
+    tracing::info!(target:"test", "connect 0 to 1");
+    pm0.connect_to(&pm1.peer_info(), tcp::Tier::T2).await;
+    pm0.wait_for_direct_connection(id1.clone()).await;
+
+    tracing::info!(target:"test", "blacklist the localhost subnet on node 0");
+    let mut events = pm0.events.from_now();
+    let blacklist = config::parse_blacklist(&["127.0.0.0/8".to_string()]).unwrap();
+    pm0.with_state(move |s| async move {
+        s.set_access_lists(blacklist, vec![]);
+        assert!(s.peer_store.get_peer_state(&id1).is_none());
+    })
+    .await;
+
+    tracing::info!(target:"test", "wait for the connection to be closed");
+    wait_for_connection_closed(&mut events, ClosingReason::PeerManagerRequest).await;
+    pm0.wait_for_routing_table(&[]).await;
+}
+
 // Spawn 3 nodes with max peers configured to 2, then allow them to connect to each other in a triangle.
 // Spawn a fourth node and see it fail to connect since the first three are at max capacity.
 #[tokio::test]
@@ -156,6 +156,7 @@ impl Connection {
                 &self.secret_key,
             ),
             owned_account: None,
+            noise: None,
         });
 
         self.write_message(&handshake).await.map_err(ConnectError::IO)?;
@@ -0,0 +1,461 @@
+//! Opt-in recorder of the network traffic, for debugging.
+//!
+//! When enabled, every message sent or received by a PeerActor is appended to a file
+//! in the configured directory, together with a timestamp, the id of the peer and
+//! the direction of the message. Once the current file reaches `max_file_size`,
+//! a new one is started and the oldest files exceeding `max_files` are deleted.
+//!
+//! A recording can be read back with `read()` and fed to a client with `replay()`,
+//! which is what the mock node does to reproduce network incidents locally.
+//! The records are replayed one by one, in the recorded order.
+//!
+//! File format: a borsh-encoded `FileHeader`, followed by records. Every record is a
+//! borsh-encoded `RawRecord`, prefixed with its length (u32, little endian).
+//! Messages are stored in the protobuf encoding, regardless of the encoding used
+//! on the wire.
+use crate::client;
+use crate::network_protocol::{Encoding, PeerIdOrHash, PeerMessage, RoutedMessageBody};
+use crate::peer::peer_actor::PeerActor;
+use crate::stats::metrics;
+use crate::time;
+use anyhow::Context as _;
+use borsh::{BorshDeserialize, BorshSerialize};
+use near_primitives::network::PeerId;
+use std::io::Write as _;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+
+/// Extension of the recording files.
+const FILE_EXTENSION: &str = "rec";
+
+/// Version of the file format, stored in the FileHeader.
+const FORMAT_VERSION: u32 = 2;
+
+/// Maximal number of records waiting to be written to disk.
+/// If the writer falls behind, new records are dropped.
+const QUEUE_SIZE: usize = 10000;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Config {
+    /// Directory to store the recording in.
+    pub dir: PathBuf,
+    /// A new file is started once the current one reaches this size (in bytes).
+    pub max_file_size: u64,
+    /// Number of files to keep. The oldest files are deleted.
+    pub max_files: usize,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Direction {
+    Inbound,
+    Outbound,
+}
+
+#[derive(BorshSerialize, BorshDeserialize)]
+struct FileHeader {
+    version: u32,
+    /// Id of the node which made the recording.
+    node_id: PeerId,
+}
+
+#[derive(BorshSerialize, BorshDeserialize)]
+struct RawRecord {
+    /// UNIX timestamp in nanoseconds.
+    time: i128,
+    peer_id: Option<PeerId>,
+    direction: Direction,
+    was_requested: bool,
+    /// Message in the protobuf encoding.
+    message: Vec<u8>,
+}
+
+/// A recorded message.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Record {
+    pub time: time::Utc,
+    /// Peer which the message was sent to or received from.
+    /// It is None for inbound connections until the handshake is received.
+    pub peer_id: Option<PeerId>,
+    pub direction: Direction,
+    /// Whether the message was a response to a request of the recording node.
+    /// Tracked only for the inbound blocks.
+    pub was_requested: bool,
+    pub message: PeerMessage,
+}
+
+/// Messages recorded by a single node, in the order they were recorded.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Recording {
+    pub node_id: PeerId,
+    pub records: Vec<Record>,
+}
+
+/// Returns the recording files in `dir`, ordered from the oldest.
+fn list_files(dir: &Path) -> std::io::Result<Vec<(u64, PathBuf)>> {
+    let mut files = vec![];
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.extension().map_or(true, |ext| ext != FILE_EXTENSION) {
+            continue;
+        }
+        if let Some(index) = path.file_stem().and_then(|s| s.to_str()?.parse().ok()) {
+            files.push((index, path));
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+struct Writer {
+    cfg: Config,
+    node_id: PeerId,
+    next_index: u64,
+    /// Current file and its size.
+    file: Option<(std::io::BufWriter<std::fs::File>, u64)>,
+}
+
+impl Writer {
+    fn new(cfg: Config, node_id: PeerId) -> anyhow::Result<Self> {
+        std::fs::create_dir_all(&cfg.dir)?;
+        // Continue the numbering of the files left by the previous run.
+        let next_index = list_files(&cfg.dir)?.last().map_or(0, |(index, _)| index + 1);
+        Ok(Self { cfg, node_id, next_index, file: None })
+    }
+
+    /// Starts a new file and deletes the oldest ones.
+    fn rotate(&mut self) -> anyhow::Result<()> {
+        self.flush()?;
+        let path = self.cfg.dir.join(format!("{:010}.{FILE_EXTENSION}", self.next_index));
+        self.next_index += 1;
+        let mut file = std::io::BufWriter::new(std::fs::File::create(&path)?);
+        let header = FileHeader { version: FORMAT_VERSION, node_id: self.node_id.clone() };
+        let header = header.try_to_vec()?;
+        file.write_all(&header)?;
+        self.file = Some((file, header.len() as u64));
+        for (_, path) in list_files(&self.cfg.dir)?.iter().rev().skip(self.cfg.max_files) {
+            std::fs::remove_file(path)?;
+        }
+        Ok(())
+    }
+
+    fn write(&mut self, record: &RawRecord) -> anyhow::Result<()> {
+        let data = record.try_to_vec()?;
+        let len = (4 + data.len()) as u64;
+        let full = match &self.file {
+            None => true,
+            // Every file contains at least one record, even if it exceeds max_file_size.
+            Some((_, size)) => size + len > self.cfg.max_file_size,
+        };
+        if full {
+            self.rotate()?;
+        }
+        let (file, size) = self.file.as_mut().unwrap();
+        file.write_all(&(data.len() as u32).to_le_bytes())?;
+        file.write_all(&data)?;
+        *size += len;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> anyhow::Result<()> {
+        if let Some((file, _)) = &mut self.file {
+            file.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Writes the received records until the channel is closed.
+    /// The file is flushed whenever the queue is empty.
+    fn run(mut self, queue: mpsc::Receiver<RawRecord>) {
+        loop {
+            let record = match queue.try_recv() {
+                Ok(record) => record,
+                Err(mpsc::TryRecvError::Empty) => {
+                    if let Err(err) = self.flush() {
+                        tracing::error!(target: "network", ?err, "Failed to flush the traffic recording");
+                    }
+                    match queue.recv() {
+                        Ok(record) => record,
+                        Err(mpsc::RecvError) => break,
+                    }
+                }
+                Err(mpsc::TryRecvError::Disconnected) => break,
+            };
+            if let Err(err) = self.write(&record) {
+                metrics::TRAFFIC_RECORDER_DROPPED_RECORDS.inc();
+                tracing::error!(target: "network", ?err, "Failed to write the traffic recording");
+            }
+        }
+        if let Err(err) = self.flush() {
+            tracing::error!(target: "network", ?err, "Failed to flush the traffic recording");
+        }
+    }
+}
+
+/// Handle to the background thread writing the recording.
+/// The thread exits once the Recorder is dropped.
+pub(crate) struct Recorder {
+    queue: mpsc::SyncSender<RawRecord>,
+}
+
+impl Recorder {
+    pub fn new(cfg: Config, node_id: PeerId) -> anyhow::Result<Self> {
+        anyhow::ensure!(cfg.max_file_size > 0, "max_file_size has to be positive");
+        anyhow::ensure!(cfg.max_files > 0, "max_files has to be positive");
+        let dir = cfg.dir.clone();
+        let writer = Writer::new(cfg, node_id)
+            .with_context(|| format!("failed to open traffic recording dir {}", dir.display()))?;
+        let (send, recv) = mpsc::sync_channel(QUEUE_SIZE);
+        std::thread::Builder::new()
+            .name("traffic_recorder".to_string())
+            .spawn(move || writer.run(recv))?;
+        Ok(Self { queue: send })
+    }
+
+    /// Enqueues the message to be written to the recording.
+    pub fn record(
+        &self,
+        clock: &time::Clock,
+        peer_id: Option<&PeerId>,
+        direction: Direction,
+        was_requested: bool,
+        msg: &PeerMessage,
+    ) {
+        let record = RawRecord {
+            time: clock.now_utc().unix_timestamp_nanos(),
+            peer_id: peer_id.cloned(),
+            direction,
+            was_requested,
+            message: msg.serialize(Encoding::Proto),
+        };
+        if self.queue.try_send(record).is_err() {
+            metrics::TRAFFIC_RECORDER_DROPPED_RECORDS.inc();
+        }
+    }
+}
+
+fn read_file(path: &Path) -> anyhow::Result<Recording> {
+    let data = std::fs::read(path)?;
+    let mut data = &data[..];
+    let header = FileHeader::deserialize(&mut data).context("header")?;
+    anyhow::ensure!(
+        header.version == FORMAT_VERSION,
+        "unsupported format version {}",
+        header.version
+    );
+    let mut records = vec![];
+    while data.len() >= 4 {
+        let len = u32::from_le_bytes(data[..4].try_into().unwrap()) as usize;
+        let raw = match data.get(4..4 + len) {
+            Some(raw) => RawRecord::try_from_slice(raw).context("record")?,
+            None => break,
+        };
+        data = &data[4 + len..];
+        records.push(Record {
+            time: time::Utc::from_unix_timestamp_nanos(raw.time)?,
+            peer_id: raw.peer_id,
+            direction: raw.direction,
+            was_requested: raw.was_requested,
+            message: PeerMessage::deserialize(Encoding::Proto, &raw.message)?,
+        });
+    }
+    if !data.is_empty() {
+        // The node has been probably killed while writing the last record.
+        tracing::warn!(target: "network", path = %path.display(), "Ignoring truncated record at the end of the file");
+    }
+    Ok(Recording { node_id: header.node_id, records })
+}
+
+/// Reads a recording, which is either a single file or a directory written by the recorder.
+pub fn read(path: &Path) -> anyhow::Result<Recording> {
+    if !path.is_dir() {
+        return read_file(path).with_context(|| format!("failed to read {}", path.display()));
+    }
+    let mut recording: Option<Recording> = None;
+    for (_, path) in list_files(path)? {
+        let file =
+            read_file(&path).with_context(|| format!("failed to read {}", path.display()))?;
+        match &mut recording {
+            None => recording = Some(file),
+            Some(recording) => {
+                anyhow::ensure!(
+                    recording.node_id == file.node_id,
+                    "{} was recorded by a different node",
+                    path.display()
+                );
+                recording.records.extend(file.records);
+            }
+        }
+    }
+    recording.with_context(|| format!("no recording files in {}", path.display()))
+}
+
+/// Delivers a recorded inbound message to the client, the same way PeerActor does it.
+/// `node_id` is the id of the node which made the recording.
+/// Returns once the client has processed the message, so that replaying the records
+/// one after another preserves their order.
+///
+/// Outbound messages, messages handled by the network layer itself (handshakes,
+/// routing table sync, pings, etc.) and routed messages addressed to other nodes
+/// are skipped. Responses to routed requests are always delivered, since the route
+/// back information is not recorded. The responses of the client are discarded.
+pub async fn replay(
+    clock: &time::Clock,
+    client: &dyn client::Client,
+    node_id: &PeerId,
+    record: Record,
+) {
+    let peer_id = match (record.direction, record.peer_id) {
+        (Direction::Inbound, Some(peer_id)) => peer_id,
+        _ => return,
+    };
+    match record.message {
+        PeerMessage::Routed(msg) => {
+            if let PeerIdOrHash::PeerId(target) = &msg.target {
+                if target != node_id {
+                    return;
+                }
+            }
+            if let RoutedMessageBody::Ping(_) | RoutedMessageBody::Pong(_) = &msg.body {
+                return;
+            }
+            let msg_hash = msg.hash();
+            let _ =
+                PeerActor::receive_routed_message(clock, client, peer_id, msg_hash, msg.msg.body)
+                    .await;
+        }
+        PeerMessage::BlockRequest(hash) => {
+            client.block_request(hash).await;
+        }
+        PeerMessage::BlockHeadersRequest(hashes) => {
+            client.block_headers_request(hashes).await;
+        }
+        PeerMessage::Block(block) => client.block(block, peer_id, record.was_requested).await,
+        PeerMessage::Transaction(transaction) => {
+            client.transaction(transaction, /*is_forwarded=*/ false).await
+        }
+        PeerMessage::BlockHeaders(headers) => {
+            let _ = client.block_headers(headers, peer_id).await;
+        }
+        PeerMessage::Challenge(challenge) => client.challenge(challenge).await,
+        PeerMessage::EpochSyncRequest(epoch_id) => {
+            client.epoch_sync_request(epoch_id).await;
+        }
+        PeerMessage::EpochSyncResponse(resp) => client.epoch_sync_response(resp, peer_id).await,
+        PeerMessage::EpochSyncFinalizationRequest(epoch_id) => {
+            client.epoch_sync_finalization_request(epoch_id).await;
+        }
+        PeerMessage::EpochSyncFinalizationResponse(resp) => {
+            client.epoch_sync_finalization_response(resp, peer_id).await
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::broadcast;
+    use crate::network_protocol::testonly as data;
+    use crate::testonly::fake_client;
+    use crate::testonly::make_rng;
+
+    /// Waits until the recording in `dir` satisfies `pred`.
+    /// The records are written asynchronously by the writer thread.
+    fn wait_for(dir: &Path, pred: impl Fn(&Recording) -> bool) -> Recording {
+        for _ in 0..100 {
+            if let Ok(r) = read(dir) {
+                if pred(&r) {
+                    return r;
+                }
+            }
+            std::thread::sleep(std::time::Duration::from_millis(50));
+        }
+        panic!("recording not written");
+    }
+
+    #[test]
+    fn test_record_and_read() {
+        let mut rng = make_rng(921853233);
+        let rng = &mut rng;
+        let mut clock = time::FakeClock::default();
+        let chain = data::Chain::make(&mut clock, rng, 3);
+        let dir = tempfile::tempdir().unwrap();
+        let node_id = data::make_peer_id(rng);
+        let peer_id = data::make_peer_id(rng);
+        let cfg = Config { dir: dir.path().to_path_buf(), max_file_size: 1, max_files: 2 };
+
+        let msgs: Vec<_> = chain.blocks.iter().map(|b| PeerMessage::Block(b.clone())).collect();
+        let recorder = Recorder::new(cfg.clone(), node_id.clone()).unwrap();
+        let mut want = vec![];
+        for (i, msg) in msgs.iter().enumerate() {
+            let direction = if i % 2 == 0 { Direction::Inbound } else { Direction::Outbound };
+            recorder.record(&clock.clock(), Some(&peer_id), direction, i % 3 == 0, msg);
+            want.push(Record {
+                time: clock.now_utc(),
+                peer_id: Some(peer_id.clone()),
+                direction,
+                was_requested: i % 3 == 0,
+                message: msg.clone(),
+            });
+            clock.advance(time::Duration::seconds(1));
+        }
+        drop(recorder);
+        // max_file_size is tiny, so that every record gets its own file,
+        // and only the last max_files of them are kept.
+        let got = wait_for(dir.path(), |r| {
+            r.records.len() == cfg.max_files && r.records.last() == want.last()
+        });
+        assert_eq!(cfg.max_files, list_files(dir.path()).unwrap().len());
+        assert_eq!(node_id, got.node_id);
+        assert_eq!(want[want.len() - cfg.max_files..], got.records[..]);
+
+        // A restarted recorder continues the numbering of the files.
+        let recorder = Recorder::new(cfg.clone(), node_id.clone()).unwrap();
+        recorder.record(&clock.clock(), None, Direction::Inbound, false, &msgs[0]);
+        drop(recorder);
+        let got = wait_for(dir.path(), |r| r.records.last().unwrap().peer_id.is_none());
+        assert_eq!(msgs[0], got.records.last().unwrap().message);
+        assert_eq!(msgs.len() as u64, list_files(dir.path()).unwrap().last().unwrap().0);
+    }
+
+    #[tokio::test]
+    async fn test_replay() {
+        let mut rng = make_rng(921853233);
+        let rng = &mut rng;
+        let mut clock = time::FakeClock::default();
+        let chain = data::Chain::make(&mut clock, rng, 3);
+        let dir = tempfile::tempdir().unwrap();
+        let node_id = data::make_peer_id(rng);
+        let peer_id = data::make_peer_id(rng);
+        let cfg = Config { dir: dir.path().to_path_buf(), max_file_size: 1 << 20, max_files: 1 };
+
+        let b = &chain.blocks;
+        let recorder = Recorder::new(cfg, node_id.clone()).unwrap();
+        let record = |direction, was_requested, msg| {
+            recorder.record(&clock.clock(), Some(&peer_id), direction, was_requested, &msg)
+        };
+        record(Direction::Outbound, false, PeerMessage::BlockRequest(*b[1].hash()));
+        record(Direction::Inbound, false, PeerMessage::Block(b[2].clone()));
+        record(Direction::Inbound, true, PeerMessage::Block(b[1].clone()));
+        record(Direction::Inbound, false, PeerMessage::BlockHeadersRequest(vec![*b[0].hash()]));
+        record(Direction::Outbound, false, PeerMessage::Block(b[0].clone()));
+        record(Direction::Inbound, false, PeerMessage::BlockHeaders(chain.get_block_headers()));
+        drop(recorder);
+        let recording = wait_for(dir.path(), |r| {
+            matches!(r.records.last().unwrap().message, PeerMessage::BlockHeaders(_))
+        });
+        assert_eq!(6, recording.records.len());
+
+        let (send, mut recv) = broadcast::unbounded_channel();
+        let client = fake_client::Fake { event_sink: send.sink() };
+        for record in recording.records {
+            replay(&clock.clock(), &client, &recording.node_id, record).await;
+        }
+        // Only the inbound messages are delivered, in the recorded order.
+        assert_eq!(fake_client::Event::Block(b[2].clone(), false), recv.recv().await);
+        assert_eq!(fake_client::Event::Block(b[1].clone(), true), recv.recv().await);
+        assert_eq!(fake_client::Event::BlockHeadersRequest(vec![*b[0].hash()]), recv.recv().await);
+        assert_eq!(fake_client::Event::BlockHeaders(chain.get_block_headers()), recv.recv().await);
+        assert_eq!(None, recv.try_recv());
+    }
+}
@@ -59,13 +59,19 @@ impl Drop for GaugePoint {
 pub struct Connection {
     pub type_: PeerType,
     pub encoding: Option<Encoding>,
+    /// Whether the encrypted transport has been negotiated for the connection.
+    pub encrypted: bool,
 }
 
 impl Labels for Connection {
-    type Array = [&'static str; 2];
-    const NAMES: Self::Array = ["peer_type", "encoding"];
+    type Array = [&'static str; 3];
+    const NAMES: Self::Array = ["peer_type", "encoding", "transport"];
     fn values(&self) -> Self::Array {
-        [self.type_.into(), self.encoding.map(|e| e.into()).unwrap_or("unknown")]
+        [
+            self.type_.into(),
+            self.encoding.map(|e| e.into()).unwrap_or("unknown"),
+            if self.encrypted { "encrypted" } else { "plaintext" },
+        ]
     }
 }
 
@@ -152,6 +158,20 @@ pub(crate) static PEER_DATA_READ_BUFFER_SIZE: Lazy<IntGaugeVec> = Lazy::new(|| {
     )
     .unwrap()
 });
+pub(crate) static PEER_PLAINTEXT_FRAMES_DROPPED: Lazy<IntCounter> = Lazy::new(|| {
+    try_create_int_counter(
+        "near_peer_plaintext_frames_dropped",
+        "Number of plaintext frames dropped because they were received after the encrypted transport had been established",
+    )
+    .unwrap()
+});
+pub(crate) static TRAFFIC_RECORDER_DROPPED_RECORDS: Lazy<IntCounter> = Lazy::new(|| {
+    try_create_int_counter(
+        "near_traffic_recorder_dropped_records",
+        "Number of messages which the traffic recorder failed to write",
+    )
+    .unwrap()
+});
 pub(crate) static PEER_DATA_WRITE_BUFFER_SIZE: Lazy<IntGaugeVec> = Lazy::new(|| {
     try_create_int_gauge_vec(
         "near_peer_write_buffer_size",
@@ -287,6 +307,15 @@ pub(crate) static ROUTED_MESSAGE_DROPPED: Lazy<IntCounterVec> = Lazy::new(|| {
     .unwrap()
 });
 
+pub(crate) static PEER_MESSAGE_RATE_LIMITED: Lazy<IntCounterVec> = Lazy::new(|| {
+    try_create_int_counter_vec(
+        "near_peer_message_rate_limited",
+        "Number of received messages exceeding the inbound rate limits, by message type and the action taken",
+        &["type", "action"],
+    )
+    .unwrap()
+});
+
 pub(crate) static PEER_REACHABLE: Lazy<IntGauge> = Lazy::new(|| {
     try_create_int_gauge(
         "near_peer_reachable",
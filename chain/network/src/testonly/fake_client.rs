@@ -10,7 +10,10 @@ use near_primitives::challenge::Challenge;
 use near_primitives::hash::CryptoHash;
 use near_primitives::network::{AnnounceAccount, PeerId};
 use near_primitives::sharding::{ChunkHash, PartialEncodedChunk, PartialEncodedChunkPart};
-use near_primitives::syncing::{ShardStateSyncResponse, ShardStateSyncResponseV2};
+use near_primitives::syncing::{
+    EpochSyncFinalizationResponse, EpochSyncResponse, ShardStateSyncResponse,
+    ShardStateSyncResponseV2,
+};
 use near_primitives::transaction::SignedTransaction;
 use near_primitives::types::{AccountId, EpochId, ShardId};
 use near_primitives::views::FinalExecutionOutcomeView;
@@ -18,7 +21,8 @@ use near_primitives::views::FinalExecutionOutcomeView;
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub enum Event {
     AnnounceAccount(Vec<(AnnounceAccount, Option<EpochId>)>),
-    Block(Block),
+    /// Block and whether it was requested.
+    Block(Block, bool),
     BlockHeaders(Vec<BlockHeader>),
     BlockApproval(Approval, PeerId),
     BlockHeadersRequest(Vec<CryptoHash>),
@@ -26,6 +30,8 @@ pub enum Event {
     Challenge(Challenge),
     Chunk(Vec<PartialEncodedChunkPart>),
     ChunkRequest(ChunkHash),
+    EpochSyncFinalizationRequest(EpochId),
+    EpochSyncRequest(EpochId),
     Transaction(SignedTransaction),
 }
 
@@ -116,8 +122,8 @@ impl client::Client for Fake {
         None
     }
 
-    async fn block(&self, block: Block, _peer_id: PeerId, _was_requested: bool) {
-        self.event_sink.push(Event::Block(block));
+    async fn block(&self, block: Block, _peer_id: PeerId, was_requested: bool) {
+        self.event_sink.push(Event::Block(block, was_requested));
     }
 
     async fn block_headers(
@@ -133,6 +139,31 @@ impl client::Client for Fake {
         self.event_sink.push(Event::Challenge(challenge));
     }
 
+    async fn epoch_sync_request(&self, epoch_id: EpochId) -> Option<Box<EpochSyncResponse>> {
+        self.event_sink.push(Event::EpochSyncRequest(epoch_id));
+        None
+    }
+
+    async fn epoch_sync_response(&self, _resp: Box<EpochSyncResponse>, _peer_id: PeerId) {
+        unimplemented!();
+    }
+
+    async fn epoch_sync_finalization_request(
+        &self,
+        epoch_id: EpochId,
+    ) -> Option<Box<EpochSyncFinalizationResponse>> {
+        self.event_sink.push(Event::EpochSyncFinalizationRequest(epoch_id));
+        None
+    }
+
+    async fn epoch_sync_finalization_response(
+        &self,
+        _resp: Box<EpochSyncFinalizationResponse>,
+        _peer_id: PeerId,
+    ) {
+        unimplemented!();
+    }
+
     async fn network_info(&self, _info: NetworkInfo) {}
 
     async fn announce_account(
@@ -4,17 +4,25 @@ use bytes::BytesMut;
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 
 use crate::network_protocol::{Encoding, PeerMessage};
+use crate::peer::noise;
 use crate::tcp;
 
 pub struct Stream {
     stream: tcp::Stream,
     force_encoding: Option<Encoding>,
     protocol_buffers_supported: bool,
+    session_keys: Option<noise::SessionKeys>,
 }
 
 impl Stream {
     pub fn new(force_encoding: Option<Encoding>, stream: tcp::Stream) -> Self {
-        Self { stream, force_encoding, protocol_buffers_supported: false }
+        Self { stream, force_encoding, protocol_buffers_supported: false, session_keys: None }
+    }
+
+    /// Encrypts all the frames written afterwards. Encrypted frames are decrypted on read,
+    /// while plaintext frames are skipped.
+    pub(crate) fn enable_encryption(&mut self, keys: noise::SessionKeys) {
+        self.session_keys = Some(keys);
     }
 
     fn encoding(&self) -> Option<Encoding> {
@@ -29,10 +37,18 @@ impl Stream {
 
     pub async fn read(&mut self) -> Result<PeerMessage, std::io::Error> {
         'read: loop {
-            let n = self.stream.stream.read_u32_le().await? as usize;
+            let n = self.stream.stream.read_u32_le().await?;
+            let encrypted = n & noise::ENCRYPTED_FRAME_BIT != 0;
+            let n = (n & !noise::ENCRYPTED_FRAME_BIT) as usize;
             let mut buf = BytesMut::new();
             buf.resize(n, 0);
             self.stream.stream.read_exact(&mut buf[..]).await?;
+            let buf = match (&mut self.session_keys, encrypted) {
+                (Some(keys), true) => BytesMut::from(&keys.recv.decrypt(&buf).unwrap()[..]),
+                (None, true) => panic!("unexpected encrypted frame"),
+                (Some(_), false) => continue 'read,
+                (None, false) => buf,
+            };
             for enc in [Encoding::Proto, Encoding::Borsh] {
                 if let Ok(msg) = PeerMessage::deserialize(enc, &buf[..]) {
                     // If deserialize() succeeded but we expected different encoding, ignore the
@@ -61,8 +77,15 @@ impl Stream {
     }
 
     async fn write_encoded(&mut self, msg: &[u8]) {
-        self.stream.stream.write_u32_le(msg.len() as u32).await.unwrap();
-        self.stream.stream.write_all(msg).await.unwrap();
+        let (n, msg) = match &mut self.session_keys {
+            Some(keys) => {
+                let msg = keys.send.encrypt(msg);
+                (msg.len() as u32 | noise::ENCRYPTED_FRAME_BIT, msg)
+            }
+            None => (msg.len() as u32, msg.to_vec()),
+        };
+        self.stream.stream.write_u32_le(n).await.unwrap();
+        self.stream.stream.write_all(&msg).await.unwrap();
         self.stream.stream.flush().await.unwrap();
     }
 }
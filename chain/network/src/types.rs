@@ -16,7 +16,7 @@ use near_primitives::network::{AnnounceAccount, PeerId};
 use near_primitives::sharding::PartialEncodedChunkWithArcReceipts;
 use near_primitives::transaction::SignedTransaction;
 use near_primitives::types::BlockHeight;
-use near_primitives::types::{AccountId, ShardId};
+use near_primitives::types::{AccountId, EpochId, ShardId};
 use once_cell::sync::OnceCell;
 use std::collections::{HashMap, HashSet};
 use std::fmt::Debug;
@@ -231,6 +231,10 @@ pub enum NetworkRequests {
     BlockRequest { hash: CryptoHash, peer_id: PeerId },
     /// Request given block headers.
     BlockHeadersRequest { hashes: Vec<CryptoHash>, peer_id: PeerId },
+    /// Request light client block of given epoch from given peer (epoch sync).
+    EpochSyncRequest { epoch_id: EpochId, peer_id: PeerId },
+    /// Request data needed to finish epoch sync at given epoch from given peer.
+    EpochSyncFinalizationRequest { epoch_id: EpochId, peer_id: PeerId },
     /// Request state header for given shard at given state root.
     StateRequestHeader { shard_id: ShardId, sync_hash: CryptoHash, target: AccountOrPeerIdOrHash },
     /// Request state part for given shard at given state root.
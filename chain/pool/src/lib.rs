@@ -1,25 +1,70 @@
-use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
 
 use crate::types::{PoolIterator, PoolKey, TransactionGroup};
 use borsh::BorshSerialize;
+use near_chain_configs::{TransactionPoolConfig, TransactionPoolEvictionPolicy};
 use near_crypto::PublicKey;
 use near_primitives::epoch_manager::RngSeed;
 use near_primitives::hash::{hash, CryptoHash};
 use near_primitives::transaction::SignedTransaction;
-use near_primitives::types::AccountId;
+use near_primitives::types::{AccountId, Balance, Gas};
 use std::ops::Bound;
 
 mod metrics;
 pub mod types;
 
+/// Result of an attempt to insert a transaction into the pool.
+#[derive(Debug, PartialEq, Eq)]
+pub enum InsertTransactionResult {
+    /// Transaction was inserted, possibly evicting other transactions.
+    Success,
+    /// Transaction with the same hash is already in the pool.
+    Duplicate,
+    /// Pool is full and no transactions could be evicted to make room.
+    NoSpaceLeft,
+    /// Signer of the transaction already has the maximum allowed number of
+    /// transactions in the pool.
+    SignerLimitExceeded,
+}
+
+/// Position of a transaction in the eviction order. Transactions with smaller
+/// keys are evicted first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+struct EvictionKey {
+    /// Value attached to the transaction as (gas, deposit). Always zero for
+    /// the `OldestFirst` policy.
+    value: (Gas, Balance),
+    /// Insertion order of the transaction.
+    seq: u64,
+}
+
+/// Bookkeeping kept for every transaction in the pool.
+struct PooledTransaction {
+    key: PoolKey,
+    signer_id: AccountId,
+    size: u64,
+    eviction_key: EvictionKey,
+}
+
 /// Transaction pool: keeps track of transactions that were not yet accepted into the block chain.
 pub struct TransactionPool {
     /// Transactions are grouped by a pair of (account ID, signer public key).
     /// NOTE: It's more efficient on average to keep transactions unsorted and with potentially
     /// conflicting nonce than to create a BTreeMap for every transaction.
     transactions: BTreeMap<PoolKey, Vec<SignedTransaction>>,
-    /// Set of all hashes to quickly check if the given transaction is in the pool.
-    unique_transactions: HashSet<CryptoHash>,
+    /// All transactions in the pool by hash, to quickly check if the given transaction is in the
+    /// pool and to find it when it needs to be evicted.
+    unique_transactions: HashMap<CryptoHash, PooledTransaction>,
+    /// Transactions in the order in which they are evicted when the pool is full.
+    eviction_queue: BTreeSet<(EvictionKey, CryptoHash)>,
+    /// Number of transactions in the pool for every signer account.
+    signer_counts: HashMap<AccountId, usize>,
+    /// Total size of transactions in the pool, in bytes.
+    total_size: u64,
+    /// Sequence number assigned to the next inserted transaction.
+    next_seq: u64,
+    /// Limits and eviction policy of the pool.
+    config: TransactionPoolConfig,
     /// A uniquely generated key seed to randomize PoolKey order.
     key_seed: RngSeed,
     /// The key after which the pool iterator starts. Doesn't have to be present in the pool.
@@ -27,11 +72,16 @@ pub struct TransactionPool {
 }
 
 impl TransactionPool {
-    pub fn new(key_seed: RngSeed) -> Self {
+    pub fn new(key_seed: RngSeed, config: TransactionPoolConfig) -> Self {
         Self {
             key_seed,
             transactions: BTreeMap::new(),
-            unique_transactions: HashSet::new(),
+            unique_transactions: HashMap::new(),
+            eviction_queue: BTreeSet::new(),
+            signer_counts: HashMap::new(),
+            total_size: 0,
+            next_seq: 0,
+            config,
             last_used_key: CryptoHash::default(),
         }
     }
@@ -39,6 +89,11 @@ impl TransactionPool {
     pub fn init_metrics() {
         // A `get()` call initializes a metric even if its value is zero.
         metrics::TRANSACTION_POOL_TOTAL.get();
+        metrics::TRANSACTION_POOL_SIZE.get();
+        metrics::TRANSACTION_POOL_EVICTED_TOTAL.get();
+        for reason in ["no_space_left", "signer_limit_exceeded"] {
+            metrics::TRANSACTION_POOL_REJECTED_TOTAL.with_label_values(&[reason]).get();
+        }
     }
 
     fn key(&self, account_id: &AccountId, public_key: &PublicKey) -> PoolKey {
@@ -48,20 +103,144 @@ impl TransactionPool {
         hash(&v)
     }
 
+    fn eviction_key(&mut self, signed_transaction: &SignedTransaction) -> EvictionKey {
+        let seq = self.next_seq;
+        self.next_seq += 1;
+        let value = match self.config.eviction_policy {
+            TransactionPoolEvictionPolicy::OldestFirst => (0, 0),
+            TransactionPoolEvictionPolicy::LowestAttachedValueFirst => signed_transaction
+                .transaction
+                .actions
+                .iter()
+                .fold((0, 0), |(gas, deposit): (Gas, Balance), a| {
+                    (
+                        gas.saturating_add(a.get_prepaid_gas()),
+                        deposit.saturating_add(a.get_deposit_balance()),
+                    )
+                }),
+        };
+        EvictionKey { value, seq }
+    }
+
+    /// Whether a pool holding `count` transactions of `size` total bytes is over its limits.
+    fn exceeds_limits(&self, count: usize, size: u64) -> bool {
+        self.config.max_transactions.map_or(false, |limit| count > limit)
+            || self.config.max_size_bytes.map_or(false, |limit| size > limit)
+    }
+
+    /// Returns transactions that need to be evicted to make room for a transaction of the given
+    /// size and eviction key, or `None` if there is no way to make room for it.
+    fn select_for_eviction(&self, size: u64, key: &EvictionKey) -> Option<Vec<CryptoHash>> {
+        let mut count = self.len() + 1;
+        let mut total_size = self.total_size + size;
+        let mut evicted = vec![];
+        for (candidate_key, hash) in &self.eviction_queue {
+            if !self.exceeds_limits(count, total_size) {
+                break;
+            }
+            if self.config.eviction_policy
+                == TransactionPoolEvictionPolicy::LowestAttachedValueFirst
+                && candidate_key.value >= key.value
+            {
+                return None;
+            }
+            count -= 1;
+            total_size -= self.unique_transactions[hash].size;
+            evicted.push(*hash);
+        }
+        if self.exceeds_limits(count, total_size) {
+            return None;
+        }
+        Some(evicted)
+    }
+
     /// Insert a signed transaction into the pool that passed validation.
-    pub fn insert_transaction(&mut self, signed_transaction: SignedTransaction) -> bool {
-        if !self.unique_transactions.insert(signed_transaction.get_hash()) {
+    pub fn insert_transaction(
+        &mut self,
+        signed_transaction: SignedTransaction,
+    ) -> InsertTransactionResult {
+        let tx_hash = signed_transaction.get_hash();
+        if self.unique_transactions.contains_key(&tx_hash) {
             // The hash of this transaction was already seen, skip it.
-            return false;
+            return InsertTransactionResult::Duplicate;
         }
-        metrics::TRANSACTION_POOL_TOTAL.inc();
 
         let signer_id = &signed_transaction.transaction.signer_id;
-        let signer_public_key = &signed_transaction.transaction.public_key;
-        self.transactions
-            .entry(self.key(signer_id, signer_public_key))
-            .or_insert_with(Vec::new)
-            .push(signed_transaction);
+        if let Some(limit) = self.config.max_transactions_per_signer {
+            if self.signer_counts.get(signer_id).map_or(false, |count| *count >= limit) {
+                metrics::TRANSACTION_POOL_REJECTED_TOTAL
+                    .with_label_values(&["signer_limit_exceeded"])
+                    .inc();
+                return InsertTransactionResult::SignerLimitExceeded;
+            }
+        }
+
+        let size = signed_transaction.get_size();
+        let eviction_key = self.eviction_key(&signed_transaction);
+        let evicted = match self.select_for_eviction(size, &eviction_key) {
+            Some(evicted) => evicted,
+            None => {
+                metrics::TRANSACTION_POOL_REJECTED_TOTAL
+                    .with_label_values(&["no_space_left"])
+                    .inc();
+                return InsertTransactionResult::NoSpaceLeft;
+            }
+        };
+        for hash in evicted {
+            self.evict_transaction(&hash);
+        }
+
+        let key = self.key(signer_id, &signed_transaction.transaction.public_key);
+        *self.signer_counts.entry(signer_id.clone()).or_insert(0) += 1;
+        self.total_size += size;
+        self.eviction_queue.insert((eviction_key, tx_hash));
+        self.unique_transactions.insert(
+            tx_hash,
+            PooledTransaction { key, signer_id: signer_id.clone(), size, eviction_key },
+        );
+        metrics::TRANSACTION_POOL_TOTAL.inc();
+        metrics::TRANSACTION_POOL_SIZE.add(size as i64);
+
+        self.transactions.entry(key).or_insert_with(Vec::new).push(signed_transaction);
+        InsertTransactionResult::Success
+    }
+
+    /// Removes a transaction that is still stored in its group from the pool.
+    fn evict_transaction(&mut self, hash: &CryptoHash) {
+        let key = match self.unique_transactions.get(hash) {
+            Some(pooled) => pooled.key,
+            None => return,
+        };
+        let mut remove_entry = false;
+        if let Some(v) = self.transactions.get_mut(&key) {
+            v.retain(|tx| tx.get_hash() != *hash);
+            remove_entry = v.is_empty();
+        }
+        if remove_entry {
+            self.transactions.remove(&key);
+        }
+        if self.forget_transaction(hash) {
+            metrics::TRANSACTION_POOL_EVICTED_TOTAL.inc();
+        }
+    }
+
+    /// Drops the bookkeeping for a transaction that was already taken out of its group.
+    /// Returns whether the transaction was known to the pool.
+    fn forget_transaction(&mut self, hash: &CryptoHash) -> bool {
+        let pooled = match self.unique_transactions.remove(hash) {
+            Some(pooled) => pooled,
+            None => return false,
+        };
+        self.eviction_queue.remove(&(pooled.eviction_key, *hash));
+        if let Some(count) = self.signer_counts.get_mut(&pooled.signer_id) {
+            *count -= 1;
+            if *count == 0 {
+                self.signer_counts.remove(&pooled.signer_id);
+            }
+        }
+        self.total_size -= pooled.size;
+        metrics::TRANSACTION_POOL_TOTAL.dec();
+        metrics::TRANSACTION_POOL_SIZE.sub(pooled.size as i64);
         true
     }
 
@@ -77,11 +256,9 @@ impl TransactionPool {
     pub fn remove_transactions(&mut self, transactions: &[SignedTransaction]) {
         let mut grouped_transactions = HashMap::new();
         for tx in transactions {
-            if self.unique_transactions.contains(&tx.get_hash()) {
-                let signer_id = &tx.transaction.signer_id;
-                let signer_public_key = &tx.transaction.public_key;
+            if let Some(pooled) = self.unique_transactions.get(&tx.get_hash()) {
                 grouped_transactions
-                    .entry(self.key(signer_id, signer_public_key))
+                    .entry(pooled.key)
                     .or_insert_with(HashSet::new)
                     .insert(tx.get_hash());
             }
@@ -96,9 +273,7 @@ impl TransactionPool {
                 self.transactions.remove(&key);
             }
             for hash in &hashes {
-                if self.unique_transactions.remove(&hash) {
-                    metrics::TRANSACTION_POOL_TOTAL.dec();
-                }
+                self.forget_transaction(hash);
             }
         }
     }
@@ -110,9 +285,25 @@ impl TransactionPool {
         }
     }
 
+    /// Drops all transactions from the pool.
+    pub fn clear(&mut self) {
+        metrics::TRANSACTION_POOL_TOTAL.sub(self.unique_transactions.len() as i64);
+        metrics::TRANSACTION_POOL_SIZE.sub(self.total_size as i64);
+        self.transactions.clear();
+        self.unique_transactions.clear();
+        self.eviction_queue.clear();
+        self.signer_counts.clear();
+        self.total_size = 0;
+    }
+
     pub fn len(&self) -> usize {
         self.unique_transactions.len()
     }
+
+    /// Total size of transactions in the pool, in bytes.
+    pub fn transaction_size(&self) -> u64 {
+        self.total_size
+    }
 }
 
 /// PoolIterator is a structure to pull transactions from the pool.
@@ -140,7 +331,7 @@ impl<'a> PoolIteratorWrapper<'a> {
 /// If the pool is empty, the iterator gets the group from the front of the sorted groups queue.
 ///
 /// If this group is empty (no transactions left inside), then the iterator discards it and
-/// updates `unique_transactions` in the pool. Then gets the next one.
+/// forgets them in the pool. Then gets the next one.
 ///
 /// Once a non-empty group is found, this group is pushed to the back of the sorted groups queue
 /// and the iterator returns a mutable reference to this group.
@@ -179,9 +370,7 @@ impl<'a> PoolIterator for PoolIteratorWrapper<'a> {
             while let Some(sorted_group) = self.sorted_groups.pop_front() {
                 if sorted_group.transactions.is_empty() {
                     for hash in sorted_group.removed_transaction_hashes {
-                        if self.pool.unique_transactions.remove(&hash) {
-                            metrics::TRANSACTION_POOL_TOTAL.dec();
-                        }
+                        self.pool.forget_transaction(&hash);
                     }
                 } else {
                     self.sorted_groups.push_back(sorted_group);
@@ -200,9 +389,7 @@ impl<'a> Drop for PoolIteratorWrapper<'a> {
     fn drop(&mut self) {
         for group in self.sorted_groups.drain(..) {
             for hash in group.removed_transaction_hashes {
-                if self.pool.unique_transactions.remove(&hash) {
-                    metrics::TRANSACTION_POOL_TOTAL.dec();
-                }
+                self.pool.forget_transaction(&hash);
             }
             if !group.transactions.is_empty() {
                 self.pool.transactions.insert(group.key, group.transactions);
@@ -253,7 +440,7 @@ mod tests {
         mut transactions: Vec<SignedTransaction>,
         expected_weight: u32,
     ) -> (Vec<u64>, TransactionPool) {
-        let mut pool = TransactionPool::new(TEST_SEED);
+        let mut pool = TransactionPool::new(TEST_SEED, TransactionPoolConfig::default());
         let mut rng = thread_rng();
         transactions.shuffle(&mut rng);
         for tx in transactions {
@@ -364,7 +551,7 @@ mod tests {
             })
             .collect::<Vec<_>>();
 
-        let mut pool = TransactionPool::new(TEST_SEED);
+        let mut pool = TransactionPool::new(TEST_SEED, TransactionPoolConfig::default());
         let mut rng = thread_rng();
         transactions.shuffle(&mut rng);
         for tx in transactions.clone() {
@@ -465,4 +652,133 @@ mod tests {
         new_nonces.sort();
         assert_ne!(nonces, new_nonces);
     }
+
+    fn insert_all(
+        pool: &mut TransactionPool,
+        transactions: Vec<SignedTransaction>,
+    ) -> Vec<InsertTransactionResult> {
+        transactions.into_iter().map(|tx| pool.insert_transaction(tx)).collect()
+    }
+
+    /// Test that the pool doesn't grow over its transaction count limit and evicts the oldest
+    /// transactions first.
+    #[test]
+    fn test_count_limit_evicts_oldest() {
+        let config = TransactionPoolConfig { max_transactions: Some(5), ..Default::default() };
+        let mut pool = TransactionPool::new(TEST_SEED, config);
+        let results =
+            insert_all(&mut pool, generate_transactions("alice.near", "alice.near", 1, 8));
+        assert!(results.iter().all(|r| *r == InsertTransactionResult::Success));
+        assert_eq!(pool.len(), 5);
+        let nonces: Vec<u64> =
+            prepare_transactions(&mut pool, 10).iter().map(|tx| tx.transaction.nonce).collect();
+        assert_eq!(nonces, (4..=8).collect::<Vec<u64>>());
+        assert_eq!(pool.len(), 0);
+        assert_eq!(pool.transaction_size(), 0);
+    }
+
+    /// Test that the total size of the pool is bounded and tracked across insertions and removals.
+    #[test]
+    fn test_size_limit() {
+        let transactions = generate_transactions("alice.near", "alice.near", 1, 10);
+        let tx_size = transactions[0].get_size();
+        let config =
+            TransactionPoolConfig { max_size_bytes: Some(tx_size * 3), ..Default::default() };
+        let mut pool = TransactionPool::new(TEST_SEED, config);
+        insert_all(&mut pool, transactions.clone());
+        assert_eq!(pool.len(), 3);
+        assert_eq!(pool.transaction_size(), tx_size * 3);
+
+        pool.remove_transactions(&transactions[8..]);
+        assert_eq!(pool.len(), 1);
+        assert_eq!(pool.transaction_size(), tx_size);
+    }
+
+    /// Test that clearing the pool drops all transactions and the pool stays usable.
+    #[test]
+    fn test_clear() {
+        let mut pool = TransactionPool::new(TEST_SEED, TransactionPoolConfig::default());
+        insert_all(&mut pool, generate_transactions("alice.near", "alice.near", 1, 10));
+        assert_eq!(pool.len(), 10);
+
+        pool.clear();
+        assert_eq!(pool.len(), 0);
+        assert_eq!(pool.transaction_size(), 0);
+        assert!(prepare_transactions(&mut pool, 10).is_empty());
+
+        let results =
+            insert_all(&mut pool, generate_transactions("alice.near", "alice.near", 1, 3));
+        assert!(results.iter().all(|r| *r == InsertTransactionResult::Success));
+        assert_eq!(pool.len(), 3);
+    }
+
+    /// Test that a transaction larger than the whole pool is rejected without evicting anything.
+    #[test]
+    fn test_too_large_transaction_is_rejected() {
+        let transactions = generate_transactions("alice.near", "alice.near", 1, 2);
+        let config = TransactionPoolConfig {
+            max_size_bytes: Some(transactions[0].get_size() - 1),
+            ..Default::default()
+        };
+        let mut pool = TransactionPool::new(TEST_SEED, config);
+        assert_eq!(
+            insert_all(&mut pool, transactions),
+            vec![InsertTransactionResult::NoSpaceLeft, InsertTransactionResult::NoSpaceLeft]
+        );
+        assert_eq!(pool.len(), 0);
+    }
+
+    /// Test that a single signer can't take more than its share of the pool, while other
+    /// signers are still admitted.
+    #[test]
+    fn test_per_signer_limit() {
+        let config =
+            TransactionPoolConfig { max_transactions_per_signer: Some(3), ..Default::default() };
+        let mut pool = TransactionPool::new(TEST_SEED, config);
+        let mut transactions = generate_transactions("alice.near", "alice.near", 1, 2);
+        transactions.extend(generate_transactions("alice.near", "bob.near", 3, 4));
+        let results = insert_all(&mut pool, transactions);
+        assert_eq!(results[3], InsertTransactionResult::SignerLimitExceeded);
+        let results = insert_all(&mut pool, generate_transactions("bob.near", "bob.near", 1, 3));
+        assert!(results.iter().all(|r| *r == InsertTransactionResult::Success));
+        assert_eq!(pool.len(), 6);
+
+        // Once a transaction of the signer leaves the pool, there is room for another one.
+        prepare_transactions(&mut pool, 6);
+        let results =
+            insert_all(&mut pool, generate_transactions("alice.near", "alice.near", 5, 5));
+        assert_eq!(results, vec![InsertTransactionResult::Success]);
+    }
+
+    /// Test that with the value based policy the cheapest transactions are evicted, and a
+    /// transaction which is not worth more than any in the pool is rejected.
+    #[test]
+    fn test_lowest_attached_value_eviction() {
+        let config = TransactionPoolConfig {
+            max_transactions: Some(3),
+            eviction_policy: TransactionPoolEvictionPolicy::LowestAttachedValueFirst,
+            ..Default::default()
+        };
+        let mut pool = TransactionPool::new(TEST_SEED, config);
+        // Transactions generated by `generate_transactions` attach a deposit equal to the nonce.
+        let mut transactions = generate_transactions("alice.near", "alice.near", 1, 5);
+        transactions.reverse();
+        let results = insert_all(&mut pool, transactions);
+        assert_eq!(
+            results,
+            vec![
+                InsertTransactionResult::Success,
+                InsertTransactionResult::Success,
+                InsertTransactionResult::Success,
+                InsertTransactionResult::NoSpaceLeft,
+                InsertTransactionResult::NoSpaceLeft,
+            ]
+        );
+        let results = insert_all(&mut pool, generate_transactions("bob.near", "bob.near", 10, 10));
+        assert_eq!(results, vec![InsertTransactionResult::Success]);
+        let mut nonces: Vec<u64> =
+            prepare_transactions(&mut pool, 10).iter().map(|tx| tx.transaction.nonce).collect();
+        nonces.sort();
+        assert_eq!(nonces, vec![4, 5, 10]);
+    }
 }
@@ -1,4 +1,4 @@
-use near_o11y::metrics::IntGauge;
+use near_o11y::metrics::{IntCounter, IntCounterVec, IntGauge};
 use once_cell::sync::Lazy;
 
 pub static TRANSACTION_POOL_TOTAL: Lazy<IntGauge> = Lazy::new(|| {
@@ -8,3 +8,28 @@ pub static TRANSACTION_POOL_TOTAL: Lazy<IntGauge> = Lazy::new(|| {
     )
     .unwrap()
 });
+
+pub static TRANSACTION_POOL_SIZE: Lazy<IntGauge> = Lazy::new(|| {
+    near_o11y::metrics::try_create_int_gauge(
+        "near_transaction_pool_size",
+        "Total size in bytes of transactions currently in the pools tracked by the node",
+    )
+    .unwrap()
+});
+
+pub static TRANSACTION_POOL_EVICTED_TOTAL: Lazy<IntCounter> = Lazy::new(|| {
+    near_o11y::metrics::try_create_int_counter(
+        "near_transaction_pool_evicted_total",
+        "Number of transactions evicted from the pools to make room for new ones",
+    )
+    .unwrap()
+});
+
+pub static TRANSACTION_POOL_REJECTED_TOTAL: Lazy<IntCounterVec> = Lazy::new(|| {
+    near_o11y::metrics::try_create_int_counter_vec(
+        "near_transaction_pool_rejected_total",
+        "Number of transactions not admitted to the pools, by reason",
+        &["reason"],
+    )
+    .unwrap()
+});
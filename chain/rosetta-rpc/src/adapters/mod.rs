@@ -240,6 +240,11 @@ impl From<NearActions> for Vec<crate::models::Operation> {
                     );
                 }
 
+                // There are no Rosetta operations for delegate actions yet.
+                // Their inner actions are executed in a separate receipt.
+                #[cfg(feature = "protocol_feature_nep366_delegate_action")]
+                near_primitives::transaction::Action::Delegate(_) => {}
+
                 near_primitives::transaction::Action::DeleteAccount(action) => {
                     let initiate_delete_account_operation_id =
                         crate::models::OperationIdentifier::new(&operations);
@@ -1,6 +1,7 @@
 //! Chain Client Configuration
 use std::cmp::max;
 use std::cmp::min;
+use std::path::PathBuf;
 use std::time::Duration;
 
 use serde::{Deserialize, Serialize};
@@ -70,6 +71,79 @@ impl GCConfig {
     }
 }
 
+/// Order in which transactions are evicted when the transaction pool is full.
+#[derive(Debug, Copy, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TransactionPoolEvictionPolicy {
+    /// Evict transactions that have been in the pool the longest.
+    OldestFirst,
+    /// Evict transactions with the least attached gas, then the least attached
+    /// deposit. A new transaction which isn't worth more than the cheapest one
+    /// in the pool is rejected instead.
+    LowestAttachedValueFirst,
+}
+
+/// Default limit on the total size of transactions in the pool of a single
+/// shard, in bytes.
+pub const DEFAULT_TX_POOL_MAX_SIZE_BYTES: u64 = 100_000_000;
+
+/// Limits on the transaction pool kept for each shard.
+///
+/// Configured in the `transaction_pool` section of `config.json`.  See
+/// `docs/advanced_configuration/transaction_pool.md` for details.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct TransactionPoolConfig {
+    /// Maximum number of transactions in the pool. `None` means no limit.
+    #[serde(default)]
+    pub max_transactions: Option<usize>,
+
+    /// Maximum total size of transactions in the pool, in bytes. `None` means
+    /// no limit.  Defaults to [`DEFAULT_TX_POOL_MAX_SIZE_BYTES`].
+    #[serde(default = "default_tx_pool_max_size_bytes")]
+    pub max_size_bytes: Option<u64>,
+
+    /// Maximum number of transactions in the pool signed by a single account.
+    /// `None` means no limit.
+    #[serde(default)]
+    pub max_transactions_per_signer: Option<usize>,
+
+    /// Which transactions to evict to make room for a new one once the pool
+    /// reaches one of its limits.
+    #[serde(default = "default_tx_pool_eviction_policy")]
+    pub eviction_policy: TransactionPoolEvictionPolicy,
+}
+
+impl Default for TransactionPoolConfig {
+    fn default() -> Self {
+        Self {
+            max_transactions: None,
+            max_size_bytes: Some(DEFAULT_TX_POOL_MAX_SIZE_BYTES),
+            max_transactions_per_signer: None,
+            eviction_policy: TransactionPoolEvictionPolicy::OldestFirst,
+        }
+    }
+}
+
+fn default_tx_pool_max_size_bytes() -> Option<u64> {
+    TransactionPoolConfig::default().max_size_bytes
+}
+
+fn default_tx_pool_eviction_policy() -> TransactionPoolEvictionPolicy {
+    TransactionPoolConfig::default().eviction_policy
+}
+
+/// External location that state sync downloads state parts from before
+/// falling back to requesting them from peers.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ExternalStatePartsConfig {
+    /// Parts are read from a local directory laid out as
+    /// `<root_dir>/<epoch_id>/<shard_id>/state_part_<part_id>` with the part id
+    /// padded to six digits, i.e. with a `state-viewer dump-state-parts` output
+    /// directory for every epoch and shard.
+    Filesystem { root_dir: PathBuf },
+}
+
 #[derive(Clone, Serialize, Deserialize)]
 pub struct ClientConfig {
     /// Version of the binary.
@@ -142,6 +216,10 @@ pub struct ClientConfig {
     pub tracked_shards: Vec<ShardId>,
     /// Not clear old data, set `true` for archive nodes.
     pub archive: bool,
+    /// Whether the archive node has its storage split into hot and cold
+    /// databases.  Hot database of such node is garbage collected, but only up
+    /// to the height which has already been copied to the cold database.
+    pub split_storage: bool,
     /// Number of threads for ViewClientActor pool.
     pub view_client_threads: usize,
     /// Run Epoch Sync on the start.
@@ -158,6 +236,14 @@ pub struct ClientConfig {
     pub enable_statistics_export: bool,
     /// Number of threads to execute background migration work in client.
     pub client_background_migration_threads: usize,
+    /// Limits on the size of the transaction pool.
+    pub transaction_pool: TransactionPoolConfig,
+    /// Where to download state parts from during state sync before asking
+    /// peers.  `None` means that parts are requested from peers only.
+    pub state_sync_external_parts: Option<ExternalStatePartsConfig>,
+    /// Directory in which database checkpoints requested through the debug
+    /// RPC are created.  `None` disables creating checkpoints.
+    pub checkpoints_dir: Option<PathBuf>,
 }
 
 impl ClientConfig {
@@ -210,6 +296,7 @@ impl ClientConfig {
             tracked_accounts: vec![],
             tracked_shards: vec![],
             archive,
+            split_storage: false,
             log_summary_style: LogSummaryStyle::Colored,
             view_client_threads: 1,
             epoch_sync_enabled,
@@ -218,6 +305,9 @@ impl ClientConfig {
             max_gas_burnt_view: None,
             enable_statistics_export: true,
             client_background_migration_threads: 1,
+            transaction_pool: TransactionPoolConfig::default(),
+            state_sync_external_parts: None,
+            checkpoints_dir: None,
         }
     }
 }
@@ -3,7 +3,8 @@ mod genesis_config;
 pub mod genesis_validate;
 
 pub use client_config::{
-    ClientConfig, GCConfig, LogSummaryStyle, DEFAULT_GC_NUM_EPOCHS_TO_KEEP,
+    ClientConfig, ExternalStatePartsConfig, GCConfig, LogSummaryStyle, TransactionPoolConfig,
+    TransactionPoolEvictionPolicy, DEFAULT_GC_NUM_EPOCHS_TO_KEEP, DEFAULT_TX_POOL_MAX_SIZE_BYTES,
     MIN_GC_NUM_EPOCHS_TO_KEEP, TEST_STATE_SYNC_TIMEOUT,
 };
 pub use genesis_config::{
@@ -1,8 +1,10 @@
 #![doc = include_str!("../README.md")]
 
+use near_account_id::AccountId;
 use near_o11y::metrics::{try_create_int_counter, IntCounter};
 use once_cell::sync::Lazy;
 use std::sync::atomic::{AtomicU64, Ordering};
+use tokio::sync::watch;
 
 /// An indicator for dynamic config changes
 pub static DYN_CONFIG_CHANGE: Lazy<IntCounter> = Lazy::new(|| {
@@ -17,12 +19,162 @@ pub static DYN_CONFIG_CHANGE: Lazy<IntCounter> = Lazy::new(|| {
 // shutdown
 pub static EXPECTED_SHUTDOWN_AT: AtomicU64 = AtomicU64::new(0);
 
+/// Network access lists overriding the ones from config.json.
+/// `None` means that the value from config.json is in effect.
+/// The fields have the same format as the corresponding fields of the network config.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct NetworkAccessLists {
+    pub blacklist: Option<Vec<String>>,
+    pub whitelist_nodes: Option<String>,
+}
+
+/// The current network access lists. PeerManagerActor subscribes to it
+/// and applies the changes to the existing connections.
+pub static NETWORK_ACCESS_LISTS: Lazy<watch::Sender<NetworkAccessLists>> =
+    Lazy::new(|| watch::channel(NetworkAccessLists::default()).0);
+
+/// Values of the config.json fields which can be changed without restarting the node.
+/// neard reloads them from config.json on SIGHUP, see `update`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UpdateableConfigs {
+    /// `gc_blocks_limit`
+    pub gc_blocks_limit: u64,
+    /// `network.max_num_peers`
+    pub max_num_peers: u32,
+    /// `network.ideal_connections_lo`
+    pub ideal_connections_lo: u32,
+    /// `network.ideal_connections_hi`
+    pub ideal_connections_hi: u32,
+    /// `tracked_accounts`
+    pub tracked_accounts: Vec<AccountId>,
+    /// `rpc.limits_config`, `None` if the RPC server is disabled.
+    pub rpc_limits: Option<RpcLimits>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RpcLimits {
+    pub json_payload_max_size: usize,
+    pub max_batch_size: usize,
+}
+
+impl UpdateableConfigs {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.gc_blocks_limit == 0 {
+            return Err("gc_blocks_limit has to be positive".to_string());
+        }
+        if self.ideal_connections_lo > self.ideal_connections_hi {
+            return Err(format!(
+                "ideal_connections_lo({}) > ideal_connections_hi({})",
+                self.ideal_connections_lo, self.ideal_connections_hi
+            ));
+        }
+        if self.ideal_connections_hi > self.max_num_peers {
+            return Err(format!(
+                "ideal_connections_hi({}) > max_num_peers({})",
+                self.ideal_connections_hi, self.max_num_peers
+            ));
+        }
+        if let Some(limits) = &self.rpc_limits {
+            if limits.json_payload_max_size == 0 || limits.max_batch_size == 0 {
+                return Err("rpc limits have to be positive".to_string());
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The current values of the updateable config fields.
+/// `None` until the config is reloaded for the first time, which means that
+/// the values the node has been started with are in effect.
+pub static UPDATEABLE_CONFIGS: Lazy<watch::Sender<Option<UpdateableConfigs>>> =
+    Lazy::new(|| watch::channel(None).0);
+
+/// Validates and applies the updateable configs.
+/// Every changed field is counted by the near_dynamic_config_changes metric.
+pub fn update(configs: UpdateableConfigs) -> Result<(), String> {
+    configs.validate()?;
+    UPDATEABLE_CONFIGS.send_if_modified(|current| {
+        let changes = match current {
+            Some(current) => count_changes(current, &configs),
+            // The first update just records the values the node has been started with:
+            // neard performs it at startup.
+            None => 0,
+        };
+        DYN_CONFIG_CHANGE.inc_by(changes);
+        let modified = current.as_ref() != Some(&configs);
+        *current = Some(configs);
+        modified
+    });
+    Ok(())
+}
+
+fn count_changes(a: &UpdateableConfigs, b: &UpdateableConfigs) -> u64 {
+    [
+        a.gc_blocks_limit != b.gc_blocks_limit,
+        a.max_num_peers != b.max_num_peers,
+        a.ideal_connections_lo != b.ideal_connections_lo,
+        a.ideal_connections_hi != b.ideal_connections_hi,
+        a.tracked_accounts != b.tracked_accounts,
+        a.rpc_limits != b.rpc_limits,
+    ]
+    .into_iter()
+    .filter(|changed| *changed)
+    .count() as u64
+}
+
 /// Reload the dynamic config, and increase the counting metric near_dynamic_config_changes
-pub fn reload(expected_shutdown: Option<u64>) {
+pub fn reload(expected_shutdown: Option<u64>, network_access_lists: NetworkAccessLists) {
     if let Some(expected_shutdown) = expected_shutdown {
         EXPECTED_SHUTDOWN_AT.store(expected_shutdown, Ordering::Relaxed);
     } else {
         EXPECTED_SHUTDOWN_AT.store(0, Ordering::Relaxed);
     }
+    NETWORK_ACCESS_LISTS.send_if_modified(|lists| {
+        let modified = *lists != network_access_lists;
+        *lists = network_access_lists;
+        modified
+    });
     DYN_CONFIG_CHANGE.inc();
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn configs() -> UpdateableConfigs {
+        UpdateableConfigs {
+            gc_blocks_limit: 2,
+            max_num_peers: 40,
+            ideal_connections_lo: 30,
+            ideal_connections_hi: 35,
+            tracked_accounts: vec![],
+            rpc_limits: Some(RpcLimits { json_payload_max_size: 10, max_batch_size: 5 }),
+        }
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(configs().validate().is_ok());
+        assert!(UpdateableConfigs { gc_blocks_limit: 0, ..configs() }.validate().is_err());
+        assert!(UpdateableConfigs { ideal_connections_lo: 36, ..configs() }.validate().is_err());
+        assert!(UpdateableConfigs { max_num_peers: 34, ..configs() }.validate().is_err());
+        assert!(UpdateableConfigs {
+            rpc_limits: Some(RpcLimits { json_payload_max_size: 10, max_batch_size: 0 }),
+            ..configs()
+        }
+        .validate()
+        .is_err());
+        assert!(UpdateableConfigs { rpc_limits: None, ..configs() }.validate().is_ok());
+    }
+
+    #[test]
+    fn test_count_changes() {
+        assert_eq!(0, count_changes(&configs(), &configs()));
+        let changed = UpdateableConfigs {
+            gc_blocks_limit: 3,
+            tracked_accounts: vec!["alice.near".parse().unwrap()],
+            ..configs()
+        };
+        assert_eq!(2, count_changes(&configs(), &changed));
+    }
+}
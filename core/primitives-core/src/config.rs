@@ -466,6 +466,8 @@ pub enum ActionCosts {
     new_action_receipt,
     new_data_receipt_base,
     new_data_receipt_byte,
+    #[cfg(feature = "protocol_feature_nep366_delegate_action")]
+    delegate,
 }
 
 impl ExtCosts {
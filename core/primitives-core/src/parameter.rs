@@ -31,6 +31,7 @@ pub enum Parameter {
     StorageAmountPerByte,
     StorageNumBytesAccount,
     StorageNumExtraBytesRecord,
+    StorageZeroBalanceAccountLimit,
 
     // Static action costs
     // send_sir / send_not_sir is burned when creating a receipt on the signer shard.
@@ -81,6 +82,9 @@ pub enum Parameter {
     ActionDeleteKeySendSir,
     ActionDeleteKeySendNotSir,
     ActionDeleteKeyExecution,
+    ActionDelegateSendSir,
+    ActionDelegateSendNotSir,
+    ActionDelegateExecution,
 
     // Smart contract dynamic gas costs
     WasmRegularOpCost,
@@ -207,6 +211,7 @@ pub enum FeeParameter {
     ActionAddFunctionCallKey,
     ActionAddFunctionCallKeyPerByte,
     ActionDeleteKey,
+    ActionDelegate,
 }
 
 impl Parameter {
@@ -265,6 +270,8 @@ impl From<ActionCosts> for FeeParameter {
             ActionCosts::new_action_receipt => Self::ActionReceiptCreation,
             ActionCosts::new_data_receipt_base => Self::DataReceiptCreationBase,
             ActionCosts::new_data_receipt_byte => Self::DataReceiptCreationPerByte,
+            #[cfg(feature = "protocol_feature_nep366_delegate_action")]
+            ActionCosts::delegate => Self::ActionDelegate,
         }
     }
 }
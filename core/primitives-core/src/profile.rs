@@ -12,7 +12,9 @@ use strum::IntoEnumIterator;
 pub struct DataArray(Box<[u64; Self::LEN]>);
 
 impl DataArray {
-    pub const LEN: usize = if cfg!(feature = "protocol_feature_ed25519_verify") { 72 } else { 70 };
+    pub const LEN: usize = 70
+        + if cfg!(feature = "protocol_feature_ed25519_verify") { 2 } else { 0 }
+        + cfg!(feature = "protocol_feature_nep366_delegate_action") as usize;
 }
 
 impl Index<usize> for DataArray {
@@ -260,6 +262,8 @@ impl Cost {
             Cost::ExtCost { ext_cost_kind: ExtCosts::ed25519_verify_base } => 70,
             #[cfg(feature = "protocol_feature_ed25519_verify")]
             Cost::ExtCost { ext_cost_kind: ExtCosts::ed25519_verify_byte } => 71,
+            #[cfg(feature = "protocol_feature_nep366_delegate_action")]
+            Cost::ActionCost { action_cost_kind: ActionCosts::delegate } => DataArray::LEN - 1,
         }
     }
 }
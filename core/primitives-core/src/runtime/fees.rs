@@ -8,7 +8,7 @@ use serde::{Deserialize, Serialize};
 
 use crate::config::ActionCosts;
 use crate::num_rational::Rational;
-use crate::types::{Balance, Gas};
+use crate::types::{Balance, Gas, StorageUsage};
 
 /// Costs associated with an object that can only be sent over the network (and executed
 /// by the receiver).
@@ -131,6 +131,9 @@ pub struct StorageUsageConfig {
     pub num_bytes_account: u64,
     /// Additional number of bytes for a k/v record
     pub num_extra_bytes_record: u64,
+    /// Storage usage an account without a contract may have without holding
+    /// any balance for it. Accounts using more storage pay for all of it.
+    pub zero_balance_account_storage_limit: StorageUsage,
 }
 
 impl RuntimeFeesConfig {
@@ -220,6 +223,12 @@ impl RuntimeFeesConfig {
                     send_not_sir: 59357464,
                     execution: 59357464,
                 },
+                #[cfg(feature = "protocol_feature_nep366_delegate_action")]
+                ActionCosts::delegate => Fee {
+                    send_sir: 200000000000,
+                    send_not_sir: 200000000000,
+                    execution: 200000000000,
+                },
             },
         }
     }
@@ -251,11 +260,17 @@ impl StorageUsageConfig {
             num_bytes_account: 100,
             num_extra_bytes_record: 40,
             storage_amount_per_byte: 909 * 100_000_000_000_000_000,
+            zero_balance_account_storage_limit: 0,
         }
     }
 
     pub(crate) fn free() -> StorageUsageConfig {
-        Self { num_bytes_account: 0, num_extra_bytes_record: 0, storage_amount_per_byte: 0 }
+        Self {
+            num_bytes_account: 0,
+            num_extra_bytes_record: 0,
+            storage_amount_per_byte: 0,
+            zero_balance_account_storage_limit: 0,
+        }
     }
 }
 
@@ -0,0 +1,187 @@
+//! Delegate actions, i.e. meta transactions.
+//!
+//! A delegate action is a list of actions signed by its sender but submitted
+//! on chain by another account, the relayer, wrapped in an
+//! [`Action::Delegate`].  The relayer pays for the gas and the deposits while
+//! the inner actions are executed as if sent by the sender.  See
+//! <https://github.com/near/NEPs/pull/366>.
+use std::io::{Error, ErrorKind};
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use serde::{Deserialize, Serialize};
+
+use near_crypto::{PublicKey, Signature};
+
+use crate::hash::{hash, CryptoHash};
+use crate::transaction::Action;
+use crate::types::{AccountId, BlockHeight, Nonce};
+
+/// Borsh tag of [`Action::Delegate`].
+const ACTION_DELEGATE_NUMBER: u8 = 8;
+
+/// Actions to be executed on behalf of `sender_id`.
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, PartialEq, Eq, Clone, Debug)]
+pub struct DelegateAction {
+    /// Account the actions are executed on behalf of.  It must be the
+    /// receiver of the receipt carrying the delegate action.
+    pub sender_id: AccountId,
+    /// Receiver of the inner actions.
+    pub receiver_id: AccountId,
+    /// Actions to execute.  They can’t contain another delegate action.
+    pub actions: Vec<NonDelegateAction>,
+    /// Nonce of the `public_key` access key of `sender_id`.  Just like for
+    /// transactions, it must be greater than the access key’s nonce, which
+    /// prevents relayers from submitting the same delegate action twice.
+    pub nonce: Nonce,
+    /// Height of the last block the delegate action may be executed in.
+    pub max_block_height: BlockHeight,
+    /// Key of `sender_id` the delegate action is signed with.
+    pub public_key: PublicKey,
+}
+
+impl DelegateAction {
+    pub fn get_actions(&self) -> Vec<Action> {
+        self.actions.iter().map(|action| action.0.clone()).collect()
+    }
+
+    /// Hash of the delegate action which is signed by the sender.
+    ///
+    /// Borsh serialisation of a delegate action can’t be parsed as
+    /// a transaction, as `receiver_id` takes the place of the transaction’s
+    /// public key and its length prefix is never a valid key type, so the
+    /// signature can’t be reused to submit a transaction.
+    pub fn get_hash(&self) -> CryptoHash {
+        let bytes = self.try_to_vec().expect("Failed to serialize");
+        hash(&bytes)
+    }
+}
+
+/// Delegate action signed by its sender.
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, PartialEq, Eq, Clone, Debug)]
+pub struct SignedDelegateAction {
+    pub delegate_action: DelegateAction,
+    pub signature: Signature,
+}
+
+impl SignedDelegateAction {
+    /// Checks the signature against the delegate action’s public key.
+    pub fn verify(&self) -> bool {
+        let delegate_action = &self.delegate_action;
+        self.signature.verify(delegate_action.get_hash().as_ref(), &delegate_action.public_key)
+    }
+}
+
+impl From<SignedDelegateAction> for Action {
+    fn from(delegate_action: SignedDelegateAction) -> Self {
+        Self::Delegate(delegate_action)
+    }
+}
+
+/// An [`Action`] other than [`Action::Delegate`].
+///
+/// Deserialisation fails on delegate actions so they can’t be nested.
+#[derive(BorshSerialize, Serialize, Deserialize, PartialEq, Eq, Clone, Debug)]
+#[serde(try_from = "Action", into = "Action")]
+pub struct NonDelegateAction(Action);
+
+impl From<NonDelegateAction> for Action {
+    fn from(action: NonDelegateAction) -> Self {
+        action.0
+    }
+}
+
+/// Error returned when trying to nest a delegate action.
+#[derive(Debug, PartialEq, Eq)]
+pub struct IsDelegateAction;
+
+impl std::fmt::Display for IsDelegateAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("delegate action can’t contain another delegate action")
+    }
+}
+
+impl TryFrom<Action> for NonDelegateAction {
+    type Error = IsDelegateAction;
+
+    fn try_from(action: Action) -> Result<Self, IsDelegateAction> {
+        match action {
+            Action::Delegate(_) => Err(IsDelegateAction),
+            action => Ok(Self(action)),
+        }
+    }
+}
+
+impl BorshDeserialize for NonDelegateAction {
+    fn deserialize(buf: &mut &[u8]) -> Result<Self, Error> {
+        match buf.first() {
+            None => Err(Error::new(ErrorKind::InvalidInput, "no action tag")),
+            Some(&ACTION_DELEGATE_NUMBER) => {
+                Err(Error::new(ErrorKind::InvalidInput, IsDelegateAction.to_string()))
+            }
+            Some(_) => Ok(Self(<Action as BorshDeserialize>::deserialize(buf)?)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transaction::TransferAction;
+    use near_crypto::{InMemorySigner, KeyType, Signer};
+
+    fn delegate_action(actions: Vec<Action>) -> DelegateAction {
+        DelegateAction {
+            sender_id: "alice.near".parse().unwrap(),
+            receiver_id: "bob.near".parse().unwrap(),
+            actions: actions.into_iter().map(|action| action.try_into().unwrap()).collect(),
+            nonce: 1,
+            max_block_height: 100,
+            public_key: PublicKey::empty(KeyType::ED25519),
+        }
+    }
+
+    #[test]
+    fn test_delegate_action_tag() {
+        let signed = SignedDelegateAction {
+            delegate_action: delegate_action(vec![]),
+            signature: Signature::empty(KeyType::ED25519),
+        };
+        let bytes = Action::Delegate(signed).try_to_vec().unwrap();
+        assert_eq!(bytes[0], ACTION_DELEGATE_NUMBER);
+    }
+
+    #[test]
+    fn test_nested_delegate_action_rejected() {
+        let inner = SignedDelegateAction {
+            delegate_action: delegate_action(vec![]),
+            signature: Signature::empty(KeyType::ED25519),
+        };
+        assert_eq!(
+            NonDelegateAction::try_from(Action::Delegate(inner.clone())),
+            Err(IsDelegateAction)
+        );
+
+        // Bypass the check when constructing to make sure it holds when
+        // deserialising as well.
+        let mut outer = delegate_action(vec![]);
+        outer.actions.push(NonDelegateAction(Action::Delegate(inner)));
+        let bytes = outer.try_to_vec().unwrap();
+        assert!(DelegateAction::try_from_slice(&bytes).is_err());
+        let json = serde_json::to_string(&outer).unwrap();
+        assert!(serde_json::from_str::<DelegateAction>(&json).is_err());
+    }
+
+    #[test]
+    fn test_verify() {
+        let signer =
+            InMemorySigner::from_seed("alice.near".parse().unwrap(), KeyType::ED25519, "a");
+        let mut delegate_action =
+            delegate_action(vec![Action::Transfer(TransferAction { deposit: 1 })]);
+        delegate_action.public_key = signer.public_key();
+        let signature = signer.sign(delegate_action.get_hash().as_ref());
+        let mut signed = SignedDelegateAction { delegate_action, signature };
+        assert!(signed.verify());
+        signed.delegate_action.nonce += 1;
+        assert!(!signed.verify());
+    }
+}
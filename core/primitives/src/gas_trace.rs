@@ -0,0 +1,177 @@
+//! Per host function call gas traces of function calls.
+//!
+//! Unlike the aggregated [`crate::profile::ProfileData`], which is stored with
+//! execution outcomes, traces are only collected on request (see
+//! `near_vm_logic::gas_trace`) and are never part of the protocol.
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::hash::CryptoHash;
+use crate::types::{AccountId, Gas};
+
+/// A single call of a host function made by a contract.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct HostFunctionCall {
+    /// Name of the host function, e.g. `storage_write`.
+    pub name: String,
+    /// Arguments passed to the host function, e.g. `key_len`.
+    pub args: BTreeMap<String, u64>,
+    /// Gas burnt executing WebAssembly code since the previous host function
+    /// call.
+    pub wasm_gas: Gas,
+    /// Gas burnt by the host function itself.
+    pub gas_burnt: Gas,
+    /// Gas used by the host function, including gas attached to promises.
+    pub gas_used: Gas,
+    /// Gas burnt by the function call so far, including this host function.
+    pub total_gas_burnt: Gas,
+}
+
+/// Host function calls made by a single function call action.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct FunctionCallTrace {
+    /// Receipt the function call action belongs to.
+    pub receipt_id: CryptoHash,
+    pub account_id: AccountId,
+    pub method_name: String,
+    pub calls: Vec<HostFunctionCall>,
+    /// Total gas burnt by the function call.
+    pub gas_burnt: Gas,
+    /// Total gas used by the function call.
+    pub gas_used: Gas,
+}
+
+/// Format gas traces are exported in.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum GasTraceFormat {
+    /// The traces as they are.
+    #[default]
+    Json,
+    /// Trace Event Format understood by `chrome://tracing` and Perfetto.
+    Chrome,
+}
+
+impl std::str::FromStr for GasTraceFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "json" => Ok(Self::Json),
+            "chrome" => Ok(Self::Chrome),
+            _ => Err(format!("unknown gas trace format '{}', expected 'json' or 'chrome'", s)),
+        }
+    }
+}
+
+/// Gas per microsecond of the Chrome trace timeline.
+///
+/// Gas costs are calibrated so that 1 Tgas is about 1 ms of execution, so the
+/// timeline roughly corresponds to the time the calls take on a validator.
+const GAS_PER_MICROSECOND: f64 = 1e9;
+
+/// Exports the traces in the given format.
+pub fn export(traces: &[FunctionCallTrace], format: GasTraceFormat) -> serde_json::Value {
+    match format {
+        GasTraceFormat::Json => serde_json::to_value(traces).unwrap(),
+        GasTraceFormat::Chrome => to_chrome_trace(traces),
+    }
+}
+
+/// Converts the traces to the Trace Event Format using burnt gas as time.
+///
+/// Function calls are laid out one after another, each containing the host
+/// functions it called.  Gaps between host functions are WebAssembly execution.
+pub fn to_chrome_trace(traces: &[FunctionCallTrace]) -> serde_json::Value {
+    let mut events = Vec::new();
+    let mut start = 0;
+    for trace in traces {
+        events.push(serde_json::json!({
+            "name": format!("{}.{}", trace.account_id, trace.method_name),
+            "cat": "function_call",
+            "ph": "X",
+            "pid": 1,
+            "tid": 1,
+            "ts": start as f64 / GAS_PER_MICROSECOND,
+            "dur": trace.gas_burnt as f64 / GAS_PER_MICROSECOND,
+            "args": {
+                "receipt_id": trace.receipt_id.to_string(),
+                "gas_burnt": trace.gas_burnt,
+                "gas_used": trace.gas_used,
+            },
+        }));
+        for call in &trace.calls {
+            let call_start = start + call.total_gas_burnt - call.gas_burnt;
+            let mut args = serde_json::Map::new();
+            for (name, value) in &call.args {
+                args.insert(name.clone(), (*value).into());
+            }
+            args.insert("gas_burnt".to_string(), call.gas_burnt.into());
+            args.insert("gas_used".to_string(), call.gas_used.into());
+            args.insert("total_gas_burnt".to_string(), call.total_gas_burnt.into());
+            events.push(serde_json::json!({
+                "name": call.name,
+                "cat": "host_function",
+                "ph": "X",
+                "pid": 1,
+                "tid": 1,
+                "ts": call_start as f64 / GAS_PER_MICROSECOND,
+                "dur": call.gas_burnt as f64 / GAS_PER_MICROSECOND,
+                "args": args,
+            }));
+        }
+        start += trace.gas_burnt;
+    }
+    serde_json::json!({ "traceEvents": events, "displayTimeUnit": "ns" })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chrome_trace() {
+        let call = |name: &str, gas_burnt, total_gas_burnt| HostFunctionCall {
+            name: name.to_string(),
+            args: [("key_len".to_string(), 3)].into_iter().collect(),
+            wasm_gas: 0,
+            gas_burnt,
+            gas_used: gas_burnt,
+            total_gas_burnt,
+        };
+        let trace = |method_name: &str| FunctionCallTrace {
+            receipt_id: CryptoHash::default(),
+            account_id: "test0".parse().unwrap(),
+            method_name: method_name.to_string(),
+            calls: vec![call("storage_write", 2_000, 3_000), call("storage_read", 1_000, 5_000)],
+            gas_burnt: 6_000,
+            gas_used: 6_000,
+        };
+        let exported =
+            export(&[trace("write"), trace("read")], GasTraceFormat::Chrome)["traceEvents"].clone();
+        let events = exported.as_array().unwrap();
+        let timeline = events
+            .iter()
+            .map(|event| {
+                (
+                    event["name"].as_str().unwrap(),
+                    (event["ts"].as_f64().unwrap() * GAS_PER_MICROSECOND).round() as u64,
+                    (event["dur"].as_f64().unwrap() * GAS_PER_MICROSECOND).round() as u64,
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            timeline,
+            vec![
+                ("test0.write", 0, 6_000),
+                ("storage_write", 1_000, 2_000),
+                ("storage_read", 4_000, 1_000),
+                ("test0.read", 6_000, 6_000),
+                ("storage_write", 7_000, 2_000),
+                ("storage_read", 10_000, 1_000),
+            ]
+        );
+        assert_eq!(events[1]["args"]["key_len"], 3);
+    }
+}
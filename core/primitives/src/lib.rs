@@ -10,8 +10,11 @@ pub use near_primitives_core::serialize;
 pub mod block;
 pub mod block_header;
 pub mod challenge;
+#[cfg(feature = "protocol_feature_nep366_delegate_action")]
+pub mod delegate_action;
 pub mod epoch_manager;
 pub mod errors;
+pub mod gas_trace;
 pub mod merkle;
 pub mod network;
 pub mod rand;
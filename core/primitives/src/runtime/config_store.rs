@@ -28,13 +28,16 @@ static CONFIG_DIFFS: &[(ProtocolVersion, &str)] = &[
     // set read_cached_trie_node cost, decrease storage key limit
     (53, include_config!("53.txt")),
     (57, include_config!("57.txt")),
+    // Free storage allowance for accounts without a contract
+    #[cfg(feature = "protocol_feature_zero_balance_account")]
+    (134, include_config!("134.txt")),
 ];
 
 /// Testnet parameters for versions <= 29, which (incorrectly) differed from mainnet parameters
 pub static INITIAL_TESTNET_CONFIG: &str = include_config!("parameters_testnet.txt");
 
 /// Stores runtime config for each protocol version where it was updated.
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct RuntimeConfigStore {
     /// Maps protocol version to the config.
     store: BTreeMap<ProtocolVersion, Arc<RuntimeConfig>>,
@@ -228,6 +231,18 @@ mod tests {
         );
     }
 
+    #[test]
+    #[cfg(feature = "protocol_feature_zero_balance_account")]
+    fn test_zero_balance_account_storage_limit() {
+        use crate::version::ProtocolFeature::ZeroBalanceAccount;
+
+        let store = RuntimeConfigStore::new(None);
+        let base_cfg = store.get_config(ZeroBalanceAccount.protocol_version() - 1);
+        let new_cfg = store.get_config(ZeroBalanceAccount.protocol_version());
+        assert_eq!(base_cfg.fees.storage_usage_config.zero_balance_account_storage_limit, 0);
+        assert_eq!(new_cfg.fees.storage_usage_config.zero_balance_account_storage_limit, 328);
+    }
+
     /// Use snapshot testing to check that the JSON representation of the
     /// configurations of each version is unchanged.
     /// If tests fail after an intended change, run `cargo insta review` accept
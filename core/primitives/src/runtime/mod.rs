@@ -2,6 +2,7 @@ pub use near_primitives_core::runtime::fees;
 pub use near_primitives_core::runtime::*;
 
 use crate::account::Account;
+use crate::hash::CryptoHash;
 use crate::runtime::config::RuntimeConfig;
 use crate::types::Balance;
 
@@ -16,12 +17,19 @@ pub mod parameter_table;
 ///  - Some(insufficient_balance) if account doesn't have enough and how much need to be added,
 ///  - Err(message) if account has invalid storage usage or amount/locked.
 ///
+/// Accounts without a contract that fit into the free storage allowance
+/// (see [`is_zero_balance_account`]) don't need any balance.  Once an account
+/// outgrows the allowance, it has to cover its entire storage usage.
+///
 /// Read details of state staking
 /// <https://nomicon.io/Economics/README.html#state-stake>.
 pub fn get_insufficient_storage_stake(
     account: &Account,
     runtime_config: &RuntimeConfig,
 ) -> Result<Option<Balance>, String> {
+    if is_zero_balance_account(account, runtime_config) {
+        return Ok(None);
+    }
     let required_amount = Balance::from(account.storage_usage())
         .checked_mul(runtime_config.storage_amount_per_byte())
         .ok_or_else(|| {
@@ -40,3 +48,11 @@ pub fn get_insufficient_storage_stake(
         Ok(Some(required_amount - available_amount))
     }
 }
+
+/// Checks if the account has no contract deployed and uses no more storage
+/// than the free allowance given to every account.
+pub fn is_zero_balance_account(account: &Account, runtime_config: &RuntimeConfig) -> bool {
+    account.code_hash() == CryptoHash::default()
+        && account.storage_usage()
+            <= runtime_config.fees.storage_usage_config.zero_balance_account_storage_limit
+}
@@ -78,6 +78,8 @@ impl TryFrom<&ParameterTable> for RuntimeConfig {
                     num_bytes_account: params.get_parsed(Parameter::StorageNumBytesAccount)?,
                     num_extra_bytes_record: params
                         .get_parsed(Parameter::StorageNumExtraBytesRecord)?,
+                    zero_balance_account_storage_limit: params
+                        .get_parsed(Parameter::StorageZeroBalanceAccountLimit)?,
                 },
             },
             wasm_config: VMConfig {
@@ -1,6 +1,7 @@
 #[cfg(feature = "sandbox")]
 pub mod state_patch {
     use crate::state_record::StateRecord;
+    use crate::types::AccountId;
 
     /// Changes to the state to be applied via sandbox-only state patching
     /// feature.
@@ -11,27 +12,47 @@ pub mod state_patch {
     /// short-circuited by treating the type as always empty.
     #[derive(Default)]
     pub struct SandboxStatePatch {
+        /// Accounts whose state (account, code, access keys and data) is
+        /// removed before the records are applied.
+        removed_accounts: Vec<AccountId>,
         records: Vec<StateRecord>,
     }
 
     impl SandboxStatePatch {
         pub fn new(records: Vec<StateRecord>) -> SandboxStatePatch {
-            SandboxStatePatch { records }
+            SandboxStatePatch { removed_accounts: vec![], records }
+        }
+
+        /// Patch replacing the whole state of `account_ids` with `records`.
+        pub fn replace_accounts(
+            account_ids: Vec<AccountId>,
+            records: Vec<StateRecord>,
+        ) -> SandboxStatePatch {
+            SandboxStatePatch { removed_accounts: account_ids, records }
+        }
+
+        pub fn removed_accounts(&self) -> &[AccountId] {
+            &self.removed_accounts
         }
 
         pub fn is_empty(&self) -> bool {
-            self.records.is_empty()
+            self.removed_accounts.is_empty() && self.records.is_empty()
         }
 
         pub fn clear(&mut self) {
+            self.removed_accounts.clear();
             self.records.clear();
         }
 
         pub fn take(&mut self) -> SandboxStatePatch {
-            Self { records: core::mem::take(&mut self.records) }
+            Self {
+                removed_accounts: core::mem::take(&mut self.removed_accounts),
+                records: core::mem::take(&mut self.records),
+            }
         }
 
         pub fn merge(&mut self, other: SandboxStatePatch) {
+            self.removed_accounts.extend(other.removed_accounts);
             self.records.extend(other.records);
         }
     }
@@ -49,11 +70,16 @@ pub mod state_patch {
 #[cfg(not(feature = "sandbox"))]
 pub mod state_patch {
     use crate::state_record::StateRecord;
+    use crate::types::AccountId;
 
     #[derive(Default)]
     pub struct SandboxStatePatch;
 
     impl SandboxStatePatch {
+        #[inline(always)]
+        pub fn removed_accounts(&self) -> &[AccountId] {
+            &[]
+        }
         #[inline(always)]
         pub fn is_empty(&self) -> bool {
             true
@@ -10,6 +10,8 @@ use near_o11y::pretty;
 use near_primitives_core::profile::ProfileData;
 
 use crate::account::AccessKey;
+#[cfg(feature = "protocol_feature_nep366_delegate_action")]
+use crate::delegate_action::SignedDelegateAction;
 use crate::errors::TxExecutionError;
 use crate::hash::{hash, CryptoHash};
 use crate::merkle::MerklePath;
@@ -68,6 +70,10 @@ pub enum Action {
     AddKey(AddKeyAction),
     DeleteKey(DeleteKeyAction),
     DeleteAccount(DeleteAccountAction),
+    /// Actions signed by the receiver and submitted by the signer, who pays
+    /// for them.  See [`crate::delegate_action`].
+    #[cfg(feature = "protocol_feature_nep366_delegate_action")]
+    Delegate(SignedDelegateAction),
 }
 
 impl Action {
@@ -405,6 +405,11 @@ pub mod trie_key_parsers {
         res
     }
 
+    /// Prefix shared by the raw keys of all `TrieKey::ContractCode` entries.
+    pub fn get_raw_prefix_for_contract_codes() -> Vec<u8> {
+        vec![col::CONTRACT_CODE]
+    }
+
     pub fn get_raw_prefix_for_contract_data(account_id: &AccountId, prefix: &[u8]) -> Vec<u8> {
         let mut res = Vec::with_capacity(
             col::CONTRACT_DATA.len()
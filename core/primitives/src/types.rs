@@ -902,9 +902,33 @@ pub enum CompiledContract {
     Code(Vec<u8>),
 }
 
+/// Describes what a compiled contract stored in `CompiledContractCache` has
+/// been compiled from and with.
+#[derive(Debug, Clone, PartialEq, Eq, BorshDeserialize, BorshSerialize)]
+pub struct CompiledContractInfo {
+    /// Hash of the contract code.
+    pub code_hash: CryptoHash,
+    /// Identifies the VM and the VM config the contract has been compiled
+    /// with, see `near_vm_runner::vm_cache_id`.
+    pub vm_id: CryptoHash,
+    /// Time the compilation took, in microseconds.
+    pub compile_time_us: u64,
+}
+
 /// Cache for compiled modules
 pub trait CompiledContractCache: Send + Sync {
     fn put(&self, key: &CryptoHash, value: CompiledContract) -> std::io::Result<()>;
+    /// Same as `put`, but also records the `info` about the compiled contract.
+    /// Caches which never evict entries can ignore it.
+    fn put_with_info(
+        &self,
+        key: &CryptoHash,
+        value: CompiledContract,
+        info: CompiledContractInfo,
+    ) -> std::io::Result<()> {
+        let _ = info;
+        self.put(key, value)
+    }
     fn get(&self, key: &CryptoHash) -> std::io::Result<Option<CompiledContract>>;
     fn has(&self, key: &CryptoHash) -> std::io::Result<bool> {
         self.get(key).map(|entry| entry.is_some())
@@ -147,6 +147,14 @@ pub enum ProtocolFeature {
     Ed25519Verify,
     #[cfg(feature = "protocol_feature_reject_blocks_with_outdated_protocol_version")]
     RejectBlocksWithOutdatedProtocolVersions,
+    /// Delegate actions letting a relayer pay for actions signed by another
+    /// account, see <https://github.com/near/NEPs/pull/366>.
+    #[cfg(feature = "protocol_feature_nep366_delegate_action")]
+    DelegateAction,
+    /// Free storage allowance for accounts without a contract, so that they can
+    /// be created without any balance.
+    #[cfg(feature = "protocol_feature_zero_balance_account")]
+    ZeroBalanceAccount,
 }
 
 /// Both, outgoing and incoming tcp connections to peers, will be rejected if `peer's`
@@ -161,7 +169,7 @@ const STABLE_PROTOCOL_VERSION: ProtocolVersion = 57;
 /// Largest protocol version supported by the current binary.
 pub const PROTOCOL_VERSION: ProtocolVersion = if cfg!(feature = "nightly_protocol") {
     // On nightly, pick big enough version to support all features.
-    132
+    134
 } else {
     // Enable all stable features.
     STABLE_PROTOCOL_VERSION
@@ -234,6 +242,10 @@ impl ProtocolFeature {
             ProtocolFeature::Ed25519Verify => 131,
             #[cfg(feature = "protocol_feature_reject_blocks_with_outdated_protocol_version")]
             ProtocolFeature::RejectBlocksWithOutdatedProtocolVersions => 132,
+            #[cfg(feature = "protocol_feature_nep366_delegate_action")]
+            ProtocolFeature::DelegateAction => 133,
+            #[cfg(feature = "protocol_feature_zero_balance_account")]
+            ProtocolFeature::ZeroBalanceAccount => 134,
         }
     }
 }
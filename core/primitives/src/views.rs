@@ -26,6 +26,8 @@ use crate::block_header::{
 };
 use crate::challenge::{Challenge, ChallengesResult};
 use crate::contract::ContractCode;
+#[cfg(feature = "protocol_feature_nep366_delegate_action")]
+use crate::delegate_action::{DelegateAction, SignedDelegateAction};
 use crate::errors::TxExecutionError;
 use crate::hash::{hash, CryptoHash};
 use crate::merkle::{combine_hash, MerklePath};
@@ -1073,6 +1075,11 @@ pub enum ActionView {
     DeleteAccount {
         beneficiary_id: AccountId,
     },
+    #[cfg(feature = "protocol_feature_nep366_delegate_action")]
+    Delegate {
+        delegate_action: DelegateAction,
+        signature: Signature,
+    },
 }
 
 impl From<Action> for ActionView {
@@ -1101,6 +1108,11 @@ impl From<Action> for ActionView {
             Action::DeleteAccount(action) => {
                 ActionView::DeleteAccount { beneficiary_id: action.beneficiary_id }
             }
+            #[cfg(feature = "protocol_feature_nep366_delegate_action")]
+            Action::Delegate(action) => ActionView::Delegate {
+                delegate_action: action.delegate_action,
+                signature: action.signature,
+            },
         }
     }
 }
@@ -1130,6 +1142,10 @@ impl TryFrom<ActionView> for Action {
             ActionView::DeleteAccount { beneficiary_id } => {
                 Action::DeleteAccount(DeleteAccountAction { beneficiary_id })
             }
+            #[cfg(feature = "protocol_feature_nep366_delegate_action")]
+            ActionView::Delegate { delegate_action, signature } => {
+                Action::Delegate(SignedDelegateAction { delegate_action, signature })
+            }
         })
     }
 }
@@ -2080,6 +2096,10 @@ pub struct ActionCreationConfigView {
 
     /// Base cost of deleting an account.
     pub delete_account_cost: Fee,
+
+    /// Base cost of a delegate action.
+    #[cfg(feature = "protocol_feature_nep366_delegate_action")]
+    pub delegate_cost: Fee,
 }
 
 /// Describes the cost of creating an access key.
@@ -2100,6 +2120,9 @@ pub struct StorageUsageConfigView {
     pub num_bytes_account: u64,
     /// Additional number of bytes for a k/v record
     pub num_extra_bytes_record: u64,
+    /// Storage usage of accounts without a contract that is free of charge.
+    #[cfg(feature = "protocol_feature_zero_balance_account")]
+    pub zero_balance_account_storage_limit: u64,
 }
 
 impl From<RuntimeConfig> for RuntimeConfigView {
@@ -2145,10 +2168,17 @@ impl From<RuntimeConfig> for RuntimeConfigView {
                     },
                     delete_key_cost: config.fees.fee(ActionCosts::delete_key).clone(),
                     delete_account_cost: config.fees.fee(ActionCosts::delete_account).clone(),
+                    #[cfg(feature = "protocol_feature_nep366_delegate_action")]
+                    delegate_cost: config.fees.fee(ActionCosts::delegate).clone(),
                 },
                 storage_usage_config: StorageUsageConfigView {
                     num_bytes_account: config.fees.storage_usage_config.num_bytes_account,
                     num_extra_bytes_record: config.fees.storage_usage_config.num_extra_bytes_record,
+                    #[cfg(feature = "protocol_feature_zero_balance_account")]
+                    zero_balance_account_storage_limit: config
+                        .fees
+                        .storage_usage_config
+                        .zero_balance_account_storage_limit,
                 },
                 burnt_gas_reward: config.fees.burnt_gas_reward,
                 pessimistic_gas_price_inflation_ratio: config
@@ -2181,6 +2211,13 @@ impl From<RuntimeConfigView> for RuntimeConfig {
                         .transaction_costs
                         .storage_usage_config
                         .num_extra_bytes_record,
+                    #[cfg(feature = "protocol_feature_zero_balance_account")]
+                    zero_balance_account_storage_limit: config
+                        .transaction_costs
+                        .storage_usage_config
+                        .zero_balance_account_storage_limit,
+                    #[cfg(not(feature = "protocol_feature_zero_balance_account"))]
+                    zero_balance_account_storage_limit: 0,
                 },
                 burnt_gas_reward: config.transaction_costs.burnt_gas_reward,
                 pessimistic_gas_price_inflation_ratio: config
@@ -2202,6 +2239,8 @@ impl From<RuntimeConfigView> for RuntimeConfig {
                     ActionCosts::new_action_receipt => config.transaction_costs.action_receipt_creation_config.clone(),
                     ActionCosts::new_data_receipt_base => config.transaction_costs.data_receipt_creation_config.base_cost.clone(),
                     ActionCosts::new_data_receipt_byte => config.transaction_costs.data_receipt_creation_config.cost_per_byte.clone(),
+                    #[cfg(feature = "protocol_feature_nep366_delegate_action")]
+                    ActionCosts::delegate => config.transaction_costs.action_creation_config.delegate_cost.clone(),
 
                 },
             },
@@ -0,0 +1,185 @@
+//! Backups of node’s database.
+//!
+//! A backup is a directory holding a RocksDB checkpoint of the database in
+//! `data` subdirectory and a `backup_info.json` file describing it (see
+//! [`BackupInfo`]).  Creating a checkpoint doesn’t require stopping writes to
+//! the database and, if the backup is located on the same file system as the
+//! database, is cheap since data files are hard-linked rather than copied.
+//!
+//! Only the hot (or RPC or archive) database is backed up.
+use std::path::Path;
+use std::sync::Arc;
+
+use anyhow::Context;
+use near_primitives::block::{BlockHeader, Tip};
+use near_primitives::hash::CryptoHash;
+use near_primitives::types::BlockHeight;
+
+use crate::db::RocksDB;
+use crate::metadata::{DbMetadata, DbVersion, DB_VERSION};
+use crate::{DBCol, Mode, Store, StoreConfig, Temperature, HEAD_KEY};
+
+/// Name of the directory within a backup holding the database checkpoint.
+const DATA_DIR: &str = "data";
+
+/// Name of the file within a backup holding serialised [`BackupInfo`].
+const BACKUP_INFO_FILENAME: &str = "backup_info.json";
+
+/// Description of a database backup.
+///
+/// It’s saved alongside the database checkpoint when backup is created and
+/// then compared with what the checkpoint holds when the backup is verified.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct BackupInfo {
+    /// Version of the backed up database.
+    pub db_version: DbVersion,
+    /// Height of the chain head at the moment the backup was taken.
+    pub head_height: BlockHeight,
+    /// Hash of the chain head at the moment the backup was taken.
+    pub head_hash: CryptoHash,
+}
+
+/// Creates a backup of the database backing given store in `dir` directory.
+///
+/// The directory must not exist.  The backup is verified after creation and
+/// its description is returned.
+pub fn create_backup(store: &Store, dir: &Path) -> anyhow::Result<BackupInfo> {
+    anyhow::ensure!(!dir.exists(), "{}: already exists", dir.display());
+    std::fs::create_dir_all(dir)
+        .with_context(|| format!("{}: creating directory", dir.display()))?;
+    let data_dir = dir.join(DATA_DIR);
+    tracing::info!(target: "db", path=%data_dir.display(), "Creating database checkpoint");
+    store
+        .create_checkpoint(&data_dir)
+        .with_context(|| format!("{}: creating checkpoint", data_dir.display()))?;
+    let info = read_backup_info(&data_dir)?;
+    let info_path = dir.join(BACKUP_INFO_FILENAME);
+    std::fs::write(&info_path, serde_json::to_vec_pretty(&info)?)
+        .with_context(|| format!("{}: writing backup info", info_path.display()))?;
+    Ok(info)
+}
+
+/// Verifies that backup in `dir` directory holds the database it describes.
+///
+/// Checks that version and head of the database in the backup match those
+/// saved when the backup was created and that the head block header is
+/// present.  Returns description of the backup.
+pub fn verify_backup(dir: &Path) -> anyhow::Result<BackupInfo> {
+    let info_path = dir.join(BACKUP_INFO_FILENAME);
+    let want: BackupInfo = serde_json::from_slice(
+        &std::fs::read(&info_path)
+            .with_context(|| format!("{}: reading backup info", info_path.display()))?,
+    )
+    .with_context(|| format!("{}: parsing backup info", info_path.display()))?;
+    let got = read_backup_info(&dir.join(DATA_DIR))?;
+    anyhow::ensure!(
+        want == got,
+        "{}: backup doesn’t match its description; expected {want:?} but got {got:?}",
+        dir.display()
+    );
+    Ok(got)
+}
+
+/// Restores database from backup in `dir` directory into `db_path`.
+///
+/// The backup is verified before being restored.  `db_path` must not exist
+/// so existing database has to be moved away (or deleted) first.  The data
+/// is copied so the backup stays intact.
+pub fn restore_backup(dir: &Path, db_path: &Path) -> anyhow::Result<BackupInfo> {
+    let info = verify_backup(dir)?;
+    anyhow::ensure!(
+        info.db_version <= DB_VERSION,
+        "{}: database version {} is newer than version {DB_VERSION} supported by this binary",
+        dir.display(),
+        info.db_version
+    );
+    anyhow::ensure!(
+        !db_path.exists(),
+        "{}: already exists; move existing database away before restoring a backup",
+        db_path.display()
+    );
+    let data_dir = dir.join(DATA_DIR);
+    tracing::info!(target: "db", from=%data_dir.display(), to=%db_path.display(),
+                   "Restoring database from backup");
+    std::fs::create_dir_all(db_path)?;
+    for entry in std::fs::read_dir(&data_dir)? {
+        let entry = entry?;
+        std::fs::copy(entry.path(), db_path.join(entry.file_name()))
+            .with_context(|| format!("{}: copying", entry.path().display()))?;
+    }
+    Ok(info)
+}
+
+/// Reads description of a database located at `path`.
+fn read_backup_info(path: &Path) -> anyhow::Result<BackupInfo> {
+    let db = RocksDB::open(path, &StoreConfig::default(), Mode::ReadOnly, Temperature::Hot)
+        .with_context(|| format!("{}: opening database", path.display()))?;
+    let db_version = DbMetadata::read(&db)?.version;
+    let store = Store { storage: Arc::new(db), contract_cache_last_used: Default::default() };
+    let head = store
+        .get_ser::<Tip>(DBCol::BlockMisc, HEAD_KEY)?
+        .with_context(|| format!("{}: missing chain head", path.display()))?;
+    let header = store
+        .get_ser::<BlockHeader>(DBCol::BlockHeader, head.last_block_hash.as_ref())?
+        .with_context(|| {
+        format!("{}: missing header of head block {}", path.display(), head.last_block_hash)
+    })?;
+    anyhow::ensure!(
+        header.height() == head.height,
+        "{}: head block {} is at height {} rather than {}",
+        path.display(),
+        head.last_block_hash,
+        header.height(),
+        head.height
+    );
+    Ok(BackupInfo { db_version, head_height: head.height, head_hash: head.last_block_hash })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::NodeStorage;
+    use near_primitives::block::Block;
+    use near_primitives::version::PROTOCOL_VERSION;
+
+    #[test]
+    fn test_backup_and_restore() {
+        let (home, opener) = NodeStorage::test_opener();
+        let store = opener.open().unwrap().get_store(Temperature::Hot);
+
+        let block = Block::genesis(
+            PROTOCOL_VERSION,
+            vec![],
+            near_primitives::time::Utc::now(),
+            0,
+            100,
+            1_000_000,
+            CryptoHash::default(),
+        );
+        let mut update = store.store_update();
+        update.insert_ser(DBCol::BlockHeader, block.hash().as_ref(), block.header()).unwrap();
+        update.set_ser(DBCol::BlockMisc, HEAD_KEY, &Tip::from_header(block.header())).unwrap();
+        update.commit().unwrap();
+
+        let backup_dir = home.path().join("backup");
+        let info = create_backup(&store, &backup_dir).unwrap();
+        assert_eq!(
+            BackupInfo { db_version: DB_VERSION, head_height: 0, head_hash: *block.hash() },
+            info
+        );
+        assert!(create_backup(&store, &backup_dir).is_err());
+        assert_eq!(info, verify_backup(&backup_dir).unwrap());
+
+        let db_path = home.path().join("restored");
+        assert_eq!(info, restore_backup(&backup_dir, &db_path).unwrap());
+        assert_eq!(info, read_backup_info(&db_path).unwrap());
+        // Restoring over an existing database is refused.
+        assert!(restore_backup(&backup_dir, &db_path).is_err());
+
+        // Backup whose description doesn’t match the data fails verification.
+        let info_path = backup_dir.join(BACKUP_INFO_FILENAME);
+        let bogus = BackupInfo { head_height: 42, ..info };
+        std::fs::write(&info_path, serde_json::to_vec(&bogus).unwrap()).unwrap();
+        assert!(verify_backup(&backup_dir).is_err());
+    }
+}
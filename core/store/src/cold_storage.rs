@@ -1,7 +1,7 @@
 use crate::columns::DBKeyType;
 use crate::db::ColdDB;
 use crate::trie::TrieRefcountChange;
-use crate::{DBCol, DBTransaction, Database, Store, TrieChanges, HEAD_KEY};
+use crate::{DBCol, DBTransaction, Database, Store, TrieChanges, COLD_HEAD_KEY, HEAD_KEY};
 
 use borsh::{BorshDeserialize, BorshSerialize};
 use near_primitives::block::{Block, BlockHeader, Tip};
@@ -98,6 +98,7 @@ fn copy_from_store<D: Database>(
 }
 
 /// This function sets HEAD key in BlockMisc column to the Tip that reflect provided height.
+/// It also sets COLD_HEAD key in BlockMisc column of the hot store to the same Tip.
 /// This function should be used after all of the blocks from genesis to `height` inclusive had been copied.
 ///
 /// This method relies on the fact that BlockHeight and BlockHeader are not garbage collectable.
@@ -115,19 +116,65 @@ pub fn update_cold_head<D: Database>(
     let height_key = height.to_le_bytes();
     let block_hash_key = store.get_or_err(DBCol::BlockHeight, &height_key)?.as_slice().to_vec();
 
+    let tip = Tip::from_header(
+        &store.get_ser_or_err::<BlockHeader>(DBCol::BlockHeader, &block_hash_key)?,
+    )
+    .try_to_vec()?;
+
     let mut transaction = DBTransaction::new();
-    transaction.set(
-        DBCol::BlockMisc,
-        HEAD_KEY.to_vec(),
-        Tip::from_header(
-            &store.get_ser_or_err::<BlockHeader>(DBCol::BlockHeader, &block_hash_key)?,
-        )
-        .try_to_vec()?,
-    );
+    transaction.set(DBCol::BlockMisc, HEAD_KEY.to_vec(), tip.clone());
     cold_db.write(transaction)?;
+
+    // Hot storage is garbage collected only up to COLD_HEAD so it must be
+    // updated only after the data has been persisted in cold storage.
+    let mut store_update = hot_store.store_update();
+    store_update.set(DBCol::BlockMisc, COLD_HEAD_KEY, &tip);
+    store_update.commit()?;
     return Ok(());
 }
 
+/// Copies all the data of cold columns from hot store to cold database.
+///
+/// This is used to populate a newly created cold database from an existing
+/// archival database.  Data is written to cold database in transactions of
+/// roughly `batch_size` bytes.  Since the function only ever sets values, it
+/// can be safely started from scratch again if it was interrupted.
+///
+/// The function does not update HEAD of cold database.  Once it finishes, the
+/// caller should use [`update_cold_head`] to set it to a final height observed
+/// *before* the copying started.
+pub fn copy_all_data_to_cold<D: Database>(
+    cold_db: &ColdDB<D>,
+    hot_store: &Store,
+    batch_size: usize,
+) -> io::Result<()> {
+    for col in DBCol::iter() {
+        if !col.is_cold() {
+            continue;
+        }
+        let _span =
+            tracing::info_span!(target: "store", "copy all data to cold db", col = %col).entered();
+        let mut transaction = DBTransaction::new();
+        let mut transaction_size = 0;
+        for item in hot_store.iter(col) {
+            let (key, value) = item?;
+            transaction_size += key.len() + value.len();
+            transaction.set(col, key.into_vec(), value.into_vec());
+            crate::metrics::COLD_MIGRATION_INITIAL_WRITES
+                .with_label_values(&[<&str>::from(col)])
+                .inc();
+            if transaction_size >= batch_size {
+                cold_db.write(std::mem::take(&mut transaction))?;
+                transaction_size = 0;
+            }
+        }
+        if transaction_size > 0 {
+            cold_db.write(transaction)?;
+        }
+    }
+    Ok(())
+}
+
 pub fn test_cold_genesis_update<D: Database>(
     cold_db: &ColdDB<D>,
     hot_store: &Store,
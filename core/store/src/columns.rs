@@ -225,6 +225,11 @@ pub enum DBCol {
     /// - *Rows*: ContractCacheKey or code hash (not sure)
     /// - *Column type*: near-vm-runner CacheRecord
     CachedContractCode,
+    /// Description of the entries of DBCol::CachedContractCode, used to evict
+    /// and prune the cache.  See `crate::contract_cache`.
+    /// - *Rows*: ContractCacheKey
+    /// - *Column type*: ContractCacheEntryInfo
+    CachedContractCodeInfo,
     /// Epoch validator information used for rpc purposes.
     /// - *Rows*: epoch id (CryptoHash)
     /// - *Column type*: EpochSummary
@@ -452,6 +457,7 @@ impl DBCol {
             DBCol::ProcessedBlockHeights => &[DBKeyType::BlockHeight],
             DBCol::Receipts => &[DBKeyType::ReceiptHash],
             DBCol::CachedContractCode => &[DBKeyType::ContractCacheKey],
+            DBCol::CachedContractCodeInfo => &[DBKeyType::ContractCacheKey],
             DBCol::EpochValidatorInfo => &[DBKeyType::EpochId],
             DBCol::HeaderHashesByHeight => &[DBKeyType::BlockHeight],
             DBCol::StateChangesForSplitStates => &[DBKeyType::BlockHash, DBKeyType::ShardId],
@@ -0,0 +1,406 @@
+//! Bookkeeping of the compiled contracts cache.
+//!
+//! Compiled contracts are stored in `DBCol::CachedContractCode` under keys
+//! which depend on the VM and its config, so whenever those change, old
+//! entries stop being used but stay on disk.  To be able to remove them, every
+//! entry written with [`CompiledContractCache::put_with_info`] is described by
+//! a [`ContractCacheEntryInfo`] stored under the same key in
+//! `DBCol::CachedContractCodeInfo`.  Entries without the description, i.e.
+//! written before it has been introduced or with plain `put`, are called
+//! untracked.  The first [`prune`] run describes the untracked entries as
+//! compiled by an unknown VM and never used, so that they are accounted for
+//! and evicted first, unless they are used in the meantime.
+//!
+//! Reading an entry doesn’t write to the database: the time of the last use
+//! is kept in memory, by the [`Store`], and written by the next [`prune`] run.
+//!
+//! [`CompiledContractCache::put_with_info`]: near_primitives::types::CompiledContractCache::put_with_info
+use std::collections::{HashMap, HashSet};
+use std::io;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::RwLock;
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use near_primitives::hash::CryptoHash;
+use near_primitives::time::Clock;
+use near_primitives::types::CompiledContractInfo;
+
+use crate::{metrics, DBCol, Store, StoreUpdate};
+
+/// Key in `DBCol::BlockMisc` marking that the untracked entries have been
+/// described, see [`prune`].
+const UNTRACKED_DESCRIBED_KEY: &[u8] = b"CONTRACT_CACHE_UNTRACKED_DESCRIBED";
+
+/// `vm_id` of the entries written before their description has been introduced.
+pub const UNKNOWN_VM_ID: CryptoHash = CryptoHash::new();
+
+/// Description of an entry of the compiled contracts cache.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ContractCacheEntryInfo {
+    pub info: CompiledContractInfo,
+    /// Size of the serialised compiled contract in bytes.
+    pub size: u64,
+    /// When the entry has been last written or read from the database, in
+    /// seconds since the Unix epoch.  Reads are only reflected once [`prune`]
+    /// runs, and contracts served from the in-memory cache of the VM don’t
+    /// update it.
+    pub last_used: u64,
+}
+
+impl ContractCacheEntryInfo {
+    /// Description of an entry written without one.
+    fn untracked(size: u64) -> Self {
+        let info = CompiledContractInfo {
+            code_hash: CryptoHash::default(),
+            vm_id: UNKNOWN_VM_ID,
+            compile_time_us: 0,
+        };
+        Self { info, size, last_used: 0 }
+    }
+
+    /// Whether the entry has been written without its description.
+    pub fn is_untracked(&self) -> bool {
+        self.info.vm_id == UNKNOWN_VM_ID
+    }
+}
+
+/// Entry of the compiled contracts cache as returned by [`list`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ContractCacheEntry {
+    pub key: CryptoHash,
+    /// Size of the serialised compiled contract in bytes.
+    pub size: u64,
+    /// `None` if the entry is untracked and hasn’t been described yet.
+    pub info: Option<ContractCacheEntryInfo>,
+}
+
+/// Statistics of a single [`prune`] run.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PruneStats {
+    /// Number of removed entries compiled by VMs which aren’t in use.
+    pub stale: u64,
+    /// Number of least recently used entries evicted to fit in the size limit.
+    pub evicted: u64,
+    /// Number of removed untracked entries.
+    pub untracked: u64,
+    /// Number of untracked entries which have been described.
+    pub described: u64,
+    /// Total size of the removed entries in bytes.
+    pub freed_bytes: u64,
+    /// Total size of the tracked entries left in the cache in bytes.
+    pub remaining_bytes: u64,
+}
+
+/// Current time in the unit of [`ContractCacheEntryInfo::last_used`].
+pub(crate) fn now() -> u64 {
+    Clock::utc().timestamp().max(0) as u64
+}
+
+/// Times of the last use of the entries read since they have been last
+/// written by [`prune`].
+///
+/// Shared by the stores of the same database.  Entries which have already
+/// been read only need the read lock to be updated.
+#[derive(Default)]
+pub(crate) struct LastUsed(RwLock<HashMap<CryptoHash, AtomicU64>>);
+
+impl LastUsed {
+    /// Records that the entry under `key` has just been used.
+    pub(crate) fn record(&self, key: &CryptoHash) {
+        let now = now();
+        if let Some(last_used) = self.0.read().unwrap().get(key) {
+            last_used.fetch_max(now, Ordering::Relaxed);
+            return;
+        }
+        self.0.write().unwrap().entry(*key).or_default().fetch_max(now, Ordering::Relaxed);
+    }
+
+    /// Returns the recorded uses and forgets them.
+    fn take(&self) -> HashMap<CryptoHash, u64> {
+        let last_used = std::mem::take(&mut *self.0.write().unwrap());
+        last_used.into_iter().map(|(key, last_used)| (key, last_used.into_inner())).collect()
+    }
+}
+
+fn parse_key(key: &[u8]) -> io::Result<CryptoHash> {
+    CryptoHash::try_from(key).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+}
+
+fn read_infos(store: &Store) -> io::Result<HashMap<CryptoHash, ContractCacheEntryInfo>> {
+    store
+        .iter(DBCol::CachedContractCodeInfo)
+        .map(|row| {
+            let (key, value) = row?;
+            Ok((parse_key(&key)?, ContractCacheEntryInfo::try_from_slice(&value)?))
+        })
+        .collect()
+}
+
+fn remove(update: &mut StoreUpdate, key: &[u8]) {
+    update.delete(DBCol::CachedContractCode, key);
+    update.delete(DBCol::CachedContractCodeInfo, key);
+}
+
+/// Lists all entries of the compiled contracts cache.
+///
+/// This reads the whole cache so it’s meant for offline tools only.
+pub fn list(store: &Store) -> io::Result<Vec<ContractCacheEntry>> {
+    let mut infos = read_infos(store)?;
+    store
+        .iter(DBCol::CachedContractCode)
+        .map(|row| {
+            let (key, value) = row?;
+            let key = parse_key(&key)?;
+            Ok(ContractCacheEntry { key, size: value.len() as u64, info: infos.remove(&key) })
+        })
+        .collect()
+}
+
+/// Writes the times of the last use of the entries, recorded in memory since
+/// the last run, and removes entries of the compiled contracts cache which
+/// have been compiled by VMs other than `vm_ids`.  Then evicts the least
+/// recently used entries until the total size of the cache doesn’t exceed
+/// `max_size` bytes.
+///
+/// Finding untracked entries requires reading the whole cache, so it’s done
+/// only by the first run, which describes them, or if `remove_untracked` is
+/// set, in which case they are all removed.
+pub fn prune(
+    store: &Store,
+    vm_ids: &HashSet<CryptoHash>,
+    max_size: Option<u64>,
+    remove_untracked: bool,
+) -> io::Result<PruneStats> {
+    let mut stats = PruneStats::default();
+    let mut update = store.store_update();
+    let mut infos = read_infos(store)?;
+
+    if remove_untracked || !store.exists(DBCol::BlockMisc, UNTRACKED_DESCRIBED_KEY)? {
+        for row in store.iter(DBCol::CachedContractCode) {
+            let (key, value) = row?;
+            let key = parse_key(&key)?;
+            if infos.contains_key(&key) {
+                continue;
+            }
+            if remove_untracked {
+                update.delete(DBCol::CachedContractCode, key.as_ref());
+                stats.untracked += 1;
+                stats.freed_bytes += value.len() as u64;
+            } else {
+                let entry = ContractCacheEntryInfo::untracked(value.len() as u64);
+                update.set_ser(DBCol::CachedContractCodeInfo, key.as_ref(), &entry)?;
+                infos.insert(key, entry);
+                stats.described += 1;
+            }
+        }
+        update.set(DBCol::BlockMisc, UNTRACKED_DESCRIBED_KEY, &[]);
+    }
+
+    // Entries read in the meantime, but removed since, are forgotten.
+    for (key, last_used) in store.contract_cache_last_used.take() {
+        if let Some(entry) = infos.get_mut(&key) {
+            if entry.last_used < last_used {
+                entry.last_used = last_used;
+                update.set_ser(DBCol::CachedContractCodeInfo, key.as_ref(), &*entry)?;
+            }
+        }
+    }
+
+    let mut live = Vec::with_capacity(infos.len());
+    for (key, entry) in infos {
+        if remove_untracked && entry.is_untracked() {
+            remove(&mut update, key.as_ref());
+            stats.untracked += 1;
+            stats.freed_bytes += entry.size;
+        } else if entry.is_untracked() || vm_ids.contains(&entry.info.vm_id) {
+            live.push((key, entry));
+        } else {
+            remove(&mut update, key.as_ref());
+            stats.stale += 1;
+            stats.freed_bytes += entry.size;
+        }
+    }
+
+    let mut total: u64 = live.iter().map(|(_, entry)| entry.size).sum();
+    if let Some(max_size) = max_size {
+        live.sort_by_key(|(_, entry)| entry.last_used);
+        for (key, entry) in &live {
+            if total <= max_size {
+                break;
+            }
+            remove(&mut update, key.as_ref());
+            stats.evicted += 1;
+            stats.freed_bytes += entry.size;
+            total -= entry.size;
+        }
+    }
+    stats.remaining_bytes = total;
+    update.commit()?;
+
+    metrics::COMPILED_CONTRACT_CACHE_REMOVED.with_label_values(&["stale"]).inc_by(stats.stale);
+    metrics::COMPILED_CONTRACT_CACHE_REMOVED.with_label_values(&["evicted"]).inc_by(stats.evicted);
+    metrics::COMPILED_CONTRACT_CACHE_REMOVED
+        .with_label_values(&["untracked"])
+        .inc_by(stats.untracked);
+    metrics::COMPILED_CONTRACT_CACHE_SIZE.set(total as i64);
+    Ok(stats)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::StoreCompiledContractCache;
+    use near_primitives::hash::hash;
+    use near_primitives::types::{CompiledContract, CompiledContractCache};
+
+    fn put(store: &Store, key: &[u8], vm: &[u8], size: usize, last_used: u64) -> CryptoHash {
+        let key = hash(key);
+        let info = CompiledContractInfo { code_hash: key, vm_id: hash(vm), compile_time_us: 1000 };
+        let cache = StoreCompiledContractCache::new(store);
+        // Borsh adds 5 bytes: variant tag and vector length.
+        let code = CompiledContract::Code(vec![0; size - 5]);
+        cache.put_with_info(&key, code, info.clone()).unwrap();
+        let mut update = store.store_update();
+        let entry = ContractCacheEntryInfo { info, size: size as u64, last_used };
+        update.set_ser(DBCol::CachedContractCodeInfo, key.as_ref(), &entry).unwrap();
+        update.commit().unwrap();
+        key
+    }
+
+    fn keys(store: &Store) -> Vec<(CryptoHash, bool)> {
+        let mut keys = list(store)
+            .unwrap()
+            .into_iter()
+            .map(|entry| (entry.key, entry.info.is_some()))
+            .collect::<Vec<_>>();
+        keys.sort();
+        keys
+    }
+
+    fn put_untracked(store: &Store, key: &[u8], size: usize) -> CryptoHash {
+        let key = hash(key);
+        let code = CompiledContract::Code(vec![0; size - 5]);
+        StoreCompiledContractCache::new(store).put(&key, code).unwrap();
+        key
+    }
+
+    #[test]
+    fn test_prune() {
+        let store = crate::test_utils::create_test_store();
+        let old = put(&store, b"old", b"vm", 100, 1);
+        let new = put(&store, b"new", b"vm", 100, 3);
+        let recent = put(&store, b"recent", b"vm", 100, 2);
+        let stale = put(&store, b"stale", b"old vm", 100, 4);
+        let untracked = put_untracked(&store, b"untracked", 100);
+        let entries = list(&store).unwrap();
+        assert_eq!(entries.len(), 5);
+        assert!(entries.iter().all(|entry| entry.size == 100));
+
+        // The first run describes the untracked entry.
+        let vm_ids = HashSet::from([hash(b"vm")]);
+        let stats = prune(&store, &vm_ids, None, false).unwrap();
+        assert_eq!(
+            stats,
+            PruneStats {
+                stale: 1,
+                evicted: 0,
+                untracked: 0,
+                described: 1,
+                freed_bytes: 100,
+                remaining_bytes: 400
+            }
+        );
+        let mut want = vec![(old, true), (new, true), (recent, true), (untracked, true)];
+        want.sort();
+        assert_eq!(keys(&store), want);
+        assert!(!store.exists(DBCol::CachedContractCodeInfo, stale.as_ref()).unwrap());
+        let entry = list(&store).unwrap().into_iter().find(|entry| entry.key == untracked);
+        assert!(entry.unwrap().info.unwrap().is_untracked());
+
+        // The untracked entry is evicted first.
+        let stats = prune(&store, &vm_ids, Some(250), false).unwrap();
+        assert_eq!(
+            stats,
+            PruneStats {
+                stale: 0,
+                evicted: 2,
+                untracked: 0,
+                described: 0,
+                freed_bytes: 200,
+                remaining_bytes: 200
+            }
+        );
+        let mut want = vec![(new, true), (recent, true)];
+        want.sort();
+        assert_eq!(keys(&store), want);
+        assert!(!store.exists(DBCol::CachedContractCodeInfo, old.as_ref()).unwrap());
+    }
+
+    #[test]
+    fn test_prune_remove_untracked() {
+        let store = crate::test_utils::create_test_store();
+        let vm_ids = HashSet::from([hash(b"vm")]);
+        let new = put(&store, b"new", b"vm", 100, 3);
+        put_untracked(&store, b"described", 100);
+        assert_eq!(prune(&store, &vm_ids, None, false).unwrap().described, 1);
+        put(&store, b"stale", b"old vm", 100, 4);
+        put_untracked(&store, b"untracked", 100);
+
+        let stats = prune(&store, &vm_ids, None, true).unwrap();
+        assert_eq!(
+            stats,
+            PruneStats {
+                stale: 1,
+                evicted: 0,
+                untracked: 2,
+                described: 0,
+                freed_bytes: 300,
+                remaining_bytes: 100
+            }
+        );
+        assert_eq!(keys(&store), vec![(new, true)]);
+    }
+
+    #[test]
+    fn test_get_records_last_used() {
+        let store = crate::test_utils::create_test_store();
+        let key = put(&store, b"code", b"vm", 100, 0);
+        let untracked = put_untracked(&store, b"untracked code", 100);
+        let cache = StoreCompiledContractCache::new(&store);
+        assert!(cache.get(&key).unwrap().is_some());
+        assert!(cache.get(&untracked).unwrap().is_some());
+        let last_used = |key: &CryptoHash| {
+            let entries = list(&store).unwrap();
+            let entry = entries.into_iter().find(|entry| &entry.key == key).unwrap();
+            entry.info.map(|info| info.last_used)
+        };
+        // Reads don’t write to the database.
+        assert_eq!(last_used(&key), Some(0));
+        assert_eq!(last_used(&untracked), None);
+
+        prune(&store, &HashSet::from([hash(b"vm")]), None, false).unwrap();
+        assert!(last_used(&key).unwrap() > 0);
+        // The untracked entry has been described and used since.
+        assert!(last_used(&untracked).unwrap() > 0);
+    }
+
+    #[test]
+    fn test_prune_forgets_removed_entries() {
+        let store = crate::test_utils::create_test_store();
+        let key = put(&store, b"code", b"vm", 100, 0);
+        let cache = StoreCompiledContractCache::new(&store);
+        assert!(cache.get(&key).unwrap().is_some());
+        let mut update = store.store_update();
+        remove(&mut update, key.as_ref());
+        update.commit().unwrap();
+
+        prune(&store, &HashSet::from([hash(b"vm")]), None, false).unwrap();
+        assert!(store.contract_cache_last_used.take().is_empty());
+        // Other stores don’t share the recorded uses.
+        let other = crate::test_utils::create_test_store();
+        let key = put(&other, b"code", b"vm", 100, 0);
+        assert!(StoreCompiledContractCache::new(&other).get(&key).unwrap().is_some());
+        assert!(store.contract_cache_last_used.take().is_empty());
+        assert_eq!(other.contract_cache_last_used.take().keys().collect::<Vec<_>>(), vec![&key]);
+    }
+}
@@ -7,12 +7,16 @@ mod colddb;
 pub mod refcount;
 pub(crate) mod rocksdb;
 mod slice;
+#[cfg(feature = "cold_store")]
+mod splitdb;
 mod testdb;
 
 #[cfg(feature = "cold_store")]
 pub use self::colddb::ColdDB;
 pub use self::rocksdb::RocksDB;
 pub use self::slice::DBSlice;
+#[cfg(feature = "cold_store")]
+pub use self::splitdb::SplitDB;
 pub use self::testdb::TestDB;
 
 pub const HEAD_KEY: &[u8; 4] = b"HEAD";
@@ -21,6 +25,9 @@ pub const CHUNK_TAIL_KEY: &[u8; 10] = b"CHUNK_TAIL";
 pub const FORK_TAIL_KEY: &[u8; 9] = b"FORK_TAIL";
 pub const HEADER_HEAD_KEY: &[u8; 11] = b"HEADER_HEAD";
 pub const FINAL_HEAD_KEY: &[u8; 10] = b"FINAL_HEAD";
+/// Key in hot storage’s BlockMisc column holding the Tip up to which data has
+/// been copied to cold storage.  Set only on nodes with split storage.
+pub const COLD_HEAD_KEY: &[u8; 9] = b"COLD_HEAD";
 pub const LATEST_KNOWN_KEY: &[u8; 12] = b"LATEST_KNOWN";
 pub const LARGEST_TARGET_HEIGHT_KEY: &[u8; 21] = b"LARGEST_TARGET_HEIGHT";
 pub const GENESIS_JSON_HASH_KEY: &[u8; 17] = b"GENESIS_JSON_HASH";
@@ -146,6 +153,14 @@ pub trait Database: Sync + Send {
 
     /// Returns statistics about the database if available.
     fn get_store_statistics(&self) -> Option<StoreStatistics>;
+
+    /// Creates a checkpoint of the database in given directory.
+    ///
+    /// The checkpoint is a consistent point-in-time copy of the database which
+    /// can be opened as a regular database.  Writes to the database don’t need
+    /// to be stopped while it’s created.  Fails if the directory already
+    /// exists or if the database doesn’t support checkpoints.
+    fn create_checkpoint(&self, path: &std::path::Path) -> io::Result<()>;
 }
 
 fn assert_no_overwrite(col: DBCol, key: &[u8], value: &[u8], old_value: &[u8]) {
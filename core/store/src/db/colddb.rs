@@ -208,6 +208,10 @@ impl<D: Database> super::Database for ColdDB<D> {
     fn get_store_statistics(&self) -> Option<crate::StoreStatistics> {
         self.cold.get_store_statistics()
     }
+
+    fn create_checkpoint(&self, path: &std::path::Path) -> std::io::Result<()> {
+        self.cold.create_checkpoint(path)
+    }
 }
 
 /// Returns key as used in cold database for given column in hot database.
@@ -354,6 +354,11 @@ impl Database for RocksDB {
             Some(result)
         }
     }
+
+    fn create_checkpoint(&self, path: &Path) -> io::Result<()> {
+        let cp = ::rocksdb::checkpoint::Checkpoint::new(&self.db).map_err(into_other)?;
+        cp.create_checkpoint(path).map_err(into_other)
+    }
 }
 
 /// DB level options
@@ -0,0 +1,103 @@
+use std::io;
+use std::sync::Arc;
+
+use crate::db::{DBIterator, DBSlice, DBTransaction, Database, StoreStatistics};
+use crate::DBCol;
+
+/// A database which reads from the hot storage and falls back to the cold
+/// storage for data which has been garbage collected from the hot one.
+///
+/// This lets view requests access archival data (e.g. state of old blocks) on
+/// nodes with split storage the same way they would on a single archival
+/// database.  Only point reads of cold columns (see [`DBCol::is_cold`]) fall
+/// back to the cold storage.  Iterators, writes and all the maintenance
+/// operations only ever touch the hot storage.
+pub struct SplitDB {
+    hot: Arc<dyn Database>,
+    cold: Arc<dyn Database>,
+}
+
+impl SplitDB {
+    pub fn new(hot: Arc<dyn Database>, cold: Arc<dyn Database>) -> Self {
+        Self { hot, cold }
+    }
+}
+
+impl Database for SplitDB {
+    fn get_raw_bytes(&self, col: DBCol, key: &[u8]) -> io::Result<Option<DBSlice<'_>>> {
+        match self.hot.get_raw_bytes(col, key)? {
+            None if col.is_cold() => self.cold.get_raw_bytes(col, key),
+            value => Ok(value),
+        }
+    }
+
+    fn get_with_rc_stripped(&self, col: DBCol, key: &[u8]) -> io::Result<Option<DBSlice<'_>>> {
+        assert!(col.is_rc());
+        match self.hot.get_with_rc_stripped(col, key)? {
+            None if col.is_cold() => self.cold.get_with_rc_stripped(col, key),
+            value => Ok(value),
+        }
+    }
+
+    fn iter<'a>(&'a self, column: DBCol) -> DBIterator<'a> {
+        self.hot.iter(column)
+    }
+
+    fn iter_prefix<'a>(&'a self, col: DBCol, key_prefix: &'a [u8]) -> DBIterator<'a> {
+        self.hot.iter_prefix(col, key_prefix)
+    }
+
+    fn iter_raw_bytes<'a>(&'a self, column: DBCol) -> DBIterator<'a> {
+        self.hot.iter_raw_bytes(column)
+    }
+
+    fn write(&self, batch: DBTransaction) -> io::Result<()> {
+        self.hot.write(batch)
+    }
+
+    fn flush(&self) -> io::Result<()> {
+        self.hot.flush()
+    }
+
+    fn compact(&self) -> io::Result<()> {
+        self.hot.compact()
+    }
+
+    fn get_store_statistics(&self) -> Option<StoreStatistics> {
+        self.hot.get_store_statistics()
+    }
+
+    fn create_checkpoint(&self, path: &std::path::Path) -> io::Result<()> {
+        self.hot.create_checkpoint(path)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::db::{ColdDB, TestDB};
+
+    #[test]
+    fn test_falls_back_to_cold() {
+        let hot: Arc<dyn Database> = TestDB::new();
+        let cold: Arc<dyn Database> = Arc::new(ColdDB::new(hot.clone(), TestDB::default()));
+        let db = SplitDB::new(hot.clone(), cold.clone());
+
+        let mut transaction = DBTransaction::new();
+        transaction.set(DBCol::Block, b"hot".to_vec(), b"hot value".to_vec());
+        transaction.set(DBCol::BlockMisc, b"hot".to_vec(), b"hot value".to_vec());
+        hot.write(transaction).unwrap();
+        let mut transaction = DBTransaction::new();
+        transaction.set(DBCol::Block, b"hot".to_vec(), b"cold value".to_vec());
+        transaction.set(DBCol::Block, b"cold".to_vec(), b"cold value".to_vec());
+        transaction.set(DBCol::BlockMisc, b"cold".to_vec(), b"cold value".to_vec());
+        cold.write(transaction).unwrap();
+
+        let get = |col, key: &[u8]| db.get_raw_bytes(col, key).unwrap().map(|v| v.to_vec());
+        assert_eq!(get(DBCol::Block, b"hot"), Some(b"hot value".to_vec()));
+        assert_eq!(get(DBCol::Block, b"cold"), Some(b"cold value".to_vec()));
+        // Only cold columns fall back to the cold storage.
+        assert_eq!(get(DBCol::BlockMisc, b"hot"), Some(b"hot value".to_vec()));
+        assert_eq!(get(DBCol::BlockMisc, b"cold"), None);
+    }
+}
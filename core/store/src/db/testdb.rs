@@ -96,4 +96,8 @@ impl Database for TestDB {
     fn get_store_statistics(&self) -> Option<StoreStatistics> {
         None
     }
+
+    fn create_checkpoint(&self, _path: &std::path::Path) -> io::Result<()> {
+        Err(io::Error::new(io::ErrorKind::Unsupported, "TestDB doesn’t support checkpoints"))
+    }
 }
@@ -11,7 +11,7 @@ use once_cell::sync::Lazy;
 
 pub use columns::DBCol;
 pub use db::{
-    CHUNK_TAIL_KEY, FINAL_HEAD_KEY, FORK_TAIL_KEY, HEADER_HEAD_KEY, HEAD_KEY,
+    CHUNK_TAIL_KEY, COLD_HEAD_KEY, FINAL_HEAD_KEY, FORK_TAIL_KEY, HEADER_HEAD_KEY, HEAD_KEY,
     LARGEST_TARGET_HEIGHT_KEY, LATEST_KNOWN_KEY, TAIL_KEY,
 };
 use near_crypto::PublicKey;
@@ -23,7 +23,9 @@ use near_primitives::hash::CryptoHash;
 use near_primitives::receipt::{DelayedReceiptIndices, Receipt, ReceivedData};
 pub use near_primitives::shard_layout::ShardUId;
 use near_primitives::trie_key::{trie_key_parsers, TrieKey};
-use near_primitives::types::{AccountId, CompiledContract, CompiledContractCache, StateRoot};
+use near_primitives::types::{
+    AccountId, CompiledContract, CompiledContractCache, CompiledContractInfo, StateRoot,
+};
 
 use crate::db::{
     refcount, DBIterator, DBOp, DBSlice, DBTransaction, Database, StoreStatistics,
@@ -39,10 +41,12 @@ pub use crate::trie::{
 };
 pub use flat_state::FlatStateDelta;
 
+pub mod backup;
 #[cfg(feature = "cold_store")]
 pub mod cold_storage;
 mod columns;
 pub mod config;
+pub mod contract_cache;
 pub mod db;
 pub mod flat_state;
 pub mod metadata;
@@ -76,6 +80,8 @@ pub enum Temperature {
 /// the storage.
 pub struct NodeStorage<D = crate::db::RocksDB> {
     hot_storage: Arc<dyn Database>,
+    /// Shared by all [`Store`]s of the hot storage.
+    contract_cache_last_used: Arc<contract_cache::LastUsed>,
     #[cfg(feature = "cold_store")]
     cold_storage: Option<Arc<crate::db::ColdDB<D>>>,
     #[cfg(not(feature = "cold_store"))]
@@ -94,6 +100,8 @@ pub struct NodeStorage<D = crate::db::RocksDB> {
 #[derive(Clone)]
 pub struct Store {
     storage: Arc<dyn Database>,
+    /// Uses of the compiled contracts cache entries, see [`contract_cache`].
+    contract_cache_last_used: Arc<contract_cache::LastUsed>,
 }
 
 // Those are temporary.  While cold_store feature is stabilised, remove those
@@ -131,7 +139,12 @@ impl NodeStorage {
             .map(|cold_db| Arc::new(crate::db::ColdDB::new(hot_storage.clone(), cold_db)));
         #[cfg(not(feature = "cold_store"))]
         let cold_storage = cold_storage.map(|_| unreachable!());
-        Self { hot_storage, cold_storage, _phantom: PhantomData {} }
+        Self {
+            hot_storage,
+            contract_cache_last_used: Default::default(),
+            cold_storage,
+            _phantom: PhantomData {},
+        }
     }
 
     /// Initialises an opener for a new temporary test store.
@@ -164,7 +177,12 @@ impl NodeStorage {
     /// possibly [`crate::test_utils::create_test_store`] (depending whether you
     /// need [`NodeStorage`] or [`Store`] object.
     pub fn new(storage: Arc<dyn Database>) -> Self {
-        Self { hot_storage: storage, cold_storage: None, _phantom: PhantomData {} }
+        Self {
+            hot_storage: storage,
+            contract_cache_last_used: Default::default(),
+            cold_storage: None,
+            _phantom: PhantomData {},
+        }
     }
 }
 
@@ -183,12 +201,35 @@ impl<D: Database + 'static> NodeStorage<D> {
     /// cold.
     pub fn get_store(&self, temp: Temperature) -> Store {
         match temp {
-            Temperature::Hot => Store { storage: self.hot_storage.clone() },
+            Temperature::Hot => Store {
+                storage: self.hot_storage.clone(),
+                contract_cache_last_used: self.contract_cache_last_used.clone(),
+            },
             #[cfg(feature = "cold_store")]
-            Temperature::Cold => Store { storage: self.cold_storage.as_ref().unwrap().clone() },
+            Temperature::Cold => Store {
+                storage: self.cold_storage.as_ref().unwrap().clone(),
+                contract_cache_last_used: Default::default(),
+            },
         }
     }
 
+    /// Returns a store which reads hot storage and falls back to cold storage
+    /// for data which has been garbage collected from the hot one, or `None` if
+    /// the node has no cold storage.
+    ///
+    /// This is meant for serving view requests for archival blocks.  Writes go
+    /// to the hot storage only.
+    #[cfg(feature = "cold_store")]
+    pub fn get_split_store(&self) -> Option<Store> {
+        self.cold_storage.as_ref().map(|cold_storage| Store {
+            storage: Arc::new(crate::db::SplitDB::new(
+                self.hot_storage.clone(),
+                cold_storage.clone(),
+            )),
+            contract_cache_last_used: self.contract_cache_last_used.clone(),
+        })
+    }
+
     /// Returns underlying database for given temperature.
     ///
     /// With (currently unimplemented) cold storage, this allows accessing
@@ -248,6 +289,7 @@ impl<D> NodeStorage<D> {
     pub fn new_with_cold(hot: Arc<dyn Database>, cold: D) -> Self {
         Self {
             hot_storage: hot.clone(),
+            contract_cache_last_used: Default::default(),
             cold_storage: Some(Arc::new(crate::db::ColdDB::<D>::new(hot, cold))),
             _phantom: PhantomData::<D> {},
         }
@@ -300,6 +342,13 @@ impl Store {
         self.storage.iter(column)
     }
 
+    /// Creates a checkpoint of the underlying database in given directory.
+    ///
+    /// See [`Database::create_checkpoint`].
+    pub fn create_checkpoint(&self, path: &std::path::Path) -> io::Result<()> {
+        self.storage.create_checkpoint(path)
+    }
+
     /// Fetches raw key/value pairs from the database.
     ///
     /// Practically, this means that for rc columns rc is included in the value.
@@ -847,11 +896,12 @@ pub fn set_genesis_state_roots(store_update: &mut StoreUpdate, genesis_roots: &[
 
 pub struct StoreCompiledContractCache {
     db: Arc<dyn Database>,
+    last_used: Arc<contract_cache::LastUsed>,
 }
 
 impl StoreCompiledContractCache {
     pub fn new(store: &Store) -> Self {
-        Self { db: store.storage.clone() }
+        Self { db: store.storage.clone(), last_used: store.contract_cache_last_used.clone() }
     }
 }
 
@@ -870,10 +920,39 @@ impl CompiledContractCache for StoreCompiledContractCache {
         self.db.write(update)
     }
 
+    /// Also records the description of the entry in DBCol::CachedContractCodeInfo,
+    /// see [`contract_cache`].
+    fn put_with_info(
+        &self,
+        key: &CryptoHash,
+        value: CompiledContract,
+        info: CompiledContractInfo,
+    ) -> io::Result<()> {
+        metrics::COMPILED_CONTRACT_COMPILE_TIME.observe(info.compile_time_us as f64 / 1e6);
+        let value = value.try_to_vec().unwrap();
+        let entry = contract_cache::ContractCacheEntryInfo {
+            info,
+            size: value.len() as u64,
+            last_used: contract_cache::now(),
+        };
+        let mut update = crate::db::DBTransaction::new();
+        // See `put` for why `.set` is used.
+        update.set(DBCol::CachedContractCode, key.as_ref().to_vec(), value);
+        update.set(DBCol::CachedContractCodeInfo, key.as_ref().to_vec(), entry.try_to_vec()?);
+        self.db.write(update)
+    }
+
     fn get(&self, key: &CryptoHash) -> io::Result<Option<CompiledContract>> {
         match self.db.get_raw_bytes(DBCol::CachedContractCode, key.as_ref()) {
-            Ok(Some(bytes)) => Ok(Some(CompiledContract::try_from_slice(&bytes)?)),
-            Ok(None) => Ok(None),
+            Ok(Some(bytes)) => {
+                metrics::COMPILED_CONTRACT_CACHE_HITS.inc();
+                self.last_used.record(key);
+                Ok(Some(CompiledContract::try_from_slice(&bytes)?))
+            }
+            Ok(None) => {
+                metrics::COMPILED_CONTRACT_CACHE_MISSES.inc();
+                Ok(None)
+            }
             Err(err) => Err(err),
         }
     }
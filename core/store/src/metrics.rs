@@ -1,6 +1,8 @@
 use near_o11y::metrics::{
-    try_create_histogram_vec, try_create_int_counter_vec, try_create_int_gauge_vec, HistogramVec,
-    IntCounterVec, IntGaugeVec,
+    exponential_buckets, try_create_histogram_vec, try_create_histogram_with_buckets,
+    try_create_int_counter, try_create_int_counter_vec, try_create_int_gauge,
+    try_create_int_gauge_vec, Histogram, HistogramVec, IntCounter, IntCounterVec, IntGauge,
+    IntGaugeVec,
 };
 use once_cell::sync::Lazy;
 
@@ -205,6 +207,43 @@ pub static PREFETCH_STAGED_SLOTS: Lazy<IntGaugeVec> = Lazy::new(|| {
     )
     .unwrap()
 });
+pub static COMPILED_CONTRACT_CACHE_HITS: Lazy<IntCounter> = Lazy::new(|| {
+    try_create_int_counter(
+        "near_compiled_contract_cache_hits",
+        "Compiled contracts read from the database cache",
+    )
+    .unwrap()
+});
+pub static COMPILED_CONTRACT_CACHE_MISSES: Lazy<IntCounter> = Lazy::new(|| {
+    try_create_int_counter(
+        "near_compiled_contract_cache_misses",
+        "Compiled contracts not found in the database cache",
+    )
+    .unwrap()
+});
+pub static COMPILED_CONTRACT_COMPILE_TIME: Lazy<Histogram> = Lazy::new(|| {
+    try_create_histogram_with_buckets(
+        "near_compiled_contract_compile_time",
+        "Time spent compiling contracts stored in the database cache, in seconds",
+        exponential_buckets(0.001, 2.0, 14).unwrap(),
+    )
+    .unwrap()
+});
+pub static COMPILED_CONTRACT_CACHE_SIZE: Lazy<IntGauge> = Lazy::new(|| {
+    try_create_int_gauge(
+        "near_compiled_contract_cache_size_bytes",
+        "Total size of the tracked compiled contracts cache entries as of the last pruning",
+    )
+    .unwrap()
+});
+pub static COMPILED_CONTRACT_CACHE_REMOVED: Lazy<IntCounterVec> = Lazy::new(|| {
+    try_create_int_counter_vec(
+        "near_compiled_contract_cache_removed",
+        "Compiled contracts removed from the database cache by reason",
+        &["reason"],
+    )
+    .unwrap()
+});
 #[cfg(feature = "cold_store")]
 pub static COLD_MIGRATION_READS: Lazy<IntCounterVec> = Lazy::new(|| {
     try_create_int_counter_vec(
@@ -214,3 +253,12 @@ pub static COLD_MIGRATION_READS: Lazy<IntCounterVec> = Lazy::new(|| {
     )
     .unwrap()
 });
+#[cfg(feature = "cold_store")]
+pub static COLD_MIGRATION_INITIAL_WRITES: Lazy<IntCounterVec> = Lazy::new(|| {
+    try_create_int_counter_vec(
+        "near_cold_migration_initial_writes",
+        "Number of values written to cold storage for every column during the initial copying of an existing archive.",
+        &["col"],
+    )
+    .unwrap()
+});
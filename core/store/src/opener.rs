@@ -259,8 +259,13 @@ impl<'a> StoreOpener<'a> {
                 }
             } else if cold_meta.is_some() {
                 // If cold database is configured and hot database exists,
-                // cold database must exist as well.
+                // cold database must exist as well unless hot database is an
+                // archive in which case it’s converted into split storage.
                 assert!(cfg!(feature = "cold_store"));
+                #[cfg(feature = "cold_store")]
+                if hot_meta.kind == Some(DbKind::Archive) {
+                    return self.open_archive_as_split(mode, hot_meta);
+                }
                 return Err(StoreOpenerError::HotColdExistenceMismatch);
             } else if !matches!(hot_meta.kind, None | Some(DbKind::RPC | DbKind::Archive)) {
                 // If cold database is not configured, hot database must be
@@ -306,6 +311,39 @@ impl<'a> StoreOpener<'a> {
         Ok(storage)
     }
 
+    /// Converts an existing archive database into a hot database and creates an
+    /// empty cold database paired with it.
+    ///
+    /// The cold database is populated by the node in background (see
+    /// `nearcore::cold_storage`).  Until that finishes, the hot database is not
+    /// garbage collected.  The archive must have current version since
+    /// migrations aren’t run as part of the conversion.
+    #[cfg(feature = "cold_store")]
+    fn open_archive_as_split(
+        &self,
+        mode: Mode,
+        hot_meta: DbMetadata,
+    ) -> Result<crate::NodeStorage, StoreOpenerError> {
+        if mode.read_only() || mode.must_create() {
+            return Err(StoreOpenerError::HotColdExistenceMismatch);
+        }
+        if hot_meta.version != DB_VERSION {
+            return Err(StoreOpenerError::DbVersionMismatch {
+                got: hot_meta.version,
+                want: DB_VERSION,
+            });
+        }
+        tracing::info!(target: "near", path=%self.path().display(),
+                       "Converting an archive database into a hot database and creating an empty cold database");
+        let cold = self.cold.as_ref().unwrap().create()?;
+        let (hot, _) = self.hot.open(Mode::ReadWriteExisting, DB_VERSION)?;
+        let storage = NodeStorage::from_rocksdb(hot, Some(cold));
+        // With cold storage present this sets kinds of the databases to Hot
+        // and Cold respectively.
+        set_store_metadata(&storage, DbMetadata { version: DB_VERSION, kind: None })?;
+        Ok(storage)
+    }
+
     /// Makes sure that database’s kind is correct.
     fn ensure_kind(
         &self,
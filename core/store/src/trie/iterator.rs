@@ -75,6 +75,11 @@ impl<'a> TrieIterator<'a> {
         self.seek_nibble_slice(NibbleSlice::new(key.as_ref()), true).map(drop)
     }
 
+    /// Position the iterator on the first element with key >= `key`.
+    pub fn seek<K: AsRef<[u8]>>(&mut self, key: K) -> Result<(), StorageError> {
+        self.seek_nibble_slice(NibbleSlice::new(key.as_ref()), false).map(drop)
+    }
+
     /// Configures whether the iterator should remember all the nodes its
     /// visiting.
     ///
@@ -437,6 +442,7 @@ mod tests {
             test_get_trie_items(&trie, &map, min_key, max_key);
             for (seek_key, _) in trie_changes.iter() {
                 test_seek_prefix(&trie, &map, seek_key);
+                test_seek(&trie, &map, seek_key);
                 test_get_trie_items(&trie, &map, min_key, seek_key);
                 test_get_trie_items(&trie, &map, seek_key, max_key);
             }
@@ -446,6 +452,7 @@ mod tests {
                 let seek_key: Vec<u8> =
                     (0..key_length).map(|_| *alphabet.choose(&mut rng).unwrap()).collect();
                 test_seek_prefix(&trie, &map, &seek_key);
+                test_seek(&trie, &map, &seek_key);
 
                 let seek_key2: Vec<u8> =
                     (0..key_length).map(|_| *alphabet.choose(&mut rng).unwrap()).collect();
@@ -499,6 +506,15 @@ mod tests {
         assert_eq!(got, want);
     }
 
+    fn test_seek(trie: &Trie, map: &BTreeMap<Vec<u8>, Vec<u8>>, seek_key: &[u8]) {
+        let mut iterator = trie.iter().unwrap();
+        iterator.seek(&seek_key).unwrap();
+        let got: Vec<_> = iterator.map(Result::unwrap).collect();
+        let want: Vec<_> =
+            map.range(seek_key.to_vec()..).map(|(k, v)| (k.clone(), v.clone())).collect();
+        assert_eq!(got, want);
+    }
+
     #[test]
     fn test_has_value() {
         let mut rng = rand::thread_rng();
@@ -24,7 +24,9 @@ pub use crate::trie::nibble_slice::NibbleSlice;
 pub use crate::trie::prefetching_trie_storage::{PrefetchApi, PrefetchError};
 pub use crate::trie::shard_tries::{KeyForStateChanges, ShardTries, WrappedTrieChanges};
 pub use crate::trie::trie_storage::{TrieCache, TrieCachingStorage, TrieDBStorage, TrieStorage};
-use crate::trie::trie_storage::{TrieMemoryPartialStorage, TrieRecordingStorage};
+use crate::trie::trie_storage::{
+    TrieMemoryPartialStorage, TrieOverlayStorage, TrieRecordingStorage,
+};
 use crate::StorageError;
 pub use near_primitives::types::TrieNodesCount;
 use std::fmt::Write;
@@ -594,6 +596,17 @@ impl Trie {
         Self::new(storage, root, None)
     }
 
+    /// Returns trie with the given root which reads `nodes` before falling back to the storage
+    /// of this trie. Flat state is dropped as it doesn't know about the overlaid nodes.
+    pub fn with_overlay(
+        self,
+        nodes: HashMap<CryptoHash, std::sync::Arc<[u8]>>,
+        root: StateRoot,
+    ) -> Self {
+        let storage = Box::new(TrieOverlayStorage { base: self.storage, nodes });
+        Self::new(storage, root, None)
+    }
+
     pub fn get_root(&self) -> &StateRoot {
         &self.root
     }
@@ -182,6 +182,10 @@ impl ShardTries {
         self.0.store.clone()
     }
 
+    pub fn trie_config(&self) -> &TrieConfig {
+        &self.0.trie_config
+    }
+
     pub(crate) fn get_db(&self) -> &Arc<dyn crate::Database> {
         &self.0.store.storage
     }
@@ -366,6 +366,32 @@ impl TrieStorage for TrieMemoryPartialStorage {
     }
 }
 
+/// Storage serving nodes which were never written to the DB on top of another storage.
+/// Allows to apply several state updates one after another without committing them.
+/// Nodes counting and caching modes are delegated to the underlying storage, so only reads
+/// reaching it are accounted for.
+pub struct TrieOverlayStorage {
+    pub(crate) base: Box<dyn TrieStorage>,
+    pub(crate) nodes: HashMap<CryptoHash, Arc<[u8]>>,
+}
+
+impl TrieStorage for TrieOverlayStorage {
+    fn retrieve_raw_bytes(&self, hash: &CryptoHash) -> Result<Arc<[u8]>, StorageError> {
+        match self.nodes.get(hash) {
+            Some(val) => Ok(val.clone()),
+            None => self.base.retrieve_raw_bytes(hash),
+        }
+    }
+
+    fn as_caching_storage(&self) -> Option<&TrieCachingStorage> {
+        self.base.as_caching_storage()
+    }
+
+    fn get_trie_nodes_count(&self) -> TrieNodesCount {
+        self.base.get_trie_nodes_count()
+    }
+}
+
 /// Storage for reading State nodes and values from DB which caches reads.
 pub struct TrieCachingStorage {
     pub(crate) store: Store,
@@ -1,21 +1,32 @@
 use crate::tests::client::process_blocks::create_nightshade_runtimes;
+use actix::System;
 use borsh::BorshDeserialize;
-use near_chain::{ChainGenesis, Provenance};
-use near_chain_configs::Genesis;
+use near_actix_test_utils::run_actix;
+use near_chain::{ChainGenesis, ChainStoreAccess, Provenance, RuntimeAdapter};
+use near_chain_configs::{ClientConfig, Genesis};
 use near_client::test_utils::TestEnv;
+use near_client::{start_view_client, Query, QueryError};
 use near_crypto::{InMemorySigner, KeyType};
+use near_network::test_utils::MockPeerManagerAdapter;
 use near_o11y::testonly::init_test_logger;
+use near_o11y::WithSpanContextExt;
 use near_primitives::block::Tip;
 use near_primitives::sharding::ShardChunk;
 use near_primitives::transaction::{
     Action, DeployContractAction, FunctionCallAction, SignedTransaction,
 };
+use near_primitives::types::{BlockId, BlockReference};
+use near_primitives::views::{QueryRequest, QueryResponseKind};
 use near_store::cold_storage::{
-    test_cold_genesis_update, test_get_store_reads, update_cold_db, update_cold_head,
+    copy_all_data_to_cold, test_cold_genesis_update, test_get_store_reads, update_cold_db,
+    update_cold_head,
 };
 use near_store::test_utils::create_test_node_storage_with_cold;
-use near_store::{DBCol, Store, Temperature, HEAD_KEY};
+use near_store::{DBCol, Store, Temperature, COLD_HEAD_KEY, HEAD_KEY};
 use nearcore::config::GenesisExt;
+use nearcore::NightshadeRuntime;
+use std::path::Path;
+use std::sync::Arc;
 use strum::IntoEnumIterator;
 
 fn check_key(first_store: &Store, second_store: &Store, col: DBCol, key: &[u8]) {
@@ -222,3 +233,159 @@ fn test_cold_db_head_update() {
         );
     }
 }
+
+/// Producing 4 epochs of blocks and then copying all the data to cold storage
+/// in one go, as done when converting an existing archive into split storage.
+/// Checking that everything that exists in cold columns of the hot store also
+/// exists in cold storage.
+#[test]
+fn test_copy_all_data_to_cold() {
+    init_test_logger();
+
+    let epoch_length = 5;
+    let max_height = epoch_length * 4;
+
+    let mut genesis = Genesis::test(vec!["test0".parse().unwrap(), "test1".parse().unwrap()], 1);
+
+    genesis.config.epoch_length = epoch_length;
+    let mut chain_genesis = ChainGenesis::test();
+    chain_genesis.epoch_length = epoch_length;
+    let mut env = TestEnv::builder(chain_genesis)
+        .runtime_adapters(create_nightshade_runtimes(&genesis, 1))
+        .build();
+
+    let store = create_test_node_storage_with_cold();
+
+    for h in 1..max_height {
+        let signer = InMemorySigner::from_seed("test0".parse().unwrap(), KeyType::ED25519, "test0");
+        let last_hash = env.clients[0].chain.head().unwrap().last_block_hash;
+        let tx = SignedTransaction::send_money(
+            h,
+            "test0".parse().unwrap(),
+            "test1".parse().unwrap(),
+            &signer,
+            1,
+            last_hash,
+        );
+        env.clients[0].process_tx(tx, false, false);
+        env.produce_block(0, h);
+    }
+
+    let hot_store = env.clients[0].runtime_adapter.store();
+    // Use small batches so that more than one write per column is made.
+    copy_all_data_to_cold(&*store.cold_db().unwrap(), &hot_store, 1000).unwrap();
+
+    for col in DBCol::iter() {
+        if col.is_cold() {
+            check_iter(&hot_store, &store.get_store(Temperature::Cold), col, &vec![]);
+        }
+    }
+}
+
+/// Producing 10 epochs of blocks on a node which copied data to cold storage
+/// only up to a small height.  Checking that garbage collection doesn’t go past
+/// that height.
+#[test]
+fn test_gc_stops_at_cold_head() {
+    init_test_logger();
+
+    let epoch_length = 5;
+    let max_height = epoch_length * 10;
+    let cold_head_height = epoch_length;
+
+    let mut genesis = Genesis::test(vec!["test0".parse().unwrap(), "test1".parse().unwrap()], 1);
+
+    genesis.config.epoch_length = epoch_length;
+    let mut chain_genesis = ChainGenesis::test();
+    chain_genesis.epoch_length = epoch_length;
+    let mut env = TestEnv::builder(chain_genesis)
+        .runtime_adapters(create_nightshade_runtimes(&genesis, 1))
+        .build();
+
+    let store = create_test_node_storage_with_cold();
+    let hot_store = env.clients[0].runtime_adapter.store();
+
+    for h in 1..max_height {
+        env.produce_block(0, h);
+        if h == cold_head_height {
+            update_cold_head(&*store.cold_db().unwrap(), &hot_store, &h).unwrap();
+        }
+    }
+
+    let cold_head = hot_store.get_ser::<Tip>(DBCol::BlockMisc, COLD_HEAD_KEY).unwrap().unwrap();
+    assert_eq!(cold_head.height, cold_head_height);
+    assert!(env.clients[0].chain.store().tail().unwrap() < cold_head_height);
+    for h in cold_head_height..max_height {
+        assert!(env.clients[0].chain.get_block_by_height(h).is_ok(), "block {h} was GCed");
+    }
+}
+
+/// Producing 10 epochs of blocks on a node with split storage, copying every
+/// block to cold storage.  Checking that a view client on the split store
+/// serves queries for blocks garbage collected from hot storage and refuses
+/// queries for blocks which are neither in hot nor in cold storage.
+#[test]
+fn test_view_client_reads_cold_storage() {
+    init_test_logger();
+
+    let epoch_length = 5;
+    let max_height = epoch_length * 10;
+
+    let mut genesis = Genesis::test(vec!["test0".parse().unwrap(), "test1".parse().unwrap()], 1);
+
+    genesis.config.epoch_length = epoch_length;
+    let mut chain_genesis = ChainGenesis::test();
+    chain_genesis.epoch_length = epoch_length;
+    let storage = create_test_node_storage_with_cold();
+    let hot_store = storage.get_store(Temperature::Hot);
+    let runtime =
+        Arc::new(NightshadeRuntime::test(Path::new("../../../.."), hot_store.clone(), &genesis));
+    let mut env = TestEnv::builder(chain_genesis.clone())
+        .runtime_adapters(vec![runtime.clone() as Arc<dyn RuntimeAdapter>])
+        .build();
+
+    let cold_db = storage.cold_db().unwrap();
+    test_cold_genesis_update(&*cold_db, &hot_store).unwrap();
+    for h in 1..max_height {
+        env.produce_block(0, h);
+        update_cold_db(&*cold_db, &hot_store, &genesis.config.shard_layout, &h).unwrap();
+        update_cold_head(&*cold_db, &hot_store, &h).unwrap();
+    }
+    let tail = env.clients[0].chain.store().tail().unwrap();
+    assert!(tail > epoch_length, "nothing was garbage collected");
+    assert!(env.clients[0].chain.get_block_by_height(1).is_err());
+
+    let view_runtime = Arc::new(runtime.with_view_store(storage.get_split_store().unwrap()));
+    let query = |height| {
+        Query::new(
+            BlockReference::BlockId(BlockId::Height(height)),
+            QueryRequest::ViewAccount { account_id: "test0".parse().unwrap() },
+        )
+        .with_span_context()
+    };
+    run_actix(async move {
+        let view_client = start_view_client(
+            None,
+            chain_genesis,
+            view_runtime,
+            Arc::new(MockPeerManagerAdapter::default()),
+            ClientConfig::test(true, 10, 20, 1, true, false),
+            Default::default(),
+        );
+        let response = view_client.send(query(1)).await.unwrap().unwrap();
+        assert!(matches!(response.kind, QueryResponseKind::ViewAccount(_)));
+
+        // Pretend that cold storage is behind the garbage collected blocks.
+        let mut store_update = hot_store.store_update();
+        let cold_head =
+            Tip::from_header(&env.clients[0].chain.get_block_header_by_height(0).unwrap());
+        store_update.set_ser(DBCol::BlockMisc, COLD_HEAD_KEY, &cold_head).unwrap();
+        store_update.commit().unwrap();
+        let response = view_client.send(query(1)).await.unwrap();
+        assert!(
+            matches!(response, Err(QueryError::GarbageCollectedBlock { block_height: 1, .. })),
+            "{response:?}"
+        );
+        System::current().stop();
+    });
+}
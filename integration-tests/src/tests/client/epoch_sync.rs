@@ -0,0 +1,201 @@
+use std::sync::Arc;
+
+use near_chain::{BlockProcessingArtifact, ChainGenesis, Provenance};
+use near_chain_configs::Genesis;
+use near_client::test_utils::TestEnv;
+use near_epoch_manager::EpochManagerAdapter;
+use near_network::types::{HighestHeightPeerInfo, NetworkRequests, PeerInfo};
+use near_o11y::testonly::init_test_logger;
+use near_primitives::merkle::PartialMerkleTree;
+use near_primitives::network::PeerId;
+use near_primitives::syncing::EpochSyncFinalizationResponse;
+use near_primitives::types::{BlockHeight, EpochId};
+use nearcore::config::GenesisExt;
+
+use crate::tests::client::process_blocks::create_nightshade_runtimes;
+
+const EPOCH_LENGTH: u64 = 5;
+
+/// Sets up two clients: the first one produces blocks up to `height`, the
+/// second one has nothing but genesis and is going to epoch sync from the
+/// first one.
+fn setup_env(height: BlockHeight) -> TestEnv {
+    let mut genesis = Genesis::test(vec!["test0".parse().unwrap()], 1);
+    genesis.config.epoch_length = EPOCH_LENGTH;
+    let chain_genesis = ChainGenesis::new(&genesis);
+    let mut env = TestEnv::builder(chain_genesis)
+        .clients_count(2)
+        .runtime_adapters(create_nightshade_runtimes(&genesis, 2))
+        .build();
+    for i in 1..=height {
+        env.produce_block(0, i);
+    }
+    env
+}
+
+fn peers() -> Vec<HighestHeightPeerInfo> {
+    vec![HighestHeightPeerInfo {
+        peer_info: PeerInfo::random(),
+        genesis_id: Default::default(),
+        highest_block_height: 0,
+        highest_block_hash: Default::default(),
+        tracked_shards: vec![],
+        archival: false,
+    }]
+}
+
+/// Runs a step of epoch sync of the second client and returns the request it
+/// has sent.
+fn run_epoch_sync(env: &mut TestEnv, peers: &[HighestHeightPeerInfo]) -> NetworkRequests {
+    let highest_height = env.clients[0].chain.head().unwrap().height;
+    let client = &mut env.clients[1];
+    assert!(client
+        .epoch_sync
+        .run(&mut client.sync_status, &client.chain, highest_height, peers, EPOCH_LENGTH)
+        .unwrap());
+    env.network_adapters[1].pop().unwrap().as_network_requests()
+}
+
+/// Serves light client blocks of the first client to the second one until the
+/// latter requests the finalization data.  Returns the epoch and the peer the
+/// finalization data has been requested for and from.
+fn sync_light_client_blocks(
+    env: &mut TestEnv,
+    peers: &[HighestHeightPeerInfo],
+) -> (EpochId, PeerId) {
+    loop {
+        match run_epoch_sync(env, peers) {
+            NetworkRequests::EpochSyncRequest { epoch_id, peer_id } => {
+                let response = env.clients[0].chain.get_epoch_sync_response(&epoch_id).unwrap();
+                env.clients[1].epoch_sync.on_response(response, peer_id);
+            }
+            NetworkRequests::EpochSyncFinalizationRequest { epoch_id, peer_id } => {
+                return (epoch_id, peer_id)
+            }
+            request => panic!("unexpected request {:?}", request),
+        }
+    }
+}
+
+/// Checks that a fresh node epoch syncs past a few epochs, ends up with the
+/// header head at the end of the epoch before the last synced one and then
+/// syncs headers and blocks as usual.
+#[test]
+fn test_epoch_sync() {
+    init_test_logger();
+    let mut env = setup_env(4 * EPOCH_LENGTH + 3);
+    let peers = peers();
+    let head = env.clients[0].chain.head().unwrap();
+
+    let (epoch_id, peer_id) = sync_light_client_blocks(&mut env, &peers);
+    let response = env.clients[0].chain.get_epoch_sync_finalization_response(&epoch_id).unwrap();
+    let client = &mut env.clients[1];
+    client.epoch_sync.on_finalization_response(response, peer_id, &mut client.chain).unwrap();
+    assert!(env.clients[1].epoch_sync.done);
+
+    // The last synced epoch is the one before the epoch of the head and
+    // the header head is the last block of the epoch before it.
+    let runtime = env.clients[0].runtime_adapter.clone();
+    let head_epoch_start = runtime.get_epoch_start_height(&head.last_block_hash).unwrap();
+    let epoch_header = env.clients[0].chain.get_block_header_by_height(head_epoch_start).unwrap();
+    assert_eq!(epoch_id, runtime.get_epoch_id(epoch_header.prev_hash()).unwrap());
+    let epoch_start = runtime.get_epoch_start_height(epoch_header.prev_hash()).unwrap();
+    let expected_header_head =
+        env.clients[0].chain.get_block_header_by_height(epoch_start - 1).unwrap();
+    let header_head = env.clients[1].chain.header_head().unwrap();
+    assert_eq!(&header_head.last_block_hash, expected_header_head.hash());
+    assert!(header_head.height >= 2 * EPOCH_LENGTH);
+    assert_eq!(env.clients[1].chain.head().unwrap().height, 0);
+
+    // Epoch manager knows the epoch of the header head and the two after it.
+    let synced_epoch_ids = [
+        header_head.epoch_id.clone(),
+        epoch_id.clone(),
+        runtime.get_next_epoch_id_from_prev_block(&header_head.last_block_hash).unwrap(),
+    ];
+    for epoch_id in &synced_epoch_ids {
+        assert_eq!(
+            env.clients[1].runtime_adapter.get_epoch_info(epoch_id).unwrap(),
+            runtime.get_epoch_info(epoch_id).unwrap()
+        );
+    }
+
+    // Header sync continues from the header head.
+    let headers = (header_head.height + 1..=head.height)
+        .map(|height| env.clients[0].chain.get_block_header_by_height(height).unwrap())
+        .collect();
+    env.clients[1].sync_block_headers(headers).unwrap();
+    assert_eq!(env.clients[1].chain.header_head().unwrap().last_block_hash, head.last_block_hash);
+
+    // Blocks are synced from the first block of the epoch of the header
+    // head.  The second client doesn’t track any shard so there is no state
+    // to sync.
+    let sync_block = env.clients[0].chain.get_block_by_height(head_epoch_start).unwrap();
+    let prev_block = env.clients[0].chain.get_block(sync_block.header().prev_hash()).unwrap();
+    let chain = &mut env.clients[1].chain;
+    chain.reset_data_pre_state_sync(*sync_block.hash()).unwrap();
+    chain.save_block(prev_block.into()).unwrap();
+    chain
+        .reset_heads_post_state_sync(
+            &None,
+            *sync_block.hash(),
+            &mut BlockProcessingArtifact::default(),
+            Arc::new(|_| {}),
+        )
+        .unwrap();
+    for height in head_epoch_start..=head.height {
+        let block = env.clients[0].chain.get_block_by_height(height).unwrap();
+        env.process_block(1, block, Provenance::NONE);
+    }
+    assert_eq!(env.clients[1].chain.head().unwrap().last_block_hash, head.last_block_hash);
+}
+
+/// Checks that tampered finalization responses are rejected, their sender is
+/// banned and epoch sync carries on.
+#[test]
+fn test_epoch_sync_invalid_finalization() {
+    init_test_logger();
+    let mut env = setup_env(4 * EPOCH_LENGTH + 3);
+    let peers = peers();
+    let genesis_hash = *env.clients[1].chain.genesis().hash();
+
+    let (epoch_id, mut peer_id) = sync_light_client_blocks(&mut env, &peers);
+    let response = env.clients[0].chain.get_epoch_sync_finalization_response(&epoch_id).unwrap();
+    let tampers: [fn(&mut EpochSyncFinalizationResponse); 4] = [
+        |response| {
+            response.prev_epoch_headers.remove(0);
+        },
+        |response| response.header_sync_init_header_tree = PartialMerkleTree::default(),
+        |response| response.next_epoch_info = response.cur_epoch_info.clone(),
+        |response| {
+            response.prev_epoch_last_block_info = response.prev_epoch_first_block_info.clone()
+        },
+    ];
+    for tamper in tampers {
+        let mut invalid = response.clone();
+        tamper(&mut invalid);
+        assert_ne!(invalid, response);
+        let client = &mut env.clients[1];
+        client.epoch_sync.on_finalization_response(invalid, peer_id, &mut client.chain).unwrap();
+        assert!(!env.clients[1].epoch_sync.done);
+        assert_eq!(env.clients[1].chain.header_head().unwrap().last_block_hash, genesis_hash);
+        match env.network_adapters[1].pop().unwrap().as_network_requests() {
+            NetworkRequests::BanPeer { .. } => {}
+            request => panic!("expected the peer to be banned, got {:?}", request),
+        }
+
+        // The finalization data is requested again.
+        peer_id = match run_epoch_sync(&mut env, &peers) {
+            NetworkRequests::EpochSyncFinalizationRequest { epoch_id: requested, peer_id } => {
+                assert_eq!(requested, epoch_id);
+                peer_id
+            }
+            request => panic!("unexpected request {:?}", request),
+        };
+    }
+
+    let client = &mut env.clients[1];
+    client.epoch_sync.on_finalization_response(response, peer_id, &mut client.chain).unwrap();
+    assert!(env.clients[1].epoch_sync.done);
+    assert_ne!(env.clients[1].chain.header_head().unwrap().last_block_hash, genesis_hash);
+}
@@ -3,6 +3,7 @@ mod challenges;
 mod chunks_management;
 #[cfg(feature = "cold_store")]
 mod cold_storage;
+mod epoch_sync;
 mod features;
 mod flat_storage;
 mod process_blocks;
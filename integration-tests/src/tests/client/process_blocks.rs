@@ -20,7 +20,10 @@ use near_chain::{
     Block, BlockProcessingArtifact, ChainGenesis, ChainStore, ChainStoreAccess, Error, Provenance,
     RuntimeAdapter,
 };
-use near_chain_configs::{ClientConfig, Genesis, DEFAULT_GC_NUM_EPOCHS_TO_KEEP};
+use near_chain_configs::{
+    ClientConfig, Genesis, TransactionPoolConfig, DEFAULT_GC_NUM_EPOCHS_TO_KEEP,
+};
+use near_chunks::client::ShardedTransactionPool;
 use near_chunks::{ChunkStatus, ShardsManager};
 use near_client::test_utils::{
     create_chunk_on_height, setup_client, setup_mock, setup_mock_all_validators, TestEnv,
@@ -49,7 +52,7 @@ use near_primitives::merkle::{verify_hash, PartialMerkleTree};
 use near_primitives::receipt::DelayedReceiptIndices;
 use near_primitives::runtime::config::RuntimeConfig;
 use near_primitives::runtime::config_store::RuntimeConfigStore;
-use near_primitives::shard_layout::ShardUId;
+use near_primitives::shard_layout::{account_id_to_shard_id, ShardUId};
 use near_primitives::sharding::{
     EncodedShardChunk, ReedSolomonWrapper, ShardChunkHeader, ShardChunkHeaderInner,
     ShardChunkHeaderV3,
@@ -1095,6 +1098,40 @@ fn test_process_invalid_tx() {
     );
 }
 
+/// When the transaction pool has no room left, a validator should reject the transaction rather
+/// than report it as accepted.
+#[test]
+fn test_process_tx_transaction_pool_full() {
+    init_test_logger();
+    let genesis = Genesis::test(vec!["test0".parse().unwrap(), "test1".parse().unwrap()], 1);
+    let chain_genesis = ChainGenesis::new(&genesis);
+    let mut env = TestEnv::builder(chain_genesis)
+        .runtime_adapters(create_nightshade_runtimes(&genesis, 1))
+        .build();
+    env.clients[0].sharded_tx_pool = ShardedTransactionPool::new(
+        [0; 32],
+        TransactionPoolConfig { max_transactions_per_signer: Some(1), ..Default::default() },
+    );
+    let genesis_hash = *env.clients[0].chain.genesis().hash();
+    let signer = InMemorySigner::from_seed("test0".parse().unwrap(), KeyType::ED25519, "test0");
+    let send_money = |nonce| {
+        SignedTransaction::send_money(
+            nonce,
+            "test0".parse().unwrap(),
+            "test1".parse().unwrap(),
+            &signer,
+            100,
+            genesis_hash,
+        )
+    };
+
+    assert_eq!(env.clients[0].process_tx(send_money(1), false, false), ProcessTxResponse::ValidTx);
+    assert_eq!(
+        env.clients[0].process_tx(send_money(2), false, false),
+        ProcessTxResponse::TransactionPoolFull
+    );
+}
+
 /// If someone produce a block with Utc::now() + 1 min, we should produce a block with valid timestamp
 #[test]
 fn test_time_attack() {
@@ -2393,6 +2430,130 @@ fn test_catchup_gas_price_change() {
     assert_eq!(chunk_extra_after_sync, expected_chunk_extra);
 }
 
+/// Tracked accounts changed at runtime apply from the epoch after the next one. The newly
+/// tracked shard is caught up with state sync at the start of the next epoch.
+#[test]
+fn test_update_tracked_accounts() {
+    init_test_logger();
+    let epoch_length = 5;
+    let mut genesis =
+        Genesis::test_sharded_new_version(vec!["test0".parse().unwrap()], 1, vec![1, 1]);
+    genesis.config.epoch_length = epoch_length;
+    let chain_genesis = ChainGenesis::new(&genesis);
+    let mut env = TestEnv::builder(chain_genesis)
+        .clients_count(2)
+        .runtime_adapters(create_nightshade_runtimes(&genesis, 2))
+        .build();
+    let rt = env.clients[1].runtime_adapter.clone();
+    let tracked_account: AccountId = "test0".parse().unwrap();
+    let shard_layout = rt.get_shard_layout(&EpochId::default()).unwrap();
+    let shard_id = account_id_to_shard_id(&tracked_account, &shard_layout);
+    let shard_uid = ShardUId::from_shard_id_and_layout(shard_id, &shard_layout);
+
+    for i in 1..3 {
+        let block = env.clients[0].produce_block(i).unwrap().unwrap();
+        env.process_block(0, block.clone(), Provenance::PRODUCED);
+        env.process_block(1, block, Provenance::NONE);
+    }
+    let activation_height =
+        env.clients[1].update_tracked_accounts(vec![tracked_account]).unwrap().unwrap();
+    let head = env.clients[1].chain.head().unwrap();
+    assert_eq!(
+        activation_height,
+        rt.get_epoch_height_from_prev_block(&head.prev_block_hash).unwrap() + 2
+    );
+
+    // Process blocks up to the last one before the activation epoch. The first block of the
+    // epoch before it has to catch up the tracked shard.
+    let mut sync_hash = None;
+    for i in 3.. {
+        let block = env.clients[0].produce_block(i).unwrap().unwrap();
+        env.process_block(0, block.clone(), Provenance::PRODUCED);
+        env.process_block(1, block.clone(), Provenance::NONE);
+
+        let prev_hash = block.header().prev_hash();
+        let epoch_height = rt.get_epoch_height_from_prev_block(prev_hash).unwrap();
+        let blocks_to_catchup =
+            env.clients[1].chain.store().get_blocks_to_catchup(prev_hash).unwrap();
+        if sync_hash.is_none() && blocks_to_catchup.contains(block.hash()) {
+            assert!(rt.is_next_block_epoch_start(prev_hash).unwrap());
+            assert_eq!(epoch_height + 1, activation_height);
+            sync_hash = Some(*block.hash());
+        }
+
+        let next_epoch_height = rt.get_epoch_height_from_prev_block(block.hash()).unwrap();
+        assert_eq!(
+            rt.cares_about_shard(None, block.hash(), shard_id, true),
+            next_epoch_height >= activation_height
+        );
+        assert_eq!(
+            rt.will_care_about_shard(None, block.hash(), shard_id, true),
+            next_epoch_height + 1 >= activation_height
+        );
+        if next_epoch_height == activation_height {
+            break;
+        }
+    }
+    let sync_hash = sync_hash.expect("the tracked shard has not been caught up");
+
+    // Simulate state sync of the tracked shard.
+    let sync_prev_hash = *env.clients[0].chain.get_block_header(&sync_hash).unwrap().prev_hash();
+    assert!(env.clients[1].chain.get_chunk_extra(&sync_prev_hash, &shard_uid).is_err());
+    let state_sync_header =
+        env.clients[0].chain.get_state_response_header(shard_id, sync_hash).unwrap();
+    let state_root = match &state_sync_header {
+        ShardStateSyncResponseHeader::V1(header) => header.chunk.header.inner.prev_state_root,
+        ShardStateSyncResponseHeader::V2(header) => {
+            *header.chunk.cloned_header().take_inner().prev_state_root()
+        }
+    };
+    let state_root_node = env.clients[0]
+        .runtime_adapter
+        .get_state_root_node(shard_id, &sync_hash, &state_root)
+        .unwrap();
+    let num_parts = get_num_state_parts(state_root_node.memory_usage);
+    let state_sync_parts = (0..num_parts)
+        .map(|i| env.clients[0].chain.get_state_response_part(shard_id, i, sync_hash).unwrap())
+        .collect::<Vec<_>>();
+
+    env.clients[1].chain.set_state_header(shard_id, sync_hash, state_sync_header).unwrap();
+    for i in 0..num_parts {
+        env.clients[1]
+            .chain
+            .set_state_part(
+                shard_id,
+                sync_hash,
+                PartId::new(i, num_parts),
+                &state_sync_parts[i as usize],
+            )
+            .unwrap();
+    }
+    let f = move |msg: ApplyStatePartsRequest| {
+        use borsh::BorshSerialize;
+        let store = rt.store();
+
+        for part_id in 0..msg.num_parts {
+            let key = StatePartKey(msg.sync_hash, msg.shard_id, part_id).try_to_vec().unwrap();
+            let part = store.get(DBCol::StateParts, &key).unwrap().unwrap();
+
+            rt.apply_state_part(
+                msg.shard_id,
+                &msg.state_root,
+                PartId::new(part_id, msg.num_parts),
+                &part,
+                &msg.epoch_id,
+            )
+            .unwrap();
+        }
+    };
+    env.clients[1].chain.schedule_apply_state_parts(shard_id, sync_hash, num_parts, &f).unwrap();
+    env.clients[1].chain.set_state_finalize(shard_id, sync_hash, Ok(())).unwrap();
+    assert_eq!(
+        env.clients[1].chain.get_chunk_extra(&sync_prev_hash, &shard_uid).unwrap(),
+        env.clients[0].chain.get_chunk_extra(&sync_prev_hash, &shard_uid).unwrap()
+    );
+}
+
 #[test]
 fn test_block_execution_outcomes() {
     init_test_logger();
@@ -6,25 +6,31 @@ use near_chain_configs::Genesis;
 use near_client::test_utils::TestEnv;
 use near_crypto::{InMemorySigner, KeyType};
 use near_primitives::account::Account;
+use near_primitives::hash::CryptoHash;
 use near_primitives::sandbox::state_patch::SandboxStatePatch;
+use near_primitives::shard_layout::ShardUId;
 use near_primitives::state_record::StateRecord;
 use near_primitives::transaction::{
     Action, DeployContractAction, FunctionCallAction, SignedTransaction,
 };
 use near_primitives::types::{AccountId, BlockHeight, Nonce};
+use near_primitives::views::QueryRequest;
 use near_store::test_utils::create_test_store;
 use nearcore::config::GenesisExt;
 
 fn test_setup() -> (TestEnv, InMemorySigner) {
+    test_setup_with_gas_traces(false)
+}
+
+fn test_setup_with_gas_traces(record_gas_traces: bool) -> (TestEnv, InMemorySigner) {
     let epoch_length = 5;
     let mut genesis = Genesis::test(vec!["test0".parse().unwrap(), "test1".parse().unwrap()], 1);
     genesis.config.epoch_length = epoch_length;
+    let runtime =
+        nearcore::NightshadeRuntime::test(Path::new("../../../.."), create_test_store(), &genesis)
+            .with_gas_traces(record_gas_traces);
     let mut env = TestEnv::builder(ChainGenesis::test())
-        .runtime_adapters(vec![Arc::new(nearcore::NightshadeRuntime::test(
-            Path::new("../../../.."),
-            create_test_store(),
-            &genesis,
-        )) as Arc<dyn RuntimeAdapter>])
+        .runtime_adapters(vec![Arc::new(runtime) as Arc<dyn RuntimeAdapter>])
         .build();
     let signer = InMemorySigner::from_seed("test0".parse().unwrap(), KeyType::ED25519, "test0");
     send_tx(
@@ -70,10 +76,12 @@ fn send_tx(
     receiver_id: AccountId,
     signer: &InMemorySigner,
     actions: Vec<Action>,
-) {
+) -> CryptoHash {
     let hash = env.clients[0].chain.head().unwrap().last_block_hash;
     let tx = SignedTransaction::from_actions(nonce, signer_id, receiver_id, signer, actions, hash);
+    let tx_hash = tx.get_hash();
     env.clients[0].process_tx(tx, false, false);
+    tx_hash
 }
 
 #[test]
@@ -107,3 +115,111 @@ fn test_patch_account() {
     let test1_after = env.query_account("test1".parse().unwrap());
     assert_eq!(test1_after.amount, 10);
 }
+
+#[test]
+fn test_replace_account() {
+    let (mut env, signer) = test_setup();
+    assert_eq!(env.query_state("test0".parse().unwrap()).len(), 1);
+    let mut test0: Account = env.query_account("test0".parse().unwrap()).into();
+    test0.set_amount(10);
+
+    env.clients[0].chain.patch_state(SandboxStatePatch::replace_accounts(
+        vec!["test0".parse().unwrap()],
+        vec![
+            StateRecord::Account { account_id: "test0".parse().unwrap(), account: test0 },
+            StateRecord::Contract {
+                account_id: "test0".parse().unwrap(),
+                code: near_test_contracts::rs_contract().to_vec(),
+            },
+        ],
+    ));
+    do_blocks(&mut env, 9, 20);
+    assert_eq!(env.query_account("test0".parse().unwrap()).amount, 10);
+    // Contract data and access keys not present in the records are removed.
+    assert!(env.query_state("test0".parse().unwrap()).is_empty());
+    let head = env.clients[0].chain.head().unwrap();
+    let last_block = env.clients[0].chain.get_block(&head.last_block_hash).unwrap();
+    let access_key = env.clients[0].runtime_adapter.query(
+        ShardUId::single_shard(),
+        &last_block.chunks()[0].prev_state_root(),
+        last_block.header().height(),
+        last_block.header().raw_timestamp(),
+        last_block.header().prev_hash(),
+        last_block.header().hash(),
+        last_block.header().epoch_id(),
+        &QueryRequest::ViewAccessKey {
+            account_id: "test0".parse().unwrap(),
+            public_key: signer.public_key,
+        },
+    );
+    assert!(access_key.is_err());
+}
+
+#[test]
+fn test_snapshot_restore() {
+    let (mut env, _signer) = test_setup();
+    let snapshot_head = env.clients[0].chain.head().unwrap();
+    let test1_before = env.query_account("test1".parse().unwrap());
+    env.clients[0].sandbox_snapshot("before_patch".to_string()).unwrap();
+
+    let mut test1: Account = test1_before.clone().into();
+    test1.set_amount(10);
+    env.clients[0].chain.patch_state(SandboxStatePatch::new(vec![StateRecord::Account {
+        account_id: "test1".parse().unwrap(),
+        account: test1,
+    }]));
+    do_blocks(&mut env, 9, 20);
+    assert_eq!(env.query_account("test1".parse().unwrap()).amount, 10);
+
+    env.clients[0].sandbox_restore("before_patch").unwrap();
+    assert_eq!(env.clients[0].chain.head().unwrap(), snapshot_head);
+    assert_eq!(env.query_account("test1".parse().unwrap()).amount, test1_before.amount);
+
+    // Blocks are produced on top of the restored head at heights that were not used yet.
+    do_blocks(&mut env, 21, 25);
+    let head = env.clients[0].chain.head().unwrap();
+    assert_eq!(head.height, 24);
+    let block_21 = env.clients[0].chain.get_block_by_height(21).unwrap();
+    assert_eq!(*block_21.header().prev_hash(), snapshot_head.last_block_hash);
+    assert_eq!(env.query_account("test1".parse().unwrap()).amount, test1_before.amount);
+
+    assert!(env.clients[0].sandbox_restore("missing").is_err());
+}
+
+fn call_write_key_value(env: &mut TestEnv, signer: &InMemorySigner) -> CryptoHash {
+    let tx_hash = send_tx(
+        env,
+        3,
+        "test0".parse().unwrap(),
+        "test0".parse().unwrap(),
+        signer,
+        vec![Action::FunctionCall(FunctionCallAction {
+            method_name: "write_key_value".to_string(),
+            args: [1u64.to_le_bytes(), 2u64.to_le_bytes()].concat(),
+            gas: 100000000000000,
+            deposit: 0,
+        })],
+    );
+    do_blocks(env, 9, 12);
+    let tx_outcome = env.clients[0].chain.get_execution_outcome(&tx_hash).unwrap();
+    tx_outcome.outcome_with_id.outcome.receipt_ids[0]
+}
+
+#[test]
+fn test_gas_trace() {
+    let (mut env, signer) = test_setup_with_gas_traces(true);
+    let receipt_id = call_write_key_value(&mut env, &signer);
+    let traces = env.clients[0].runtime_adapter.get_gas_traces(&receipt_id).unwrap();
+    assert_eq!(traces.len(), 1);
+    assert_eq!(traces[0].method_name, "write_key_value");
+    assert!(traces[0].calls.iter().any(|call| call.name == "storage_write"));
+
+    assert!(env.clients[0].runtime_adapter.get_gas_traces(&CryptoHash::default()).is_none());
+}
+
+#[test]
+fn test_gas_trace_disabled() {
+    let (mut env, signer) = test_setup();
+    let receipt_id = call_write_key_value(&mut env, &signer);
+    assert!(env.clients[0].runtime_adapter.get_gas_traces(&receipt_id).is_none());
+}
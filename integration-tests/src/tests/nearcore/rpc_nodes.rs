@@ -642,6 +642,72 @@ fn test_check_tx_on_lightclient_must_return_does_not_track_shard() {
     });
 }
 
+#[test]
+#[cfg_attr(not(feature = "expensive_tests"), ignore)]
+fn test_simulate_tx_does_not_commit() {
+    init_integration_logger();
+
+    let cluster = NodeCluster::default()
+        .set_num_shards(1)
+        .set_num_nodes(2)
+        .set_num_validator_seats(1)
+        .set_num_lightclients(0)
+        .set_epoch_length(10)
+        .set_genesis_height(0);
+
+    cluster.exec_until_stop(|genesis, rpc_addrs, clients| async move {
+        let view_client = clients[0].1.clone();
+
+        let genesis_hash = *genesis_block(&genesis).hash();
+        let signer =
+            InMemorySigner::from_seed("near.0".parse().unwrap(), KeyType::ED25519, "near.0");
+        let transaction = SignedTransaction::send_money(
+            1,
+            "near.0".parse().unwrap(),
+            "near.1".parse().unwrap(),
+            &signer,
+            10000,
+            genesis_hash,
+        );
+
+        let client = new_client(&format!("http://{}", rpc_addrs[0]));
+        let tx_hash = transaction.get_hash();
+        let bytes = transaction.try_to_vec().unwrap();
+
+        spawn_interruptible(async move {
+            loop {
+                let res = view_client.send(GetBlock::latest().with_span_context()).await;
+                if let Ok(Ok(block)) = res {
+                    if block.header.height > 10 {
+                        let response =
+                            client.EXPERIMENTAL_simulate_tx(to_base64(&bytes)).await.unwrap();
+                        assert_eq!(response["transaction_outcome"]["id"], tx_hash.to_string());
+                        let receipts_outcome = response["receipts_outcome"].as_array().unwrap();
+                        assert!(!receipts_outcome.is_empty());
+                        assert!(
+                            receipts_outcome[0]["outcome"]["metadata"]["gas_profile"].is_array()
+                        );
+                        assert!(response["state_changes"]
+                            .as_array()
+                            .unwrap()
+                            .iter()
+                            .any(|change| change["type"] == "account_update"
+                                && change["change"]["account_id"] == "near.1"));
+                        // Nothing was committed, so the transaction is unknown to the chain.
+                        client
+                            .tx(tx_hash.to_string(), "near.0".parse().unwrap())
+                            .await
+                            .unwrap_err();
+                        System::current().stop();
+                        break;
+                    }
+                }
+                sleep(std::time::Duration::from_millis(500)).await;
+            }
+        });
+    });
+}
+
 #[test]
 #[cfg_attr(not(feature = "expensive_tests"), ignore)]
 fn test_validators_by_epoch_id_current_epoch_not_fails() {
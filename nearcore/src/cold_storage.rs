@@ -0,0 +1,192 @@
+//! Background loop which keeps cold storage up to date with the chain.
+//!
+//! On nodes with split storage, the loop copies data of every final block from
+//! hot to cold storage and advances cold storage’s HEAD afterwards.  Since the
+//! HEAD is persisted, the loop resumes where it left off after the node is
+//! restarted.  If cold storage has no HEAD at all (which is the case for a new
+//! node or for an archive which was just converted into split storage), all the
+//! data is first copied from hot storage in bulk.
+//!
+//! Hot storage is garbage collected only up to the cold HEAD (see
+//! [`near_store::COLD_HEAD_KEY`]) so that nothing is lost if the loop falls
+//! behind.
+use crate::metrics;
+use crate::NightshadeRuntime;
+use actix::{Actor, ActorContext, Arbiter, ArbiterHandle, Context, Supervised, Supervisor};
+use anyhow::Context as _;
+use near_epoch_manager::EpochManagerAdapter;
+use near_primitives::block::{BlockHeader, Tip};
+use near_primitives::hash::CryptoHash;
+use near_primitives::types::BlockHeight;
+use near_store::cold_storage::{copy_all_data_to_cold, update_cold_db, update_cold_head};
+use near_store::db::ColdDB;
+use near_store::{DBCol, NodeStorage, Store, Temperature, FINAL_HEAD_KEY, HEAD_KEY};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{debug, error, info, warn};
+
+/// Configuration of the loop copying data from hot to cold storage.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct ColdStoreMigrationConfig {
+    /// Time between checks for new final blocks once cold storage has caught
+    /// up with the final head.
+    pub migration_period: Duration,
+    /// Maximum number of heights copied in a single step of the loop.
+    pub max_heights_per_step: u64,
+    /// Approximate size in bytes of a single write to cold storage during the
+    /// initial copying of an existing archive.
+    pub initial_migration_batch_size: usize,
+}
+
+impl Default for ColdStoreMigrationConfig {
+    fn default() -> Self {
+        Self {
+            migration_period: Duration::from_secs(1),
+            max_heights_per_step: 100,
+            initial_migration_batch_size: 500_000_000,
+        }
+    }
+}
+
+/// Actor running the cold storage migration loop.
+///
+/// The actor is supervised: if a step fails, it stops and gets restarted which
+/// makes it start again from the cold HEAD persisted in cold storage.
+pub struct ColdStoreActor {
+    hot_store: Store,
+    cold_store: Store,
+    cold_db: Arc<ColdDB>,
+    runtime: Arc<NightshadeRuntime>,
+    config: ColdStoreMigrationConfig,
+}
+
+impl ColdStoreActor {
+    /// Copies the next batch of final blocks to cold storage.
+    ///
+    /// Returns whether there’s more data to copy, i.e. whether the next step
+    /// should be run immediately.
+    fn migration_step(&self) -> anyhow::Result<bool> {
+        let final_head = match self.hot_store.get_ser::<Tip>(DBCol::BlockMisc, FINAL_HEAD_KEY)? {
+            Some(tip) => tip.height,
+            None => return Ok(false),
+        };
+        let cold_head = self.cold_store.get_ser::<Tip>(DBCol::BlockMisc, HEAD_KEY)?;
+        let cold_head = match cold_head {
+            Some(tip) => tip.height,
+            None => {
+                self.initial_migration(final_head)?;
+                return Ok(true);
+            }
+        };
+        metrics::COLD_STORE_MIGRATION_LAG.set(final_head.saturating_sub(cold_head) as i64);
+
+        let last_height = final_head.min(cold_head + self.config.max_heights_per_step);
+        for height in cold_head + 1..=last_height {
+            self.copy_height(height)?;
+        }
+        Ok(last_height < final_head)
+    }
+
+    /// Copies all the data hot storage has and sets cold HEAD to `final_head`.
+    fn initial_migration(&self, final_head: BlockHeight) -> anyhow::Result<()> {
+        info!(target: "cold_store", final_head, "Cold storage is empty; copying all data from hot storage");
+        metrics::COLD_STORE_INITIAL_MIGRATION_IN_PROGRESS.set(1);
+        let result = copy_all_data_to_cold(
+            &*self.cold_db,
+            &self.hot_store,
+            self.config.initial_migration_batch_size,
+        )
+        .and_then(|()| update_cold_head(&*self.cold_db, &self.hot_store, &final_head));
+        metrics::COLD_STORE_INITIAL_MIGRATION_IN_PROGRESS.set(0);
+        result.context("initial migration to cold storage")?;
+        metrics::COLD_HEAD_HEIGHT.set(final_head as i64);
+        info!(target: "cold_store", final_head, "Finished initial migration to cold storage");
+        Ok(())
+    }
+
+    /// Copies data of the block at `height` to cold storage and updates cold
+    /// HEAD.  Does nothing if there’s no block at given height.
+    fn copy_height(&self, height: BlockHeight) -> anyhow::Result<()> {
+        let block_hash = match self
+            .hot_store
+            .get_ser::<CryptoHash>(DBCol::BlockHeight, &height.to_le_bytes())?
+        {
+            Some(block_hash) => block_hash,
+            None => return Ok(()),
+        };
+        let header = self
+            .hot_store
+            .get_ser::<BlockHeader>(DBCol::BlockHeader, block_hash.as_ref())?
+            .with_context(|| format!("missing header of block {block_hash}"))?;
+        let shard_layout = self.runtime.get_shard_layout(header.epoch_id())?;
+
+        let _timer = metrics::COLD_STORE_COPY_BLOCK_TIME.start_timer();
+        update_cold_db(&*self.cold_db, &self.hot_store, &shard_layout, &height)
+            .with_context(|| format!("copying height {height} to cold storage"))?;
+        update_cold_head(&*self.cold_db, &self.hot_store, &height)?;
+        metrics::COLD_HEAD_HEIGHT.set(height as i64);
+        debug!(target: "cold_store", height, "Copied block to cold storage");
+        Ok(())
+    }
+
+    fn run_migration(&mut self, ctx: &mut Context<Self>) {
+        let wait = match self.migration_step() {
+            Ok(true) => Duration::ZERO,
+            Ok(false) => self.config.migration_period,
+            Err(err) => {
+                error!(target: "cold_store", ?err, "Failed to copy data to cold storage");
+                metrics::COLD_STORE_MIGRATION_ERRORS.inc();
+                ctx.stop();
+                return;
+            }
+        };
+        near_performance_metrics::actix::run_later(ctx, wait, move |act, ctx| {
+            act.run_migration(ctx);
+        });
+    }
+}
+
+impl Actor for ColdStoreActor {
+    type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        self.run_migration(ctx);
+    }
+}
+
+impl Supervised for ColdStoreActor {
+    fn restarting(&mut self, _ctx: &mut Self::Context) {
+        warn!(target: "cold_store", "Restarting cold storage migration loop");
+    }
+}
+
+/// Starts the cold storage migration loop in a new arbiter.
+///
+/// Returns `None` if the storage doesn’t have a cold database.
+pub fn spawn_cold_store_loop(
+    storage: &NodeStorage,
+    runtime: Arc<NightshadeRuntime>,
+    config: ColdStoreMigrationConfig,
+) -> anyhow::Result<Option<ArbiterHandle>> {
+    if !storage.has_cold() {
+        return Ok(None);
+    }
+    let hot_store = storage.get_store(Temperature::Hot);
+    let cold_store = storage.get_store(Temperature::Cold);
+    let cold_db = storage.cold_db()?.clone();
+    if let Some(tip) = cold_store.get_ser::<Tip>(DBCol::BlockMisc, HEAD_KEY)? {
+        metrics::COLD_HEAD_HEIGHT.set(tip.height as i64);
+    }
+
+    let arbiter = Arbiter::new().handle();
+    Supervisor::start_in_arbiter(&arbiter, move |_ctx| ColdStoreActor {
+        hot_store,
+        cold_store,
+        cold_db,
+        runtime,
+        config,
+    });
+    Ok(Some(arbiter))
+}
@@ -16,8 +16,8 @@ use tempfile::tempdir;
 use tracing::{info, warn};
 
 use near_chain_configs::{
-    get_initial_supply, ClientConfig, GCConfig, Genesis, GenesisConfig, GenesisValidationMode,
-    LogSummaryStyle,
+    get_initial_supply, ClientConfig, ExternalStatePartsConfig, GCConfig, Genesis, GenesisConfig,
+    GenesisValidationMode, LogSummaryStyle, TransactionPoolConfig,
 };
 use near_crypto::{InMemorySigner, KeyFile, KeyType, PublicKey, Signer};
 #[cfg(feature = "json_rpc")]
@@ -322,12 +322,41 @@ pub struct Config {
     /// If set, overrides value in genesis configuration.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub max_gas_burnt_view: Option<Gas>,
+    /// Limits on the size of the transaction pool.
+    pub transaction_pool: TransactionPoolConfig,
+    /// Location to download state parts from during state sync before
+    /// requesting them from peers.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub state_sync_external_parts: Option<ExternalStatePartsConfig>,
+    /// If set, state parts of tracked shards are dumped at the beginning of
+    /// every epoch.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub state_parts_dump: Option<crate::state_parts_dump::StatePartsDumpConfig>,
+    /// If set, the compiled contracts cache is periodically pruned of entries
+    /// which won’t be used anymore and kept within the size limit.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub contract_cache: Option<crate::contract_cache::ContractCacheConfig>,
+    /// Directory in which the `/debug/api/checkpoint` endpoint creates
+    /// checkpoints of the database, relative to the home directory.  Creating
+    /// checkpoints is disabled if not set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub checkpoints_dir: Option<PathBuf>,
+    /// If set, gas traces of the function calls executed by the node are kept
+    /// in memory for the `sandbox_gas_trace` JSON RPC method.  Recording the
+    /// traces slows down execution of the contracts.
+    #[cfg(feature = "sandbox")]
+    #[serde(skip_serializing_if = "is_false")]
+    pub sandbox_gas_traces: bool,
     /// Different parameters to configure underlying storage.
     pub store: near_store::StoreConfig,
     /// Different parameters to configure underlying cold storage.
     #[cfg(feature = "cold_store")]
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub cold_store: Option<near_store::StoreConfig>,
+    /// Configuration of the loop copying data from hot to cold storage.
+    #[cfg(feature = "cold_store")]
+    #[serde(default)]
+    pub cold_store_migration: crate::cold_storage::ColdStoreMigrationConfig,
 
     // TODO(mina86): Remove those two altogether at some point.  We need to be
     // somewhat careful though and make sure that we don’t start silently
@@ -370,11 +399,20 @@ impl Default for Config {
             view_client_throttle_period: default_view_client_throttle_period(),
             trie_viewer_state_size_limit: default_trie_viewer_state_size_limit(),
             max_gas_burnt_view: None,
+            transaction_pool: TransactionPoolConfig::default(),
+            state_sync_external_parts: None,
+            state_parts_dump: None,
+            contract_cache: None,
+            checkpoints_dir: None,
+            #[cfg(feature = "sandbox")]
+            sandbox_gas_traces: false,
             db_migration_snapshot_path: None,
             use_db_migration_snapshot: None,
             store: near_store::StoreConfig::default(),
             #[cfg(feature = "cold_store")]
             cold_store: None,
+            #[cfg(feature = "cold_store")]
+            cold_store_migration: Default::default(),
         }
     }
 }
@@ -428,6 +466,27 @@ impl Config {
         None
     }
 
+    /// Returns the values of the fields which can be changed without restarting the node.
+    pub fn updateable_configs(&self) -> near_dyn_configs::UpdateableConfigs {
+        #[allow(unused_mut)]
+        let mut rpc_limits = None;
+        #[cfg(feature = "json_rpc")]
+        if let Some(rpc) = &self.rpc {
+            rpc_limits = Some(near_dyn_configs::RpcLimits {
+                json_payload_max_size: rpc.limits_config.json_payload_max_size,
+                max_batch_size: rpc.limits_config.max_batch_size,
+            });
+        }
+        near_dyn_configs::UpdateableConfigs {
+            gc_blocks_limit: self.gc.gc_blocks_limit,
+            max_num_peers: self.network.max_num_peers,
+            ideal_connections_lo: self.network.ideal_connections_lo,
+            ideal_connections_hi: self.network.ideal_connections_hi,
+            tracked_accounts: self.tracked_accounts.clone(),
+            rpc_limits,
+        }
+    }
+
     #[allow(unused_variables)]
     pub fn set_rpc_addr(&mut self, addr: String) {
         #[cfg(feature = "json_rpc")]
@@ -598,6 +657,10 @@ impl NearConfig {
                 tracked_accounts: config.tracked_accounts,
                 tracked_shards: config.tracked_shards,
                 archive: config.archive,
+                #[cfg(feature = "cold_store")]
+                split_storage: config.cold_store.is_some(),
+                #[cfg(not(feature = "cold_store"))]
+                split_storage: false,
                 log_summary_style: config.log_summary_style,
                 gc: config.gc,
                 view_client_threads: config.view_client_threads,
@@ -607,6 +670,9 @@ impl NearConfig {
                 max_gas_burnt_view: config.max_gas_burnt_view,
                 enable_statistics_export: config.store.enable_statistics_export,
                 client_background_migration_threads: config.store.background_migration_threads,
+                transaction_pool: config.transaction_pool,
+                state_sync_external_parts: config.state_sync_external_parts,
+                checkpoints_dir: config.checkpoints_dir,
             },
             network_config: NetworkConfig::new(
                 config.network,
@@ -1317,7 +1383,15 @@ pub fn load_config(
                         "Validator must track all shards. Please change `tracked_shards` field in config.json to be any non-empty vector");
     }
 
-    NearConfig::new(config, genesis, network_signer.into(), validator_signer)
+    let mut near_config =
+        NearConfig::new(config, genesis, network_signer.into(), validator_signer)?;
+    if let Some(recorder) = &mut near_config.network_config.traffic_recorder {
+        recorder.dir = dir.join(&recorder.dir);
+    }
+    if let Some(checkpoints_dir) = &mut near_config.client_config.checkpoints_dir {
+        *checkpoints_dir = dir.join(&checkpoints_dir);
+    }
+    Ok(near_config)
 }
 
 pub fn load_test_config(seed: &str, port: u16, genesis: Genesis) -> NearConfig {
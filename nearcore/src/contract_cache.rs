@@ -0,0 +1,507 @@
+//! Background loop keeping the compiled contracts cache in check.
+//!
+//! Compiled contracts are cached in the database under keys depending on the
+//! VM and its config, so once the protocol version or the neard binary
+//! changes, the old entries are never used again.  The loop periodically
+//! removes entries compiled by VMs which aren’t used at the current epoch’s
+//! protocol version or any later one, and evicts the least recently used
+//! entries once the cache exceeds its size limit.  See
+//! [`near_store::contract_cache`] for what is tracked about the entries.
+//!
+//! Optionally, once the next epoch is known to switch to a VM which compiles
+//! contracts differently, the loop precompiles all contracts deployed on the
+//! tracked shards for it so that they don’t all get compiled lazily at the
+//! beginning of the new epoch.  The same can be done with `neard
+//! contract-cache precompile` on a stopped node.
+use crate::{load_config, metrics, NearConfig, NightshadeRuntime};
+use actix::{Actor, Arbiter, ArbiterHandle, Context};
+use anyhow::Context as _;
+use borsh::{BorshDeserialize, BorshSerialize};
+use near_chain::{ChainStore, ChainStoreAccess, RuntimeAdapter};
+use near_chain_configs::GenesisValidationMode;
+use near_epoch_manager::EpochManagerAdapter;
+use near_primitives::contract::ContractCode;
+use near_primitives::hash::CryptoHash;
+use near_primitives::trie_key::trie_key_parsers;
+use near_primitives::types::{AccountId, ProtocolVersion};
+use near_store::contract_cache::{self, ContractCacheEntry, PruneStats};
+use near_store::{DBCol, Mode, NodeStorage, Store, Temperature};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{error, info};
+
+/// Configuration of the compiled contracts cache.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ContractCacheConfig {
+    /// Maximum total size of the cache.  Least recently used entries are
+    /// evicted once it’s exceeded.  Not limited if unset.
+    #[serde(default)]
+    pub max_size: Option<bytesize::ByteSize>,
+    /// Time between cache prunings.
+    #[serde(default = "default_prune_period")]
+    pub prune_period: Duration,
+    /// Whether to precompile contracts deployed on the tracked shards once the
+    /// next epoch’s protocol version is known to use a different VM.
+    #[serde(default)]
+    pub precompile_upcoming: bool,
+}
+
+fn default_prune_period() -> Duration {
+    Duration::from_secs(3600)
+}
+
+/// Actor pruning the compiled contracts cache.
+pub struct ContractCacheActor {
+    store: Store,
+    chain_store: ChainStore,
+    runtime: Arc<NightshadeRuntime>,
+    /// Validator account of the node, if any.
+    account_id: Option<AccountId>,
+    config: ContractCacheConfig,
+}
+
+/// Identifiers of the VMs used from the current epoch onwards.
+fn current_vm_ids(
+    chain_store: &ChainStore,
+    runtime: &NightshadeRuntime,
+) -> anyhow::Result<HashSet<CryptoHash>> {
+    let head = chain_store.head()?;
+    let protocol_version = runtime.get_epoch_protocol_version(&head.epoch_id)?;
+    Ok(runtime.contract_cache_vm_ids(protocol_version))
+}
+
+/// Key in `DBCol::BlockMisc` under which [`PrecompilationProgress`] is stored.
+const PRECOMPILATION_PROGRESS_KEY: &[u8] = b"CONTRACT_PRECOMPILATION";
+
+/// Number of contracts precompiled between saves of the progress.
+const PRECOMPILATION_SAVE_INTERVAL: u64 = 100;
+
+/// Outcome of precompiling a single contract.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PrecompilationResult {
+    Compiled,
+    /// The contract has already been in the cache.
+    Cached,
+    /// The contract failed to compile.  The error is cached so the contract
+    /// won’t be compiled again when called.
+    Failed,
+}
+
+impl PrecompilationResult {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Compiled => "compiled",
+            Self::Cached => "cached",
+            Self::Failed => "failed",
+        }
+    }
+}
+
+/// Statistics of a single [`precompile_contracts`] run.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PrecompilationStats {
+    pub compiled: u64,
+    pub cached: u64,
+    pub failed: u64,
+    /// Number of tracked shards whose contracts have all been precompiled,
+    /// including ones finished by earlier runs.
+    pub shards_done: u64,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq, Eq)]
+enum ShardPrecompilationProgress {
+    /// Contains the raw trie key of the last precompiled contract.
+    InProgress(Vec<u8>),
+    Done,
+}
+
+/// Progress of the precompilation persisted so that it can be resumed after
+/// a restart.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, Default, PartialEq, Eq)]
+struct PrecompilationProgress {
+    /// VM the contracts are compiled for.  The progress is discarded when
+    /// precompiling for a different one.
+    vm_id: CryptoHash,
+    /// Progress by shard, keyed by the bytes of its `ShardUId`.
+    shards: BTreeMap<[u8; 8], ShardPrecompilationProgress>,
+}
+
+impl PrecompilationProgress {
+    fn save(&self, store: &Store) -> std::io::Result<()> {
+        let mut update = store.store_update();
+        update.set_ser(DBCol::BlockMisc, PRECOMPILATION_PROGRESS_KEY, self)?;
+        update.commit()
+    }
+}
+
+/// Precompiles contracts deployed on the shards tracked at the head of the
+/// chain, including the ones validated by `me`, for `protocol_version` and
+/// stores them in the compiled contracts cache.  The contracts are read from
+/// the state after the head block.
+///
+/// Progress is saved in the database and the contracts of a shard are
+/// iterated in the order of their trie keys, so an interrupted run continues
+/// where it left off.  Contracts deployed in the meantime to accounts already
+/// passed over are compiled lazily as usual.
+pub fn precompile_contracts(
+    store: &Store,
+    chain_store: &ChainStore,
+    runtime: &NightshadeRuntime,
+    me: Option<&AccountId>,
+    protocol_version: ProtocolVersion,
+) -> anyhow::Result<PrecompilationStats> {
+    let head = chain_store.head()?;
+    let vm_id = runtime.contract_cache_vm_id(protocol_version);
+    let mut progress = store
+        .get_ser::<PrecompilationProgress>(DBCol::BlockMisc, PRECOMPILATION_PROGRESS_KEY)?
+        .filter(|progress| progress.vm_id == vm_id)
+        .unwrap_or_else(|| PrecompilationProgress { vm_id, shards: BTreeMap::new() });
+    metrics::CONTRACT_PRECOMPILATION_VERSION.set(protocol_version.into());
+
+    let mut stats = PrecompilationStats::default();
+    let code_prefix = trie_key_parsers::get_raw_prefix_for_contract_codes();
+    for shard_id in 0..runtime.num_shards(&head.epoch_id)? {
+        if !runtime.cares_about_shard(me, &head.prev_block_hash, shard_id, true) {
+            continue;
+        }
+        let shard_uid = runtime.shard_id_to_uid(shard_id, &head.epoch_id)?;
+        let last_key = match progress.shards.get(&shard_uid.to_bytes()) {
+            Some(ShardPrecompilationProgress::Done) => {
+                stats.shards_done += 1;
+                continue;
+            }
+            Some(ShardPrecompilationProgress::InProgress(key)) => Some(key.clone()),
+            None => None,
+        };
+        let chunk_extra = chain_store.get_chunk_extra(&head.last_block_hash, &shard_uid)?;
+        let trie =
+            runtime.get_tries().get_view_trie_for_shard(shard_uid, *chunk_extra.state_root());
+        let mut iter = trie.iter()?;
+        match &last_key {
+            Some(key) => iter.seek(key)?,
+            None => iter.seek_prefix(&code_prefix)?,
+        }
+        info!(target: "contract_cache", shard_uid = %shard_uid, protocol_version, "Precompiling contracts");
+        let mut processed = 0;
+        for item in iter {
+            let (key, code) = item?;
+            if !key.starts_with(&code_prefix) {
+                break;
+            }
+            if last_key.as_ref() == Some(&key) {
+                continue;
+            }
+            let code = ContractCode::new(code, None);
+            let result = runtime.precompile_contract_for_version(&code, protocol_version)?;
+            metrics::CONTRACT_PRECOMPILATION_CONTRACTS.with_label_values(&[result.as_str()]).inc();
+            match result {
+                PrecompilationResult::Compiled => stats.compiled += 1,
+                PrecompilationResult::Cached => stats.cached += 1,
+                PrecompilationResult::Failed => stats.failed += 1,
+            }
+            processed += 1;
+            if processed % PRECOMPILATION_SAVE_INTERVAL == 0 {
+                progress
+                    .shards
+                    .insert(shard_uid.to_bytes(), ShardPrecompilationProgress::InProgress(key));
+                progress.save(store)?;
+            }
+        }
+        progress.shards.insert(shard_uid.to_bytes(), ShardPrecompilationProgress::Done);
+        progress.save(store)?;
+        stats.shards_done += 1;
+        metrics::CONTRACT_PRECOMPILATION_SHARDS_DONE.set(stats.shards_done as i64);
+    }
+    metrics::CONTRACT_PRECOMPILATION_SHARDS_DONE.set(stats.shards_done as i64);
+    Ok(stats)
+}
+
+impl ContractCacheActor {
+    fn prune_step(&self) -> anyhow::Result<()> {
+        let vm_ids = current_vm_ids(&self.chain_store, &self.runtime)?;
+        let max_size = self.config.max_size.map(|size| size.as_u64());
+        let stats = contract_cache::prune(&self.store, &vm_ids, max_size, false)?;
+        info!(target: "contract_cache", ?stats, "Pruned compiled contracts cache");
+        Ok(())
+    }
+
+    /// Precompiles contracts for the next epoch if its protocol version
+    /// compiles them differently than the current one.
+    fn precompile_step(&self) -> anyhow::Result<()> {
+        let head = self.chain_store.head()?;
+        let current_version = self.runtime.get_epoch_protocol_version(&head.epoch_id)?;
+        let next_version = self.runtime.get_epoch_protocol_version(&head.next_epoch_id)?;
+        if self.runtime.contract_cache_vm_id(current_version)
+            == self.runtime.contract_cache_vm_id(next_version)
+        {
+            return Ok(());
+        }
+        let stats = precompile_contracts(
+            &self.store,
+            &self.chain_store,
+            &self.runtime,
+            self.account_id.as_ref(),
+            next_version,
+        )?;
+        info!(target: "contract_cache", ?stats, next_version, "Precompiled contracts");
+        Ok(())
+    }
+
+    fn run_prune(&mut self, ctx: &mut Context<Self>) {
+        if let Err(err) = self.prune_step() {
+            error!(target: "contract_cache", ?err, "Failed to prune compiled contracts cache");
+        }
+        if self.config.precompile_upcoming {
+            if let Err(err) = self.precompile_step() {
+                error!(target: "contract_cache", ?err, "Failed to precompile contracts");
+            }
+        }
+        near_performance_metrics::actix::run_later(ctx, self.config.prune_period, |act, ctx| {
+            act.run_prune(ctx);
+        });
+    }
+}
+
+impl Actor for ContractCacheActor {
+    type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        self.run_prune(ctx);
+    }
+}
+
+/// Starts the loop pruning the compiled contracts cache in a new arbiter.
+///
+/// Returns `None` if the cache isn’t configured.
+pub fn spawn_contract_cache_loop(
+    config: &NearConfig,
+    store: Store,
+    runtime: Arc<NightshadeRuntime>,
+) -> Option<ArbiterHandle> {
+    let cache_config = config.config.contract_cache.clone()?;
+    let chain_store = ChainStore::new(
+        store.clone(),
+        config.genesis.config.genesis_height,
+        !config.client_config.archive,
+    );
+    let account_id = config.validator_signer.as_ref().map(|signer| signer.validator_id().clone());
+    let arbiter = Arbiter::new().handle();
+    ContractCacheActor::start_in_arbiter(&arbiter, move |_ctx| ContractCacheActor {
+        store,
+        chain_store,
+        runtime,
+        account_id,
+        config: cache_config,
+    });
+    Some(arbiter)
+}
+
+/// Compiled contracts cache of a stopped node.
+pub struct OfflineContractCache {
+    pub store: Store,
+    /// Identifiers of the VMs used from the current epoch onwards.
+    pub vm_ids: HashSet<CryptoHash>,
+    pub config: Option<ContractCacheConfig>,
+    chain_store: ChainStore,
+    runtime: NightshadeRuntime,
+    /// Validator account of the node, if any.
+    account_id: Option<AccountId>,
+}
+
+impl OfflineContractCache {
+    /// Opens the database of the node in `home_dir`.
+    pub fn open(home_dir: &Path) -> anyhow::Result<Self> {
+        let near_config = load_config(home_dir, GenesisValidationMode::UnsafeFast)?;
+        let opener = NodeStorage::opener(home_dir, &near_config.config.store, None);
+        let store = opener
+            .open_in_mode(Mode::ReadWriteExisting)
+            .with_context(|| format!("Opening database at {}", opener.path().display()))?
+            .get_store(Temperature::Hot);
+        let runtime = NightshadeRuntime::from_config(home_dir, store.clone(), &near_config);
+        let chain_store = ChainStore::new(
+            store.clone(),
+            near_config.genesis.config.genesis_height,
+            !near_config.client_config.archive,
+        );
+        let vm_ids = current_vm_ids(&chain_store, &runtime)?;
+        let account_id =
+            near_config.validator_signer.as_ref().map(|signer| signer.validator_id().clone());
+        Ok(Self {
+            store,
+            vm_ids,
+            config: near_config.config.contract_cache,
+            chain_store,
+            runtime,
+            account_id,
+        })
+    }
+
+    pub fn list(&self) -> anyhow::Result<Vec<ContractCacheEntry>> {
+        Ok(contract_cache::list(&self.store)?)
+    }
+
+    /// Removes stale and untracked entries and evicts the least recently used
+    /// ones exceeding `max_size`, which defaults to the configured limit.
+    pub fn prune(&self, max_size: Option<bytesize::ByteSize>) -> anyhow::Result<PruneStats> {
+        let max_size = max_size.or_else(|| self.config.as_ref().and_then(|c| c.max_size));
+        let max_size = max_size.map(|size| size.as_u64());
+        Ok(contract_cache::prune(&self.store, &self.vm_ids, max_size, true)?)
+    }
+
+    /// Precompiles contracts deployed on the tracked shards for
+    /// `protocol_version`, see [`precompile_contracts`].
+    pub fn precompile(
+        &self,
+        protocol_version: ProtocolVersion,
+    ) -> anyhow::Result<PrecompilationStats> {
+        precompile_contracts(
+            &self.store,
+            &self.chain_store,
+            &self.runtime,
+            self.account_id.as_ref(),
+            protocol_version,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::GenesisExt;
+    use crate::shard_tracker::TrackedConfig;
+    use near_chain::ChainGenesis;
+    use near_chain_configs::Genesis;
+    use near_client::test_utils::TestEnv;
+    use near_primitives::runtime::config_store::RuntimeConfigStore;
+    use near_primitives::shard_layout::ShardUId;
+    use near_primitives::state_record::StateRecord;
+    use near_primitives::trie_key::TrieKey;
+    use near_primitives::version::PROTOCOL_VERSION;
+    use near_store::test_utils::create_test_store;
+
+    const EPOCH_LENGTH: u64 = 5;
+
+    /// Starts a chain with contracts deployed to `test0` and `test1` and
+    /// returns it along with an empty compiled contracts cache.
+    fn setup() -> (TestEnv, Store, ChainStore, Arc<NightshadeRuntime>) {
+        let mut genesis =
+            Genesis::test(vec!["test0".parse().unwrap(), "test1".parse().unwrap()], 1);
+        genesis.config.epoch_length = EPOCH_LENGTH;
+        let contracts = [
+            ("test0", near_test_contracts::rs_contract()),
+            ("test1", near_test_contracts::trivial_contract()),
+        ];
+        let records = genesis.force_read_records().as_mut();
+        for record in records.iter_mut() {
+            if let StateRecord::Account { account_id, account } = record {
+                for (contract_account_id, code) in contracts {
+                    if account_id.as_ref() == contract_account_id {
+                        account.set_code_hash(*ContractCode::new(code.to_vec(), None).hash());
+                    }
+                }
+            }
+        }
+        for (account_id, code) in contracts {
+            records.push(StateRecord::Contract {
+                account_id: account_id.parse().unwrap(),
+                code: code.to_vec(),
+            });
+        }
+        let mut chain_genesis = ChainGenesis::test();
+        chain_genesis.epoch_length = EPOCH_LENGTH;
+        let store = create_test_store();
+        let runtime = Arc::new(NightshadeRuntime::test_with_runtime_config_store(
+            Path::new("."),
+            store.clone(),
+            &genesis,
+            TrackedConfig::new_empty(),
+            RuntimeConfigStore::test(),
+        ));
+        let mut env = TestEnv::builder(chain_genesis)
+            .runtime_adapters(vec![runtime.clone() as Arc<dyn RuntimeAdapter>])
+            .build();
+        for height in 1..=EPOCH_LENGTH {
+            env.produce_block(0, height);
+        }
+        let mut update = store.store_update();
+        update.delete_all(DBCol::CachedContractCode);
+        update.delete_all(DBCol::CachedContractCodeInfo);
+        update.commit().unwrap();
+        let chain_store = ChainStore::new(store.clone(), genesis.config.genesis_height, true);
+        (env, store, chain_store, runtime)
+    }
+
+    #[test]
+    fn test_precompile_contracts() {
+        let (_env, store, chain_store, runtime) = setup();
+        let me: AccountId = "test0".parse().unwrap();
+
+        // The node doesn’t track any shard unless it validates it.
+        let stats =
+            precompile_contracts(&store, &chain_store, &runtime, None, PROTOCOL_VERSION).unwrap();
+        assert_eq!(stats, PrecompilationStats::default());
+
+        let stats =
+            precompile_contracts(&store, &chain_store, &runtime, Some(&me), PROTOCOL_VERSION)
+                .unwrap();
+        assert_eq!(
+            stats,
+            PrecompilationStats { compiled: 2, shards_done: 1, ..Default::default() }
+        );
+        let entries = contract_cache::list(&store).unwrap();
+        assert_eq!(entries.len(), 2);
+        let vm_id = runtime.contract_cache_vm_id(PROTOCOL_VERSION);
+        assert!(entries.iter().all(|entry| entry.info.as_ref().unwrap().info.vm_id == vm_id));
+
+        // The shard is done, so the contracts aren’t looked at again.
+        let stats =
+            precompile_contracts(&store, &chain_store, &runtime, Some(&me), PROTOCOL_VERSION)
+                .unwrap();
+        assert_eq!(stats, PrecompilationStats { shards_done: 1, ..Default::default() });
+    }
+
+    #[test]
+    fn test_precompile_contracts_resume() {
+        let (_env, store, chain_store, runtime) = setup();
+        let me: AccountId = "test0".parse().unwrap();
+        let vm_id = runtime.contract_cache_vm_id(PROTOCOL_VERSION);
+        let shard_uid = ShardUId::single_shard();
+
+        // A run has been interrupted after compiling the contract of test0.
+        let last_key = TrieKey::ContractCode { account_id: me.clone() }.to_vec();
+        let progress = PrecompilationProgress {
+            vm_id,
+            shards: BTreeMap::from([(
+                shard_uid.to_bytes(),
+                ShardPrecompilationProgress::InProgress(last_key),
+            )]),
+        };
+        progress.save(&store).unwrap();
+        let stats =
+            precompile_contracts(&store, &chain_store, &runtime, Some(&me), PROTOCOL_VERSION)
+                .unwrap();
+        assert_eq!(
+            stats,
+            PrecompilationStats { compiled: 1, shards_done: 1, ..Default::default() }
+        );
+        assert_eq!(contract_cache::list(&store).unwrap().len(), 1);
+
+        // Progress of precompiling for another VM is discarded.
+        let progress = PrecompilationProgress {
+            vm_id: CryptoHash::default(),
+            shards: BTreeMap::from([(shard_uid.to_bytes(), ShardPrecompilationProgress::Done)]),
+        };
+        progress.save(&store).unwrap();
+        let stats =
+            precompile_contracts(&store, &chain_store, &runtime, Some(&me), PROTOCOL_VERSION)
+                .unwrap();
+        assert_eq!(
+            stats,
+            PrecompilationStats { compiled: 1, cached: 1, shards_done: 1, ..Default::default() }
+        );
+        assert_eq!(contract_cache::list(&store).unwrap().len(), 2);
+    }
+}
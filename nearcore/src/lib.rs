@@ -21,12 +21,16 @@ use tokio::sync::oneshot;
 use tracing::{info, trace};
 
 pub mod append_only_map;
+#[cfg(feature = "cold_store")]
+pub mod cold_storage;
 pub mod config;
+pub mod contract_cache;
 mod download_file;
 mod metrics;
 pub mod migrations;
 mod runtime;
 mod shard_tracker;
+pub mod state_parts_dump;
 
 pub fn get_default_home() -> PathBuf {
     if let Ok(near_home) = std::env::var("NEAR_HOME") {
@@ -76,8 +80,8 @@ fn open_storage(home_dir: &Path, near_config: &mut NearConfig) -> anyhow::Result
         Err(StoreOpenerError::HotColdExistenceMismatch) => {
             Err(anyhow::anyhow!(
                 "Hot and cold databases must either both exist or both not exist.\n\
-                 Note that at this moment it’s not possible to convert and RPC or legacy archive database into split hot+cold database.\n\
-                 To set up node in that configuration, start with neither of the databases existing.",
+                 Note that only an archive database can be converted into split hot+cold database.\n\
+                 To set up node in that configuration, start with neither of the databases existing or with just the archive one.",
             ))
         },
         Err(err @ StoreOpenerError::HotColdVersionMismatch { .. }) => {
@@ -173,6 +177,34 @@ pub fn start_with_config_and_synchronization(
         &config,
     ));
 
+    let mut arbiters = Vec::new();
+    #[cfg(feature = "cold_store")]
+    arbiters.extend(cold_storage::spawn_cold_store_loop(
+        &store,
+        runtime.clone(),
+        config.config.cold_store_migration.clone(),
+    )?);
+    arbiters.extend(state_parts_dump::spawn_state_parts_dump_loop(
+        &config,
+        store.get_store(Temperature::Hot),
+        runtime.clone(),
+    ));
+    arbiters.extend(contract_cache::spawn_contract_cache_loop(
+        &config,
+        store.get_store(Temperature::Hot),
+        runtime.clone(),
+    ));
+
+    // With split storage, view requests for blocks which have been garbage
+    // collected from hot storage are served from cold storage.
+    #[cfg(feature = "cold_store")]
+    let view_runtime = match store.get_split_store() {
+        Some(split_store) => Arc::new(runtime.with_view_store(split_store)),
+        None => runtime.clone(),
+    };
+    #[cfg(not(feature = "cold_store"))]
+    let view_runtime = runtime.clone();
+
     let telemetry = TelemetryActor::new(config.telemetry_config.clone()).start();
     let chain_genesis = ChainGenesis::new(&config.genesis);
     let genesis_block = Chain::make_genesis_block(&*runtime, &chain_genesis)?;
@@ -188,7 +220,7 @@ pub fn start_with_config_and_synchronization(
     let view_client = start_view_client(
         config.validator_signer.as_ref().map(|signer| signer.validator_id().clone()),
         chain_genesis.clone(),
-        runtime.clone(),
+        view_runtime,
         network_adapter.clone(),
         config.client_config.clone(),
         adv.clone(),
@@ -205,6 +237,8 @@ pub fn start_with_config_and_synchronization(
         adv,
     );
 
+    arbiters.push(client_arbiter_handle);
+
     #[allow(unused_mut)]
     let mut rpc_servers = Vec::new();
     let network_actor = PeerManagerActor::spawn(
@@ -250,12 +284,7 @@ pub fn start_with_config_and_synchronization(
     #[cfg(feature = "performance_stats")]
     reset_memory_usage_max();
 
-    Ok(NearNode {
-        client: client_actor,
-        view_client,
-        rpc_servers,
-        arbiters: vec![client_arbiter_handle],
-    })
+    Ok(NearNode { client: client_actor, view_client, rpc_servers, arbiters })
 }
 
 pub struct RecompressOpts {
@@ -388,3 +417,51 @@ pub fn recompress_storage(home_dir: &Path, opts: RecompressOpts) -> anyhow::Resu
     info!(target: "recompress", dest = %dst_path.display(), "Database recompressed");
     Ok(())
 }
+
+/// Loads node configuration for database backup commands.
+///
+/// Backups cover a single database so nodes with cold storage configured are
+/// refused; such nodes should back up their hot database through the debug
+/// checkpoint endpoint instead.
+fn load_backup_config(home_dir: &Path) -> anyhow::Result<config::Config> {
+    let config_path = home_dir.join(config::CONFIG_FILENAME);
+    let config = config::Config::from_file(&config_path)
+        .map_err(|err| anyhow::anyhow!("{}: {}", config_path.display(), err))?;
+    #[cfg(feature = "cold_store")]
+    anyhow::ensure!(
+        config.cold_store.is_none(),
+        "{}: backups of nodes with cold storage are not supported",
+        config_path.display()
+    );
+    Ok(config)
+}
+
+/// Creates a backup of node’s database in `dir` directory.
+///
+/// The node must not be running.  To back up a running node, use the
+/// `/debug/api/checkpoint` RPC endpoint.
+pub fn backup_database(
+    home_dir: &Path,
+    dir: &Path,
+) -> anyhow::Result<near_store::backup::BackupInfo> {
+    let config = load_backup_config(home_dir)?;
+    let opener = NodeStorage::opener(home_dir, &config.store, None);
+    let store = opener
+        .open_in_mode(Mode::ReadWriteExisting)
+        .with_context(|| format!("Opening database at {}", opener.path().display()))?
+        .get_store(Temperature::Hot);
+    near_store::backup::create_backup(&store, dir)
+}
+
+/// Restores node’s database from backup in `dir` directory.
+///
+/// The node must not be running and its existing database must have been
+/// moved away.
+pub fn restore_database(
+    home_dir: &Path,
+    dir: &Path,
+) -> anyhow::Result<near_store::backup::BackupInfo> {
+    let config = load_backup_config(home_dir)?;
+    let opener = NodeStorage::opener(home_dir, &config.store, None);
+    near_store::backup::restore_backup(dir, opener.path())
+}
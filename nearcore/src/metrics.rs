@@ -1,4 +1,9 @@
-use near_o11y::metrics::{linear_buckets, try_create_histogram_vec, HistogramVec};
+#[cfg(feature = "cold_store")]
+use near_o11y::metrics::{exponential_buckets, try_create_histogram_with_buckets, Histogram};
+use near_o11y::metrics::{
+    linear_buckets, try_create_histogram_vec, try_create_int_counter, try_create_int_counter_vec,
+    try_create_int_gauge, HistogramVec, IntCounter, IntCounterVec, IntGauge,
+};
 use once_cell::sync::Lazy;
 
 pub static APPLY_CHUNK_DELAY: Lazy<HistogramVec> = Lazy::new(|| {
@@ -24,3 +29,90 @@ pub static SECONDS_PER_PETAGAS: Lazy<HistogramVec> = Lazy::new(|| {
     )
     .unwrap()
 });
+
+#[cfg(feature = "cold_store")]
+pub static COLD_HEAD_HEIGHT: Lazy<IntGauge> = Lazy::new(|| {
+    try_create_int_gauge("near_cold_head_height", "Height of the head of cold storage").unwrap()
+});
+
+#[cfg(feature = "cold_store")]
+pub static COLD_STORE_MIGRATION_LAG: Lazy<IntGauge> = Lazy::new(|| {
+    try_create_int_gauge(
+        "near_cold_store_migration_lag",
+        "Number of final heights which haven’t been copied to cold storage yet",
+    )
+    .unwrap()
+});
+
+#[cfg(feature = "cold_store")]
+pub static COLD_STORE_INITIAL_MIGRATION_IN_PROGRESS: Lazy<IntGauge> = Lazy::new(|| {
+    try_create_int_gauge(
+        "near_cold_store_initial_migration_in_progress",
+        "Whether all data of an existing archive is being copied to cold storage",
+    )
+    .unwrap()
+});
+
+#[cfg(feature = "cold_store")]
+pub static COLD_STORE_COPY_BLOCK_TIME: Lazy<Histogram> = Lazy::new(|| {
+    try_create_histogram_with_buckets(
+        "near_cold_store_copy_block_time",
+        "Time taken to copy data of a single block to cold storage",
+        exponential_buckets(0.001, 1.6, 20).unwrap(),
+    )
+    .unwrap()
+});
+
+#[cfg(feature = "cold_store")]
+pub static COLD_STORE_MIGRATION_ERRORS: Lazy<IntCounter> = Lazy::new(|| {
+    try_create_int_counter(
+        "near_cold_store_migration_errors",
+        "Number of failed attempts to copy data to cold storage",
+    )
+    .unwrap()
+});
+
+pub static STATE_PARTS_DUMP_EPOCH_HEIGHT: Lazy<IntGauge> = Lazy::new(|| {
+    try_create_int_gauge(
+        "near_state_parts_dump_epoch_height",
+        "Height of the most recent epoch whose state parts have been dumped",
+    )
+    .unwrap()
+});
+
+pub static STATE_PARTS_DUMPED: Lazy<IntCounter> = Lazy::new(|| {
+    try_create_int_counter("near_state_parts_dumped_total", "Number of state parts dumped").unwrap()
+});
+
+pub static STATE_PARTS_DUMP_ERRORS: Lazy<IntCounter> = Lazy::new(|| {
+    try_create_int_counter(
+        "near_state_parts_dump_errors",
+        "Number of failed attempts to dump state parts",
+    )
+    .unwrap()
+});
+
+pub static CONTRACT_PRECOMPILATION_VERSION: Lazy<IntGauge> = Lazy::new(|| {
+    try_create_int_gauge(
+        "near_contract_precompilation_protocol_version",
+        "Protocol version deployed contracts are being or have last been precompiled for",
+    )
+    .unwrap()
+});
+
+pub static CONTRACT_PRECOMPILATION_CONTRACTS: Lazy<IntCounterVec> = Lazy::new(|| {
+    try_create_int_counter_vec(
+        "near_contract_precompilation_contracts",
+        "Deployed contracts processed by the precompilation by result: compiled, cached or failed",
+        &["result"],
+    )
+    .unwrap()
+});
+
+pub static CONTRACT_PRECOMPILATION_SHARDS_DONE: Lazy<IntGauge> = Lazy::new(|| {
+    try_create_int_gauge(
+        "near_contract_precompilation_shards_done",
+        "Number of shards whose deployed contracts have all been precompiled",
+    )
+    .unwrap()
+});
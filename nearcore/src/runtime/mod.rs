@@ -1,3 +1,4 @@
+use crate::contract_cache::PrecompilationResult;
 use crate::metrics;
 use crate::migrations::load_migration_data;
 use crate::shard_tracker::{ShardTracker, TrackedConfig};
@@ -5,7 +6,9 @@ use crate::NearConfig;
 use borsh::ser::BorshSerialize;
 use borsh::BorshDeserialize;
 use errors::FromStateViewerErrors;
-use near_chain::types::{ApplySplitStateResult, ApplyTransactionResult, BlockHeaderInfo};
+use near_chain::types::{
+    ApplySplitStateResult, ApplyTransactionResult, BlockHeaderInfo, SimulateTransactionResult,
+};
 use near_chain::{Error, RuntimeAdapter};
 use near_chain_configs::{
     Genesis, GenesisConfig, ProtocolConfig, DEFAULT_GC_NUM_EPOCHS_TO_KEEP,
@@ -23,8 +26,10 @@ use near_primitives::contract::ContractCode;
 use near_primitives::epoch_manager::block_info::BlockInfo;
 use near_primitives::epoch_manager::EpochConfig;
 use near_primitives::errors::{InvalidTxError, RuntimeError, StorageError};
+#[cfg(feature = "sandbox")]
+use near_primitives::gas_trace::FunctionCallTrace;
 use near_primitives::hash::{hash, CryptoHash};
-use near_primitives::receipt::Receipt;
+use near_primitives::receipt::{DelayedReceiptIndices, Receipt};
 use near_primitives::runtime::config_store::RuntimeConfigStore;
 use near_primitives::runtime::migration_data::{MigrationData, MigrationFlags};
 use near_primitives::sandbox::state_patch::SandboxStatePatch;
@@ -35,13 +40,14 @@ use near_primitives::state_part::PartId;
 use near_primitives::state_record::{state_record_to_account_id, StateRecord};
 use near_primitives::syncing::{get_num_state_parts, STATE_PART_MEMORY_LIMIT};
 use near_primitives::transaction::SignedTransaction;
+use near_primitives::trie_key::TrieKey;
 use near_primitives::types::validator_stake::ValidatorStakeIter;
 use near_primitives::types::{
     AccountId, Balance, BlockHeight, CompiledContractCache, EpochHeight, EpochId,
-    EpochInfoProvider, Gas, MerkleHash, NumShards, ShardId, StateChangeCause,
-    StateChangesForSplitStates, StateRoot, StateRootNode,
+    EpochInfoProvider, Gas, MerkleHash, NumShards, ShardId, StateChangeCause, StateChanges,
+    StateChangesExt, StateChangesForSplitStates, StateRoot, StateRootNode,
 };
-use near_primitives::version::ProtocolVersion;
+use near_primitives::version::{ProtocolVersion, PROTOCOL_VERSION};
 use near_primitives::views::{
     AccessKeyInfoView, CallResult, QueryRequest, QueryResponse, QueryResponseKind, ViewApplyState,
     ViewStateResult,
@@ -56,7 +62,7 @@ use near_store::{
     ApplyStatePartResult, DBCol, PartialStorage, ShardTries, Store, StoreCompiledContractCache,
     StoreUpdate, Trie, TrieConfig, WrappedTrieChanges,
 };
-use near_vm_runner::precompile_contract;
+use near_vm_runner::{precompile_contract, ContractPrecompilatonResult};
 use node_runtime::adapter::ViewRuntimeAdapter;
 use node_runtime::config::RuntimeConfig;
 use node_runtime::state_viewer::TrieViewer;
@@ -64,7 +70,7 @@ use node_runtime::{
     validate_transaction, verify_and_charge_transaction, ApplyState, Runtime,
     ValidatorAccountsUpdate,
 };
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fs;
 use std::path::Path;
 use std::sync::{Arc, RwLockReadGuard, RwLockWriteGuard};
@@ -75,6 +81,17 @@ pub mod errors;
 
 const STATE_DUMP_FILE: &str = "state_dump";
 const GENESIS_ROOTS_FILE: &str = "genesis_roots";
+/// Upper bound on the number of blocks a simulated transaction may need to finish.
+const MAX_SIMULATION_ROUNDS: usize = 64;
+/// Gas a simulated transaction may burn across all the blocks it takes, when the caller does not
+/// set a limit. Matches the gas limit of a single chunk.
+const DEFAULT_SIMULATION_GAS_LIMIT: Gas = 1_000_000_000_000_000;
+/// Simulation is refused on shards with more delayed receipts than this, as they would be
+/// executed before the receipts of the simulated transaction.
+const MAX_SIMULATION_DELAYED_RECEIPTS: u64 = 100;
+/// Number of receipts sandbox nodes keep gas traces of.
+#[cfg(feature = "sandbox")]
+const SANDBOX_GAS_TRACES_CACHE_SIZE: usize = 1024;
 
 /// Defines Nightshade state transition and validator rotation.
 /// TODO: this possibly should be merged with the runtime cargo or at least reconciled on the interfaces.
@@ -88,15 +105,22 @@ pub struct NightshadeRuntime {
     flat_state_factory: FlatStateFactory,
     pub runtime: Runtime,
     epoch_manager: EpochManagerHandle,
-    shard_tracker: ShardTracker,
+    shard_tracker: Arc<ShardTracker>,
     genesis_state_roots: Vec<StateRoot>,
     migration_data: Arc<MigrationData>,
     gc_num_epochs_to_keep: u64,
+    /// Whether gas traces of executed function calls are recorded, see
+    /// `Config::sandbox_gas_traces`.
+    #[cfg(feature = "sandbox")]
+    record_gas_traces: bool,
+    /// Gas traces of recently executed receipts, for `sandbox_gas_trace`.
+    #[cfg(feature = "sandbox")]
+    gas_traces: std::sync::Mutex<lru::LruCache<CryptoHash, Vec<FunctionCallTrace>>>,
 }
 
 impl NightshadeRuntime {
     pub fn from_config(home_dir: &Path, store: Store, config: &NearConfig) -> Self {
-        Self::new(
+        let runtime = Self::new(
             home_dir,
             store,
             &config.genesis,
@@ -106,7 +130,18 @@ impl NightshadeRuntime {
             None,
             config.config.gc.gc_num_epochs_to_keep(),
             TrieConfig::from_store_config(&config.config.store),
-        )
+        );
+        #[cfg(feature = "sandbox")]
+        let runtime = runtime.with_gas_traces(config.config.sandbox_gas_traces);
+        runtime
+    }
+
+    /// Enables recording of the gas traces of executed function calls, for
+    /// `sandbox_gas_trace`.
+    #[cfg(feature = "sandbox")]
+    pub fn with_gas_traces(mut self, record_gas_traces: bool) -> Self {
+        self.record_gas_traces = record_gas_traces;
+        self
     }
 
     fn new(
@@ -147,7 +182,7 @@ impl NightshadeRuntime {
         let epoch_manager = EpochManager::new_from_genesis_config(store.clone(), &genesis_config)
             .expect("Failed to start Epoch Manager")
             .into_handle();
-        let shard_tracker = ShardTracker::new(tracked_config, epoch_manager.clone());
+        let shard_tracker = Arc::new(ShardTracker::new(tracked_config, epoch_manager.clone()));
         NightshadeRuntime {
             genesis_config,
             runtime_config_store,
@@ -161,6 +196,46 @@ impl NightshadeRuntime {
             genesis_state_roots: state_roots,
             migration_data: Arc::new(load_migration_data(&genesis.config.chain_id)),
             gc_num_epochs_to_keep: gc_num_epochs_to_keep.max(MIN_GC_NUM_EPOCHS_TO_KEEP),
+            #[cfg(feature = "sandbox")]
+            record_gas_traces: false,
+            #[cfg(feature = "sandbox")]
+            gas_traces: std::sync::Mutex::new(lru::LruCache::new(SANDBOX_GAS_TRACES_CACHE_SIZE)),
+        }
+    }
+
+    /// Returns a runtime reading state and chain data from `store` which
+    /// shares the epoch manager and the shard tracker with this one.
+    ///
+    /// Used to serve view requests from the split storage, see
+    /// [`near_store::NodeStorage::get_split_store`].  Genesis state is not
+    /// initialised in `store`, it must be the store of this runtime seen
+    /// through a different database.
+    #[cfg(feature = "cold_store")]
+    pub fn with_view_store(&self, store: Store) -> Self {
+        let flat_state_factory = FlatStateFactory::new(store.clone());
+        let tries = ShardTries::new(
+            store.clone(),
+            self.tries.trie_config().clone(),
+            &self.genesis_config.shard_layout.get_shard_uids(),
+            flat_state_factory.clone(),
+        );
+        NightshadeRuntime {
+            genesis_config: self.genesis_config.clone(),
+            runtime_config_store: self.runtime_config_store.clone(),
+            store,
+            tries,
+            runtime: Runtime::new(),
+            trie_viewer: self.trie_viewer.clone(),
+            epoch_manager: self.epoch_manager.clone(),
+            shard_tracker: self.shard_tracker.clone(),
+            flat_state_factory,
+            genesis_state_roots: self.genesis_state_roots.clone(),
+            migration_data: Arc::clone(&self.migration_data),
+            gc_num_epochs_to_keep: self.gc_num_epochs_to_keep,
+            #[cfg(feature = "sandbox")]
+            record_gas_traces: self.record_gas_traces,
+            #[cfg(feature = "sandbox")]
+            gas_traces: std::sync::Mutex::new(lru::LruCache::new(SANDBOX_GAS_TRACES_CACHE_SIZE)),
         }
     }
 
@@ -210,6 +285,43 @@ impl NightshadeRuntime {
         }
     }
 
+    /// Compiles `code` for `protocol_version` and stores it in the compiled
+    /// contracts cache unless it’s there already.
+    pub fn precompile_contract_for_version(
+        &self,
+        code: &ContractCode,
+        protocol_version: ProtocolVersion,
+    ) -> Result<PrecompilationResult, Error> {
+        let runtime_config = self.runtime_config_store.get_config(protocol_version);
+        let cache = StoreCompiledContractCache::new(&self.store);
+        match precompile_contract(code, &runtime_config.wasm_config, protocol_version, Some(&cache))
+        {
+            Ok(Ok(ContractPrecompilatonResult::ContractAlreadyInCache)) => {
+                Ok(PrecompilationResult::Cached)
+            }
+            Ok(Ok(_)) => Ok(PrecompilationResult::Compiled),
+            // Compilation errors are cached as well.
+            Ok(Err(_)) => Ok(PrecompilationResult::Failed),
+            Err(err) => Err(Error::Other(format!("compiled contracts cache: {err:?}"))),
+        }
+    }
+
+    /// Identifiers of the VMs compiling contracts at protocol versions from
+    /// `protocol_version` up to the latest one supported by this binary, see
+    /// `near_vm_runner::vm_cache_id`.  Compiled contracts cache entries of
+    /// other VMs won’t be used anymore.
+    pub fn contract_cache_vm_ids(&self, protocol_version: ProtocolVersion) -> HashSet<CryptoHash> {
+        (protocol_version..=PROTOCOL_VERSION)
+            .map(|version| self.contract_cache_vm_id(version))
+            .collect()
+    }
+
+    /// Identifier of the VM compiling contracts at given protocol version.
+    pub fn contract_cache_vm_id(&self, protocol_version: ProtocolVersion) -> CryptoHash {
+        let config = self.runtime_config_store.get_config(protocol_version);
+        near_vm_runner::vm_cache_id(&config.wasm_config, protocol_version)
+    }
+
     fn genesis_state_from_dump(store: Store, home_dir: &Path) -> Vec<StateRoot> {
         error!(target: "near", "Loading genesis from a state dump file. Do not use this outside of genesis-tools");
         let mut state_file = home_dir.to_path_buf();
@@ -499,9 +611,8 @@ impl NightshadeRuntime {
         };
 
         let instant = Instant::now();
-        let apply_result = self
-            .runtime
-            .apply(
+        let apply = || {
+            self.runtime.apply(
                 trie,
                 &validator_accounts_update,
                 &apply_state,
@@ -510,22 +621,42 @@ impl NightshadeRuntime {
                 &self.epoch_manager,
                 state_patch,
             )
-            .map_err(|e| match e {
-                RuntimeError::InvalidTxError(err) => {
-                    tracing::warn!("Invalid tx {:?}", err);
-                    Error::InvalidTransactions
-                }
-                // TODO(#2152): process gracefully
-                RuntimeError::BalanceMismatchError(e) => panic!("{}", e),
-                // TODO(#2152): process gracefully
-                RuntimeError::UnexpectedIntegerOverflow => {
-                    panic!("RuntimeError::UnexpectedIntegerOverflow")
-                }
-                RuntimeError::StorageError(e) => Error::StorageError(e),
-                // TODO(#2152): process gracefully
-                RuntimeError::ReceiptValidationError(e) => panic!("{}", e),
-                RuntimeError::ValidatorError(e) => e.into(),
-            })?;
+        };
+        #[cfg(feature = "sandbox")]
+        let apply_result = if self.record_gas_traces {
+            let (apply_result, traces) = node_runtime::record_gas_traces(apply);
+            // A receipt may be applied again, e.g. on another fork or after
+            // `sandbox_restore`, so traces of earlier executions are replaced.
+            let mut receipt_traces: HashMap<CryptoHash, Vec<FunctionCallTrace>> = HashMap::new();
+            for trace in traces {
+                receipt_traces.entry(trace.receipt_id).or_default().push(trace);
+            }
+            let mut gas_traces = self.gas_traces.lock().unwrap();
+            for (receipt_id, traces) in receipt_traces {
+                gas_traces.put(receipt_id, traces);
+            }
+            apply_result
+        } else {
+            apply()
+        };
+        #[cfg(not(feature = "sandbox"))]
+        let apply_result = apply();
+        let apply_result = apply_result.map_err(|e| match e {
+            RuntimeError::InvalidTxError(err) => {
+                tracing::warn!("Invalid tx {:?}", err);
+                Error::InvalidTransactions
+            }
+            // TODO(#2152): process gracefully
+            RuntimeError::BalanceMismatchError(e) => panic!("{}", e),
+            // TODO(#2152): process gracefully
+            RuntimeError::UnexpectedIntegerOverflow => {
+                panic!("RuntimeError::UnexpectedIntegerOverflow")
+            }
+            RuntimeError::StorageError(e) => Error::StorageError(e),
+            // TODO(#2152): process gracefully
+            RuntimeError::ReceiptValidationError(e) => panic!("{}", e),
+            RuntimeError::ValidatorError(e) => e.into(),
+        })?;
         let elapsed = instant.elapsed();
 
         let total_gas_burnt =
@@ -895,6 +1026,14 @@ impl RuntimeAdapter for NightshadeRuntime {
         self.shard_tracker.will_care_about_shard(account_id, parent_hash, shard_id, is_me)
     }
 
+    fn update_tracked_accounts(
+        &self,
+        tracked_accounts: Vec<AccountId>,
+        epoch_id: &EpochId,
+    ) -> Result<Option<EpochHeight>, Error> {
+        Ok(self.shard_tracker.update_tracked_accounts(tracked_accounts, epoch_id)?)
+    }
+
     fn get_gc_stop_height(&self, block_hash: &CryptoHash) -> BlockHeight {
         (|| -> Result<BlockHeight, Error> {
             let epoch_manager = self.epoch_manager.read();
@@ -1011,6 +1150,170 @@ impl RuntimeAdapter for NightshadeRuntime {
         }
     }
 
+    fn simulate_transaction(
+        &self,
+        state_roots: &HashMap<ShardId, StateRoot>,
+        height: BlockHeight,
+        block_timestamp: u64,
+        prev_block_hash: &CryptoHash,
+        gas_price: Balance,
+        gas_limit: Option<Gas>,
+        transaction: &SignedTransaction,
+    ) -> Result<SimulateTransactionResult, Error> {
+        let _span = tracing::debug_span!(target: "runtime", "simulate_transaction").entered();
+        let gas_limit = gas_limit.unwrap_or(DEFAULT_SIMULATION_GAS_LIMIT);
+        let epoch_id = self.get_epoch_id_from_prev_block(prev_block_hash)?;
+        let shard_layout = self.get_shard_layout(&epoch_id)?;
+        let epoch_height = self.get_epoch_height_from_prev_block(prev_block_hash)?;
+        let current_protocol_version = self.get_epoch_protocol_version(&epoch_id)?;
+        let mut apply_state = ApplyState {
+            block_height: height,
+            prev_block_hash: *prev_block_hash,
+            block_hash: CryptoHash::default(),
+            epoch_id,
+            epoch_height,
+            gas_price,
+            block_timestamp,
+            gas_limit: Some(gas_limit),
+            random_seed: CryptoHash::default(),
+            current_protocol_version,
+            config: self.runtime_config_store.get_config(current_protocol_version).clone(),
+            cache: Some(Box::new(StoreCompiledContractCache::new(&self.store))),
+            is_new_chunk: true,
+            migration_data: Arc::clone(&self.migration_data),
+            migration_flags: MigrationFlags::default(),
+        };
+
+        let signer_shard_id =
+            account_id_to_shard_id(&transaction.transaction.signer_id, &shard_layout);
+        if !state_roots.contains_key(&signer_shard_id) {
+            return Err(Error::Other(format!("Shard {} is not tracked", signer_shard_id)));
+        }
+        let mut transactions = vec![transaction.clone()];
+        let mut pending = BTreeMap::from([(signer_shard_id, vec![])]);
+        // State roots after the previous applies and the nodes they need, which are not in the
+        // store, per shard.
+        let mut overlays: HashMap<ShardId, (StateRoot, HashMap<CryptoHash, Arc<[u8]>>)> =
+            HashMap::new();
+        // The chain may have its own receipts executed along with ours, they are filtered out
+        // by following the ids of the receipts produced by the transaction.
+        let mut ids = HashSet::from([transaction.get_hash()]);
+        let mut result = SimulateTransactionResult {
+            outcomes: vec![],
+            state_changes: vec![],
+            unprocessed_receipts: vec![],
+        };
+        let mut gas_left = gas_limit;
+        let mut rounds = 0;
+        while !pending.is_empty() {
+            rounds += 1;
+            if rounds > MAX_SIMULATION_ROUNDS {
+                return Err(Error::Other(format!(
+                    "Transaction produced receipts for more than {} blocks",
+                    MAX_SIMULATION_ROUNDS
+                )));
+            }
+            for (shard_id, receipts) in std::mem::take(&mut pending) {
+                let state_root = state_roots[&shard_id];
+                let (root, nodes) =
+                    overlays.entry(shard_id).or_insert_with(|| (state_root, HashMap::new()));
+                let trie = self
+                    .get_view_trie_for_shard(shard_id, prev_block_hash, state_root)?
+                    .with_overlay(nodes.clone(), *root);
+                // The runtime executes the delayed receipts of the shard before the new ones, so
+                // a congested shard would burn the whole budget on receipts of other users.
+                let delayed_receipts = near_store::get::<DelayedReceiptIndices>(
+                    &trie,
+                    &TrieKey::DelayedReceiptIndices,
+                )?
+                .map_or(0, |indices| indices.next_available_index - indices.first_index);
+                if delayed_receipts > MAX_SIMULATION_DELAYED_RECEIPTS {
+                    return Err(Error::Other(format!(
+                        "Shard {} has {} delayed receipts, simulation is not possible",
+                        shard_id, delayed_receipts
+                    )));
+                }
+                // Anything left over the budget gets delayed instead of executed.
+                apply_state.gas_limit = Some(gas_left);
+                let transactions = if shard_id == signer_shard_id {
+                    std::mem::take(&mut transactions)
+                } else {
+                    vec![]
+                };
+                let apply_result = self
+                    .runtime
+                    .apply(
+                        trie,
+                        &None,
+                        &apply_state,
+                        &receipts,
+                        &transactions,
+                        &self.epoch_manager,
+                        Default::default(),
+                    )
+                    .map_err(|e| match e {
+                        RuntimeError::InvalidTxError(_) => Error::InvalidTransactions,
+                        RuntimeError::StorageError(e) => Error::StorageError(e),
+                        RuntimeError::ValidatorError(e) => e.into(),
+                        e => Error::Other(e.to_string()),
+                    })?;
+
+                *root = apply_result.state_root;
+                nodes.extend(
+                    apply_result
+                        .trie_changes
+                        .insertions()
+                        .iter()
+                        .map(|insertion| (*insertion.hash(), insertion.payload().into())),
+                );
+                let mut executed = HashSet::new();
+                for outcome in apply_result.outcomes {
+                    gas_left = gas_left.saturating_sub(outcome.outcome.gas_burnt);
+                    executed.insert(outcome.id);
+                    if ids.contains(&outcome.id) {
+                        ids.extend(outcome.outcome.receipt_ids.iter().cloned());
+                        result.outcomes.push(outcome);
+                    }
+                }
+                let state_changes =
+                    apply_result.state_changes.into_iter().filter_map(|mut state_change| {
+                        state_change.changes.retain(|change| match change.cause {
+                            StateChangeCause::TransactionProcessing { tx_hash: hash }
+                            | StateChangeCause::ActionReceiptProcessingStarted {
+                                receipt_hash: hash,
+                            }
+                            | StateChangeCause::ActionReceiptGasReward { receipt_hash: hash }
+                            | StateChangeCause::ReceiptProcessing { receipt_hash: hash }
+                            | StateChangeCause::PostponedReceipt { receipt_hash: hash } => {
+                                ids.contains(&hash)
+                            }
+                            _ => false,
+                        });
+                        (!state_change.changes.is_empty()).then_some(Ok(state_change))
+                    });
+                result.state_changes.extend(StateChanges::from_changes(state_changes)?);
+                if receipts.iter().any(|receipt| !executed.contains(&receipt.receipt_id)) {
+                    return Err(Error::Other(format!(
+                        "Transaction needs more than {} gas to be simulated",
+                        gas_limit
+                    )));
+                }
+                for receipt in apply_result.outgoing_receipts {
+                    if !ids.contains(&receipt.receipt_id) {
+                        continue;
+                    }
+                    let shard_id = account_id_to_shard_id(&receipt.receiver_id, &shard_layout);
+                    if state_roots.contains_key(&shard_id) {
+                        pending.entry(shard_id).or_default().push(receipt);
+                    } else {
+                        result.unprocessed_receipts.push(receipt);
+                    }
+                }
+            }
+        }
+        Ok(result)
+    }
+
     fn check_state_transition(
         &self,
         partial_storage: PartialStorage,
@@ -1404,6 +1707,11 @@ impl RuntimeAdapter for NightshadeRuntime {
             && chunk_epoch_id != head_next_epoch_id)
     }
 
+    #[cfg(feature = "sandbox")]
+    fn get_gas_traces(&self, receipt_id: &CryptoHash) -> Option<Vec<FunctionCallTrace>> {
+        self.gas_traces.lock().unwrap().get(receipt_id).cloned()
+    }
+
     fn get_protocol_config(&self, epoch_id: &EpochId) -> Result<ProtocolConfig, Error> {
         let protocol_version = self.get_epoch_protocol_version(epoch_id)?;
         let mut genesis_config = self.genesis_config.clone();
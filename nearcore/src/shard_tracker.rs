@@ -4,7 +4,10 @@ use near_epoch_manager::EpochManagerHandle;
 use near_primitives::errors::EpochError;
 use near_primitives::hash::CryptoHash;
 use near_primitives::shard_layout::account_id_to_shard_id;
-use near_primitives::types::{AccountId, EpochId, ShardId};
+use near_primitives::types::{AccountId, EpochHeight, EpochId, ShardId};
+use std::sync::RwLock;
+
+const POISONED_LOCK_ERR: &str = "The lock was poisoned.";
 
 pub enum TrackedConfig {
     Accounts(Vec<AccountId>),
@@ -33,15 +36,43 @@ type BitMask = Vec<bool>;
 /// TrackedConfig::AllShards: track all shards
 pub struct ShardTracker {
     tracked_config: TrackedConfig,
-    /// Stores shard tracking information by epoch, only useful if TrackedState == Accounts
-    tracking_shards: AppendOnlyMap<EpochId, BitMask>,
+    /// Changes of the tracked accounts made at runtime, see `update_tracked_accounts`,
+    /// along with the height of the first epoch they apply to. The heights are non-decreasing,
+    /// so the last update applying to an epoch supersedes the earlier ones.
+    tracked_accounts_updates: RwLock<Vec<(EpochHeight, Vec<AccountId>)>>,
+    /// Stores shard tracking information by epoch and the index of the tracked accounts update
+    /// in effect, only useful if TrackedState == Accounts
+    tracking_shards: AppendOnlyMap<(EpochId, Option<usize>), BitMask>,
     /// Epoch manager that for given block hash computes the epoch id.
     epoch_manager: EpochManagerHandle,
 }
 
 impl ShardTracker {
     pub fn new(tracked_config: TrackedConfig, epoch_manager: EpochManagerHandle) -> Self {
-        ShardTracker { tracked_config, tracking_shards: AppendOnlyMap::new(), epoch_manager }
+        ShardTracker {
+            tracked_config,
+            tracked_accounts_updates: RwLock::new(vec![]),
+            tracking_shards: AppendOnlyMap::new(),
+            epoch_manager,
+        }
+    }
+
+    /// Changes the tracked accounts, starting from the epoch after the next one of `epoch_id`.
+    /// The shards to catch up for the next epoch are chosen at the start of the current one,
+    /// so that's the first epoch for which the node can sync the state of the newly tracked
+    /// shards. Returns the height of that epoch, or None if all shards are tracked anyway.
+    pub fn update_tracked_accounts(
+        &self,
+        tracked_accounts: Vec<AccountId>,
+        epoch_id: &EpochId,
+    ) -> Result<Option<EpochHeight>, EpochError> {
+        if matches!(self.tracked_config, TrackedConfig::AllShards) {
+            return Ok(None);
+        }
+        let epoch_height = self.epoch_manager.read().get_epoch_info(epoch_id)?.epoch_height() + 2;
+        let mut updates = self.tracked_accounts_updates.write().expect(POISONED_LOCK_ERR);
+        updates.push((epoch_height, tracked_accounts));
+        Ok(Some(epoch_height))
     }
 
     fn tracks_shard_at_epoch(
@@ -53,14 +84,22 @@ impl ShardTracker {
             TrackedConfig::Accounts(tracked_accounts) => {
                 let epoch_manager = self.epoch_manager.read();
                 let shard_layout = epoch_manager.get_shard_layout(epoch_id)?;
-                let tracking_mask = self.tracking_shards.get_or_insert(epoch_id, || {
-                    let mut tracking_mask = vec![false; shard_layout.num_shards() as usize];
-                    for account_id in tracked_accounts {
-                        let shard_id = account_id_to_shard_id(account_id, &shard_layout);
-                        *tracking_mask.get_mut(shard_id as usize).unwrap() = true;
-                    }
-                    tracking_mask
-                });
+                let epoch_height = epoch_manager.get_epoch_info(epoch_id)?.epoch_height();
+                let updates = self.tracked_accounts_updates.read().expect(POISONED_LOCK_ERR);
+                let update = updates.iter().rposition(|(height, _)| *height <= epoch_height);
+                let tracked_accounts = match update {
+                    Some(i) => &updates[i].1,
+                    None => tracked_accounts,
+                };
+                let tracking_mask =
+                    self.tracking_shards.get_or_insert(&(epoch_id.clone(), update), || {
+                        let mut tracking_mask = vec![false; shard_layout.num_shards() as usize];
+                        for account_id in tracked_accounts {
+                            let shard_id = account_id_to_shard_id(account_id, &shard_layout);
+                            *tracking_mask.get_mut(shard_id as usize).unwrap() = true;
+                        }
+                        tracking_mask
+                    });
                 Ok(tracking_mask.get(shard_id as usize).copied().unwrap_or(false))
             }
             TrackedConfig::AllShards => Ok(true),
@@ -75,6 +114,35 @@ impl ShardTracker {
         self.tracks_shard_at_epoch(shard_id, &epoch_id)
     }
 
+    // `shard_id` refers to a shard in the epoch of the next block from `prev_hash`.
+    // If shard layout will change next epoch, returns true if it tracks any shard
+    // that `shard_id` will split to
+    fn will_track_shard(
+        &self,
+        shard_id: ShardId,
+        prev_hash: &CryptoHash,
+    ) -> Result<bool, EpochError> {
+        let (next_epoch_id, next_shard_ids) = {
+            let epoch_manager = self.epoch_manager.read();
+            let next_epoch_id = epoch_manager.get_next_epoch_id_from_prev_block(prev_hash)?;
+            let next_shard_ids = if epoch_manager.will_shard_layout_change(prev_hash)? {
+                epoch_manager
+                    .get_shard_layout(&next_epoch_id)?
+                    .get_split_shard_ids(shard_id)
+                    .expect("all shard layouts expect the first one must have a split map")
+            } else {
+                vec![shard_id]
+            };
+            (next_epoch_id, next_shard_ids)
+        };
+        for next_shard_id in next_shard_ids {
+            if self.tracks_shard_at_epoch(next_shard_id, &next_epoch_id)? {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
     pub fn care_about_shard(
         &self,
         account_id: Option<&AccountId>,
@@ -125,7 +193,7 @@ impl ShardTracker {
             }
         }
         matches!(self.tracked_config, TrackedConfig::AllShards)
-            || self.tracks_shard(shard_id, parent_hash).unwrap_or(false)
+            || self.will_track_shard(shard_id, parent_hash).unwrap_or(false)
     }
 }
 
@@ -334,12 +402,23 @@ mod tests {
         // verify tracker is tracking the correct shards before and after resharding
         for i in 1..8 {
             let mut total_tracked_shards = HashSet::new();
+            let mut total_will_track_shards = HashSet::new();
             let num_shards = {
                 let epoch_manager = epoch_manager.read();
                 let epoch_id = epoch_manager.get_epoch_id_from_prev_block(&h[i - 1]).unwrap();
                 let shard_layout = epoch_manager.get_shard_layout(&epoch_id).unwrap();
+                let next_epoch_id =
+                    epoch_manager.get_next_epoch_id_from_prev_block(&h[i - 1]).unwrap();
+                let next_shard_layout = epoch_manager.get_shard_layout(&next_epoch_id).unwrap();
                 for account_id in tracked_accounts.iter() {
                     total_tracked_shards.insert(account_id_to_shard_id(account_id, &shard_layout));
+                    // Shards of the next epoch are reported as the shards they are split from.
+                    let next_shard_id = account_id_to_shard_id(account_id, &next_shard_layout);
+                    total_will_track_shards.insert(if next_shard_layout == shard_layout {
+                        next_shard_id
+                    } else {
+                        next_shard_layout.get_parent_shard_id(next_shard_id).unwrap()
+                    });
                 }
                 shard_layout.num_shards()
             };
@@ -350,8 +429,91 @@ mod tests {
             );
             assert_eq!(
                 get_all_shards_will_care_about(&tracker, num_shards, &h[i - 1]),
-                total_tracked_shards
+                total_will_track_shards
             );
         }
     }
+
+    #[test]
+    fn test_update_tracked_accounts() {
+        let num_shards = 4;
+        let epoch_manager = get_epoch_manager(PROTOCOL_VERSION, num_shards, false);
+        let (test1, test2) = ("test1".parse().unwrap(), "test2".parse().unwrap());
+        let tracker =
+            ShardTracker::new(TrackedConfig::Accounts(vec![test1.clone()]), epoch_manager.clone());
+
+        let h = hash_range(8);
+        let shard_layout = {
+            let mut epoch_manager = epoch_manager.write();
+            record_block(
+                &mut epoch_manager,
+                CryptoHash::default(),
+                h[0],
+                0,
+                vec![],
+                PROTOCOL_VERSION,
+            );
+            for i in 1..8 {
+                record_block(
+                    &mut epoch_manager,
+                    h[i - 1],
+                    h[i],
+                    i as u64,
+                    vec![],
+                    PROTOCOL_VERSION,
+                );
+            }
+            epoch_manager.get_shard_layout(&EpochId::default()).unwrap()
+        };
+        let shard1 = account_id_to_shard_id(&test1, &shard_layout);
+        let shard2 = account_id_to_shard_id(&test2, &shard_layout);
+        assert_ne!(shard1, shard2);
+
+        // Before the update is applied, the shards are computed with the old accounts.
+        assert_eq!(get_all_shards_care_about(&tracker, num_shards, &h[6]), HashSet::from([shard1]));
+
+        let (epoch_id, epoch_height) = {
+            let epoch_manager = epoch_manager.read();
+            let epoch_id = epoch_manager.get_epoch_id(&h[2]).unwrap();
+            let epoch_height = epoch_manager.get_epoch_info(&epoch_id).unwrap().epoch_height();
+            (epoch_id, epoch_height)
+        };
+        let activation_height =
+            tracker.update_tracked_accounts(vec![test2], &epoch_id).unwrap().unwrap();
+        assert_eq!(activation_height, epoch_height + 2);
+
+        let epoch_height_of_next_block = |prev_hash: &CryptoHash, next_epoch: bool| {
+            let epoch_manager = epoch_manager.read();
+            let epoch_id = if next_epoch {
+                epoch_manager.get_next_epoch_id_from_prev_block(prev_hash).unwrap()
+            } else {
+                epoch_manager.get_epoch_id_from_prev_block(prev_hash).unwrap()
+            };
+            epoch_manager.get_epoch_info(&epoch_id).unwrap().epoch_height()
+        };
+        let expected_shards = |epoch_height| {
+            HashSet::from([if epoch_height < activation_height { shard1 } else { shard2 }])
+        };
+        let mut activated = false;
+        for i in 1..8 {
+            let epoch_height = epoch_height_of_next_block(&h[i - 1], false);
+            activated |= epoch_height >= activation_height;
+            assert_eq!(
+                get_all_shards_care_about(&tracker, num_shards, &h[i - 1]),
+                expected_shards(epoch_height)
+            );
+            // The newly tracked shards are known an epoch in advance, so that they can be
+            // caught up.
+            let next_epoch_height = epoch_height_of_next_block(&h[i - 1], true);
+            assert_eq!(
+                get_all_shards_will_care_about(&tracker, num_shards, &h[i - 1]),
+                expected_shards(next_epoch_height)
+            );
+        }
+        assert!(activated);
+
+        // Tracking all shards isn't affected by the updates.
+        let tracker = ShardTracker::new(TrackedConfig::AllShards, epoch_manager);
+        assert_eq!(tracker.update_tracked_accounts(vec![], &epoch_id).unwrap(), None);
+    }
 }
@@ -0,0 +1,462 @@
+//! Background loop which dumps state parts at the beginning of every epoch.
+//!
+//! Once the final head enters a new epoch, the loop generates all state parts
+//! of every tracked shard as of the epoch’s sync block (the first block of the
+//! epoch), i.e. the parts which nodes request during state sync.  The parts are
+//! written to a directory laid out as expected by `state_sync_external_parts`
+//! together with a manifest describing them, and parts of old epochs are
+//! removed.  Operators can publish or archive the directory so that nodes can
+//! sync state of a recent epoch without relying on peers serving it.
+use crate::metrics;
+use crate::{NearConfig, NightshadeRuntime};
+use actix::{Actor, Arbiter, ArbiterHandle, Context};
+use anyhow::Context as _;
+use near_chain::{ChainStore, ChainStoreAccess, RuntimeAdapter};
+use near_client::sync::external::{state_part_file_name, state_parts_dir};
+use near_epoch_manager::EpochManagerAdapter;
+use near_primitives::hash::{hash, CryptoHash};
+use near_primitives::state_part::PartId;
+use near_primitives::syncing::get_num_state_parts;
+use near_primitives::types::{AccountId, EpochHeight, EpochId, ShardId, StateRoot};
+use near_store::Store;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{debug, error, info};
+
+/// Name of the file describing state parts of a shard, stored next to them.
+pub const MANIFEST_FILE_NAME: &str = "manifest.json";
+
+/// Configuration of the loop dumping state parts.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct StatePartsDumpConfig {
+    /// Directory to write state parts to.  Parts of shard `S` as of the
+    /// beginning of epoch `E` are written to `<root_dir>/<E>/<S>/`.
+    pub root_dir: PathBuf,
+    /// Number of most recent epochs whose state parts are kept.
+    #[serde(default = "default_num_epochs_to_keep")]
+    pub num_epochs_to_keep: u64,
+    /// Time between checks whether a new epoch has started.
+    #[serde(default = "default_poll_period")]
+    pub poll_period: Duration,
+}
+
+fn default_num_epochs_to_keep() -> u64 {
+    3
+}
+
+fn default_poll_period() -> Duration {
+    Duration::from_secs(10)
+}
+
+/// Description of state parts of a single shard dumped for an epoch.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct StatePartsManifest {
+    pub epoch_id: EpochId,
+    pub epoch_height: EpochHeight,
+    /// Hash of the first block of the epoch.  The parts describe the state
+    /// after its previous block, i.e. the state nodes sync at this hash.
+    pub sync_hash: CryptoHash,
+    pub shard_id: ShardId,
+    pub state_root: StateRoot,
+    pub num_parts: u64,
+    /// Hash of each of the parts, in order.
+    pub part_hashes: Vec<CryptoHash>,
+}
+
+/// Actor dumping state parts of every new epoch.
+pub struct StatePartsDumpActor {
+    chain_store: ChainStore,
+    runtime: Arc<NightshadeRuntime>,
+    account_id: Option<AccountId>,
+    config: StatePartsDumpConfig,
+    /// The most recent epoch whose state parts have all been dumped.
+    last_dumped_epoch: Option<EpochId>,
+}
+
+impl StatePartsDumpActor {
+    /// Dumps state parts of the epoch of the final head unless it has been
+    /// dumped already.
+    fn dump_step(&mut self) -> anyhow::Result<()> {
+        let final_head = self.chain_store.final_head()?;
+        let epoch_id = final_head.epoch_id;
+        if self.last_dumped_epoch.as_ref() == Some(&epoch_id) {
+            return Ok(());
+        }
+        let sync_hash =
+            *self.runtime.get_block_info(&final_head.last_block_hash)?.epoch_first_block();
+        let sync_header = self.chain_store.get_block_header(&sync_hash)?;
+        let sync_prev_hash = *sync_header.prev_hash();
+        if sync_prev_hash == CryptoHash::default() {
+            // There’s no state to sync in the genesis epoch.
+            self.last_dumped_epoch = Some(epoch_id);
+            return Ok(());
+        }
+        let epoch_height = self.runtime.get_epoch_info(&epoch_id)?.epoch_height();
+        let sync_prev_block = self.chain_store.get_block(&sync_prev_hash)?;
+        for (shard_id, chunk) in sync_prev_block.chunks().iter().enumerate() {
+            let shard_id = shard_id as ShardId;
+            if !self.runtime.cares_about_shard(
+                self.account_id.as_ref(),
+                &sync_prev_hash,
+                shard_id,
+                true,
+            ) {
+                continue;
+            }
+            let manifest = StatePartsManifest {
+                epoch_id: epoch_id.clone(),
+                epoch_height,
+                sync_hash,
+                shard_id,
+                state_root: chunk.prev_state_root(),
+                num_parts: 0,
+                part_hashes: vec![],
+            };
+            self.dump_shard(manifest, &sync_prev_hash)
+                .with_context(|| format!("dumping state parts of shard {shard_id}"))?;
+        }
+        remove_old_epochs(
+            &self.config.root_dir,
+            &epoch_id,
+            epoch_height,
+            self.config.num_epochs_to_keep,
+        )?;
+        metrics::STATE_PARTS_DUMP_EPOCH_HEIGHT.set(epoch_height as i64);
+        self.last_dumped_epoch = Some(epoch_id);
+        Ok(())
+    }
+
+    /// Writes all state parts of a shard followed by their manifest.  Does
+    /// nothing if the manifest exists already, i.e. if the shard has been
+    /// dumped before the node restarted.
+    fn dump_shard(
+        &self,
+        mut manifest: StatePartsManifest,
+        sync_prev_hash: &CryptoHash,
+    ) -> anyhow::Result<()> {
+        let shard_id = manifest.shard_id;
+        let dir = state_parts_dir(&self.config.root_dir, &manifest.epoch_id, shard_id);
+        let manifest_path = dir.join(MANIFEST_FILE_NAME);
+        if manifest_path.exists() {
+            debug!(target: "state_parts_dump", ?manifest_path, "State parts have been dumped already");
+            return Ok(());
+        }
+        std::fs::create_dir_all(&dir)
+            .with_context(|| format!("creating directory {}", dir.display()))?;
+
+        let state_root = manifest.state_root;
+        let state_root_node =
+            self.runtime.get_state_root_node(shard_id, sync_prev_hash, &state_root)?;
+        let num_parts = get_num_state_parts(state_root_node.memory_usage);
+        info!(
+            target: "state_parts_dump",
+            epoch_id = ?manifest.epoch_id.0,
+            epoch_height = manifest.epoch_height,
+            shard_id,
+            num_parts,
+            "Dumping state parts"
+        );
+        for part_id in 0..num_parts {
+            let part = self.runtime.obtain_state_part(
+                shard_id,
+                sync_prev_hash,
+                &state_root,
+                PartId::new(part_id, num_parts),
+            )?;
+            let path = dir.join(state_part_file_name(part_id));
+            std::fs::write(&path, &part)
+                .with_context(|| format!("writing state part to {}", path.display()))?;
+            manifest.part_hashes.push(hash(&part));
+            metrics::STATE_PARTS_DUMPED.inc();
+        }
+        manifest.num_parts = num_parts;
+
+        // Write the manifest last and atomically so that its existence means
+        // that all the parts are in place.
+        let tmp_path = dir.join(format!("{MANIFEST_FILE_NAME}.tmp"));
+        std::fs::write(&tmp_path, serde_json::to_vec_pretty(&manifest)?)
+            .with_context(|| format!("writing manifest to {}", tmp_path.display()))?;
+        std::fs::rename(&tmp_path, &manifest_path)
+            .with_context(|| format!("writing manifest to {}", manifest_path.display()))?;
+        info!(target: "state_parts_dump", shard_id, num_parts, "Finished dumping state parts");
+        Ok(())
+    }
+
+    fn run_dump(&mut self, ctx: &mut Context<Self>) {
+        if let Err(err) = self.dump_step() {
+            error!(target: "state_parts_dump", ?err, "Failed to dump state parts");
+            metrics::STATE_PARTS_DUMP_ERRORS.inc();
+        }
+        near_performance_metrics::actix::run_later(ctx, self.config.poll_period, |act, ctx| {
+            act.run_dump(ctx);
+        });
+    }
+}
+
+impl Actor for StatePartsDumpActor {
+    type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        self.run_dump(ctx);
+    }
+}
+
+/// Returns height of the epoch whose state parts are stored in `epoch_dir`,
+/// or `None` if none of its shards has been fully dumped.
+fn read_epoch_height(epoch_dir: &Path) -> anyhow::Result<Option<EpochHeight>> {
+    for entry in std::fs::read_dir(epoch_dir)? {
+        let manifest_path = entry?.path().join(MANIFEST_FILE_NAME);
+        if !manifest_path.exists() {
+            continue;
+        }
+        let manifest: StatePartsManifest = serde_json::from_slice(&std::fs::read(&manifest_path)?)
+            .with_context(|| format!("reading manifest {}", manifest_path.display()))?;
+        return Ok(Some(manifest.epoch_height));
+    }
+    Ok(None)
+}
+
+/// Removes state parts of all epochs but the `num_epochs_to_keep` most recent
+/// ones, counting the current epoch `epoch_id` at height `epoch_height`.
+/// Parts of epochs which haven’t been fully dumped are removed as well unless
+/// they belong to the current epoch.  Entries of `root_dir` which aren’t named
+/// after an epoch are left untouched.
+fn remove_old_epochs(
+    root_dir: &Path,
+    epoch_id: &EpochId,
+    epoch_height: EpochHeight,
+    num_epochs_to_keep: u64,
+) -> anyhow::Result<()> {
+    let current = epoch_id.0.to_string();
+    for entry in std::fs::read_dir(root_dir)? {
+        let path = entry?.path();
+        let name = match path.file_name().and_then(|name| name.to_str()) {
+            Some(name) => name,
+            None => continue,
+        };
+        if name == current || !path.is_dir() || CryptoHash::from_str(name).is_err() {
+            continue;
+        }
+        let keep = match read_epoch_height(&path)? {
+            Some(height) => height + num_epochs_to_keep > epoch_height,
+            None => false,
+        };
+        if !keep {
+            info!(target: "state_parts_dump", ?path, "Removing state parts of an old epoch");
+            std::fs::remove_dir_all(&path)
+                .with_context(|| format!("removing {}", path.display()))?;
+        }
+    }
+    Ok(())
+}
+
+/// Starts the loop dumping state parts in a new arbiter.
+///
+/// Returns `None` if dumping state parts isn’t configured.
+pub fn spawn_state_parts_dump_loop(
+    config: &NearConfig,
+    store: Store,
+    runtime: Arc<NightshadeRuntime>,
+) -> Option<ArbiterHandle> {
+    let dump_config = config.config.state_parts_dump.clone()?;
+    let chain_store =
+        ChainStore::new(store, config.genesis.config.genesis_height, !config.client_config.archive);
+    let account_id = config.validator_signer.as_ref().map(|signer| signer.validator_id().clone());
+
+    let arbiter = Arbiter::new().handle();
+    StatePartsDumpActor::start_in_arbiter(&arbiter, move |_ctx| StatePartsDumpActor {
+        chain_store,
+        runtime,
+        account_id,
+        config: dump_config,
+        last_dumped_epoch: None,
+    });
+    Some(arbiter)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::GenesisExt;
+    use crate::shard_tracker::TrackedConfig;
+    use near_chain::ChainGenesis;
+    use near_chain_configs::Genesis;
+    use near_client::test_utils::TestEnv;
+    use near_primitives::runtime::config_store::RuntimeConfigStore;
+    use near_store::test_utils::create_test_store;
+
+    const EPOCH_LENGTH: u64 = 5;
+
+    /// Produces blocks until the final head is in the third epoch and returns
+    /// the test environment along with an actor dumping state parts of all
+    /// shards into `root_dir`.
+    fn setup(root_dir: &Path) -> (TestEnv, StatePartsDumpActor) {
+        let mut genesis =
+            Genesis::test(vec!["test0".parse().unwrap(), "test1".parse().unwrap()], 1);
+        genesis.config.epoch_length = EPOCH_LENGTH;
+        let mut chain_genesis = ChainGenesis::test();
+        chain_genesis.epoch_length = EPOCH_LENGTH;
+        let store = create_test_store();
+        let runtime = Arc::new(NightshadeRuntime::test_with_runtime_config_store(
+            Path::new("."),
+            store.clone(),
+            &genesis,
+            TrackedConfig::AllShards,
+            RuntimeConfigStore::test(),
+        ));
+        let mut env = TestEnv::builder(chain_genesis)
+            .runtime_adapters(vec![runtime.clone() as Arc<dyn RuntimeAdapter>])
+            .build();
+        for height in 1..=3 * EPOCH_LENGTH {
+            env.produce_block(0, height);
+        }
+        let actor = StatePartsDumpActor {
+            chain_store: ChainStore::new(store, genesis.config.genesis_height, true),
+            runtime,
+            account_id: None,
+            config: StatePartsDumpConfig {
+                root_dir: root_dir.to_path_buf(),
+                num_epochs_to_keep: default_num_epochs_to_keep(),
+                poll_period: default_poll_period(),
+            },
+            last_dumped_epoch: None,
+        };
+        (env, actor)
+    }
+
+    /// Returns the directory state parts of shard 0 of the final head’s epoch
+    /// are dumped to.
+    fn shard_dir(actor: &StatePartsDumpActor) -> PathBuf {
+        let epoch_id = actor.chain_store.final_head().unwrap().epoch_id;
+        state_parts_dir(&actor.config.root_dir, &epoch_id, 0)
+    }
+
+    /// Checks that the manifest in `dir` describes the parts next to it.
+    fn check_dump(env: &TestEnv, dir: &Path) -> StatePartsManifest {
+        let manifest: StatePartsManifest =
+            serde_json::from_slice(&std::fs::read(dir.join(MANIFEST_FILE_NAME)).unwrap()).unwrap();
+        let sync_header = env.clients[0].chain.get_block_header(&manifest.sync_hash).unwrap();
+        assert_eq!(sync_header.epoch_id(), &manifest.epoch_id);
+        assert_eq!(manifest.num_parts, manifest.part_hashes.len() as u64);
+        assert!(manifest.num_parts > 0);
+        for (part_id, part_hash) in manifest.part_hashes.iter().enumerate() {
+            let part = std::fs::read(dir.join(state_part_file_name(part_id as u64))).unwrap();
+            assert_eq!(&hash(&part), part_hash);
+            assert!(env.clients[0].runtime_adapter.validate_state_part(
+                &manifest.state_root,
+                PartId::new(part_id as u64, manifest.num_parts),
+                &part,
+            ));
+        }
+        manifest
+    }
+
+    #[test]
+    fn test_dump_step() {
+        let root_dir = tempfile::tempdir().unwrap();
+        let (env, mut actor) = setup(root_dir.path());
+
+        actor.dump_step().unwrap();
+        let dir = shard_dir(&actor);
+        let manifest = check_dump(&env, &dir);
+        assert_eq!(Some(manifest.epoch_id), actor.last_dumped_epoch);
+        assert!(!dir.join(format!("{MANIFEST_FILE_NAME}.tmp")).exists());
+
+        // The epoch is dumped only once.
+        std::fs::remove_dir_all(&dir).unwrap();
+        actor.dump_step().unwrap();
+        assert!(!dir.exists());
+    }
+
+    #[test]
+    fn test_dump_manifest_written_last() {
+        let root_dir = tempfile::tempdir().unwrap();
+        let (env, mut actor) = setup(root_dir.path());
+
+        // Writing a part fails, as if the node crashed in the middle of
+        // dumping the shard.
+        let dir = shard_dir(&actor);
+        let part_path = dir.join(state_part_file_name(0));
+        std::fs::create_dir_all(&part_path).unwrap();
+        assert!(actor.dump_step().is_err());
+        assert!(!dir.join(MANIFEST_FILE_NAME).exists());
+        assert_eq!(None, actor.last_dumped_epoch);
+
+        // Once the problem goes away, the dump resumes and overwrites parts
+        // written before the failure.
+        std::fs::remove_dir(&part_path).unwrap();
+        std::fs::write(&part_path, b"garbage").unwrap();
+        actor.dump_step().unwrap();
+        let manifest = check_dump(&env, &dir);
+        assert_eq!(Some(manifest.epoch_id), actor.last_dumped_epoch);
+    }
+
+    #[test]
+    fn test_dump_resumes_after_restart() {
+        let root_dir = tempfile::tempdir().unwrap();
+        let (env, mut actor) = setup(root_dir.path());
+        actor.dump_step().unwrap();
+        let dir = shard_dir(&actor);
+        let manifest = check_dump(&env, &dir);
+
+        // After a restart, shards whose manifest exists are not dumped again.
+        actor.last_dumped_epoch = None;
+        let part_path = dir.join(state_part_file_name(0));
+        std::fs::write(&part_path, b"garbage").unwrap();
+        actor.dump_step().unwrap();
+        assert_eq!(b"garbage".to_vec(), std::fs::read(&part_path).unwrap());
+        assert_eq!(Some(manifest.epoch_id), actor.last_dumped_epoch);
+    }
+
+    fn write_manifest(root_dir: &Path, epoch_id: &EpochId, epoch_height: EpochHeight) {
+        let dir = state_parts_dir(root_dir, epoch_id, 0);
+        std::fs::create_dir_all(&dir).unwrap();
+        let manifest = StatePartsManifest {
+            epoch_id: epoch_id.clone(),
+            epoch_height,
+            sync_hash: CryptoHash::default(),
+            shard_id: 0,
+            state_root: StateRoot::default(),
+            num_parts: 0,
+            part_hashes: vec![],
+        };
+        std::fs::write(dir.join(MANIFEST_FILE_NAME), serde_json::to_vec(&manifest).unwrap())
+            .unwrap();
+    }
+
+    #[test]
+    fn test_remove_old_epochs() {
+        let root_dir = tempfile::tempdir().unwrap();
+        let root_dir = root_dir.path();
+        let epochs = (0..5u8).map(|i| EpochId(hash(&[i]))).collect::<Vec<_>>();
+        for (height, epoch_id) in epochs.iter().enumerate().take(4) {
+            write_manifest(root_dir, epoch_id, height as EpochHeight);
+        }
+        // Current epoch which is still being dumped.
+        std::fs::create_dir_all(state_parts_dir(root_dir, &epochs[4], 0)).unwrap();
+        // Epoch which was never fully dumped.
+        let unfinished = EpochId(hash(b"unfinished"));
+        std::fs::create_dir_all(state_parts_dir(root_dir, &unfinished, 0)).unwrap();
+        // Unrelated directory.
+        std::fs::create_dir_all(root_dir.join("other")).unwrap();
+
+        remove_old_epochs(root_dir, &epochs[4], 4, 3).unwrap();
+
+        let mut remaining = std::fs::read_dir(root_dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect::<Vec<_>>();
+        remaining.sort();
+        let mut expected = vec![
+            epochs[2].0.to_string(),
+            epochs[3].0.to_string(),
+            epochs[4].0.to_string(),
+            "other".to_string(),
+        ];
+        expected.sort();
+        assert_eq!(remaining, expected);
+    }
+}
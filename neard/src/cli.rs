@@ -9,6 +9,7 @@ use near_amend_genesis::AmendGenesisCommand;
 use near_chain_configs::GenesisValidationMode;
 #[cfg(feature = "cold_store")]
 use near_cold_store_tool::ColdStoreCommand;
+use near_crypto::PublicKey;
 use near_jsonrpc_primitives::types::light_client::RpcLightClientExecutionProofResponse;
 use near_mirror::MirrorCommand;
 use near_o11y::tracing_subscriber::EnvFilter;
@@ -19,7 +20,10 @@ use near_o11y::{
 use near_ping::PingCommand;
 use near_primitives::hash::CryptoHash;
 use near_primitives::merkle::compute_root_from_path;
-use near_primitives::types::{Gas, NumSeats, NumShards};
+use near_primitives::types::{
+    AccountId, BlockHeight, BlockId, BlockReference, Finality, Gas, NumSeats, NumShards,
+    ProtocolVersion,
+};
 use near_state_parts::cli::StatePartsCommand;
 use near_state_viewer::StateViewerSubCommand;
 use near_store::db::RocksDB;
@@ -103,6 +107,12 @@ impl NeardCmd {
             NeardSubCommand::VerifyProof(cmd) => {
                 cmd.run();
             }
+            NeardSubCommand::Database(cmd) => {
+                cmd.run(&home_dir)?;
+            }
+            NeardSubCommand::ContractCache(cmd) => {
+                cmd.run(&home_dir)?;
+            }
             NeardSubCommand::Ping(cmd) => {
                 cmd.run()?;
             }
@@ -119,6 +129,9 @@ impl NeardCmd {
             NeardSubCommand::StateParts(cmd) => {
                 cmd.run()?;
             }
+            NeardSubCommand::SandboxImportAccounts(cmd) => {
+                cmd.run()?;
+            }
         };
         Ok(())
     }
@@ -208,6 +221,13 @@ pub(super) enum NeardSubCommand {
     #[clap(alias = "verify_proof")]
     VerifyProof(VerifyProofSubCommand),
 
+    /// Backs up and restores node’s database.
+    Database(DatabaseCommand),
+
+    /// Lists, prunes and fills the cache of compiled contracts of a stopped
+    /// node.
+    ContractCache(ContractCacheCommand),
+
     /// Connects to a NEAR node and sends ping messages to the accounts it sends
     /// us after the handshake is completed, printing stats to stdout.
     Ping(PingCommand),
@@ -225,6 +245,10 @@ pub(super) enum NeardSubCommand {
 
     /// Connects to a NEAR node and sends state parts requests after the handshake is completed.
     StateParts(StatePartsCommand),
+
+    /// Copies accounts together with their contracts, access keys and storage
+    /// from another chain into a running sandbox node.
+    SandboxImportAccounts(SandboxImportAccountsCommand),
 }
 
 #[derive(Parser)]
@@ -507,6 +531,25 @@ async fn wait_for_interrupt_signal(_home_dir: &Path, mut _rx_crash: Receiver<()>
 fn update_watchers(home_dir: &Path, behavior: UpdateBehavior) {
     LogConfig::update(home_dir.join("log_config.json"), &behavior);
     DynConfig::update(home_dir.join("dyn_config.json"), &behavior);
+    update_configs(home_dir);
+}
+
+/// Reloads the config.json fields which can be changed without restarting the node,
+/// see `near_dyn_configs::UpdateableConfigs`.
+#[cfg(unix)]
+fn update_configs(home_dir: &Path) {
+    let path = home_dir.join(nearcore::config::CONFIG_FILENAME);
+    let configs = match nearcore::config::Config::from_file(&path) {
+        Ok(config) => config.updateable_configs(),
+        Err(err) => {
+            error!(target: "neard", "Failed to reload {path:?}: {err:#}.");
+            return;
+        }
+    };
+    info!(target: "neard", ?configs, "Updating the configs from {path:?}.");
+    if let Err(err) = near_dyn_configs::update(configs) {
+        error!(target: "neard", "Rejected the configs from {path:?}: {err}.");
+    }
 }
 
 #[cfg(unix)]
@@ -631,6 +674,204 @@ impl RecompressStorageSubCommand {
     }
 }
 
+#[derive(Parser)]
+pub(super) struct DatabaseCommand {
+    #[clap(subcommand)]
+    subcmd: DatabaseSubCommand,
+}
+
+#[derive(Parser)]
+enum DatabaseSubCommand {
+    /// Creates a backup of the database of a stopped node.
+    ///
+    /// The backup holds a RocksDB checkpoint of the database and a description
+    /// of it (database version and chain head) against which it is verified
+    /// when restored.  If the backup is located on the same file system as the
+    /// database, data files are hard-linked and creating the backup is cheap.
+    ///
+    /// To back up a running node, POST `{"name": NAME}` to its
+    /// `/debug/api/checkpoint` RPC endpoint (requires `enable_debug_rpc` and
+    /// `checkpoints_dir` to be configured).
+    Backup {
+        /// Directory to save the backup in.  It must not exist.
+        #[clap(long)]
+        to: PathBuf,
+    },
+    /// Restores the database of a stopped node from a backup.
+    ///
+    /// The backup is verified first.  Existing database must be moved away
+    /// before the backup can be restored.
+    Restore {
+        /// Directory holding the backup.
+        #[clap(long)]
+        from: PathBuf,
+    },
+}
+
+impl DatabaseCommand {
+    pub(super) fn run(self, home_dir: &Path) -> anyhow::Result<()> {
+        let (verb, info) = match self.subcmd {
+            DatabaseSubCommand::Backup { to } => {
+                ("Backed up", nearcore::backup_database(home_dir, &to)?)
+            }
+            DatabaseSubCommand::Restore { from } => {
+                ("Restored", nearcore::restore_database(home_dir, &from)?)
+            }
+        };
+        info!(target: "neard", db_version = info.db_version, head_height = info.head_height,
+              head_hash = %info.head_hash, "{verb} database");
+        Ok(())
+    }
+}
+
+#[derive(Parser)]
+pub(super) struct ContractCacheCommand {
+    #[clap(subcommand)]
+    subcmd: ContractCacheSubCommand,
+}
+
+#[derive(Parser)]
+enum ContractCacheSubCommand {
+    /// Lists the compiled contracts in the cache.
+    ///
+    /// Entries compiled by VMs which aren’t used at the current epoch’s
+    /// protocol version or any later one are marked as stale.  Untracked
+    /// entries have been written by an older neard which didn’t record their
+    /// description.
+    List,
+    /// Removes stale and untracked entries from the cache and evicts the least
+    /// recently used ones until the cache fits in the size limit.
+    Prune {
+        /// Maximum total size of the cache, e.g. `10GB`.  Defaults to
+        /// `contract_cache.max_size` from config.json.
+        #[clap(long)]
+        max_size: Option<bytesize::ByteSize>,
+    },
+    /// Compiles the contracts deployed on the tracked shards ahead of time.
+    ///
+    /// Run it before a protocol upgrade changing the VM so that contracts
+    /// don’t all need to be compiled at the beginning of the new epoch.  An
+    /// interrupted run continues where it left off.
+    Precompile {
+        /// Protocol version to compile the contracts for.  Defaults to the
+        /// latest one supported by this binary.
+        #[clap(long)]
+        protocol_version: Option<ProtocolVersion>,
+    },
+}
+
+impl ContractCacheCommand {
+    pub(super) fn run(self, home_dir: &Path) -> anyhow::Result<()> {
+        let cache = nearcore::contract_cache::OfflineContractCache::open(home_dir)?;
+        match self.subcmd {
+            ContractCacheSubCommand::List => {
+                let mut entries = cache.list()?;
+                entries.sort_by_key(|entry| {
+                    std::cmp::Reverse(entry.info.as_ref().map(|info| info.last_used))
+                });
+                let total: u64 = entries.iter().map(|entry| entry.size).sum();
+                for entry in &entries {
+                    let (status, code_hash, last_used) = match &entry.info {
+                        Some(info) => (
+                            if info.is_untracked() {
+                                "untracked"
+                            } else if cache.vm_ids.contains(&info.info.vm_id) {
+                                "current"
+                            } else {
+                                "stale"
+                            },
+                            info.info.code_hash.to_string(),
+                            chrono::NaiveDateTime::from_timestamp_opt(info.last_used as i64, 0)
+                                .map_or_else(String::new, |time| time.to_string()),
+                        ),
+                        None => ("untracked", String::new(), String::new()),
+                    };
+                    println!(
+                        "{} {:>10} {:<9} {:<44} {}",
+                        entry.key,
+                        bytesize::ByteSize(entry.size).to_string(),
+                        status,
+                        code_hash,
+                        last_used
+                    );
+                }
+                println!("{} entries, {}", entries.len(), bytesize::ByteSize(total));
+            }
+            ContractCacheSubCommand::Prune { max_size } => {
+                let stats = cache.prune(max_size)?;
+                info!(target: "neard", stale = stats.stale, evicted = stats.evicted,
+                      untracked = stats.untracked, freed = %bytesize::ByteSize(stats.freed_bytes),
+                      remaining = %bytesize::ByteSize(stats.remaining_bytes),
+                      "Pruned compiled contracts cache");
+            }
+            ContractCacheSubCommand::Precompile { protocol_version } => {
+                let protocol_version =
+                    protocol_version.unwrap_or(near_primitives::version::PROTOCOL_VERSION);
+                let stats = cache.precompile(protocol_version)?;
+                info!(target: "neard", protocol_version, compiled = stats.compiled,
+                      cached = stats.cached, failed = stats.failed,
+                      shards_done = stats.shards_done, "Precompiled contracts");
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Parser)]
+pub(super) struct SandboxImportAccountsCommand {
+    /// JSON RPC address of the sandbox node to import the accounts into.
+    #[clap(long, default_value = "http://127.0.0.1:3030")]
+    sandbox_rpc_addr: String,
+    /// JSON RPC address of the node to read the accounts from.
+    #[clap(long, required_unless_present = "source-data-dir", conflicts_with = "source-data-dir")]
+    source_rpc_addr: Option<String>,
+    /// Database directory of a stopped node to read the accounts from.  It is
+    /// opened by the sandbox node so it must be on the same machine.
+    #[clap(long, parse(from_os_str))]
+    source_data_dir: Option<PathBuf>,
+    /// Accounts to import.
+    #[clap(long, required = true)]
+    account_ids: Vec<AccountId>,
+    /// Height of the block to read the accounts at.  Defaults to the final
+    /// block of the source.
+    #[clap(long)]
+    block_height: Option<BlockHeight>,
+    /// Replaces the access keys of the imported accounts with a single full
+    /// access key with the given public key.
+    #[clap(long)]
+    access_key: Option<PublicKey>,
+}
+
+impl SandboxImportAccountsCommand {
+    pub(super) fn run(self) -> anyhow::Result<()> {
+        use near_jsonrpc_primitives::types::sandbox::{
+            RpcSandboxImportAccountsRequest, RpcSandboxImportSource,
+        };
+
+        let source = match (self.source_rpc_addr, self.source_data_dir) {
+            (Some(addr), _) => RpcSandboxImportSource::RpcUrl(addr),
+            (None, Some(data_dir)) => RpcSandboxImportSource::DataDir(data_dir),
+            (None, None) => unreachable!("required by clap"),
+        };
+        let block_reference = match self.block_height {
+            Some(height) => BlockReference::BlockId(BlockId::Height(height)),
+            None => BlockReference::Finality(Finality::Final),
+        };
+        let request = RpcSandboxImportAccountsRequest {
+            source,
+            account_ids: self.account_ids,
+            block_reference,
+            access_key: self.access_key,
+        };
+        let client = near_jsonrpc_client::new_client(&self.sandbox_rpc_addr);
+        let response = actix::System::new()
+            .block_on(client.sandbox_import_accounts(request))
+            .map_err(|err| anyhow::anyhow!("sandbox_import_accounts failed: {}", err))?;
+        info!(target: "neard", records = response.records, "Imported accounts");
+        Ok(())
+    }
+}
+
 #[derive(thiserror::Error, Debug, PartialEq)]
 pub enum VerifyProofError {
     #[error("invalid outcome root proof")]
@@ -1,5 +1,5 @@
 use crate::watchers::{WatchConfigError, Watcher};
-use near_dyn_configs::reload;
+use near_dyn_configs::{reload, NetworkAccessLists};
 use serde::{Deserialize, Serialize};
 
 /// Configures logging.
@@ -7,15 +7,27 @@ use serde::{Deserialize, Serialize};
 pub(crate) struct DynConfig {
     /// Graceful shutdown at expected blockheight
     pub expected_shutdown: Option<u64>,
+    /// Overrides `network.blacklist` from config.json.
+    #[serde(default)]
+    pub blacklist: Option<Vec<String>>,
+    /// Overrides `network.whitelist_nodes` from config.json.
+    #[serde(default)]
+    pub whitelist_nodes: Option<String>,
 }
 
 impl Watcher for DynConfig {
     fn reload(config: Option<Self>) -> Result<(), WatchConfigError> {
         if let Some(config) = config {
-            reload(config.expected_shutdown);
+            reload(
+                config.expected_shutdown,
+                NetworkAccessLists {
+                    blacklist: config.blacklist,
+                    whitelist_nodes: config.whitelist_nodes,
+                },
+            );
             Ok(())
         } else {
-            reload(None);
+            reload(None, NetworkAccessLists::default());
             Ok(())
         }
     }
@@ -17,11 +17,13 @@ pub(crate) struct LogConfig {
 impl Watcher for LogConfig {
     fn reload(instance: Option<Self>) -> Result<(), WatchConfigError> {
         if let Some(LogConfig { rust_log, verbose_module, opentelemetry_level }) = instance {
-            Ok(reload(rust_log.as_deref(), verbose_module.as_deref(), opentelemetry_level)
-                .map_err(|e| into_config_err(e))?)
+            reload(rust_log.as_deref(), verbose_module.as_deref(), opentelemetry_level)
+                .map_err(|e| into_config_err(e))?;
         } else {
-            Ok(reload(None, None, None).map_err(|e| into_config_err(e))?)
+            reload(None, None, None).map_err(|e| into_config_err(e))?;
         }
+        near_dyn_configs::DYN_CONFIG_CHANGE.inc();
+        Ok(())
     }
 }
 
@@ -0,0 +1,76 @@
+#!/usr/bin/env python3
+# Import the state of an account at an older block from a JSON RPC endpoint
+# into a sandbox node, replacing its access keys with a new key.  State written
+# after the import block, and the old access key, must be gone.
+
+import sys, time
+import base64
+import pathlib
+
+sys.path.append(str(pathlib.Path(__file__).resolve().parents[2] / 'lib'))
+
+import utils
+from cluster import start_cluster
+from key import Key
+from transaction import sign_deploy_contract_tx, sign_function_call_tx
+
+CONFIG = utils.figure_out_sandbox_binary()
+
+# start node
+nodes = start_cluster(1, 0, 1, CONFIG, [["epoch_length", 100]], {})
+
+# deploy contract
+hash_ = nodes[0].get_latest_block().hash_bytes
+tx = sign_deploy_contract_tx(nodes[0].signer_key, utils.load_test_contract(),
+                             10, hash_)
+nodes[0].send_tx(tx)
+time.sleep(3)
+
+
+def write_value(key, value, nonce):
+    hash_ = nodes[0].get_latest_block().hash_bytes
+    tx = sign_function_call_tx(nodes[0].signer_key,
+                               nodes[0].signer_key.account_id,
+                               'write_key_value', key + value, 1000000000000,
+                               0, nonce, hash_)
+    res = nodes[0].send_tx_and_wait(tx, 20)
+    assert ('SuccessValue' in res['result']['status'])
+
+
+def read_value(key):
+    res = nodes[0].call_function("test0", "read_value",
+                                 base64.b64encode(key).decode('ascii'))
+    return bytes(res['result']['result'])
+
+
+k = (10).to_bytes(8, byteorder="little")
+v = (20).to_bytes(8, byteorder="little")
+write_value(k, v, 20)
+import_height = nodes[0].get_latest_block().height
+
+new_v = (30).to_bytes(8, byteorder="little")
+write_value(k, new_v, 21)
+assert read_value(k) == new_v
+k2 = (11).to_bytes(8, byteorder="little")
+write_value(k2, v, 22)
+assert read_value(k2) == v
+
+# import the account as it was before the second write
+new_key = Key.from_random("test0")
+host, port = nodes[0].rpc_addr()
+res = nodes[0].json_rpc('sandbox_import_accounts', {
+    "rpc_url": f"http://{host}:{port}",
+    "account_ids": ["test0"],
+    "block_id": import_height,
+    "access_key": new_key.pk,
+},
+                        timeout=60)
+assert 'error' not in res, res
+assert res['result']['records'] > 0, res
+
+# the value is rolled back, the key written later is removed and the old
+# access key is replaced with the new one
+assert read_value(k) == v
+assert read_value(k2) == b''
+keys = nodes[0].get_access_key_list("test0")['result']['keys']
+assert [key['public_key'] for key in keys] == [new_key.pk], keys
@@ -0,0 +1,82 @@
+//! Opt-in tracing of the gas charged by every host function call.
+//!
+//! Tracing is enabled for the current thread with [`record_gas_traces`].  While
+//! it is enabled, every [`crate::VMLogic`] records the host functions called by
+//! the contract and returns them in [`crate::VMOutcome::host_function_calls`].
+//! The runtime then attaches the receipt the calls belong to and hands the
+//! trace back with [`push_gas_trace`].
+use std::cell::RefCell;
+
+use near_primitives::gas_trace::{FunctionCallTrace, HostFunctionCall};
+use near_primitives_core::types::Gas;
+
+thread_local! {
+    static GAS_TRACES: RefCell<Option<Vec<FunctionCallTrace>>> = RefCell::new(None);
+}
+
+/// Runs `f` with gas tracing enabled on the current thread and returns the
+/// traces of all function calls it executed.
+pub fn record_gas_traces<R>(f: impl FnOnce() -> R) -> (R, Vec<FunctionCallTrace>) {
+    let outer = GAS_TRACES.with(|traces| traces.borrow_mut().replace(Vec::new()));
+    let result = f();
+    let traces = GAS_TRACES.with(|traces| std::mem::replace(&mut *traces.borrow_mut(), outer));
+    (result, traces.unwrap_or_default())
+}
+
+/// Whether gas tracing is enabled on the current thread.
+pub fn is_gas_tracing_enabled() -> bool {
+    GAS_TRACES.with(|traces| traces.borrow().is_some())
+}
+
+/// Records the trace of a function call if tracing is enabled.
+pub fn push_gas_trace(trace: FunctionCallTrace) {
+    GAS_TRACES.with(|traces| {
+        if let Some(traces) = traces.borrow_mut().as_mut() {
+            traces.push(trace);
+        }
+    })
+}
+
+/// Host function calls recorded by a single `VMLogic`.
+#[derive(Default)]
+pub(crate) struct HostFunctionRecorder {
+    calls: Vec<HostFunctionCall>,
+    /// Gas burnt when the previous host function returned.
+    last_burnt_gas: Gas,
+    /// The host function being executed with the gas burnt and used before it.
+    current: Option<(HostFunctionCall, Gas, Gas)>,
+}
+
+impl HostFunctionRecorder {
+    pub(crate) fn begin(
+        &mut self,
+        name: &str,
+        args: &[(&str, u64)],
+        burnt_gas: Gas,
+        used_gas: Gas,
+    ) {
+        let call = HostFunctionCall {
+            name: name.to_string(),
+            args: args.iter().map(|(name, value)| (name.to_string(), *value)).collect(),
+            wasm_gas: burnt_gas.saturating_sub(self.last_burnt_gas),
+            gas_burnt: 0,
+            gas_used: 0,
+            total_gas_burnt: 0,
+        };
+        self.current = Some((call, burnt_gas, used_gas));
+    }
+
+    pub(crate) fn end(&mut self, burnt_gas: Gas, used_gas: Gas) {
+        if let Some((mut call, burnt_before, used_before)) = self.current.take() {
+            call.gas_burnt = burnt_gas.saturating_sub(burnt_before);
+            call.gas_used = used_gas.saturating_sub(used_before);
+            call.total_gas_burnt = burnt_gas;
+            self.calls.push(call);
+            self.last_burnt_gas = burnt_gas;
+        }
+    }
+
+    pub(crate) fn into_calls(self) -> Vec<HostFunctionCall> {
+        self.calls
+    }
+}
@@ -4,6 +4,7 @@ mod alt_bn128;
 mod context;
 mod dependencies;
 pub mod gas_counter;
+pub mod gas_trace;
 mod logic;
 pub mod mocks;
 pub(crate) mod receipt_manager;
@@ -1,6 +1,7 @@
 use crate::context::VMContext;
 use crate::dependencies::{External, MemoryLike};
 use crate::gas_counter::{FastGasCounter, GasCounter};
+use crate::gas_trace::{is_gas_tracing_enabled, HostFunctionRecorder};
 use crate::receipt_manager::ReceiptManager;
 use crate::types::{PromiseIndex, PromiseResult, ReceiptIndex, ReturnData};
 use crate::utils::split_method_names;
@@ -9,6 +10,7 @@ use byteorder::ByteOrder;
 use near_crypto::Secp256K1Signature;
 use near_primitives::checked_feature;
 use near_primitives::config::ViewConfig;
+use near_primitives::gas_trace::HostFunctionCall;
 use near_primitives::runtime::fees::RuntimeFeesConfig;
 use near_primitives::version::is_implicit_account_creation_enabled;
 use near_primitives_core::config::ExtCosts::*;
@@ -69,6 +71,9 @@ pub struct VMLogic<'a> {
 
     /// Handles the receipts generated through execution.
     receipt_manager: ReceiptManager,
+
+    /// Records host function calls if gas tracing is enabled.
+    host_function_recorder: Option<HostFunctionRecorder>,
 }
 
 /// Promises API allows to create a DAG-structure that defines dependencies between smart contract
@@ -145,6 +150,7 @@ impl<'a> VMLogic<'a> {
             total_log_length: 0,
             current_protocol_version,
             receipt_manager: ReceiptManager::default(),
+            host_function_recorder: is_gas_tracing_enabled().then(HostFunctionRecorder::default),
         }
     }
 
@@ -2727,6 +2733,7 @@ impl<'a> VMLogic<'a> {
             profile,
             action_receipts: self.receipt_manager.action_receipts,
             aborted: None,
+            host_function_calls: self.host_function_recorder.map(HostFunctionRecorder::into_calls),
         }
     }
 
@@ -2742,6 +2749,23 @@ impl<'a> VMLogic<'a> {
         self.gas_counter.pay_base(contract_loading_base)
     }
 
+    /// Records the start of a host function call if gas tracing is enabled.
+    #[inline]
+    pub fn begin_host_function(&mut self, name: &str, args: &[(&str, u64)]) {
+        if let Some(recorder) = &mut self.host_function_recorder {
+            recorder.begin(name, args, self.gas_counter.burnt_gas(), self.gas_counter.used_gas());
+        }
+    }
+
+    /// Records the end of the host function call started with
+    /// [`Self::begin_host_function`].
+    #[inline]
+    pub fn end_host_function(&mut self) {
+        if let Some(recorder) = &mut self.host_function_recorder {
+            recorder.end(self.gas_counter.burnt_gas(), self.gas_counter.used_gas());
+        }
+    }
+
     /// Gets pointer to the fast gas counter.
     pub fn gas_counter_pointer(&mut self) -> *mut FastGasCounter {
         self.gas_counter.gas_counter_raw_ptr()
@@ -2846,6 +2870,9 @@ pub struct VMOutcome {
     pub profile: ProfileData,
     pub action_receipts: Vec<(AccountId, ReceiptMetadata)>,
     pub aborted: Option<FunctionCallError>,
+    /// Host functions called by the contract, only recorded if gas tracing
+    /// is enabled.
+    pub host_function_calls: Option<Vec<HostFunctionCall>>,
 }
 
 impl VMOutcome {
@@ -2878,6 +2905,7 @@ impl VMOutcome {
             profile: ProfileData::default(),
             action_receipts: Vec::new(),
             aborted: Some(error),
+            host_function_calls: None,
         }
     }
 
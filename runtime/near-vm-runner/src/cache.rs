@@ -3,12 +3,13 @@ use crate::vm_kind::VMKind;
 use borsh::BorshSerialize;
 use near_primitives::contract::ContractCode;
 use near_primitives::hash::CryptoHash;
-use near_primitives::types::{CompiledContract, CompiledContractCache};
+use near_primitives::types::{CompiledContract, CompiledContractCache, CompiledContractInfo};
 use near_vm_errors::{CacheError, CompilationError};
 use near_vm_logic::{ProtocolVersion, VMConfig};
 use std::collections::HashMap;
 use std::fmt;
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
 #[derive(Debug, Clone, BorshSerialize)]
 enum ContractCacheKey {
@@ -55,6 +56,31 @@ pub fn get_contract_cache_key(
     CryptoHash::hash_borsh(key)
 }
 
+/// Identifies the VM used at `protocol_version` together with its `config`,
+/// i.e. everything the cache key of a contract depends on besides its code.
+/// Cache entries compiled with VM identifiers no longer in use can be removed.
+pub fn vm_cache_id(config: &VMConfig, protocol_version: ProtocolVersion) -> CryptoHash {
+    vm_cache_id_for_kind(VMKind::for_protocol_version(protocol_version), config)
+}
+
+fn vm_cache_id_for_kind(vm_kind: VMKind, config: &VMConfig) -> CryptoHash {
+    CryptoHash::hash_borsh((config.non_crypto_hash(), vm_kind, vm_hash(vm_kind)))
+}
+
+/// Describes `code` compiled by `vm_kind` VM in `compile_time`.
+pub(crate) fn compiled_contract_info(
+    code: &ContractCode,
+    vm_kind: VMKind,
+    config: &VMConfig,
+    compile_time: Duration,
+) -> CompiledContractInfo {
+    CompiledContractInfo {
+        code_hash: *code.hash(),
+        vm_id: vm_cache_id_for_kind(vm_kind, config),
+        compile_time_us: compile_time.as_micros() as u64,
+    }
+}
+
 #[derive(Default)]
 pub struct MockCompiledContractCache {
     store: Arc<Mutex<HashMap<CryptoHash, CompiledContract>>>,
@@ -20,7 +20,10 @@ mod wasmtime_runner;
 
 pub use near_vm_logic::with_ext_cost_counter;
 
-pub use cache::{get_contract_cache_key, precompile_contract, MockCompiledContractCache};
+pub use cache::{
+    get_contract_cache_key, precompile_contract, vm_cache_id, MockCompiledContractCache,
+};
+pub use errors::ContractPrecompilatonResult;
 pub use runner::{run, VM};
 
 /// This is public for internal experimentation use only, and should otherwise be considered an
@@ -1,3 +1,4 @@
+use crate::cache::compiled_contract_info;
 use crate::errors::ContractPrecompilatonResult;
 use crate::imports::wasmer2::Wasmer2Imports;
 use crate::internal::VMKind;
@@ -289,7 +290,9 @@ impl Wasmer2VM {
         code: &ContractCode,
         cache: Option<&dyn CompiledContractCache>,
     ) -> Result<Result<UniversalExecutable, CompilationError>, CacheError> {
+        let start = std::time::Instant::now();
         let executable_or_error = self.compile_uncached(code);
+        let compile_time = start.elapsed();
         let key = get_contract_cache_key(code, VMKind::Wasmer2, &self.config);
 
         if let Some(cache) = cache {
@@ -302,7 +305,8 @@ impl Wasmer2VM {
                 }
                 Err(err) => CompiledContract::CompileModuleError(err.clone()),
             };
-            cache.put(&key, record).map_err(CacheError::WriteError)?;
+            let info = compiled_contract_info(code, VMKind::Wasmer2, &self.config, compile_time);
+            cache.put_with_info(&key, record, info).map_err(CacheError::WriteError)?;
         }
 
         Ok(executable_or_error)
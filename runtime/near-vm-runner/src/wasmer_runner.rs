@@ -1,3 +1,4 @@
+use crate::cache::compiled_contract_info;
 use crate::errors::{ContractPrecompilatonResult, IntoVMError};
 use crate::internal::VMKind;
 use crate::memory::WasmerMemory;
@@ -265,7 +266,9 @@ impl Wasmer0VM {
         code: &ContractCode,
         cache: Option<&dyn CompiledContractCache>,
     ) -> Result<Result<wasmer_runtime::Module, CompilationError>, CacheError> {
+        let start = std::time::Instant::now();
         let module_or_error = self.compile_uncached(code);
+        let compile_time = start.elapsed();
         let key = get_contract_cache_key(code, VMKind::Wasmer0, &self.config);
 
         if let Some(cache) = cache {
@@ -279,7 +282,8 @@ impl Wasmer0VM {
                 }
                 Err(err) => CompiledContract::CompileModuleError(err.clone()),
             };
-            cache.put(&key, record).map_err(CacheError::WriteError)?;
+            let info = compiled_contract_info(code, VMKind::Wasmer0, &self.config, compile_time);
+            cache.put_with_info(&key, record, info).map_err(CacheError::WriteError)?;
         }
 
         Ok(module_or_error)
@@ -154,6 +154,15 @@ pub enum Cost {
     /// Subtract the base cost of creating a sir-receipt.
     /// TODO(jakmeier): Consider different account states.
     ActionDeleteAccount,
+    /// Estimates the `action_delegate` fee which is charged for every
+    /// `Action::Delegate`, the same value for sending and executing.
+    ///
+    /// Estimation: Measure a transaction from a relayer with a delegate action
+    /// without inner actions, signed by another account.  Subtract the base
+    /// cost of creating a receipt and of creating the sir-receipt of the inner
+    /// actions.
+    #[cfg(feature = "protocol_feature_nep366_delegate_action")]
+    ActionDelegate,
 
     /// Estimates `wasm_config.ext_costs.base` which is intended to be charged
     /// once on every host function call. However, this is currently
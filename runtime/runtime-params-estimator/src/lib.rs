@@ -132,6 +132,8 @@ static ALL_COSTS: &[(Cost, fn(&mut EstimatorContext) -> GasCost)] = &[
     (Cost::ActionTransfer, action_transfer),
     (Cost::ActionCreateAccount, action_create_account),
     (Cost::ActionDeleteAccount, action_delete_account),
+    #[cfg(feature = "protocol_feature_nep366_delegate_action")]
+    (Cost::ActionDelegate, action_delegate),
     (Cost::ActionAddFullAccessKey, action_add_full_access_key),
     (Cost::ActionAddFunctionAccessKeyBase, action_add_function_access_key_base),
     (Cost::ActionAddFunctionAccessKeyPerByte, action_add_function_access_key_per_byte),
@@ -367,6 +369,53 @@ fn action_delete_account(ctx: &mut EstimatorContext) -> GasCost {
     total_cost.saturating_sub(&base_cost, &NonNegativeTolerance::PER_MILLE)
 }
 
+#[cfg(feature = "protocol_feature_nep366_delegate_action")]
+fn action_delegate(ctx: &mut EstimatorContext) -> GasCost {
+    use near_crypto::{InMemorySigner, Signer};
+    use near_primitives::delegate_action::{DelegateAction, SignedDelegateAction};
+
+    let total_cost = {
+        let mut make_transaction = |tb: &mut TransactionBuilder| -> SignedTransaction {
+            let relayer = tb.random_account();
+            let sender = loop {
+                let sender = tb.random_unused_account();
+                if sender != relayer {
+                    break sender;
+                }
+            };
+            let signer =
+                InMemorySigner::from_seed(sender.clone(), KeyType::ED25519, sender.as_ref());
+            // The delegate action takes a nonce of the sender’s access key, so
+            // it’s allocated together with the nonces of transactions.
+            let delegate_action = DelegateAction {
+                sender_id: sender.clone(),
+                receiver_id: sender.clone(),
+                actions: vec![],
+                nonce: tb.nonce(&sender),
+                max_block_height: u64::MAX,
+                public_key: signer.public_key(),
+            };
+            let signature = signer.sign(delegate_action.get_hash().as_ref());
+            let actions =
+                vec![Action::Delegate(SignedDelegateAction { delegate_action, signature })];
+            tb.transaction_from_actions(relayer, sender, actions)
+        };
+        let block_size = 100;
+        // The delegate action is executed by its sender, which may be on a
+        // different shard than the relayer, and the inner actions are
+        // executed in the following block.
+        let block_latency = 2;
+        transaction_cost_ext(ctx, block_size, &mut make_transaction, block_latency).0
+    };
+
+    let base_cost = action_receipt_creation(ctx);
+    let inner_receipt_cost = action_sir_receipt_creation(ctx);
+
+    total_cost
+        .saturating_sub(&base_cost, &NonNegativeTolerance::PER_MILLE)
+        .saturating_sub(&inner_receipt_cost, &NonNegativeTolerance::PER_MILLE)
+}
+
 fn action_add_full_access_key(ctx: &mut EstimatorContext) -> GasCost {
     let total_cost = {
         let mut make_transaction = |tb: &mut TransactionBuilder| -> SignedTransaction {
@@ -124,7 +124,7 @@ impl TransactionBuilder {
         (0..len).map(|_| self.rng().gen()).collect()
     }
 
-    fn nonce(&mut self, account_id: &AccountId) -> u64 {
+    pub(crate) fn nonce(&mut self, account_id: &AccountId) -> u64 {
         let nonce = self.nonces.entry(account_id.clone()).or_default();
         *nonce += 1;
         *nonce
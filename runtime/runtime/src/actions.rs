@@ -8,6 +8,7 @@ use near_primitives::checked_feature;
 use near_primitives::config::ViewConfig;
 use near_primitives::contract::ContractCode;
 use near_primitives::errors::{ActionError, ActionErrorKind, RuntimeError};
+use near_primitives::gas_trace::FunctionCallTrace;
 use near_primitives::hash::CryptoHash;
 use near_primitives::receipt::{ActionReceipt, Receipt, ReceiptEnum};
 use near_primitives::runtime::config::AccountCreationConfig;
@@ -31,10 +32,20 @@ use near_vm_errors::{
     CompilationError, FunctionCallError, FunctionCallErrorSer, InconsistentStateError,
     VMRunnerError,
 };
+use near_vm_logic::gas_trace::push_gas_trace;
 use near_vm_logic::types::PromiseResult;
 use near_vm_logic::{VMContext, VMOutcome};
 use near_vm_runner::precompile_contract;
 
+#[cfg(feature = "protocol_feature_nep366_delegate_action")]
+use crate::config::{total_prepaid_exec_fees, total_prepaid_gas, total_send_fees};
+#[cfg(feature = "protocol_feature_nep366_delegate_action")]
+use near_primitives::delegate_action::{DelegateAction, SignedDelegateAction};
+#[cfg(feature = "protocol_feature_nep366_delegate_action")]
+use near_primitives::errors::InvalidAccessKeyError;
+#[cfg(feature = "protocol_feature_nep366_delegate_action")]
+use near_vm_logic::ActionCosts;
+
 /// Runs given function call with given context / apply state.
 pub(crate) fn execute_function_call(
     apply_state: &ApplyState,
@@ -184,7 +195,7 @@ pub(crate) fn action_function_call(
         epoch_info_provider,
         apply_state.current_protocol_version,
     );
-    let outcome = execute_function_call(
+    let mut outcome = execute_function_call(
         apply_state,
         &mut runtime_ext,
         account,
@@ -198,6 +209,17 @@ pub(crate) fn action_function_call(
         None,
     )?;
 
+    if let Some(calls) = outcome.host_function_calls.take() {
+        push_gas_trace(FunctionCallTrace {
+            receipt_id: receipt.receipt_id,
+            account_id: account_id.clone(),
+            method_name: function_call.method_name.clone(),
+            calls,
+            gas_burnt: outcome.burnt_gas,
+            gas_used: outcome.used_gas,
+        });
+    }
+
     match &outcome.aborted {
         None => {
             metrics::FUNCTION_CALL_PROCESSED.with_label_values(&["ok"]).inc();
@@ -624,6 +646,179 @@ pub(crate) fn action_add_key(
     Ok(())
 }
 
+/// Executes a delegate action on behalf of its sender.
+///
+/// Checks the signature, the expiry and the sender's access key and creates
+/// a receipt with the inner actions whose predecessor is the sender.  The
+/// signer of the outer receipt, i.e. the relayer, has prepaid the gas and the
+/// deposits of the inner actions.
+#[cfg(feature = "protocol_feature_nep366_delegate_action")]
+pub(crate) fn apply_delegate_action(
+    state_update: &mut TrieUpdate,
+    apply_state: &ApplyState,
+    action_receipt: &ActionReceipt,
+    sender_id: &AccountId,
+    signed_delegate_action: &SignedDelegateAction,
+    result: &mut ActionResult,
+) -> Result<(), RuntimeError> {
+    let delegate_action = &signed_delegate_action.delegate_action;
+
+    if !signed_delegate_action.verify() {
+        result.result = Err(ActionErrorKind::DelegateActionInvalidSignature.into());
+        return Ok(());
+    }
+    if apply_state.block_height > delegate_action.max_block_height {
+        result.result = Err(ActionErrorKind::DelegateActionExpired.into());
+        return Ok(());
+    }
+    if &delegate_action.sender_id != sender_id {
+        result.result = Err(ActionErrorKind::DelegateActionSenderDoesNotMatchTxReceiver {
+            sender_id: delegate_action.sender_id.clone(),
+            receiver_id: sender_id.clone(),
+        }
+        .into());
+        return Ok(());
+    }
+
+    validate_delegate_action_key(state_update, apply_state, delegate_action, result)?;
+    if result.result.is_err() {
+        return Ok(());
+    }
+
+    let actions = delegate_action.get_actions();
+    let fees = &apply_state.config.fees;
+    let sender_is_receiver = delegate_action.sender_id == delegate_action.receiver_id;
+    // Gas needed to execute the new receipt, it's burnt by its receiver.
+    let required_gas = safe_add_gas(
+        fees.fee(ActionCosts::new_action_receipt).exec_fee(),
+        safe_add_gas(
+            total_prepaid_exec_fees(
+                fees,
+                &actions,
+                &delegate_action.receiver_id,
+                apply_state.current_protocol_version,
+            )?,
+            total_prepaid_gas(&actions)?,
+        )?,
+    )?;
+    // Gas needed to send the new receipt.  It was prepaid by the relayer and
+    // is burnt now, as it would've been when converting a transaction.
+    let send_fees = safe_add_gas(
+        fees.fee(ActionCosts::new_action_receipt).send_fee(sender_is_receiver),
+        total_send_fees(
+            fees,
+            sender_is_receiver,
+            &actions,
+            &delegate_action.receiver_id,
+            apply_state.current_protocol_version,
+        )?,
+    )?;
+    result.gas_used = safe_add_gas(result.gas_used, safe_add_gas(required_gas, send_fees)?)?;
+    result.gas_burnt = safe_add_gas(result.gas_burnt, send_fees)?;
+
+    // If the inner actions fail, their deposits are refunded to the
+    // predecessor, i.e. the sender, while the unused gas is refunded to the
+    // signer, i.e. the relayer.
+    result.new_receipts.push(Receipt {
+        predecessor_id: delegate_action.sender_id.clone(),
+        receiver_id: delegate_action.receiver_id.clone(),
+        receipt_id: CryptoHash::default(),
+        receipt: ReceiptEnum::Action(ActionReceipt {
+            signer_id: action_receipt.signer_id.clone(),
+            signer_public_key: action_receipt.signer_public_key.clone(),
+            gas_price: action_receipt.gas_price,
+            output_data_receivers: vec![],
+            input_data_ids: vec![],
+            actions,
+        }),
+    });
+    Ok(())
+}
+
+/// Checks the delegate action against the sender's access key and bumps the
+/// key's nonce, just like transaction verification does for the signer.
+#[cfg(feature = "protocol_feature_nep366_delegate_action")]
+fn validate_delegate_action_key(
+    state_update: &mut TrieUpdate,
+    apply_state: &ApplyState,
+    delegate_action: &DelegateAction,
+    result: &mut ActionResult,
+) -> Result<(), RuntimeError> {
+    let sender_id = &delegate_action.sender_id;
+    let public_key = &delegate_action.public_key;
+    let mut access_key = match get_access_key(state_update, sender_id, public_key)? {
+        Some(access_key) => access_key,
+        None => {
+            result.result = Err(ActionErrorKind::DelegateActionAccessKeyError(
+                InvalidAccessKeyError::AccessKeyNotFound {
+                    account_id: sender_id.clone(),
+                    public_key: public_key.clone(),
+                },
+            )
+            .into());
+            return Ok(());
+        }
+    };
+
+    if delegate_action.nonce <= access_key.nonce {
+        result.result = Err(ActionErrorKind::DelegateActionInvalidNonce {
+            delegate_nonce: delegate_action.nonce,
+            ak_nonce: access_key.nonce,
+        }
+        .into());
+        return Ok(());
+    }
+    let upper_bound = apply_state.block_height * AccessKey::ACCESS_KEY_NONCE_RANGE_MULTIPLIER;
+    if delegate_action.nonce >= upper_bound {
+        result.result = Err(ActionErrorKind::DelegateActionNonceTooLarge {
+            delegate_nonce: delegate_action.nonce,
+            upper_bound,
+        }
+        .into());
+        return Ok(());
+    }
+    access_key.nonce = delegate_action.nonce;
+
+    if let AccessKeyPermission::FunctionCall(ref function_call_permission) = access_key.permission {
+        let error = match delegate_action.actions.as_slice() {
+            [action] => match Action::from(action.clone()) {
+                Action::FunctionCall(function_call) => {
+                    if function_call.deposit > 0 {
+                        Some(InvalidAccessKeyError::DepositWithFunctionCall)
+                    } else if delegate_action.receiver_id.as_ref()
+                        != function_call_permission.receiver_id
+                    {
+                        Some(InvalidAccessKeyError::ReceiverMismatch {
+                            tx_receiver: delegate_action.receiver_id.clone(),
+                            ak_receiver: function_call_permission.receiver_id.clone(),
+                        })
+                    } else if !function_call_permission.method_names.is_empty()
+                        && function_call_permission
+                            .method_names
+                            .iter()
+                            .all(|method_name| &function_call.method_name != method_name)
+                    {
+                        Some(InvalidAccessKeyError::MethodNameMismatch {
+                            method_name: function_call.method_name,
+                        })
+                    } else {
+                        None
+                    }
+                }
+                _ => Some(InvalidAccessKeyError::RequiresFullAccess),
+            },
+            _ => Some(InvalidAccessKeyError::RequiresFullAccess),
+        };
+        if let Some(error) = error {
+            result.result = Err(ActionErrorKind::DelegateActionAccessKeyError(error).into());
+            return Ok(());
+        }
+    }
+
+    set_access_key(state_update, sender_id.clone(), public_key.clone(), &access_key);
+    Ok(())
+}
+
 pub(crate) fn check_actor_permissions(
     action: &Action,
     account: &Option<Account>,
@@ -657,6 +852,9 @@ pub(crate) fn check_actor_permissions(
             }
         }
         Action::CreateAccount(_) | Action::FunctionCall(_) | Action::Transfer(_) => (),
+        // Anyone can relay a delegate action, its signature is checked instead.
+        #[cfg(feature = "protocol_feature_nep366_delegate_action")]
+        Action::Delegate(_) => (),
     };
     Ok(())
 }
@@ -731,6 +929,15 @@ pub(crate) fn check_account_existence(
                 .into());
             }
         }
+        #[cfg(feature = "protocol_feature_nep366_delegate_action")]
+        Action::Delegate(_) => {
+            if account.is_none() {
+                return Err(ActionErrorKind::AccountDoesNotExist {
+                    account_id: account_id.clone(),
+                }
+                .into());
+            }
+        }
     };
     Ok(())
 }
@@ -55,6 +55,17 @@ fn receipt_cost(
                     )?,
                 )?;
                 total_gas = safe_add_gas(total_gas, total_prepaid_gas(&action_receipt.actions)?)?;
+                #[cfg(feature = "protocol_feature_nep366_delegate_action")]
+                {
+                    total_gas = safe_add_gas(
+                        total_gas,
+                        crate::config::total_prepaid_send_fees(
+                            transaction_costs,
+                            &action_receipt.actions,
+                            current_protocol_version,
+                        )?,
+                    )?;
+                }
                 let total_gas_cost = safe_gas_to_balance(action_receipt.gas_price, total_gas)?;
                 total_cost = safe_add_balance(total_cost, total_gas_cost)?;
             }
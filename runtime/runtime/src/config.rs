@@ -125,6 +125,10 @@ pub fn total_send_fees(
             DeleteAccount(_) => {
                 config.fee(ActionCosts::delete_account).send_fee(sender_is_receiver)
             }
+            // Send fees of the inner actions are prepaid and burnt when the
+            // delegate action is executed, see `total_prepaid_send_fees`.
+            #[cfg(feature = "protocol_feature_nep366_delegate_action")]
+            Delegate(_) => config.fee(ActionCosts::delegate).send_fee(sender_is_receiver),
         };
         result = safe_add_gas(result, delta)?;
     }
@@ -176,6 +180,8 @@ pub fn exec_fee(
         },
         DeleteKey(_) => config.fee(ActionCosts::delete_key).exec_fee(),
         DeleteAccount(_) => config.fee(ActionCosts::delete_account).exec_fee(),
+        #[cfg(feature = "protocol_feature_nep366_delegate_action")]
+        Delegate(_) => config.fee(ActionCosts::delegate).exec_fee(),
     }
 }
 
@@ -200,6 +206,11 @@ pub fn tx_cost(
         )?,
     )?;
     let prepaid_gas = total_prepaid_gas(&transaction.actions)?;
+    #[cfg(feature = "protocol_feature_nep366_delegate_action")]
+    let prepaid_gas = safe_add_gas(
+        prepaid_gas,
+        total_prepaid_send_fees(config, &transaction.actions, current_protocol_version)?,
+    )?;
     // If signer is equals to receiver the receipt will be processed at the same block as this
     // transaction. Otherwise it will processed in the next block and the gas might be inflated.
     let initial_receipt_hop = if transaction.signer_id == transaction.receiver_id { 0 } else { 1 };
@@ -248,21 +259,85 @@ pub fn total_prepaid_exec_fees(
     for action in actions {
         let delta = exec_fee(config, action, receiver_id, current_protocol_version);
         result = safe_add_gas(result, delta)?;
+        // The receipt with the inner actions of a delegate action is created
+        // when the delegate action is executed, so its execution has to be
+        // prepaid as well.
+        #[cfg(feature = "protocol_feature_nep366_delegate_action")]
+        if let Action::Delegate(signed_delegate_action) = action {
+            let delegate_action = &signed_delegate_action.delegate_action;
+            let delta = safe_add_gas(
+                config.fee(ActionCosts::new_action_receipt).exec_fee(),
+                total_prepaid_exec_fees(
+                    config,
+                    &delegate_action.get_actions(),
+                    &delegate_action.receiver_id,
+                    current_protocol_version,
+                )?,
+            )?;
+            result = safe_add_gas(result, delta)?;
+        }
+    }
+    Ok(result)
+}
+
+/// Total sum of gas that needs to be burnt to send the receipts created when
+/// executing the given actions, i.e. the inner actions of delegate actions.
+#[cfg(feature = "protocol_feature_nep366_delegate_action")]
+pub fn total_prepaid_send_fees(
+    config: &RuntimeFeesConfig,
+    actions: &[Action],
+    current_protocol_version: ProtocolVersion,
+) -> Result<Gas, IntegerOverflowError> {
+    let mut result = 0;
+    for action in actions {
+        if let Action::Delegate(signed_delegate_action) = action {
+            let delegate_action = &signed_delegate_action.delegate_action;
+            let sender_is_receiver = delegate_action.sender_id == delegate_action.receiver_id;
+            let delta = safe_add_gas(
+                config.fee(ActionCosts::new_action_receipt).send_fee(sender_is_receiver),
+                total_send_fees(
+                    config,
+                    sender_is_receiver,
+                    &delegate_action.get_actions(),
+                    &delegate_action.receiver_id,
+                    current_protocol_version,
+                )?,
+            )?;
+            result = safe_add_gas(result, delta)?;
+        }
     }
     Ok(result)
 }
+
 /// Get the total sum of deposits for given actions.
+///
+/// Deposits attached to the inner actions of a delegate action are paid by
+/// the signer of the outer receipt, i.e. the relayer.
 pub fn total_deposit(actions: &[Action]) -> Result<Balance, IntegerOverflowError> {
     let mut total_balance: Balance = 0;
     for action in actions {
         total_balance = safe_add_balance(total_balance, action.get_deposit_balance())?;
+        #[cfg(feature = "protocol_feature_nep366_delegate_action")]
+        if let Action::Delegate(signed_delegate_action) = action {
+            let inner = total_deposit(&signed_delegate_action.delegate_action.get_actions())?;
+            total_balance = safe_add_balance(total_balance, inner)?;
+        }
     }
     Ok(total_balance)
 }
 
 /// Get the total sum of prepaid gas for given actions.
 pub fn total_prepaid_gas(actions: &[Action]) -> Result<Gas, IntegerOverflowError> {
-    actions.iter().try_fold(0, |acc, action| safe_add_gas(acc, action.get_prepaid_gas()))
+    let mut total_gas: Gas = 0;
+    for action in actions {
+        total_gas = safe_add_gas(total_gas, action.get_prepaid_gas())?;
+        #[cfg(feature = "protocol_feature_nep366_delegate_action")]
+        if let Action::Delegate(signed_delegate_action) = action {
+            let inner = total_prepaid_gas(&signed_delegate_action.delegate_action.get_actions())?;
+            total_gas = safe_add_gas(total_gas, inner)?;
+        }
+    }
+    Ok(total_gas)
 }
 
 #[cfg(test)]
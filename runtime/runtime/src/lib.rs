@@ -47,7 +47,8 @@ use near_store::{
     set_account, set_postponed_receipt, set_received_data, PartialStorage, ShardTries,
     StorageError, Trie, TrieChanges, TrieUpdate,
 };
-use near_store::{set_access_key, set_code};
+use near_store::{remove_account, set_access_key, set_code};
+pub use near_vm_logic::gas_trace::record_gas_traces;
 use near_vm_logic::types::PromiseResult;
 use near_vm_logic::{ActionCosts, ReturnData};
 pub use near_vm_runner::with_ext_cost_counter;
@@ -443,6 +444,17 @@ impl Runtime {
                     apply_state.current_protocol_version,
                 )?;
             }
+            #[cfg(feature = "protocol_feature_nep366_delegate_action")]
+            Action::Delegate(signed_delegate_action) => {
+                apply_delegate_action(
+                    state_update,
+                    apply_state,
+                    action_receipt,
+                    account_id,
+                    signed_delegate_action,
+                    &mut result,
+                )?;
+            }
         };
         Ok(result)
     }
@@ -522,7 +534,11 @@ impl Runtime {
             )?;
             if new_result.result.is_ok() {
                 if let Err(e) = new_result.new_receipts.iter().try_for_each(|receipt| {
-                    validate_receipt(&apply_state.config.wasm_config.limit_config, receipt)
+                    validate_receipt(
+                        &apply_state.config.wasm_config.limit_config,
+                        receipt,
+                        apply_state.current_protocol_version,
+                    )
                 }) {
                     new_result.result = Err(ActionErrorKind::NewReceiptValidationError(e).into());
                 }
@@ -748,6 +764,15 @@ impl Runtime {
     ) -> Result<Balance, RuntimeError> {
         let total_deposit = total_deposit(&action_receipt.actions)?;
         let prepaid_gas = total_prepaid_gas(&action_receipt.actions)?;
+        #[cfg(feature = "protocol_feature_nep366_delegate_action")]
+        let prepaid_gas = safe_add_gas(
+            prepaid_gas,
+            crate::config::total_prepaid_send_fees(
+                transaction_costs,
+                &action_receipt.actions,
+                current_protocol_version,
+            )?,
+        )?;
         let prepaid_exec_gas = safe_add_gas(
             total_prepaid_exec_fees(
                 transaction_costs,
@@ -1142,6 +1167,7 @@ impl Runtime {
     /// new outgoing receipts, execution outcomes for
     /// all transactions, local action receipts (generated from transactions with signer ==
     /// receivers) and incoming action receipts.
+    /// Run it inside `record_gas_traces` to get gas traces of the executed function calls.
     pub fn apply(
         &self,
         trie: Trie,
@@ -1320,14 +1346,17 @@ impl Runtime {
             }
 
             // Validating the delayed receipt. If it fails, it's likely the state is inconsistent.
-            validate_receipt(&apply_state.config.wasm_config.limit_config, &receipt).map_err(
-                |e| {
-                    StorageError::StorageInconsistentState(format!(
-                        "Delayed receipt #{} in the state is invalid: {}",
-                        delayed_receipts_indices.first_index, e
-                    ))
-                },
-            )?;
+            validate_receipt(
+                &apply_state.config.wasm_config.limit_config,
+                &receipt,
+                apply_state.current_protocol_version,
+            )
+            .map_err(|e| {
+                StorageError::StorageInconsistentState(format!(
+                    "Delayed receipt #{} in the state is invalid: {}",
+                    delayed_receipts_indices.first_index, e
+                ))
+            })?;
 
             state_update.remove(key);
             // Math checked above: first_index is less than next_available_index
@@ -1345,8 +1374,12 @@ impl Runtime {
         for receipt in incoming_receipts.iter() {
             // Validating new incoming no matter whether we have available gas or not. We don't
             // want to store invalid receipts in state as delayed.
-            validate_receipt(&apply_state.config.wasm_config.limit_config, receipt)
-                .map_err(RuntimeError::ReceiptValidationError)?;
+            validate_receipt(
+                &apply_state.config.wasm_config.limit_config,
+                receipt,
+                apply_state.current_protocol_version,
+            )
+            .map_err(RuntimeError::ReceiptValidationError)?;
             if total_gas_burnt < gas_limit {
                 process_receipt(receipt, &mut state_update, &mut total_gas_burnt)?;
             } else {
@@ -1375,7 +1408,7 @@ impl Runtime {
         )?;
 
         state_update.commit(StateChangeCause::UpdatedDelayedReceipts);
-        self.apply_state_patch(&mut state_update, state_patch);
+        self.apply_state_patch(&mut state_update, state_patch)?;
         let (trie_changes, state_changes) = state_update.finalize()?;
 
         // Dedup proposals from the same account.
@@ -1426,9 +1459,16 @@ impl Runtime {
         Ok(())
     }
 
-    fn apply_state_patch(&self, state_update: &mut TrieUpdate, state_patch: SandboxStatePatch) {
+    fn apply_state_patch(
+        &self,
+        state_update: &mut TrieUpdate,
+        state_patch: SandboxStatePatch,
+    ) -> Result<(), StorageError> {
         if state_patch.is_empty() {
-            return;
+            return Ok(());
+        }
+        for account_id in state_patch.removed_accounts() {
+            remove_account(state_update, account_id)?;
         }
         for record in state_patch {
             match record {
@@ -1452,6 +1492,7 @@ impl Runtime {
             }
         }
         state_update.commit(StateChangeCause::Migration);
+        Ok(())
     }
 
     /// Computes the expected storage per account for a given set of StateRecord(s).
@@ -1504,7 +1545,7 @@ mod tests {
     use near_primitives::transaction::{
         AddKeyAction, DeleteKeyAction, FunctionCallAction, TransferAction,
     };
-    use near_primitives::types::MerkleHash;
+    use near_primitives::types::{MerkleHash, StorageUsage};
     use near_primitives::version::PROTOCOL_VERSION;
     use near_store::set_access_key;
     use near_store::test_utils::create_tries;
@@ -2409,6 +2450,248 @@ mod tests {
         assert_eq!(final_account_state.storage_usage(), 0);
     }
 
+    /// Checks that the free storage allowance of zero-balance accounts can't
+    /// be used to grow the state beyond the allowance or to deploy contracts.
+    #[test]
+    fn test_zero_balance_account_storage_limit() {
+        let (runtime, tries, root, mut apply_state, signer, epoch_info_provider) =
+            setup_runtime(0, 0, 10u64.pow(15));
+        let mut config = RuntimeConfig::test();
+        config.fees.storage_usage_config.zero_balance_account_storage_limit = 328;
+        apply_state.config = Arc::new(config);
+
+        let mut state_update = tries.new_trie_update(ShardUId::single_shard(), root);
+        let mut account = get_account(&state_update, &alice_account()).unwrap().unwrap();
+        account.set_code_hash(CryptoHash::default());
+        set_account(&mut state_update, alice_account(), &account);
+        state_update.commit(StateChangeCause::InitialState);
+        let trie_changes = state_update.finalize().unwrap().0;
+        let mut store_update = tries.store_update();
+        let root = tries.apply_all(&trie_changes, ShardUId::single_shard(), &mut store_update);
+        store_update.commit().unwrap();
+
+        let apply = |root, actions| {
+            let receipts = create_receipts_with_actions(alice_account(), signer.clone(), actions);
+            let apply_result = runtime
+                .apply(
+                    tries.get_trie_for_shard(ShardUId::single_shard(), root),
+                    &None,
+                    &apply_state,
+                    &receipts,
+                    &[],
+                    &epoch_info_provider,
+                    Default::default(),
+                )
+                .unwrap();
+            let mut store_update = tries.store_update();
+            let root = tries.apply_all(
+                &apply_result.trie_changes,
+                ShardUId::single_shard(),
+                &mut store_update,
+            );
+            store_update.commit().unwrap();
+            let state_update = tries.new_trie_update(ShardUId::single_shard(), root);
+            let account = get_account(&state_update, &alice_account()).unwrap().unwrap();
+            (root, account, apply_result.outcomes[0].outcome.status.clone())
+        };
+        let add_key = |seed| {
+            let signer = InMemorySigner::from_seed(alice_account(), KeyType::ED25519, seed);
+            vec![Action::AddKey(AddKeyAction {
+                public_key: signer.public_key(),
+                access_key: AccessKey::full_access(),
+            })]
+        };
+        let expect_lack_balance = |status, storage_usage: StorageUsage| match status {
+            ExecutionStatus::Failure(TxExecutionError::ActionError(ActionError {
+                kind: ActionErrorKind::LackBalanceForState { account_id, amount },
+                ..
+            })) => {
+                assert_eq!(account_id, alice_account());
+                // Once over the allowance, the whole storage has to be paid for.
+                assert_eq!(
+                    amount,
+                    Balance::from(storage_usage) * apply_state.config.storage_amount_per_byte()
+                );
+            }
+            status => panic!("expected LackBalanceForState, got {:?}", status),
+        };
+
+        // A second full access key fits into the allowance.
+        let (root, account, status) = apply(root, add_key("second"));
+        assert_eq!(status, ExecutionStatus::SuccessValue(vec![]));
+        let key_storage_usage = account.storage_usage() - 182;
+
+        // A third one doesn't, and the account has no balance to pay for it.
+        let (_, rejected_account, status) = apply(root, add_key("third"));
+        expect_lack_balance(status, account.storage_usage() + key_storage_usage);
+        assert_eq!(rejected_account, account);
+
+        // Accounts with a contract don't get the allowance, however small the
+        // contract is.
+        let code = vec![0u8];
+        let code_storage_usage = code.len() as StorageUsage;
+        let (_, rejected_account, status) =
+            apply(root, vec![Action::DeployContract(DeployContractAction { code })]);
+        expect_lack_balance(status, account.storage_usage() + code_storage_usage);
+        assert_eq!(rejected_account, account);
+    }
+
+    #[cfg(feature = "protocol_feature_nep366_delegate_action")]
+    fn create_delegate_action_receipt(
+        signer: &InMemorySigner,
+        delegate_action: near_primitives::delegate_action::DelegateAction,
+    ) -> Receipt {
+        let signature = signer.sign(delegate_action.get_hash().as_ref());
+        let signed_delegate_action =
+            near_primitives::delegate_action::SignedDelegateAction { delegate_action, signature };
+        // The relayer is the predecessor and the signer of the outer receipt.
+        Receipt {
+            predecessor_id: bob_account(),
+            receiver_id: alice_account(),
+            receipt_id: CryptoHash::default(),
+            receipt: ReceiptEnum::Action(ActionReceipt {
+                signer_id: bob_account(),
+                signer_public_key: signer.public_key(),
+                gas_price: GAS_PRICE,
+                output_data_receivers: vec![],
+                input_data_ids: vec![],
+                actions: vec![Action::Delegate(signed_delegate_action)],
+            }),
+        }
+    }
+
+    #[cfg(feature = "protocol_feature_nep366_delegate_action")]
+    fn delegate_action_with_transfer(
+        signer: &InMemorySigner,
+    ) -> near_primitives::delegate_action::DelegateAction {
+        near_primitives::delegate_action::DelegateAction {
+            sender_id: alice_account(),
+            receiver_id: bob_account(),
+            actions: vec![Action::Transfer(TransferAction { deposit: 1 }).try_into().unwrap()],
+            nonce: 1,
+            max_block_height: 100,
+            public_key: signer.public_key(),
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "protocol_feature_nep366_delegate_action")]
+    fn test_delegate_action() {
+        let (runtime, tries, root, mut apply_state, signer, epoch_info_provider) =
+            setup_runtime(to_yocto(1_000_000), 0, 10u64.pow(15));
+        apply_state.current_protocol_version = ProtocolFeature::DelegateAction.protocol_version();
+
+        let delegate_action = delegate_action_with_transfer(&signer);
+        let inner_actions = delegate_action.get_actions();
+        let receipts = vec![create_delegate_action_receipt(&signer, delegate_action)];
+
+        let apply_result = runtime
+            .apply(
+                tries.get_trie_for_shard(ShardUId::single_shard(), root),
+                &None,
+                &apply_state,
+                &receipts,
+                &[],
+                &epoch_info_provider,
+                Default::default(),
+            )
+            .unwrap();
+        assert_eq!(apply_result.outcomes[0].outcome.status, ExecutionStatus::SuccessValue(vec![]));
+
+        // The inner actions are sent on behalf of the sender.
+        let receipt = apply_result
+            .outgoing_receipts
+            .iter()
+            .find(|receipt| receipt.predecessor_id == alice_account())
+            .expect("the inner actions should be sent");
+        assert_eq!(receipt.receiver_id, bob_account());
+        match &receipt.receipt {
+            ReceiptEnum::Action(action_receipt) => {
+                assert_eq!(action_receipt.signer_id, bob_account());
+                assert_eq!(action_receipt.actions, inner_actions);
+            }
+            _ => panic!("the inner actions should be sent in an action receipt"),
+        }
+
+        let mut store_update = tries.store_update();
+        let root = tries.apply_all(
+            &apply_result.trie_changes,
+            ShardUId::single_shard(),
+            &mut store_update,
+        );
+        store_update.commit().unwrap();
+        let state_update = tries.new_trie_update(ShardUId::single_shard(), root);
+        let access_key =
+            near_store::get_access_key(&state_update, &alice_account(), &signer.public_key())
+                .unwrap()
+                .unwrap();
+        assert_eq!(access_key.nonce, 1);
+    }
+
+    #[test]
+    #[cfg(feature = "protocol_feature_nep366_delegate_action")]
+    fn test_delegate_action_invalid() {
+        let (runtime, tries, root, mut apply_state, signer, epoch_info_provider) =
+            setup_runtime(to_yocto(1_000_000), 0, 10u64.pow(15));
+        apply_state.current_protocol_version = ProtocolFeature::DelegateAction.protocol_version();
+
+        let check = |receipt: Receipt, expected: ActionErrorKind| {
+            let apply_result = runtime
+                .apply(
+                    tries.get_trie_for_shard(ShardUId::single_shard(), root),
+                    &None,
+                    &apply_state,
+                    &[receipt],
+                    &[],
+                    &epoch_info_provider,
+                    Default::default(),
+                )
+                .unwrap();
+            assert_eq!(
+                apply_result.outcomes[0].outcome.status,
+                ExecutionStatus::Failure(ActionError { index: Some(0), kind: expected }.into())
+            );
+            assert!(apply_result
+                .outgoing_receipts
+                .iter()
+                .all(|receipt| receipt.predecessor_id != alice_account()));
+        };
+
+        // Change the delegate action after signing it.
+        let mut receipt =
+            create_delegate_action_receipt(&signer, delegate_action_with_transfer(&signer));
+        if let ReceiptEnum::Action(action_receipt) = &mut receipt.receipt {
+            if let Action::Delegate(signed) = &mut action_receipt.actions[0] {
+                signed.delegate_action.nonce += 1;
+            }
+        }
+        check(receipt, ActionErrorKind::DelegateActionInvalidSignature);
+
+        let mut delegate_action = delegate_action_with_transfer(&signer);
+        delegate_action.max_block_height = 0;
+        check(
+            create_delegate_action_receipt(&signer, delegate_action),
+            ActionErrorKind::DelegateActionExpired,
+        );
+
+        let mut delegate_action = delegate_action_with_transfer(&signer);
+        delegate_action.sender_id = bob_account();
+        check(
+            create_delegate_action_receipt(&signer, delegate_action),
+            ActionErrorKind::DelegateActionSenderDoesNotMatchTxReceiver {
+                sender_id: bob_account(),
+                receiver_id: alice_account(),
+            },
+        );
+
+        let mut delegate_action = delegate_action_with_transfer(&signer);
+        delegate_action.nonce = 0;
+        check(
+            create_delegate_action_receipt(&signer, delegate_action),
+            ActionErrorKind::DelegateActionInvalidNonce { delegate_nonce: 0, ak_nonce: 0 },
+        );
+    }
+
     #[test]
     fn test_contract_precompilation() {
         let initial_balance = to_yocto(1_000_000);
@@ -2448,4 +2731,66 @@ mod tests {
             .expect("Compiled contract should be cached")
             .expect("Compilation result should be non-empty");
     }
+
+    #[test]
+    fn test_gas_trace() {
+        let initial_balance = to_yocto(1_000_000);
+        let initial_locked = to_yocto(500_000);
+        let gas_limit = 10u64.pow(15);
+        let (runtime, tries, root, apply_state, signer, epoch_info_provider) =
+            setup_runtime(initial_balance, initial_locked, gas_limit);
+
+        let wasm_code = near_test_contracts::rs_contract().to_vec();
+        let actions = vec![Action::DeployContract(DeployContractAction { code: wasm_code })];
+        let receipts = create_receipts_with_actions(alice_account(), signer.clone(), actions);
+        let apply_result = runtime
+            .apply(
+                tries.get_trie_for_shard(ShardUId::single_shard(), root),
+                &None,
+                &apply_state,
+                &receipts,
+                &[],
+                &epoch_info_provider,
+                Default::default(),
+            )
+            .unwrap();
+        let mut store_update = tries.store_update();
+        let root = tries.apply_all(
+            &apply_result.trie_changes,
+            ShardUId::single_shard(),
+            &mut store_update,
+        );
+        store_update.commit().unwrap();
+
+        let actions = vec![Action::FunctionCall(FunctionCallAction {
+            method_name: "write_key_value".to_string(),
+            args: [1u64.to_le_bytes(), 2u64.to_le_bytes()].concat(),
+            gas: 10u64.pow(14),
+            deposit: 0,
+        })];
+        let receipts = create_receipts_with_actions(alice_account(), signer, actions);
+        let (apply_result, traces) = record_gas_traces(|| {
+            runtime.apply(
+                tries.get_trie_for_shard(ShardUId::single_shard(), root),
+                &None,
+                &apply_state,
+                &receipts,
+                &[],
+                &epoch_info_provider,
+                Default::default(),
+            )
+        });
+        let outcome = &apply_result.unwrap().outcomes[0].outcome;
+
+        assert_eq!(traces.len(), 1);
+        let trace = &traces[0];
+        assert_eq!(trace.receipt_id, receipts[0].receipt_id);
+        assert_eq!(trace.method_name, "write_key_value");
+        let storage_write = trace.calls.iter().find(|call| call.name == "storage_write").unwrap();
+        assert_eq!(storage_write.args["key_len"], 8);
+        assert_eq!(storage_write.args["value_len"], 8);
+        assert!(trace.calls.windows(2).all(|w| w[0].total_gas_burnt <= w[1].total_gas_burnt));
+        assert!(trace.calls.last().unwrap().total_gas_burnt <= trace.gas_burnt);
+        assert!(trace.gas_burnt < outcome.gas_burnt);
+    }
 }
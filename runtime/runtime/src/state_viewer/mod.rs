@@ -24,6 +24,7 @@ use tracing::debug;
 
 pub mod errors;
 
+#[derive(Clone)]
 pub struct TrieViewer {
     /// Upper bound of the byte size of contract state that is still viewable. None is no limit
     state_size_limit: Option<u64>,
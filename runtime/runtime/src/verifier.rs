@@ -24,6 +24,8 @@ use crate::VerificationResult;
 use near_primitives::checked_feature;
 use near_primitives::runtime::config::RuntimeConfig;
 use near_primitives::types::BlockHeight;
+#[cfg(feature = "protocol_feature_nep366_delegate_action")]
+use near_primitives::{delegate_action::SignedDelegateAction, version::ProtocolFeature};
 
 /// Validates the transaction without using the state. It allows any node to validate a
 /// transaction before forwarding it to the node that tracks the `signer_id` account.
@@ -55,8 +57,12 @@ pub fn validate_transaction(
         .into());
     }
 
-    validate_actions(&config.wasm_config.limit_config, &transaction.actions)
-        .map_err(InvalidTxError::ActionsValidation)?;
+    validate_actions(
+        &config.wasm_config.limit_config,
+        &transaction.actions,
+        current_protocol_version,
+    )
+    .map_err(InvalidTxError::ActionsValidation)?;
 
     let sender_is_receiver = &transaction.receiver_id == signer_id;
 
@@ -219,6 +225,7 @@ pub fn verify_and_charge_transaction(
 pub(crate) fn validate_receipt(
     limit_config: &VMLimitConfig,
     receipt: &Receipt,
+    current_protocol_version: ProtocolVersion,
 ) -> Result<(), ReceiptValidationError> {
     // We retain these checks here as to maintain backwards compatibility
     // with AccountId validation since we illegally parse an AccountId
@@ -234,7 +241,7 @@ pub(crate) fn validate_receipt(
 
     match &receipt.receipt {
         ReceiptEnum::Action(action_receipt) => {
-            validate_action_receipt(limit_config, action_receipt)
+            validate_action_receipt(limit_config, action_receipt, current_protocol_version)
         }
         ReceiptEnum::Data(data_receipt) => validate_data_receipt(limit_config, data_receipt),
     }
@@ -244,6 +251,7 @@ pub(crate) fn validate_receipt(
 fn validate_action_receipt(
     limit_config: &VMLimitConfig,
     receipt: &ActionReceipt,
+    current_protocol_version: ProtocolVersion,
 ) -> Result<(), ReceiptValidationError> {
     if receipt.input_data_ids.len() as u64 > limit_config.max_number_input_data_dependencies {
         return Err(ReceiptValidationError::NumberInputDataDependenciesExceeded {
@@ -251,7 +259,7 @@ fn validate_action_receipt(
             limit: limit_config.max_number_input_data_dependencies,
         });
     }
-    validate_actions(limit_config, &receipt.actions)
+    validate_actions(limit_config, &receipt.actions, current_protocol_version)
         .map_err(ReceiptValidationError::ActionsValidation)
 }
 
@@ -274,11 +282,13 @@ fn validate_data_receipt(
 ///
 /// - Checks limits if applicable.
 /// - Checks that the total number of actions doesn't exceed the limit.
+/// - Checks that there is at most one delegate action.
 /// - Validates each individual action.
 /// - Checks that the total prepaid gas doesn't exceed the limit.
 pub(crate) fn validate_actions(
     limit_config: &VMLimitConfig,
     actions: &[Action],
+    current_protocol_version: ProtocolVersion,
 ) -> Result<(), ActionsValidationError> {
     if actions.len() as u64 > limit_config.max_actions_per_receipt {
         return Err(ActionsValidationError::TotalNumberOfActionsExceeded {
@@ -287,6 +297,8 @@ pub(crate) fn validate_actions(
         });
     }
 
+    #[cfg(feature = "protocol_feature_nep366_delegate_action")]
+    let mut found_delegate_action = false;
     let mut iter = actions.iter().peekable();
     while let Some(action) = iter.next() {
         if let Action::DeleteAccount(_) = action {
@@ -294,7 +306,14 @@ pub(crate) fn validate_actions(
                 return Err(ActionsValidationError::DeleteActionMustBeFinal);
             }
         }
-        validate_action(limit_config, action)?;
+        #[cfg(feature = "protocol_feature_nep366_delegate_action")]
+        if let Action::Delegate(_) = action {
+            if found_delegate_action {
+                return Err(ActionsValidationError::DelegateActionMustBeOnlyOne);
+            }
+            found_delegate_action = true;
+        }
+        validate_action(limit_config, action, current_protocol_version)?;
     }
 
     let total_prepaid_gas =
@@ -310,9 +329,11 @@ pub(crate) fn validate_actions(
 }
 
 /// Validates a single given action. Checks limits if applicable.
+#[cfg_attr(not(feature = "protocol_feature_nep366_delegate_action"), allow(unused_variables))]
 pub fn validate_action(
     limit_config: &VMLimitConfig,
     action: &Action,
+    current_protocol_version: ProtocolVersion,
 ) -> Result<(), ActionsValidationError> {
     match action {
         Action::CreateAccount(_) => Ok(()),
@@ -323,7 +344,31 @@ pub fn validate_action(
         Action::AddKey(a) => validate_add_key_action(limit_config, a),
         Action::DeleteKey(_) => Ok(()),
         Action::DeleteAccount(_) => Ok(()),
+        #[cfg(feature = "protocol_feature_nep366_delegate_action")]
+        Action::Delegate(a) => validate_delegate_action(limit_config, a, current_protocol_version),
+    }
+}
+
+/// Validates `SignedDelegateAction`.  Checks that the protocol feature is
+/// enabled and validates the inner actions.
+#[cfg(feature = "protocol_feature_nep366_delegate_action")]
+fn validate_delegate_action(
+    limit_config: &VMLimitConfig,
+    signed_delegate_action: &SignedDelegateAction,
+    current_protocol_version: ProtocolVersion,
+) -> Result<(), ActionsValidationError> {
+    if !checked_feature!(
+        "protocol_feature_nep366_delegate_action",
+        DelegateAction,
+        current_protocol_version
+    ) {
+        return Err(ActionsValidationError::UnsupportedProtocolFeature {
+            protocol_feature: String::from("DelegateAction"),
+            version: ProtocolFeature::DelegateAction.protocol_version(),
+        });
     }
+    let actions = signed_delegate_action.delegate_action.get_actions();
+    validate_actions(limit_config, &actions, current_protocol_version)
 }
 
 /// Validates `DeployContractAction`. Checks that the given contract size doesn't exceed the limit.
@@ -460,7 +505,7 @@ mod tests {
     use near_primitives::transaction::{
         CreateAccountAction, DeleteAccountAction, DeleteKeyAction, StakeAction, TransferAction,
     };
-    use near_primitives::types::{AccountId, Balance, MerkleHash, StateChangeCause};
+    use near_primitives::types::{AccountId, Balance, MerkleHash, StateChangeCause, StorageUsage};
     use near_primitives::version::PROTOCOL_VERSION;
     use near_store::test_utils::create_tries;
     use testlib::runtime_utils::{alice_account, bob_account, eve_dot_alice_account};
@@ -898,6 +943,65 @@ mod tests {
         );
     }
 
+    /// Setup: account without a contract that fits into the free storage
+    /// allowance. Test that it can send out all of its balance, but not once it
+    /// grows beyond the allowance or deploys a contract.
+    #[test]
+    fn test_validate_transaction_zero_balance_account() {
+        let mut config = RuntimeConfig::free();
+        config.fees.storage_usage_config.storage_amount_per_byte = 10_000_000;
+        let initial_balance = 1_000_000_000;
+        let check = |config: &RuntimeConfig, code_hash: CryptoHash, storage_usage: StorageUsage| {
+            let (signer, mut state_update, gas_price) =
+                setup_common(initial_balance, 0, Some(AccessKey::full_access()));
+            let mut account = get_account(&state_update, &alice_account()).unwrap().unwrap();
+            account.set_code_hash(code_hash);
+            account.set_storage_usage(storage_usage);
+            set_account(&mut state_update, alice_account(), &account);
+            verify_and_charge_transaction(
+                config,
+                &mut state_update,
+                gas_price,
+                &SignedTransaction::send_money(
+                    1,
+                    alice_account(),
+                    bob_account(),
+                    &*signer,
+                    initial_balance,
+                    CryptoHash::default(),
+                ),
+                true,
+                None,
+                PROTOCOL_VERSION,
+            )
+        };
+        let amount_per_byte = config.storage_amount_per_byte();
+        let lack_balance = |storage_usage: StorageUsage| {
+            RuntimeError::InvalidTxError(InvalidTxError::LackBalanceForState {
+                signer_id: alice_account(),
+                amount: Balance::from(storage_usage) * amount_per_byte,
+            })
+        };
+
+        config.fees.storage_usage_config.zero_balance_account_storage_limit = 328;
+        check(&config, CryptoHash::default(), 328).expect("valid transaction");
+        assert_eq!(
+            check(&config, CryptoHash::default(), 329).expect_err("expected an error"),
+            lack_balance(329)
+        );
+        assert_eq!(
+            check(&config, hash(&[]), 100).expect_err("expected an error"),
+            lack_balance(100)
+        );
+
+        // Without the allowance every account has to pay for its storage.
+        config.fees.storage_usage_config.zero_balance_account_storage_limit = 0;
+        assert_eq!(
+            check(&config, CryptoHash::default(), 100).expect_err("expected an error"),
+            lack_balance(100)
+        );
+    }
+
     #[test]
     fn test_validate_transaction_invalid_actions_for_function_call() {
         let config = RuntimeConfig::test();
@@ -1184,8 +1288,12 @@ mod tests {
     #[test]
     fn test_validate_receipt_valid() {
         let limit_config = VMLimitConfig::test();
-        validate_receipt(&limit_config, &Receipt::new_balance_refund(&alice_account(), 10))
-            .expect("valid receipt");
+        validate_receipt(
+            &limit_config,
+            &Receipt::new_balance_refund(&alice_account(), 10),
+            PROTOCOL_VERSION,
+        )
+        .expect("valid receipt");
     }
 
     #[test]
@@ -1202,7 +1310,8 @@ mod tests {
                     output_data_receivers: vec![],
                     input_data_ids: vec![CryptoHash::default(), CryptoHash::default()],
                     actions: vec![]
-                }
+                },
+                PROTOCOL_VERSION,
             )
             .expect_err("expected an error"),
             ReceiptValidationError::NumberInputDataDependenciesExceeded {
@@ -1253,7 +1362,7 @@ mod tests {
     #[test]
     fn test_validate_actions_empty() {
         let limit_config = VMLimitConfig::test();
-        validate_actions(&limit_config, &[]).expect("empty actions");
+        validate_actions(&limit_config, &[], PROTOCOL_VERSION).expect("empty actions");
     }
 
     #[test]
@@ -1267,6 +1376,7 @@ mod tests {
                 gas: 100,
                 deposit: 0,
             })],
+            PROTOCOL_VERSION,
         )
         .expect("valid function call action");
     }
@@ -1291,7 +1401,8 @@ mod tests {
                         gas: 150,
                         deposit: 0,
                     })
-                ]
+                ],
+                PROTOCOL_VERSION,
             )
             .expect_err("expected an error"),
             ActionsValidationError::TotalPrepaidGasExceeded { total_prepaid_gas: 250, limit: 220 }
@@ -1318,7 +1429,8 @@ mod tests {
                         gas: u64::max_value() / 2 + 1,
                         deposit: 0,
                     })
-                ]
+                ],
+                PROTOCOL_VERSION,
             )
             .expect_err("Expected an error"),
             ActionsValidationError::IntegerOverflow,
@@ -1335,7 +1447,8 @@ mod tests {
                 &[
                     Action::CreateAccount(CreateAccountAction {}),
                     Action::CreateAccount(CreateAccountAction {}),
-                ]
+                ],
+                PROTOCOL_VERSION,
             )
             .expect_err("Expected an error"),
             ActionsValidationError::TotalNumberOfActionsExceeded {
@@ -1357,7 +1470,8 @@ mod tests {
                         beneficiary_id: "bob".parse().unwrap()
                     }),
                     Action::CreateAccount(CreateAccountAction {}),
-                ]
+                ],
+                PROTOCOL_VERSION,
             )
             .expect_err("Expected an error"),
             ActionsValidationError::DeleteActionMustBeFinal,
@@ -1376,7 +1490,8 @@ mod tests {
                     Action::DeleteAccount(DeleteAccountAction {
                         beneficiary_id: "bob".parse().unwrap()
                     }),
-                ]
+                ],
+                PROTOCOL_VERSION,
             ),
             Ok(()),
         );
@@ -1386,8 +1501,12 @@ mod tests {
 
     #[test]
     fn test_validate_action_valid_create_account() {
-        validate_action(&VMLimitConfig::test(), &Action::CreateAccount(CreateAccountAction {}))
-            .expect("valid action");
+        validate_action(
+            &VMLimitConfig::test(),
+            &Action::CreateAccount(CreateAccountAction {}),
+            PROTOCOL_VERSION,
+        )
+        .expect("valid action");
     }
 
     #[test]
@@ -1400,6 +1519,7 @@ mod tests {
                 gas: 100,
                 deposit: 0,
             }),
+            PROTOCOL_VERSION,
         )
         .expect("valid action");
     }
@@ -1415,6 +1535,7 @@ mod tests {
                     gas: 0,
                     deposit: 0,
                 }),
+                PROTOCOL_VERSION,
             )
             .expect_err("expected an error"),
             ActionsValidationError::FunctionCallZeroAttachedGas,
@@ -1423,8 +1544,12 @@ mod tests {
 
     #[test]
     fn test_validate_action_valid_transfer() {
-        validate_action(&VMLimitConfig::test(), &Action::Transfer(TransferAction { deposit: 10 }))
-            .expect("valid action");
+        validate_action(
+            &VMLimitConfig::test(),
+            &Action::Transfer(TransferAction { deposit: 10 }),
+            PROTOCOL_VERSION,
+        )
+        .expect("valid action");
     }
 
     #[test]
@@ -1435,6 +1560,7 @@ mod tests {
                 stake: 100,
                 public_key: "ed25519:KuTCtARNzxZQ3YvXDeLjx83FDqxv2SdQTSbiq876zR7".parse().unwrap(),
             }),
+            PROTOCOL_VERSION,
         )
         .expect("valid action");
     }
@@ -1448,6 +1574,7 @@ mod tests {
                     stake: 100,
                     public_key: PublicKey::empty(KeyType::ED25519),
                 }),
+                PROTOCOL_VERSION,
             )
             .expect_err("Expected an error"),
             ActionsValidationError::UnsuitableStakingKey {
@@ -1464,6 +1591,7 @@ mod tests {
                 public_key: PublicKey::empty(KeyType::ED25519),
                 access_key: AccessKey::full_access(),
             }),
+            PROTOCOL_VERSION,
         )
         .expect("valid action");
     }
@@ -1483,6 +1611,7 @@ mod tests {
                     }),
                 },
             }),
+            PROTOCOL_VERSION,
         )
         .expect("valid action");
     }
@@ -1492,6 +1621,7 @@ mod tests {
         validate_action(
             &VMLimitConfig::test(),
             &Action::DeleteKey(DeleteKeyAction { public_key: PublicKey::empty(KeyType::ED25519) }),
+            PROTOCOL_VERSION,
         )
         .expect("valid action");
     }
@@ -1501,7 +1631,50 @@ mod tests {
         validate_action(
             &VMLimitConfig::test(),
             &Action::DeleteAccount(DeleteAccountAction { beneficiary_id: alice_account() }),
+            PROTOCOL_VERSION,
         )
         .expect("valid action");
     }
+
+    #[cfg(feature = "protocol_feature_nep366_delegate_action")]
+    fn delegate_action() -> Action {
+        Action::Delegate(SignedDelegateAction {
+            delegate_action: near_primitives::delegate_action::DelegateAction {
+                sender_id: alice_account(),
+                receiver_id: bob_account(),
+                actions: vec![],
+                nonce: 1,
+                max_block_height: 1,
+                public_key: PublicKey::empty(KeyType::ED25519),
+            },
+            signature: near_crypto::Signature::empty(KeyType::ED25519),
+        })
+    }
+
+    #[test]
+    #[cfg(feature = "protocol_feature_nep366_delegate_action")]
+    fn test_validate_delegate_action_must_be_only_one() {
+        assert_eq!(
+            validate_actions(
+                &VMLimitConfig::test(),
+                &[delegate_action(), delegate_action()],
+                ProtocolFeature::DelegateAction.protocol_version(),
+            ),
+            Err(ActionsValidationError::DelegateActionMustBeOnlyOne),
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "protocol_feature_nep366_delegate_action")]
+    fn test_validate_delegate_action_protocol_version() {
+        let version = ProtocolFeature::DelegateAction.protocol_version();
+        validate_action(&VMLimitConfig::test(), &delegate_action(), version).expect("valid action");
+        assert_eq!(
+            validate_action(&VMLimitConfig::test(), &delegate_action(), version - 1),
+            Err(ActionsValidationError::UnsupportedProtocolFeature {
+                protocol_feature: String::from("DelegateAction"),
+                version,
+            }),
+        );
+    }
 }
@@ -18,6 +18,7 @@ pub fn random_config() -> RuntimeConfig {
             storage_usage_config: StorageUsageConfig {
                 num_bytes_account: rng.next_u64() % 10000,
                 num_extra_bytes_record: rng.next_u64() % 10000,
+                zero_balance_account_storage_limit: rng.next_u64() % 1000,
                 storage_amount_per_byte: rng.next_u64() as u128,
             },
             burnt_gas_reward: Rational::new((rng.next_u32() % 100).try_into().unwrap(), 100),
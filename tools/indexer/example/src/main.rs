@@ -276,11 +276,12 @@ fn main() -> Result<()> {
                 sync_mode: near_indexer::SyncModeEnum::FromInterruption,
                 await_for_node_synced: near_indexer::AwaitForNodeSyncedEnum::WaitForFullSync,
                 validate_genesis: true,
+                ..Default::default()
             };
             let system = actix::System::new();
             system.block_on(async move {
                 let indexer = near_indexer::Indexer::new(indexer_config).expect("Indexer::new()");
-                let stream = indexer.streamer();
+                let stream = indexer.streamer().expect("Indexer::streamer()");
                 actix::spawn(listen_blocks(stream));
             });
             system.run()?;
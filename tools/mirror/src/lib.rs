@@ -862,10 +862,12 @@ impl<T: ChainAccess> TxMirror<T> {
             sync_mode: near_indexer::SyncModeEnum::LatestSynced,
             await_for_node_synced: near_indexer::AwaitForNodeSyncedEnum::WaitForFullSync,
             validate_genesis: false,
+            ..Default::default()
         })
         .context("failed to start target chain indexer")?;
         let (target_view_client, target_client) = target_indexer.client_actors();
-        let target_stream = target_indexer.streamer();
+        let target_stream =
+            target_indexer.streamer().context("failed to start target chain indexer streamer")?;
 
         Ok(Self {
             source_chain_access,
@@ -6,6 +6,7 @@ use anyhow::{anyhow, Context as AnyhowContext};
 use near_chain::{Block, BlockHeader, Chain, ChainStoreAccess, Error};
 use near_chain_configs::GenesisConfig;
 use near_client::sync::header::MAX_BLOCK_HEADERS;
+use near_network::recorder::{self, Recording};
 use near_network::time;
 use near_network::types::{
     BlockInfo, ConnectedPeerInfo, FullPeerInfo, NetworkInfo, NetworkRequests, NetworkResponses,
@@ -23,7 +24,7 @@ use near_primitives::time::Clock;
 use near_primitives::types::{BlockHeight, ShardId};
 use serde::Deserialize;
 use std::collections::{HashMap, HashSet};
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::time::Duration;
 
@@ -51,6 +52,9 @@ pub struct MockNetworkConfig {
     // How long we'll wait until sending replies to the client
     pub response_delay: Duration,
     pub incoming_requests: Option<MockIncomingRequestsConfig>,
+    // Network traffic recorded by a node, which is replayed instead of simulating the network.
+    #[serde(default)]
+    pub replay: Option<PathBuf>,
 }
 
 impl MockNetworkConfig {
@@ -74,7 +78,7 @@ fn default_delay() -> Duration {
 
 impl Default for MockNetworkConfig {
     fn default() -> Self {
-        Self { response_delay: default_delay(), incoming_requests: None }
+        Self { response_delay: default_delay(), incoming_requests: None, replay: None }
     }
 }
 
@@ -193,6 +197,9 @@ impl IncomingRequests {
 ///     BlockRequest, BlockHeadersRequest and PartialEncodedChunkRequest
 /// - Sends NetworkInfo to ClientActor periodically
 /// - Simulates block production and sends the most "recent" block to ClientActor
+///
+/// If a recording of the network traffic is provided, the inbound messages from it are
+/// delivered to ClientActor instead, and the requests of ClientActor are left unanswered.
 pub struct MockPeerManagerActor {
     /// Client address for the node that we are testing
     client: Arc<dyn near_network::client::Client>,
@@ -207,6 +214,10 @@ pub struct MockPeerManagerActor {
     /// The simulated peers will stop producing new blocks at this height
     target_height: BlockHeight,
     incoming_requests: IncomingRequests,
+    /// Recording to replay. It is taken once the replay starts.
+    replay: Option<Recording>,
+    /// Whether the network is replayed from a recording rather than simulated.
+    replaying: bool,
 }
 
 impl MockPeerManagerActor {
@@ -259,6 +270,10 @@ impl MockPeerManagerActor {
             tier1_accounts_keys: vec![],
             tier1_accounts_data: vec![],
         };
+        let replay = network_config.replay.as_ref().map(|path| {
+            recorder::read(path)
+                .unwrap_or_else(|err| panic!("failed to read the recording: {err:#}"))
+        });
         let incoming_requests = IncomingRequests::new(
             &network_config.incoming_requests,
             &mut chain,
@@ -273,9 +288,27 @@ impl MockPeerManagerActor {
             network_delay: network_config.response_delay,
             target_height,
             incoming_requests,
+            replaying: replay.is_some(),
+            replay,
         }
     }
 
+    /// Delivers the inbound messages of the recording to ClientActor one by one,
+    /// in the recorded order. Every message is delivered once ClientActor has processed
+    /// the previous one, regardless of the recorded timestamps.
+    fn replay_recording(&mut self, recording: Recording) {
+        let client = self.client.clone();
+        actix::spawn(async move {
+            let clock = time::Clock::real();
+            let Recording { node_id, records } = recording;
+            tracing::info!(target: "mock_node", node_id = %node_id, records = records.len(), "Replaying network traffic");
+            for record in records {
+                recorder::replay(&clock, client.as_ref(), &node_id, record).await;
+            }
+            tracing::info!(target: "mock_node", "Finished replaying network traffic");
+        });
+    }
+
     /// This function gets called periodically
     /// When it is called, it increments peer heights by 1 and sends the block at that height
     /// to ClientActor. In a way, it simulates peers that broadcast new blocks
@@ -288,7 +321,8 @@ impl MockPeerManagerActor {
         for connected_peer in self.network_info.connected_peers.iter_mut() {
             let peer = &mut connected_peer.full_peer_info;
             let current_height = peer.chain_info.last_block.unwrap().height;
-            if current_height <= self.target_height {
+            // When replaying, the blocks are delivered from the recording instead.
+            if !self.replaying && current_height <= self.target_height {
                 if let Ok(block) =
                     self.chain_history_access.retrieve_block_by_height(current_height)
                 {
@@ -377,6 +411,10 @@ impl Actor for MockPeerManagerActor {
         // Start syncing job.
         self.update_peers(ctx);
 
+        if let Some(recording) = self.replay.take() {
+            self.replay_recording(recording);
+            return;
+        }
         self.send_incoming_requests(ctx);
     }
 }
@@ -396,6 +434,10 @@ impl Handler<WithSpanContext<PeerManagerMessageRequest>> for MockPeerManagerActo
         ctx: &mut Self::Context,
     ) -> Self::Result {
         let (_span, msg) = handler_debug_span!(target: "mock-node", msg);
+        if self.replaying {
+            // The responses are delivered from the recording, if they were recorded.
+            return PeerManagerMessageResponse::NetworkResponses(NetworkResponses::NoResponse);
+        }
         match msg {
             PeerManagerMessageRequest::NetworkRequests(request) => match request {
                 NetworkRequests::BlockRequest { hash, peer_id } => {
@@ -82,6 +82,12 @@ struct Cli {
     /// If true, use in memory storage instead of rocksdb for the client
     #[clap(short = 'i', long)]
     in_memory_storage: bool,
+    /// Network traffic recorded by a node (see `network.experimental.traffic_recorder`
+    /// in config.json), either a recording directory or a single file. If specified,
+    /// the recorded inbound messages are delivered to the client instead of the responses
+    /// of the simulated network.
+    #[clap(long)]
+    replay: Option<PathBuf>,
 }
 
 fn main() -> anyhow::Result<()> {
@@ -117,6 +123,9 @@ fn main() -> anyhow::Result<()> {
     if let Some(delay) = args.network_delay {
         network_config.response_delay = Duration::from_millis(delay);
     }
+    if let Some(replay) = args.replay {
+        network_config.replay = Some(replay);
+    }
 
     let client_height = args.start_height.unwrap_or(args.client_height);
     let network_height = args.start_height.or(args.network_height);
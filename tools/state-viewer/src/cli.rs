@@ -5,6 +5,7 @@ use crate::{dump_state_parts, epoch_info};
 use clap::{Args, Parser, Subcommand};
 use near_chain_configs::{GenesisChangeConfig, GenesisValidationMode};
 use near_primitives::account::id::AccountId;
+use near_primitives::gas_trace::GasTraceFormat;
 use near_primitives::hash::CryptoHash;
 use near_primitives::sharding::ChunkHash;
 use near_primitives::types::{BlockHeight, ShardId};
@@ -447,12 +448,19 @@ impl ApplyTxCmd {
 pub struct ApplyReceiptCmd {
     #[clap(long)]
     hash: String,
+    /// Export the gas charged by every host function called by the receipt,
+    /// either as `json` or as a `chrome` trace for chrome://tracing.
+    #[clap(long)]
+    trace: Option<GasTraceFormat>,
+    /// File to write the gas trace to instead of stdout.
+    #[clap(long, requires = "trace")]
+    trace_file: Option<PathBuf>,
 }
 
 impl ApplyReceiptCmd {
     pub fn run(self, home_dir: &Path, near_config: NearConfig, store: Store) {
         let hash = CryptoHash::from_str(&self.hash).unwrap();
-        apply_receipt(home_dir, near_config, store, hash).unwrap();
+        apply_receipt(home_dir, near_config, store, hash, self.trace, self.trace_file).unwrap();
     }
 }
 
@@ -15,6 +15,7 @@ use near_epoch_manager::EpochManagerAdapter;
 use near_network::iter_peers_from_store;
 use near_primitives::account::id::AccountId;
 use near_primitives::block::{Block, BlockHeader};
+use near_primitives::gas_trace::GasTraceFormat;
 use near_primitives::hash::CryptoHash;
 use near_primitives::shard_layout::ShardUId;
 use near_primitives::sharding::ChunkHash;
@@ -31,6 +32,7 @@ use near_store::TrieConfig;
 use near_store::{NodeStorage, Store};
 use nearcore::{NearConfig, NightshadeRuntime};
 use node_runtime::adapter::ViewRuntimeAdapter;
+use node_runtime::record_gas_traces;
 use serde_json::json;
 use std::collections::HashMap;
 use std::fs::{self, File};
@@ -807,10 +809,26 @@ pub(crate) fn apply_receipt(
     near_config: NearConfig,
     store: Store,
     hash: CryptoHash,
+    trace: Option<GasTraceFormat>,
+    trace_file: Option<PathBuf>,
 ) -> anyhow::Result<()> {
     let runtime = NightshadeRuntime::from_config(home_dir, store.clone(), &near_config);
-    apply_chunk::apply_receipt(near_config.genesis.config.genesis_height, &runtime, store, hash)
-        .map(|_| ())
+    let genesis_height = near_config.genesis.config.genesis_height;
+    let apply = || apply_chunk::apply_receipt(genesis_height, &runtime, store, hash);
+    let format = match trace {
+        Some(format) => format,
+        None => return apply().map(|_| ()),
+    };
+    let (result, traces) = record_gas_traces(apply);
+    result?;
+    // The whole chunk is applied, so keep only the function calls of the receipt.
+    let traces: Vec<_> = traces.into_iter().filter(|trace| trace.receipt_id == hash).collect();
+    let exported = near_primitives::gas_trace::export(&traces, format);
+    match trace_file {
+        Some(path) => fs::write(path, serde_json::to_vec_pretty(&exported)?)?,
+        None => println!("{}", serde_json::to_string_pretty(&exported)?),
+    }
+    Ok(())
 }
 
 pub(crate) fn view_trie(